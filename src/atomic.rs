@@ -0,0 +1,114 @@
+use std::{
+    fs::{create_dir_all, metadata, remove_file, rename, set_permissions, symlink_metadata},
+    io,
+    path::Path,
+    process,
+};
+
+// This function writes a file atomically: the contents are written to a temporary file next to it,
+// which then replaces it, so readers never see a partially written file. Any missing parent
+// directories are created first. If the file is a symlink, its target is written instead, and if
+// the file already exists, its permissions (e.g., whether it's executable) are kept.
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), String> {
+    let error = |error: io::Error| format!("Unable to write {}: {error}", path.to_string_lossy());
+
+    let target = if symlink_metadata(path).is_ok_and(|metadata| metadata.is_symlink()) {
+        path.canonicalize().map_err(error)?
+    } else {
+        path.to_owned()
+    };
+
+    if let Some(parent) = target
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        create_dir_all(parent).map_err(error)?;
+    }
+
+    let permissions = metadata(&target)
+        .ok()
+        .map(|metadata| metadata.permissions());
+    let mut temporary = target.as_os_str().to_owned();
+    temporary.push(format!(".{}.tmp", process::id()));
+    std::fs::write(&temporary, contents)
+        .and_then(|()| {
+            permissions.map_or(Ok(()), |permissions| {
+                set_permissions(&temporary, permissions)
+            })
+        })
+        .and_then(|()| rename(&temporary, &target))
+        .map_err(|write_error| {
+            let _ = remove_file(&temporary);
+            error(write_error)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::atomic::write,
+        std::{
+            env::temp_dir,
+            fs::{create_dir_all, read_dir, read_to_string, remove_dir_all},
+            process,
+        },
+    };
+
+    #[test]
+    fn write_nested() {
+        let directory = temp_dir().join(format!("tagref-atomic-test-{}", process::id()));
+        let _ = remove_dir_all(&directory);
+        let path = directory.join("reports").join("report.json");
+
+        write(&path, "first").unwrap();
+        write(&path, "second").unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), "second");
+        assert_eq!(read_dir(directory.join("reports")).unwrap().count(), 1);
+
+        remove_dir_all(&directory).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_keeps_permissions() {
+        use std::{
+            fs::{metadata, set_permissions, Permissions},
+            os::unix::fs::PermissionsExt,
+        };
+
+        let directory = temp_dir().join(format!("tagref-atomic-mode-{}", process::id()));
+        let _ = remove_dir_all(&directory);
+        create_dir_all(&directory).unwrap();
+        let path = directory.join("script.sh");
+
+        write(&path, "#!/bin/sh\n").unwrap();
+        set_permissions(&path, Permissions::from_mode(0o755)).unwrap();
+        write(&path, "#!/bin/sh\nexit 0\n").unwrap();
+        let mode = metadata(&path).unwrap().permissions().mode();
+
+        remove_dir_all(&directory).unwrap();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_through_symlink() {
+        use std::{fs::symlink_metadata, os::unix::fs::symlink};
+
+        let directory = temp_dir().join(format!("tagref-atomic-symlink-{}", process::id()));
+        let _ = remove_dir_all(&directory);
+        create_dir_all(&directory).unwrap();
+        let target = directory.join("target.rs");
+        let link = directory.join("link.rs");
+
+        write(&target, "old").unwrap();
+        symlink(&target, &link).unwrap();
+        write(&link, "new").unwrap();
+        let is_symlink = symlink_metadata(&link).unwrap().is_symlink();
+        let contents = read_to_string(&target).unwrap();
+
+        remove_dir_all(&directory).unwrap();
+        assert!(is_symlink);
+        assert_eq!(contents, "new");
+    }
+}
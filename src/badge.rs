@@ -0,0 +1,49 @@
+use {crate::count::count, serde::Serialize};
+
+// This struct represents the response for a shields.io endpoint badge.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Badge {
+    schema_version: usize,
+    label: &'static str,
+    message: String,
+    color: &'static str,
+}
+
+// This function renders a shields.io endpoint badge showing the number of tags and dangling
+// references. The badge is green if there are no dangling references and red otherwise.
+pub fn render(tags: usize, dangling: usize) -> String {
+    // The `unwrap` is safe because badges always serialize successfully.
+    let mut json = serde_json::to_string_pretty(&Badge {
+        schema_version: 1,
+        label: "tagref",
+        message: format!("{} / {dangling} dangling", count(tags, "tag")),
+        color: if dangling == 0 { "brightgreen" } else { "red" },
+    })
+    .unwrap();
+    json.push('\n');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::badge::render;
+
+    #[test]
+    fn render_healthy() {
+        let json: serde_json::Value = serde_json::from_str(&render(312, 0)).unwrap();
+
+        assert_eq!(json["schemaVersion"], 1_u64);
+        assert_eq!(json["label"], "tagref");
+        assert_eq!(json["message"], "312 tags / 0 dangling");
+        assert_eq!(json["color"], "brightgreen");
+    }
+
+    #[test]
+    fn render_dangling() {
+        let json: serde_json::Value = serde_json::from_str(&render(1, 2)).unwrap();
+
+        assert_eq!(json["message"], "1 tag / 2 dangling");
+        assert_eq!(json["color"], "red");
+    }
+}
@@ -0,0 +1,111 @@
+use {
+    crate::{
+        config::TagBudget,
+        count::count,
+        diagnostic::{self, Diagnostic},
+        directive::Directive,
+    },
+    std::{collections::BTreeMap, fmt::Write, path::PathBuf},
+};
+
+// This function checks that no file contains more tags than its budgets allow. It returns a vector
+// of errors, each located at the first tag which exceeds the budget.
+pub fn check(tags: &[Directive], budgets: &[TagBudget]) -> Vec<Diagnostic> {
+    let mut errors = Vec::<Diagnostic>::new();
+
+    // Group the tags by file.
+    let mut tags_by_file = BTreeMap::<PathBuf, Vec<&Directive>>::new();
+    for tag in tags {
+        tags_by_file.entry(tag.path.clone()).or_default().push(tag);
+    }
+
+    for (path, mut file_tags) in tags_by_file {
+        file_tags.sort_by_key(|tag| tag.line_number);
+
+        for (i, budget) in budgets.iter().enumerate() {
+            if file_tags.len() > budget.max_tags
+                && budget
+                    .path
+                    .as_ref()
+                    .is_none_or(|pattern| pattern.is_match(&path))
+            {
+                let mut error = String::new();
+                let _ = writeln!(
+                    error,
+                    "{} contains {}, but `tag-budgets` entry #{}{} allows at most {}:",
+                    path.to_string_lossy(),
+                    count(file_tags.len(), "tag"),
+                    i + 1,
+                    budget
+                        .path
+                        .as_ref()
+                        .map_or_else(String::new, |pattern| format!(" (`{pattern}`)")),
+                    budget.max_tags,
+                );
+                for tag in &file_tags {
+                    let _ = writeln!(error, "  {tag}");
+                }
+                errors.push(Diagnostic::error(
+                    diagnostic::TAG_BUDGET,
+                    file_tags[budget.max_tags],
+                    error,
+                ));
+            }
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        budgets::check,
+        config::parse,
+        directive::{Directive, Type},
+    };
+
+    fn tag(label: &str, path: &str) -> Directive {
+        Directive::new(Type::Tag, label, path, 1)
+    }
+
+    #[test]
+    fn check_empty() {
+        assert!(check(&[tag("tag1", "file.rs")], &[]).is_empty());
+    }
+
+    #[test]
+    fn check_budgets() {
+        let config = parse(
+            "
+            [[tag-budgets]]
+            max-tags = 2
+
+            [[tag-budgets]]
+            path = '*.json'
+            max-tags = 0
+            ",
+        )
+        .unwrap();
+
+        let tags = [
+            tag("tag1", "./file1.rs"),
+            tag("tag2", "./file1.rs"),
+            tag("tag3", "./file2.rs"),
+            tag("tag4", "./file2.rs"),
+            tag("tag5", "./file2.rs"),
+            tag("tag6", "./data/file3.json"),
+        ];
+
+        let errors = check(&tags, &config.tag_budgets);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].message.contains("file3.json contains 1 tag,"));
+        assert!(
+            errors[0]
+                .message
+                .contains("entry #2 (`*.json`) allows at most 0"),
+        );
+        assert!(errors[1].message.contains("file2.rs contains 3 tags"));
+        assert!(errors[1].message.contains("tag5"));
+    }
+}
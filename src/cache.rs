@@ -0,0 +1,215 @@
+use {
+    crate::directive::Directives,
+    sha2::{Digest, Sha256},
+    std::{
+        env,
+        fmt::Write as _,
+        fs::{create_dir_all, read, remove_file, rename, write},
+        io::Read,
+        path::{Path, PathBuf},
+        process,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    },
+};
+
+// The version of the cache entries. It must be bumped whenever the parser or the format of the
+// entries changes, so that stale entries are never used.
+const CACHE_VERSION: u64 = 8;
+
+// The environment variable which holds a bearer token for a remote cache, if it needs one
+pub const CACHE_TOKEN_VARIABLE: &str = "TAGREF_CACHE_TOKEN";
+
+// How long to wait for a remote cache before giving up on a request
+const REMOTE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// This counter distinguishes the temporary files written by concurrent threads.
+static TEMPORARY_FILES: AtomicUsize = AtomicUsize::new(0);
+
+// This enum represents where parse results are cached. Entries are addressed by the hash of the
+// file contents, so a cache can be shared by any number of checkouts and machines. Failing to read
+// from or write to a cache is never an error; the file is just parsed as usual.
+#[derive(Clone)]
+pub enum Cache {
+    Local(PathBuf),
+    Remote {
+        url: String,
+        agent: ureq::Agent,
+        token: Option<String>,
+    },
+}
+
+impl Cache {
+    // This function interprets the location of a cache. HTTP(S) URLs refer to remote caches, and
+    // anything else is a local directory.
+    pub fn new(location: &str) -> Self {
+        if location.starts_with("http://") || location.starts_with("https://") {
+            Self::Remote {
+                url: location.trim_end_matches('/').to_owned(),
+                agent: ureq::AgentBuilder::new().timeout(REMOTE_TIMEOUT).build(),
+                token: env::var(CACHE_TOKEN_VARIABLE).ok(),
+            }
+        } else {
+            Self::Local(Path::new(location).to_owned())
+        }
+    }
+
+    // This function looks up the directives for a file in the cache. Since entries are shared by
+    // every file with the same contents, the given path is filled in on the way out.
+    pub fn get(&self, key: &str, path: &Path) -> Option<Directives> {
+        let bytes = match self {
+            Self::Local(directory) => read(directory.join(key)).ok()?,
+            Self::Remote { url, agent, token } => {
+                let mut request = agent.get(&format!("{url}/{key}"));
+                if let Some(token) = token {
+                    request = request.set("Authorization", &format!("Bearer {token}"));
+                }
+                let mut bytes = Vec::new();
+                request
+                    .call()
+                    .ok()?
+                    .into_reader()
+                    .read_to_end(&mut bytes)
+                    .ok()?;
+                bytes
+            }
+        };
+
+        let mut directives = serde_json::from_slice::<Directives>(&bytes).ok()?;
+        for directive in directives.all_mut() {
+            path.clone_into(&mut directive.path);
+        }
+        Some(directives)
+    }
+
+    // This function stores the directives for a file in the cache.
+    pub fn put(&self, key: &str, directives: &Directives) {
+        // Paths aren't part of the key, so they are left out of the entry.
+        let mut entry = directives.clone();
+        for directive in entry.all_mut() {
+            directive.path = PathBuf::new();
+        }
+        let Ok(bytes) = serde_json::to_vec(&entry) else {
+            return;
+        };
+
+        match self {
+            Self::Local(directory) => {
+                // Write to a temporary file first, so readers never see a partial entry.
+                let temporary = directory.join(format!(
+                    "{key}.{}.{}.tmp",
+                    process::id(),
+                    TEMPORARY_FILES.fetch_add(1, Ordering::SeqCst),
+                ));
+                if create_dir_all(directory)
+                    .and_then(|()| write(&temporary, &bytes))
+                    .and_then(|()| rename(&temporary, directory.join(key)))
+                    .is_err()
+                {
+                    let _ = remove_file(&temporary);
+                }
+            }
+            Self::Remote { url, agent, token } => {
+                let mut request = agent
+                    .put(&format!("{url}/{key}"))
+                    .set("Content-Type", "application/json");
+                if let Some(token) = token {
+                    request = request.set("Authorization", &format!("Bearer {token}"));
+                }
+                let _ = request.send_bytes(&bytes);
+            }
+        }
+    }
+}
+
+// This function computes the cache key for a file. It covers everything which affects how the file
+// is parsed: the version of the cache entries, the given settings (e.g., the directive patterns),
+// and the contents.
+pub fn key(settings: &[impl AsRef<str>], contents: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(CACHE_VERSION.to_le_bytes());
+    for setting in settings.iter().map(AsRef::as_ref) {
+        hasher.update((setting.len() as u64).to_le_bytes());
+        hasher.update(setting.as_bytes());
+    }
+    hasher.update(contents);
+
+    hasher
+        .finalize()
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            cache::{key, Cache},
+            directive::{compile_directive_regex, parse},
+        },
+        std::{env::temp_dir, fs::remove_dir_all, path::Path, process},
+    };
+
+    #[test]
+    fn key_depends_on_contents_and_settings() {
+        assert_eq!(key(&["tag"], b"foo"), key(&["tag"], b"foo"));
+        assert_eq!(key(&["tag"], b"foo").len(), 64);
+        assert_ne!(key(&["tag"], b"foo"), key(&["tag"], b"bar"));
+        assert_ne!(key(&["tag"], b"foo"), key(&["ref"], b"foo"));
+        assert_ne!(key(&["tag", "ref"], b"foo"), key(&["ref", "tag"], b"foo"));
+        assert_ne!(key(&["ta", "gref"], b"foo"), key(&["tag", "ref"], b"foo"));
+    }
+
+    #[test]
+    fn local_round_trip() {
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let spec_regex = compile_directive_regex("spec");
+        let url_regex = compile_directive_regex("url");
+        let contents = "[?tag:foo] Bar.\n[?ref:baz]".replace('?', "");
+        let directives = parse(
+            &tag_regex,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &spec_regex,
+            &url_regex,
+            &[],
+            Path::new("a.rs"),
+            contents.as_bytes(),
+        );
+
+        let directory = temp_dir().join(format!("tagref-cache-test-{}", process::id()));
+        let cache = Cache::new(&directory.to_string_lossy());
+        let key = key(&[tag_regex.as_str()], contents.as_bytes());
+        assert!(cache.get(&key, Path::new("b.rs")).is_none());
+
+        cache.put(&key, &directives);
+        let cached = cache.get(&key, Path::new("b.rs")).unwrap();
+        let _ = remove_dir_all(&directory);
+
+        assert_eq!(cached.tags.len(), 1);
+        assert_eq!(cached.tags[0].label, "foo");
+        assert_eq!(cached.tags[0].description, "Bar.");
+        assert_eq!(cached.tags[0].path, Path::new("b.rs"));
+        assert_eq!(cached.refs.len(), 1);
+        assert_eq!(cached.refs[0].line_number, 2);
+    }
+
+    #[test]
+    fn remote_is_selected_by_url() {
+        match Cache::new("https://cache.example.com/tagref/") {
+            Cache::Remote { url, .. } => assert_eq!(url, "https://cache.example.com/tagref"),
+            Cache::Local(_) => panic!("Expected a remote cache."),
+        }
+        match Cache::new("target/tagref-cache") {
+            Cache::Remote { .. } => panic!("Expected a local cache."),
+            Cache::Local(directory) => assert_eq!(directory, Path::new("target/tagref-cache")),
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use {
+    crate::{directive::Directive, distance::close_matches, walk::normalize},
+    std::{
+        collections::{BTreeMap, HashMap, HashSet},
+        fmt::Write,
+    },
+};
+
+// This enum represents a change to the tags between two versions.
+#[derive(Debug)]
+pub enum Change<'a> {
+    Added(&'a Directive),
+    Removed(&'a Directive),
+    Renamed(&'a Directive, &'a Directive),
+}
+
+impl Change<'_> {
+    // This function returns the label which the change is listed under.
+    fn label(&self) -> &str {
+        match self {
+            Self::Added(tag) | Self::Removed(tag) | Self::Renamed(_, tag) => &tag.label,
+        }
+    }
+}
+
+// This function compares the tags in two versions. A removed tag and an added tag are considered a
+// rename if they have the same description, or if they're in the same file and their labels are
+// similar, as long as the match is unique.
+pub fn changes<'a>(
+    old: &'a HashMap<String, Vec<Directive>>,
+    new: &'a HashMap<String, Vec<Directive>>,
+) -> Vec<Change<'a>> {
+    // Find the labels which were added or removed, sorted so the pairing is deterministic. The
+    // `[0]`s are safe because each label has at least one tag.
+    let mut removed = old
+        .iter()
+        .filter(|(label, _)| !new.contains_key(*label))
+        .map(|(_, tags)| &tags[0])
+        .collect::<Vec<_>>();
+    removed.sort_by(|x, y| x.label.cmp(&y.label));
+    let mut added = new
+        .iter()
+        .filter(|(label, _)| !old.contains_key(*label))
+        .map(|(_, tags)| &tags[0])
+        .collect::<Vec<_>>();
+    added.sort_by(|x, y| x.label.cmp(&y.label));
+
+    // Pair the removed tags with the added ones which replaced them.
+    let mut paired = HashSet::<&str>::new();
+    let mut changes = vec![];
+    for tag in removed {
+        let unpaired = added
+            .iter()
+            .filter(|candidate| !paired.contains(candidate.label.as_str()))
+            .collect::<Vec<_>>();
+        let same_description = unpaired
+            .iter()
+            .filter(|candidate| {
+                !tag.description.is_empty() && candidate.description == tag.description
+            })
+            .collect::<Vec<_>>();
+        let same_file = unpaired
+            .iter()
+            .filter(|candidate| normalize(&candidate.path) == normalize(&tag.path))
+            .map(|candidate| candidate.label.as_str());
+        let replacement = if let [candidate] = same_description[..] {
+            Some(**candidate)
+        } else if let [label] = close_matches(&tag.label, same_file)[..] {
+            added
+                .iter()
+                .find(|candidate| candidate.label == label)
+                .copied()
+        } else {
+            None
+        };
+
+        match replacement {
+            Some(replacement) => {
+                paired.insert(&replacement.label);
+                changes.push(Change::Renamed(tag, replacement));
+            }
+            None => changes.push(Change::Removed(tag)),
+        }
+    }
+    changes.extend(
+        added
+            .into_iter()
+            .filter(|tag| !paired.contains(tag.label.as_str()))
+            .map(Change::Added),
+    );
+
+    changes
+}
+
+// This function returns the namespace of a label, which is the part before the first occurrence of
+// the separator. An empty separator means labels have no namespaces.
+pub fn namespace<'a>(label: &'a str, separator: &str) -> Option<&'a str> {
+    if separator.is_empty() {
+        None
+    } else {
+        label.split_once(separator).map(|(namespace, _)| namespace)
+    }
+}
+
+// This function renders the changes as Markdown for release notes, grouped by the namespace of each
+// label (the part before the first occurrence of the separator). Labels without a namespace are
+// listed last.
+pub fn render(changes: &[Change], since: &str, commit: &str, separator: &str) -> String {
+    let mut output = format!("## Tag changes since `{since}` ({commit})\n");
+    if changes.is_empty() {
+        output.push_str("\nNo tags were added, removed, or renamed.\n");
+        return output;
+    }
+
+    let mut namespaces = BTreeMap::<Option<&str>, Vec<&Change>>::new();
+    for change in changes {
+        namespaces
+            .entry(namespace(change.label(), separator))
+            .or_default()
+            .push(change);
+    }
+
+    // `None` sorts first, so it's moved to the end.
+    let mut groups = namespaces.into_iter().collect::<Vec<_>>();
+    let without_namespace = usize::from(groups[0].0.is_none());
+    groups.rotate_left(without_namespace);
+    for (namespace, mut changes) in groups {
+        let _ = write!(
+            output,
+            "\n### {}\n\n",
+            namespace.map_or_else(|| "Other".to_owned(), |namespace| format!("`{namespace}`")),
+        );
+
+        // List the additions, then the renames, then the removals.
+        changes.sort_by_key(|change| {
+            (
+                match change {
+                    Change::Added(_) => 0_u8,
+                    Change::Renamed(_, _) => 1_u8,
+                    Change::Removed(_) => 2_u8,
+                },
+                change.label().to_owned(),
+            )
+        });
+        for change in changes {
+            let _ = match change {
+                Change::Added(tag) if tag.description.is_empty() => {
+                    writeln!(output, "- Added `{}`", tag.label)
+                }
+                Change::Added(tag) => {
+                    writeln!(output, "- Added `{}`: {}", tag.label, tag.description)
+                }
+                Change::Renamed(old, new) => {
+                    writeln!(output, "- Renamed `{}` to `{}`", old.label, new.label)
+                }
+                Change::Removed(tag) => writeln!(output, "- Removed `{}`", tag.label),
+            };
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            changelog::{changes, render, Change},
+            directive::{Directive, Type},
+        },
+        std::collections::HashMap,
+    };
+
+    fn tags(entries: &[(&str, &str, &str)]) -> HashMap<String, Vec<Directive>> {
+        entries
+            .iter()
+            .map(|(label, description, path)| {
+                (
+                    (*label).to_owned(),
+                    vec![Directive {
+                        description: (*description).to_owned(),
+                        ..Directive::new(Type::Tag, label, path, 1)
+                    }],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn changes_added_removed_renamed() {
+        let old = tags(&[
+            ("auth.tokens", "Tokens are hashed.", "auth.rs"),
+            ("auth.legacy", "", "auth.rs"),
+            ("cache_keys", "", "cache.rs"),
+            ("unchanged", "", "lib.rs"),
+        ]);
+        let new = tags(&[
+            ("auth.api_tokens", "Tokens are hashed.", "tokens.rs"),
+            ("auth.session_ttl", "Sessions expire.", "auth.rs"),
+            ("cache_key", "", "./cache.rs"),
+            ("unchanged", "", "lib.rs"),
+        ]);
+        let summary = changes(&old, &new)
+            .iter()
+            .map(|change| match change {
+                Change::Added(tag) => format!("+{}", tag.label),
+                Change::Removed(tag) => format!("-{}", tag.label),
+                Change::Renamed(x, y) => format!("{} -> {}", x.label, y.label),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            summary,
+            [
+                "-auth.legacy",
+                "auth.tokens -> auth.api_tokens",
+                "cache_keys -> cache_key",
+                "+auth.session_ttl",
+            ],
+        );
+    }
+
+    #[test]
+    fn render_grouped() {
+        let old = tags(&[("auth.legacy", "", "auth.rs"), ("cache", "", "cache.rs")]);
+        let new = tags(&[("auth.session_ttl", "Sessions expire.", "session.rs")]);
+
+        assert_eq!(
+            render(&changes(&old, &new), "v1.0.0", "abc1234", "."),
+            "## Tag changes since `v1.0.0` (abc1234)\n\
+             \n\
+             ### `auth`\n\
+             \n\
+             - Added `auth.session_ttl`: Sessions expire.\n\
+             - Removed `auth.legacy`\n\
+             \n\
+             ### Other\n\
+             \n\
+             - Removed `cache`\n",
+        );
+    }
+
+    #[test]
+    fn render_empty() {
+        assert_eq!(
+            render(&[], "v1.0.0", "abc1234", "."),
+            "## Tag changes since `v1.0.0` (abc1234)\n\
+             \n\
+             No tags were added, removed, or renamed.\n",
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use {
+    crate::{
+        diagnostic::{self, Diagnostic},
+        directive::Directive,
+        walk::normalize,
+    },
+    ignore::gitignore::{Gitignore, GitignoreBuilder},
+    std::{
+        env::current_dir,
+        fs::read_to_string,
+        io::ErrorKind,
+        path::{Component, Path, PathBuf},
+    },
+};
+
+// The metadata key used to declare the owner of a tag, as in `owner=@team`.
+pub const OWNER_KEY: &str = "owner";
+
+// These are the locations where GitHub looks for a CODEOWNERS file, in order of precedence.
+const CODEOWNERS_PATHS: &[&str] = &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+// A single line of a CODEOWNERS file
+struct Rule {
+    matcher: Gitignore,
+    owners: Vec<String>,
+}
+
+// This struct represents a parsed CODEOWNERS file.
+pub struct CodeOwners {
+    path: PathBuf,
+    rules: Vec<Rule>,
+
+    // The root of the repository (i.e., the working directory), for making absolute paths relative
+    root: Option<PathBuf>,
+}
+
+impl CodeOwners {
+    // This function parses the contents of a CODEOWNERS file. Lines with patterns that can't be
+    // parsed are skipped, just as GitHub does.
+    pub fn parse(path: &Path, contents: &str) -> Self {
+        let mut rules = Vec::new();
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+
+            // The `unwrap` is safe since the line is nonempty.
+            let pattern = tokens.next().unwrap();
+            let owners = tokens
+                .take_while(|token| !token.starts_with('#'))
+                .map(ToOwned::to_owned)
+                .collect();
+
+            let mut builder = GitignoreBuilder::new(".");
+            if builder.add_line(None, pattern).is_err() {
+                continue;
+            }
+            if let Ok(matcher) = builder.build() {
+                rules.push(Rule { matcher, owners });
+            }
+        }
+
+        Self {
+            path: path.to_owned(),
+            rules,
+            root: current_dir().ok(),
+        }
+    }
+
+    // This function returns the owners of a file. As with GitHub, the last matching rule wins.
+    // Paths are relative to the root of the repository, which is assumed to be the working
+    // directory. Absolute paths are made relative to it first, and paths outside of it have no
+    // owners.
+    pub fn owners_of(&self, path: &Path) -> &[String] {
+        let path = if path.is_absolute() {
+            let Some(relative) = self
+                .root
+                .as_ref()
+                .and_then(|root| path.strip_prefix(root).ok())
+            else {
+                return &[];
+            };
+            normalize(relative)
+        } else {
+            normalize(path)
+        };
+        if path.components().next() == Some(Component::ParentDir) {
+            return &[];
+        }
+
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| {
+                rule.matcher
+                    .matched_path_or_any_parents(&path, false)
+                    .is_ignore()
+            })
+            .map_or(&[], |rule| &rule.owners)
+    }
+}
+
+// This function loads the CODEOWNERS file from the working directory, if there is one.
+pub fn load() -> Result<Option<CodeOwners>, String> {
+    for path in CODEOWNERS_PATHS {
+        let path = Path::new(path);
+        match read_to_string(path) {
+            Ok(contents) => return Ok(Some(CodeOwners::parse(path, &contents))),
+            Err(error) if error.kind() == ErrorKind::NotFound => {}
+            Err(error) => {
+                return Err(format!(
+                    "Unable to read {}: {error}",
+                    path.to_string_lossy(),
+                ));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+// This function checks that every tag with an owner lives in a file which CODEOWNERS assigns to
+// that owner. It returns a vector of errors.
+pub fn check(code_owners: &CodeOwners, tags: &[Directive]) -> Vec<Diagnostic> {
+    let mut errors = Vec::<Diagnostic>::new();
+
+    for tag in tags {
+        if let Some(owner) = tag.metadata.get(OWNER_KEY) {
+            let owners = code_owners.owners_of(&tag.path);
+            if !owners
+                .iter()
+                .any(|candidate| candidate.eq_ignore_ascii_case(owner))
+            {
+                let message = if owners.is_empty() {
+                    format!(
+                        "{tag} is owned by `{owner}`, but {} assigns no owners to that file.",
+                        code_owners.path.to_string_lossy(),
+                    )
+                } else {
+                    format!(
+                        "{tag} is owned by `{owner}`, but {} assigns that file to {}.",
+                        code_owners.path.to_string_lossy(),
+                        owners
+                            .iter()
+                            .map(|owner| format!("`{owner}`"))
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    )
+                };
+                errors.push(Diagnostic::error(diagnostic::OWNER_MISMATCH, tag, message));
+            }
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            codeowners::{check, CodeOwners},
+            directive::{Directive, Type},
+        },
+        std::{
+            collections::BTreeMap,
+            env::{current_dir, temp_dir},
+            path::Path,
+        },
+    };
+
+    fn tag(path: &str, owner: &str) -> Directive {
+        let mut metadata = BTreeMap::new();
+        metadata.insert("owner".to_owned(), owner.to_owned());
+
+        Directive {
+            metadata,
+            ..Directive::new(Type::Tag, "tag1", path, 1)
+        }
+    }
+
+    #[test]
+    fn owners_of_last_match_wins() {
+        let code_owners = CodeOwners::parse(
+            Path::new("CODEOWNERS"),
+            "
+            # Comments are ignored.
+            *       @everyone
+            /src/   @core @reviewers # Trailing comments are ignored too.
+            *.md    @docs
+            ",
+        );
+
+        assert_eq!(
+            code_owners.owners_of(Path::new("./Cargo.toml")),
+            ["@everyone"],
+        );
+        assert_eq!(
+            code_owners.owners_of(Path::new("./src/main.rs")),
+            ["@core", "@reviewers"],
+        );
+        assert_eq!(code_owners.owners_of(Path::new("src/README.md")), ["@docs"]);
+    }
+
+    #[test]
+    fn owners_of_unowned() {
+        let code_owners = CodeOwners::parse(Path::new("CODEOWNERS"), "/src/ @core");
+
+        assert!(
+            code_owners
+                .owners_of(Path::new("./docs/index.md"))
+                .is_empty(),
+        );
+    }
+
+    #[test]
+    fn owners_of_absolute() {
+        let code_owners = CodeOwners::parse(Path::new("CODEOWNERS"), "* @everyone");
+        let root = current_dir().unwrap();
+
+        assert_eq!(
+            code_owners.owners_of(&root.join("src/main.rs")),
+            ["@everyone"],
+        );
+        assert!(
+            code_owners
+                .owners_of(&root.join("../elsewhere/a.rs"))
+                .is_empty(),
+        );
+        assert!(
+            code_owners
+                .owners_of(&temp_dir().join("tagref-codeowners/a.rs"))
+                .is_empty(),
+        );
+    }
+
+    #[test]
+    fn check_ok() {
+        let code_owners = CodeOwners::parse(Path::new("CODEOWNERS"), "/src/ @Core");
+
+        assert!(check(&code_owners, &[tag("./src/main.rs", "@core")]).is_empty());
+    }
+
+    #[test]
+    fn check_mismatch() {
+        let code_owners = CodeOwners::parse(Path::new("CODEOWNERS"), "/src/ @core");
+
+        let errors = check(
+            &code_owners,
+            &[tag("./src/main.rs", "@docs"), tag("./README.md", "@core")],
+        );
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].message.contains("`@core`"));
+        assert!(errors[1].message.contains("assigns no owners"));
+    }
+}
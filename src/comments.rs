@@ -0,0 +1,142 @@
+use std::path::Path;
+
+// This enum represents which parts of a file are searched for directives.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Extraction {
+    Everything,
+    Comments,
+    CommentsAndStrings,
+}
+
+impl Extraction {
+    // This function returns a name for the extraction mode, which is part of the cache key.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Everything => "everything",
+            Self::Comments => "comments",
+            Self::CommentsAndStrings => "comments-and-strings",
+        }
+    }
+}
+
+// This function chooses a tree-sitter grammar for a file based on its extension.
+#[cfg(feature = "tree-sitter")]
+pub fn grammar(path: &Path) -> Option<tree_sitter::Language> {
+    Some(match path.extension()?.to_str()? {
+        "c" | "h" => tree_sitter_c::LANGUAGE.into(),
+        "go" => tree_sitter_go::LANGUAGE.into(),
+        "js" | "jsx" | "mjs" | "cjs" => tree_sitter_javascript::LANGUAGE.into(),
+        "py" => tree_sitter_python::LANGUAGE.into(),
+        "rs" => tree_sitter_rust::LANGUAGE.into(),
+        "ts" => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+        "tsx" => tree_sitter_typescript::LANGUAGE_TSX.into(),
+        _ => return None,
+    })
+}
+
+// This function blanks out everything in a file except its comments (and string literals, if
+// requested), using a tree-sitter grammar chosen by the file extension. Every other character is
+// replaced by a space, and line breaks are kept, so line numbers are unaffected. It returns `None`
+// if everything should be searched, e.g., because there's no grammar for the file.
+#[cfg(feature = "tree-sitter")]
+pub fn extract(path: &Path, contents: &str, extraction: Extraction) -> Option<String> {
+    if extraction == Extraction::Everything {
+        return None;
+    }
+    let language = grammar(path)?;
+    let keep = |kind: &str| {
+        kind.contains("comment")
+            || (extraction == Extraction::CommentsAndStrings && kind.contains("string"))
+    };
+
+    let mut parser = tree_sitter::Parser::new();
+    parser.set_language(&language).ok()?;
+    let tree = parser.parse(contents, None)?;
+
+    // Visit the nodes in order, without descending into the ones which are kept.
+    let mut kept = Vec::new();
+    let mut cursor = tree.walk();
+    'walk: loop {
+        let node = cursor.node();
+        if keep(node.kind()) {
+            kept.push(node.byte_range());
+        } else if cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'walk;
+            }
+        }
+    }
+
+    // Blank out everything else. Every byte of a multi-byte character is replaced, so the result is
+    // still valid UTF-8.
+    let mut bytes = contents.as_bytes().to_owned();
+    let mut next = 0;
+    for range in kept {
+        blank(&mut bytes[next..range.start]);
+        next = range.end;
+    }
+    blank(&mut bytes[next..]);
+    String::from_utf8(bytes).ok()
+}
+
+// This function replaces everything but line breaks with spaces.
+#[cfg(feature = "tree-sitter")]
+fn blank(bytes: &mut [u8]) {
+    for byte in bytes {
+        if *byte != b'\n' {
+            *byte = b' ';
+        }
+    }
+}
+
+// Without tree-sitter, everything is searched.
+#[cfg(not(feature = "tree-sitter"))]
+pub fn extract(_path: &Path, _contents: &str, _extraction: Extraction) -> Option<String> {
+    None
+}
+
+#[cfg(all(test, feature = "tree-sitter"))]
+mod tests {
+    use {
+        crate::comments::{extract, Extraction},
+        std::path::Path,
+    };
+
+    #[test]
+    fn extract_comments() {
+        let contents =
+            "let x = \"[?tag:foo]\"; // [?tag:bar]\n/* é */ fn f() {}\n".replace('?', "");
+        let extracted = extract(Path::new("a.rs"), &contents, Extraction::Comments).unwrap();
+
+        assert_eq!(
+            extracted,
+            "                     // [?tag:bar]\n/* é */          \n".replace('?', ""),
+        );
+    }
+
+    #[test]
+    fn extract_comments_and_strings() {
+        let contents = "x = '[?tag:foo]'  # [?tag:bar]\ny = 1\n".replace('?', "");
+        let extracted =
+            extract(Path::new("a.py"), &contents, Extraction::CommentsAndStrings).unwrap();
+
+        assert_eq!(
+            extracted,
+            "    '[?tag:foo]'  # [?tag:bar]\n     \n".replace('?', ""),
+        );
+    }
+
+    #[test]
+    fn extract_unknown_language() {
+        assert!(extract(Path::new("a.txt"), "[?tag:foo]", Extraction::Comments).is_none());
+        assert!(extract(Path::new("Makefile"), "[?tag:foo]", Extraction::Comments).is_none());
+    }
+
+    #[test]
+    fn extract_everything() {
+        assert!(extract(Path::new("a.rs"), "[?tag:foo]", Extraction::Everything).is_none());
+    }
+}
@@ -0,0 +1,1236 @@
+use {
+    crate::{
+        docs::DOCS_FORMATS, history::HISTORY_FORMATS, output::FORMATS, report::REPORT_FORMATS,
+        report_diff::DIFF_FORMATS, walk::normalize,
+    },
+    globset::{Glob, GlobMatcher},
+    regex::Regex,
+    serde::{de::Error, Deserialize, Deserializer},
+    std::{
+        collections::BTreeMap,
+        fmt,
+        fs::read_to_string,
+        io::ErrorKind,
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+
+// The configuration file that is loaded when no other one is specified
+pub const DEFAULT_CONFIG_PATH: &str = ".tagref.toml";
+
+// The placeholder for the issue number in `issues.url`
+pub const ISSUE_PLACEHOLDER: &str = "{issue}";
+
+// The most times a URL is retried, since the delay doubles with each retry
+pub const MAX_URL_RETRIES: u32 = 10;
+
+// This struct represents the contents of the configuration file.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    // Tags with labels matching any of these patterns must have a description.
+    #[serde(deserialize_with = "deserialize_regexes")]
+    pub require_descriptions: Vec<Regex>,
+
+    // Whether to warn about tags with labels so similar that one is probably a typo of the other
+    pub warn_similar_labels: bool,
+
+    // Whether to warn about tags with labels which look alike because of non-ASCII characters
+    pub warn_confusable_labels: bool,
+
+    // Whether the names of files can define tags, as in `0005-caching.tag-caching_strategy.md`
+    pub path_tags: bool,
+
+    // Whether to warn about text which looks like a directive but isn't one, e.g., because of a
+    // missing colon or bracket or a misspelled sigil
+    pub warn_malformed_directives: bool,
+
+    // Whether to warn about references with descriptions which mention paths or labels which don't
+    // exist
+    pub warn_stale_descriptions: bool,
+
+    // Tags must not use these labels.
+    pub reserved_labels: Vec<ReservedLabel>,
+
+    // Labels which are intentionally used by more than one tag
+    pub duplicate_tolerance: Vec<DuplicateTolerance>,
+
+    // Limits on the number of tags in each file
+    pub tag_budgets: Vec<TagBudget>,
+
+    // Files which must contain certain tags
+    pub required_tags: Vec<RequiredTag>,
+
+    // Files which should contain a minimum number of directives
+    pub directive_density: Vec<DirectiveDensity>,
+
+    // Where references may appear relative to the tags they refer to
+    pub ref_placement: RefPlacement,
+
+    // The packages which each have their own tags
+    pub packages: Packages,
+
+    // How URL references are checked
+    pub urls: Urls,
+
+    // The issue tracker which the `until` metadata of tags refers to
+    pub issues: Issues,
+
+    // A file of words (one per line) which `--lint-descriptions` accepts in addition to the bundled
+    // word list
+    pub dictionary: Option<PathBuf>,
+
+    // How the problems found by each check (identified by its code) are reported
+    pub severity: BTreeMap<String, Level>,
+
+    // Problems which are not reported in certain files
+    pub suppressions: Vec<Suppression>,
+
+    // Where generated files are generated from, so problems in them are reported there instead
+    pub generated: Vec<Generated>,
+
+    // Sources of tags other than tag directives
+    pub providers: Vec<Provider>,
+
+    // Commands which convert files to text to be searched for directives
+    pub extractors: Vec<Extractor>,
+
+    // Commands which decide whether the labels of the tags are acceptable
+    pub label_validators: Vec<LabelValidator>,
+
+    // Sets of sigils which are scanned for in addition to the ones given on the command line
+    pub sigil_sets: Vec<SigilSet>,
+
+    // Named sets of settings which can be selected with `--profile`
+    pub profiles: BTreeMap<String, Profile>,
+
+    // The paths to scan when none are given on the command line
+    pub paths: Vec<PathBuf>,
+
+    // Paths to skip, in addition to any given on the command line
+    pub excludes: Vec<PathPattern>,
+
+    // Sigils which replace the defaults, unless others are given on the command line
+    pub sigils: Sigils,
+
+    // Options for each subcommand which apply unless they're given on the command line
+    pub arguments: BTreeMap<String, BTreeMap<String, Argument>>,
+}
+
+// This enum represents how the problems found by a check are reported.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Error,
+    Warn,
+    Off,
+}
+
+// This enum represents where references may appear relative to the tags they refer to.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RefPlacement {
+    #[default]
+    Any,
+    SameFile,
+    OtherFile,
+}
+
+// This struct represents a glob which is matched against the paths of scanned files.
+#[derive(Clone)]
+pub struct PathPattern {
+    glob: String,
+    matcher: GlobMatcher,
+}
+
+impl PathPattern {
+    // This function compiles a glob.
+    pub fn new(glob: &str) -> Result<Self, String> {
+        Glob::new(glob)
+            .map(|compiled| Self {
+                glob: glob.to_owned(),
+                matcher: compiled.compile_matcher(),
+            })
+            .map_err(|error| format!("invalid glob `{glob}`: {error}"))
+    }
+
+    // This function determines whether a path matches the glob. Paths are matched relative to the
+    // working directory, so any `.` components are ignored.
+    pub fn is_match(&self, path: &Path) -> bool {
+        self.matcher.is_match(normalize(path))
+    }
+}
+
+impl FromStr for PathPattern {
+    type Err = String;
+
+    fn from_str(glob: &str) -> Result<Self, Self::Err> {
+        Self::new(glob)
+    }
+}
+
+impl fmt::Display for PathPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.glob)
+    }
+}
+
+impl<'de> Deserialize<'de> for PathPattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Self::new(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+// This struct represents a label (or a pattern of labels) that tags are not allowed to use.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ReservedLabel {
+    pub label: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_regex")]
+    pub pattern: Option<Regex>,
+    pub reason: Option<String>,
+}
+
+impl ReservedLabel {
+    // This function determines whether the given label is forbidden by this entry.
+    pub fn matches(&self, label: &str) -> bool {
+        self.label.as_deref() == Some(label)
+            || self
+                .pattern
+                .as_ref()
+                .is_some_and(|pattern| pattern.is_match(label))
+    }
+}
+
+// This struct represents how many tags may share a label matching a pattern, for labels which are
+// intentionally defined in several places. Exactly one of `count` (the exact number of tags) or
+// `max-count` (the maximum number) must be given.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct DuplicateTolerance {
+    #[serde(deserialize_with = "deserialize_regex")]
+    pub label: Regex,
+    pub count: Option<usize>,
+    pub max_count: Option<usize>,
+}
+
+impl DuplicateTolerance {
+    // This function determines whether this entry allows the given number of tags to share a label.
+    pub fn allows(&self, tags: usize) -> bool {
+        self.count.is_none_or(|count| tags == count)
+            && self.max_count.is_none_or(|max_count| tags <= max_count)
+    }
+}
+
+// This struct represents the maximum number of tags allowed in each file matching a glob (or in
+// every file, if there is no glob).
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct TagBudget {
+    pub path: Option<PathPattern>,
+    pub max_tags: usize,
+}
+
+// This struct represents a requirement that every file matching a glob contains at least one tag
+// with a label matching a pattern. If the requirement is exclusive, such tags must also not appear
+// in any other files.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct RequiredTag {
+    pub path: PathPattern,
+    #[serde(deserialize_with = "deserialize_regex")]
+    pub label: Regex,
+    #[serde(default)]
+    pub exclusive: bool,
+}
+
+// This struct represents the minimum number of directives (tags and references of any kind) which
+// each file matching a glob should contain.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct DirectiveDensity {
+    pub path: PathPattern,
+    pub min_directives: usize,
+}
+
+// This struct represents how the packages in a workspace are found. A package is a directory which
+// contains one of the manifests (e.g., `Cargo.toml`) or matches one of the globs. Tags in different
+// packages may have the same label.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Packages {
+    pub manifests: Vec<String>,
+    pub paths: Vec<PathPattern>,
+}
+
+// This struct represents how URL references are checked. Since checking them requires network
+// access, they're only checked if `check` is set. URLs are requested `concurrency` at a time, at
+// least `interval-ms` apart per domain, and transient failures are retried. URLs which work are
+// remembered in the `cache` file (if any) for `cache-ttl-hours`. If there are any `allow-domains`,
+// only URLs on those domains (or their subdomains) are checked, and URLs on any of the
+// `deny-domains` are never checked.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Urls {
+    pub check: bool,
+    pub concurrency: usize,
+    pub interval_ms: u64,
+    pub timeout_seconds: u64,
+    pub retries: u32,
+    pub cache: Option<PathBuf>,
+    pub cache_ttl_hours: u64,
+    pub allow_domains: Vec<String>,
+    pub deny_domains: Vec<String>,
+}
+
+impl Default for Urls {
+    fn default() -> Self {
+        Self {
+            check: false,
+            concurrency: 4,
+            interval_ms: 1000,
+            timeout_seconds: 10,
+            retries: 2,
+            cache: None,
+            cache_ttl_hours: 24,
+            allow_domains: vec![],
+            deny_domains: vec![],
+        }
+    }
+}
+
+// This struct represents the issue tracker which tags refer to with `until=#<issue>` metadata. The
+// `url` is that of the tracker's API for an issue, with `{issue}` in place of the issue number
+// (e.g., `https://api.github.com/repos/org/repo/issues/{issue}`). Since checking the issues
+// requires network access and usually a token, they're only checked if the token is in the
+// environment (see `issues::TOKEN_VARIABLE`).
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Issues {
+    pub url: Option<String>,
+    pub timeout_seconds: u64,
+}
+
+impl Default for Issues {
+    fn default() -> Self {
+        Self {
+            url: None,
+            timeout_seconds: 10,
+        }
+    }
+}
+
+// This struct represents a rule for suppressing problems found in files matching a glob. If there
+// are any codes, only problems found by those checks are suppressed. If there is a label pattern,
+// only problems with directives whose labels match it are suppressed.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Suppression {
+    pub path: PathPattern,
+    #[serde(default)]
+    pub codes: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_regex")]
+    pub label: Option<Regex>,
+}
+
+// This struct represents a rule for finding the file which generated files are generated from. The
+// source of a generated file is its path with the first match of the pattern replaced by `source`,
+// which can refer to groups in the pattern (as in `$1`).
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Generated {
+    #[serde(deserialize_with = "deserialize_regex")]
+    pub pattern: Regex,
+    pub source: String,
+}
+
+// This enum represents a source of tags other than tag directives, such as the symbols defined in
+// the code. References may point to these tags, but the tags are not checked themselves.
+#[derive(Deserialize)]
+#[serde(tag = "kind", deny_unknown_fields, rename_all = "kebab-case")]
+pub enum Provider {
+    // The tags in a tags file in the format written by `ctags`
+    Ctags { file: PathBuf },
+
+    // The names of the functions, types, etc. defined in the files matching a glob
+    Symbols { path: PathPattern },
+}
+
+// This struct represents an external command which converts the files matching a glob (e.g., PDFs
+// or Word documents) to text. The path of each file is appended to the command, and the command's
+// output is searched for directives instead of the file itself.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Extractor {
+    pub path: PathPattern,
+    pub command: Vec<String>,
+}
+
+// This struct represents an external command which validates labels, e.g., against a service
+// catalog. The labels of the tags (only those matching the `label` pattern, if there is one) are
+// written to its STDIN as JSON in batches of `batch-size`, and it writes a verdict for each label
+// to its STDOUT (see `label_validators::check`).
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct LabelValidator {
+    pub command: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_regex")]
+    pub label: Option<Regex>,
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+}
+
+// This function returns the default number of labels sent to a label validator at once.
+fn default_batch_size() -> usize {
+    100
+}
+
+// This struct represents a named set of sigils which are scanned for alongside the usual ones,
+// e.g., the old sigils during a migration to new ones. Types of directives without a sigil in the
+// set aren't scanned for with it.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SigilSet {
+    pub name: String,
+    pub tag: Option<String>,
+    pub r#ref: Option<String>,
+    pub file: Option<String>,
+    pub dir: Option<String>,
+    pub spec: Option<String>,
+    pub url: Option<String>,
+}
+
+impl SigilSet {
+    // This function returns the sigils in the set.
+    pub fn sigils(&self) -> impl Iterator<Item = &str> {
+        [
+            &self.tag,
+            &self.r#ref,
+            &self.file,
+            &self.dir,
+            &self.spec,
+            &self.url,
+        ]
+        .into_iter()
+        .filter_map(Option::as_deref)
+    }
+}
+
+// This struct represents the sigils which replace the defaults, e.g., to scan for `[anchor:...]`
+// instead of the usual tags without having to pass `--tag-sigil` every time.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sigils {
+    pub tag: Option<String>,
+    pub r#ref: Option<String>,
+    pub file: Option<String>,
+    pub dir: Option<String>,
+    pub spec: Option<String>,
+    pub url: Option<String>,
+}
+
+// This enum represents the value of an option in `arguments`: `true` for a flag, or the value (or
+// values, for an option which can be given more than once) to give the option.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum Argument {
+    Flag(bool),
+    Number(u64),
+    Value(String),
+    Values(Vec<String>),
+}
+
+impl Argument {
+    // This function returns the command-line arguments which give the option this value.
+    pub fn to_args(&self, name: &str) -> Vec<String> {
+        match self {
+            Self::Flag(true) => vec![format!("--{name}")],
+            Self::Flag(false) => vec![],
+            Self::Number(value) => vec![format!("--{name}={value}")],
+            Self::Value(value) => vec![format!("--{name}={value}")],
+            Self::Values(values) => values
+                .iter()
+                .map(|value| format!("--{name}={value}"))
+                .collect(),
+        }
+    }
+}
+
+// This struct represents a named set of settings, e.g., for running a different subset of the
+// checks before committing than in CI. The paths replace the default of scanning the working
+// directory, the excluded paths are added to any given on the command line, and the severity
+// overrides take precedence over the ones above.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Profile {
+    pub paths: Vec<PathBuf>,
+    pub excludes: Vec<PathPattern>,
+    pub severity: BTreeMap<String, Level>,
+}
+
+// This function deserializes a regular expression, reporting it if it fails to compile.
+fn deserialize_regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Regex, D::Error> {
+    let pattern = String::deserialize(deserializer)?;
+    Regex::new(&pattern)
+        .map_err(|error| D::Error::custom(format!("invalid pattern `{pattern}`: {error}")))
+}
+
+// This function deserializes a list of regular expressions, reporting any that fail to compile.
+fn deserialize_regexes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Regex>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|pattern| {
+            Regex::new(pattern)
+                .map_err(|error| D::Error::custom(format!("invalid pattern `{pattern}`: {error}")))
+        })
+        .collect()
+}
+
+// This function deserializes an optional regular expression, reporting it if it fails to compile.
+pub fn deserialize_optional_regex<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Regex>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|pattern| {
+            Regex::new(&pattern)
+                .map_err(|error| D::Error::custom(format!("invalid pattern `{pattern}`: {error}")))
+        })
+        .transpose()
+}
+
+// This function deserializes a map whose values are regular expressions, reporting any that fail to
+// compile.
+pub fn deserialize_regex_map<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, Regex>, D::Error> {
+    BTreeMap::<String, String>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(key, pattern)| {
+            Regex::new(&pattern)
+                .map(|regex| (key, regex))
+                .map_err(|error| D::Error::custom(format!("invalid pattern `{pattern}`: {error}")))
+        })
+        .collect()
+}
+
+// This function returns the values the `--format` option of a subcommand accepts, so a `format` in
+// `arguments` can be checked when the configuration file is loaded.
+fn formats(subcommand: &str) -> &'static [&'static str] {
+    match subcommand {
+        "check" | "merge" => REPORT_FORMATS,
+        "docs" => DOCS_FORMATS,
+        "history" => HISTORY_FORMATS,
+        "report-diff" => DIFF_FORMATS,
+        _ => FORMATS,
+    }
+}
+
+// This function checks that the `format` (if any) given for each subcommand in `arguments` is one
+// which it accepts.
+fn check_formats(arguments: &BTreeMap<String, BTreeMap<String, Argument>>) -> Result<(), String> {
+    for (subcommand, options) in arguments {
+        let values = match options.get("format") {
+            Some(Argument::Value(value)) => vec![value],
+            Some(Argument::Values(values)) => values.iter().collect(),
+            _ => vec![],
+        };
+        let formats = formats(subcommand);
+        for format in values {
+            if !formats.contains(&format.as_str()) {
+                return Err(format!(
+                    "`arguments.{subcommand}.format` must be one of {}.",
+                    formats
+                        .iter()
+                        .map(|format| format!("`{format}`"))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// This function parses the contents of a configuration file.
+pub fn parse(contents: &str) -> Result<Config, String> {
+    let config: Config = toml::from_str(contents).map_err(|error| error.to_string())?;
+
+    // Check the invariants that can't be expressed in the types.
+    for (i, reserved_label) in config.reserved_labels.iter().enumerate() {
+        if reserved_label.label.is_some() == reserved_label.pattern.is_some() {
+            return Err(format!(
+                "`reserved-labels` entry #{} must have exactly one of `label` or `pattern`.",
+                i + 1,
+            ));
+        }
+    }
+
+    // [tag:duplicate_tolerance_count]
+    for (i, tolerance) in config.duplicate_tolerance.iter().enumerate() {
+        if tolerance.count.is_some() == tolerance.max_count.is_some() {
+            return Err(format!(
+                "`duplicate-tolerance` entry #{} must have exactly one of `count` or `max-count`.",
+                i + 1,
+            ));
+        }
+    }
+
+    // [tag:extractor_command_nonempty]
+    for (i, extractor) in config.extractors.iter().enumerate() {
+        if extractor.command.is_empty() {
+            return Err(format!(
+                "`extractors` entry #{} must have a nonempty `command`.",
+                i + 1,
+            ));
+        }
+    }
+
+    // [tag:label_validator_command_nonempty]
+    for (i, validator) in config.label_validators.iter().enumerate() {
+        if validator.command.is_empty() {
+            return Err(format!(
+                "`label-validators` entry #{} must have a nonempty `command`.",
+                i + 1,
+            ));
+        }
+        if validator.batch_size == 0 {
+            return Err(format!(
+                "`label-validators` entry #{} must have a `batch-size` of at least 1.",
+                i + 1,
+            ));
+        }
+    }
+
+    if config.urls.concurrency == 0 {
+        return Err("`urls.concurrency` must be at least 1.".to_owned());
+    }
+
+    if config.urls.retries > MAX_URL_RETRIES {
+        return Err(format!("`urls.retries` must be at most {MAX_URL_RETRIES}."));
+    }
+
+    check_formats(&config.arguments)?;
+
+    if config
+        .issues
+        .url
+        .as_ref()
+        .is_some_and(|url| !url.contains(ISSUE_PLACEHOLDER))
+    {
+        return Err(format!(
+            "`issues.url` must contain `{ISSUE_PLACEHOLDER}` where the issue number goes.",
+        ));
+    }
+
+    for (i, sigil_set) in config.sigil_sets.iter().enumerate() {
+        if sigil_set.name.is_empty()
+            || config.sigil_sets[..i]
+                .iter()
+                .any(|other| other.name == sigil_set.name)
+        {
+            return Err(format!(
+                "`sigil-sets` entry #{} must have a nonempty `name` which no other entry has.",
+                i + 1,
+            ));
+        }
+        if [
+            &sigil_set.tag,
+            &sigil_set.r#ref,
+            &sigil_set.file,
+            &sigil_set.dir,
+            &sigil_set.spec,
+            &sigil_set.url,
+        ]
+        .iter()
+        .all(|sigil| sigil.is_none())
+        {
+            return Err(format!(
+                "`sigil-sets` entry #{} must have at least one sigil.",
+                i + 1,
+            ));
+        }
+    }
+
+    Ok(config)
+}
+
+// This function finds the nearest directory containing the default configuration file, starting
+// from the given directory and moving up to the root of the Git repository (the directory which
+// contains `.git`). Outside of a Git repository, it returns `None`.
+pub fn discover(directory: &Path) -> Option<PathBuf> {
+    let depth = directory
+        .ancestors()
+        .position(|ancestor| ancestor.join(".git").exists())?;
+
+    directory
+        .ancestors()
+        .take(depth + 1)
+        .find(|ancestor| ancestor.join(DEFAULT_CONFIG_PATH).is_file())
+        .map(ToOwned::to_owned)
+}
+
+// This function loads the configuration file. If a path is given, the file must exist. Otherwise,
+// the default configuration file is loaded if it exists.
+pub fn load(path: Option<&Path>) -> Result<Config, String> {
+    let (path, required) = match path {
+        Some(path) => (path, true),
+        None => (Path::new(DEFAULT_CONFIG_PATH), false),
+    };
+
+    match read_to_string(path) {
+        Ok(contents) => parse(&contents)
+            .map_err(|error| format!("Error in {}: {error}", path.to_string_lossy())),
+        Err(error) if error.kind() == ErrorKind::NotFound && !required => Ok(Config::default()),
+        Err(error) => Err(format!(
+            "Unable to read {}: {error}",
+            path.to_string_lossy(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::config::{
+            discover, parse, Argument, Level, Provider, RefPlacement, DEFAULT_CONFIG_PATH,
+        },
+        std::{
+            env::temp_dir,
+            fs::{create_dir_all, remove_dir_all, write},
+            path::Path,
+            process,
+        },
+    };
+
+    #[test]
+    fn parse_empty() {
+        let config = parse("").unwrap();
+
+        assert!(config.require_descriptions.is_empty());
+    }
+
+    #[test]
+    fn parse_require_descriptions() {
+        let config = parse("require-descriptions = ['^invariant_', '.']").unwrap();
+
+        assert_eq!(config.require_descriptions.len(), 2);
+        assert!(config.require_descriptions[0].is_match("invariant_sorted"));
+    }
+
+    #[test]
+    fn parse_invalid_pattern() {
+        assert!(parse("require-descriptions = ['(']").is_err());
+    }
+
+    #[test]
+    fn parse_reserved_labels() {
+        let config = parse(
+            "
+            [[reserved-labels]]
+            label = 'old_cache'
+            reason = 'Retired.'
+
+            [[reserved-labels]]
+            pattern = '^(todo|fixme)$'
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(config.reserved_labels.len(), 2);
+        assert!(config.reserved_labels[0].matches("old_cache"));
+        assert!(!config.reserved_labels[0].matches("old_cache_2"));
+        assert!(config.reserved_labels[1].matches("fixme"));
+        assert!(!config.reserved_labels[1].matches("fixme_later"));
+    }
+
+    #[test]
+    fn parse_reserved_labels_ambiguous() {
+        assert!(
+            parse(
+                "
+            [[reserved-labels]]
+            label = 'todo'
+            pattern = 'todo'
+            ",
+            )
+            .is_err(),
+        );
+        assert!(parse("[[reserved-labels]]").is_err());
+    }
+
+    #[test]
+    fn parse_duplicate_tolerance() {
+        let config = parse(
+            "
+            [[duplicate-tolerance]]
+            label = '^boilerplate_header$'
+            count = 3
+
+            [[duplicate-tolerance]]
+            label = '^example_'
+            max-count = 2
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(config.duplicate_tolerance.len(), 2);
+        assert!(config.duplicate_tolerance[0].allows(3));
+        assert!(!config.duplicate_tolerance[0].allows(2));
+        assert!(config.duplicate_tolerance[1].allows(1));
+        assert!(!config.duplicate_tolerance[1].allows(3));
+        assert!(parse("[[duplicate-tolerance]]\nlabel = 'x'").is_err());
+        assert!(parse("[[duplicate-tolerance]]\nlabel = 'x'\ncount = 2\nmax-count = 3").is_err());
+    }
+
+    #[test]
+    fn parse_label_validators() {
+        let config = parse(
+            "
+            [[label-validators]]
+            command = ['scripts/check-services']
+            label = '^svc_'
+            batch-size = 10
+
+            [[label-validators]]
+            command = ['scripts/check-adrs']
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(config.label_validators.len(), 2);
+        assert_eq!(config.label_validators[0].batch_size, 10);
+        assert_eq!(config.label_validators[1].batch_size, 100);
+        assert!(config.label_validators[1].label.is_none());
+        assert!(parse("[[label-validators]]\ncommand = []").is_err());
+        assert!(parse("[[label-validators]]\ncommand = ['x']\nbatch-size = 0").is_err());
+    }
+
+    #[test]
+    fn parse_issues() {
+        let config = parse(
+            "
+            [issues]
+            url = 'https://api.github.com/repos/org/repo/issues/{issue}'
+            ",
+        )
+        .unwrap();
+
+        assert!(config.issues.url.is_some());
+        assert_eq!(config.issues.timeout_seconds, 10);
+        assert!(parse("[issues]\nurl = 'https://example.com/issues'").is_err());
+    }
+
+    #[test]
+    fn parse_tag_budgets() {
+        let config = parse(
+            "
+            [[tag-budgets]]
+            max-tags = 10
+
+            [[tag-budgets]]
+            path = '*.{lock,json}'
+            max-tags = 0
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(config.tag_budgets.len(), 2);
+        assert!(config.tag_budgets[0].path.is_none());
+        assert_eq!(config.tag_budgets[0].max_tags, 10);
+        assert!(
+            config.tag_budgets[1]
+                .path
+                .as_ref()
+                .unwrap()
+                .is_match(Path::new("./data/package.json")),
+        );
+        assert_eq!(config.tag_budgets[1].max_tags, 0);
+    }
+
+    #[test]
+    fn parse_required_tags() {
+        let config = parse(
+            "
+            [[required-tags]]
+            path = 'migrations/*.sql'
+            label = '^migration_'
+            exclusive = true
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(config.required_tags.len(), 1);
+        assert!(
+            config.required_tags[0]
+                .path
+                .is_match(Path::new("./migrations/0001_init.sql")),
+        );
+        assert!(config.required_tags[0].label.is_match("migration_init"));
+        assert!(config.required_tags[0].exclusive);
+        assert!(parse("[[required-tags]]\npath = '*.sql'").is_err());
+    }
+
+    #[test]
+    fn parse_directive_density() {
+        let config = parse(
+            "
+            [[directive-density]]
+            path = 'docs/**/*.md'
+            min-directives = 2
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(config.directive_density.len(), 1);
+        assert!(
+            config.directive_density[0]
+                .path
+                .is_match(Path::new("./docs/guide/setup.md")),
+        );
+        assert_eq!(config.directive_density[0].min_directives, 2);
+        assert!(parse("[[directive-density]]\npath = '*.md'").is_err());
+    }
+
+    #[test]
+    fn parse_warn_stale_descriptions() {
+        assert!(!parse("").unwrap().warn_stale_descriptions);
+        assert!(
+            parse("warn-stale-descriptions = true")
+                .unwrap()
+                .warn_stale_descriptions,
+        );
+    }
+
+    #[test]
+    fn parse_warn_confusable_labels() {
+        assert!(!parse("").unwrap().warn_confusable_labels);
+        assert!(
+            parse("warn-confusable-labels = true")
+                .unwrap()
+                .warn_confusable_labels,
+        );
+    }
+
+    #[test]
+    fn parse_path_tags() {
+        assert!(!parse("").unwrap().path_tags);
+        assert!(parse("path-tags = true").unwrap().path_tags);
+    }
+
+    #[test]
+    fn parse_warn_malformed_directives() {
+        assert!(!parse("").unwrap().warn_malformed_directives);
+        assert!(
+            parse("warn-malformed-directives = true")
+                .unwrap()
+                .warn_malformed_directives,
+        );
+    }
+
+    #[test]
+    fn parse_warn_similar_labels() {
+        assert!(!parse("").unwrap().warn_similar_labels);
+        assert!(
+            parse("warn-similar-labels = true")
+                .unwrap()
+                .warn_similar_labels,
+        );
+    }
+
+    #[test]
+    fn parse_ref_placement() {
+        assert_eq!(parse("").unwrap().ref_placement, RefPlacement::Any);
+        assert_eq!(
+            parse("ref-placement = 'other-file'").unwrap().ref_placement,
+            RefPlacement::OtherFile,
+        );
+        assert!(parse("ref-placement = 'elsewhere'").is_err());
+    }
+
+    #[test]
+    fn parse_severity() {
+        let config = parse(
+            "
+            [severity]
+            E003 = 'warn'
+            E101 = 'off'
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(config.severity.len(), 2);
+        assert_eq!(config.severity["E003"], Level::Warn);
+        assert_eq!(config.severity["E101"], Level::Off);
+        assert!(parse("[severity]\nE003 = 'fatal'").is_err());
+    }
+
+    #[test]
+    fn parse_suppressions() {
+        let config = parse(
+            "
+            [[suppressions]]
+            path = 'examples/**'
+            codes = ['E002']
+            label = '^example_'
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(config.suppressions.len(), 1);
+        assert!(
+            config.suppressions[0]
+                .path
+                .is_match(Path::new("./examples/a/b.rs")),
+        );
+        assert_eq!(config.suppressions[0].codes, vec!["E002"]);
+        assert!(
+            config.suppressions[0]
+                .label
+                .as_ref()
+                .unwrap()
+                .is_match("example_foo"),
+        );
+        assert!(parse("[[suppressions]]\ncodes = ['E002']").is_err());
+    }
+
+    #[test]
+    fn parse_generated() {
+        let config = parse(
+            r"
+            [[generated]]
+            pattern = '^gen/(.*)\.rs$'
+            source = 'templates/$1.rs.tmpl'
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(config.generated.len(), 1);
+        assert!(config.generated[0].pattern.is_match("gen/a.rs"));
+        assert_eq!(config.generated[0].source, "templates/$1.rs.tmpl");
+        assert!(parse("[[generated]]\npattern = '('\nsource = 'a'").is_err());
+    }
+
+    #[test]
+    fn parse_providers() {
+        let config = parse(
+            "
+            [[providers]]
+            kind = 'ctags'
+            file = 'tags'
+
+            [[providers]]
+            kind = 'symbols'
+            path = 'src/**/*.rs'
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(config.providers.len(), 2);
+        match &config.providers[0] {
+            Provider::Ctags { file } => assert_eq!(file, Path::new("tags")),
+            Provider::Symbols { .. } => panic!("Expected a ctags provider."),
+        }
+        match &config.providers[1] {
+            Provider::Symbols { path } => assert!(path.is_match(Path::new("src/a/b.rs"))),
+            Provider::Ctags { .. } => panic!("Expected a symbols provider."),
+        }
+        assert!(parse("[[providers]]\nkind = 'ctags'").is_err());
+        assert!(parse("[[providers]]\nkind = 'ctags'\nfile = 'tags'\npath = 'x'").is_err());
+    }
+
+    #[test]
+    fn parse_extractors() {
+        let config = parse(
+            "
+            [[extractors]]
+            path = '*.docx'
+            command = ['pandoc', '--to', 'plain']
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(config.extractors.len(), 1);
+        assert!(
+            config.extractors[0]
+                .path
+                .is_match(Path::new("./docs/design.docx")),
+        );
+        assert_eq!(
+            config.extractors[0].command,
+            vec!["pandoc", "--to", "plain"],
+        );
+        assert!(parse("[[extractors]]\npath = '*.pdf'\ncommand = []").is_err());
+    }
+
+    #[test]
+    fn parse_sigil_sets() {
+        let config = parse(
+            "
+            [[sigil-sets]]
+            name = 'legacy'
+            tag = 'anchor'
+            ref = 'see'
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(config.sigil_sets.len(), 1);
+        assert_eq!(config.sigil_sets[0].name, "legacy");
+        assert_eq!(config.sigil_sets[0].tag.as_deref(), Some("anchor"));
+        assert_eq!(config.sigil_sets[0].r#ref.as_deref(), Some("see"));
+        assert!(config.sigil_sets[0].file.is_none());
+        assert!(parse("[[sigil-sets]]\nname = 'legacy'").is_err());
+        assert!(
+            parse("[[sigil-sets]]\nname = 'a'\ntag = 'x'\n[[sigil-sets]]\nname = 'a'\ntag = 'y'")
+                .is_err(),
+        );
+    }
+
+    #[test]
+    fn parse_urls() {
+        let config = parse(
+            "
+            [urls]
+            check = true
+            cache = '.tagref/urls.json'
+            deny-domains = ['example.com']
+            ",
+        )
+        .unwrap();
+
+        assert!(config.urls.check);
+        assert_eq!(config.urls.concurrency, 4);
+        assert_eq!(
+            config.urls.cache.as_deref(),
+            Some(Path::new(".tagref/urls.json")),
+        );
+        assert_eq!(config.urls.deny_domains, ["example.com"]);
+        assert!(!parse("").unwrap().urls.check);
+        assert!(parse("[urls]\nconcurrency = 0").is_err());
+        assert!(parse("[urls]\nretries = 10").is_ok());
+        assert!(parse("[urls]\nretries = 4294967295").is_err());
+    }
+
+    #[test]
+    fn parse_profiles() {
+        let config = parse(
+            "
+            [profiles.pre-commit]
+            paths = ['src']
+            excludes = ['src/generated/**']
+            severity = { E101 = 'off' }
+
+            [profiles.full]
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(config.profiles.len(), 2);
+        let profile = &config.profiles["pre-commit"];
+        assert_eq!(profile.paths, vec![Path::new("src")]);
+        assert!(profile.excludes[0].is_match(Path::new("./src/generated/a.rs")));
+        assert_eq!(profile.severity["E101"], Level::Off);
+        assert!(config.profiles["full"].paths.is_empty());
+        assert!(parse("[profiles.full]\nchecks = []").is_err());
+    }
+
+    #[test]
+    fn parse_project_settings() {
+        let config = parse(
+            "
+            paths = ['src', 'docs']
+            excludes = ['src/generated/**']
+
+            [sigils]
+            tag = 'anchor'
+            ref = 'see'
+
+            [arguments.check]
+            fail-on-conflict-markers = true
+            format = 'json'
+            max-warnings = 10
+
+            [arguments.list-tags]
+            where = ['owner = @core', 'stale']
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(config.paths, vec![Path::new("src"), Path::new("docs")]);
+        assert!(config.excludes[0].is_match(Path::new("./src/generated/a.rs")));
+        assert_eq!(config.sigils.tag.as_deref(), Some("anchor"));
+        assert_eq!(config.sigils.r#ref.as_deref(), Some("see"));
+        assert_eq!(config.sigils.file, None);
+        assert_eq!(
+            config.arguments["check"]
+                .iter()
+                .flat_map(|(name, value)| value.to_args(name))
+                .collect::<Vec<_>>(),
+            [
+                "--fail-on-conflict-markers",
+                "--format=json",
+                "--max-warnings=10",
+            ],
+        );
+        assert_eq!(
+            config.arguments["list-tags"]["where"],
+            Argument::Values(vec!["owner = @core".to_owned(), "stale".to_owned()]),
+        );
+        assert!(Argument::Flag(false).to_args("fix").is_empty());
+        assert!(parse("[arguments.check]\nformat = 'sarif'").is_err());
+        assert!(parse("[arguments.history]\nformat = 'csv'").is_ok());
+        assert!(parse("[arguments.check]\nformat = ['json', 'pdf']").is_err());
+        assert!(parse("[arguments.list-tags]\nformat = 'csv'").is_err());
+        assert!(
+            parse(
+                "[sigils]
+label = 'name'",
+            )
+            .is_err(),
+        );
+    }
+
+    #[test]
+    fn parse_invalid_glob() {
+        assert!(
+            parse(
+                "
+            [[tag-budgets]]
+            path = '['
+            max-tags = 0
+            ",
+            )
+            .is_err(),
+        );
+    }
+
+    #[test]
+    fn parse_unknown_key() {
+        assert!(parse("unknown-key = true").is_err());
+    }
+
+    #[test]
+    fn discover_upward() {
+        let directory = temp_dir().join(format!("tagref-config-discover-{}", process::id()));
+        let _ = remove_dir_all(&directory);
+        let project = directory.join("project");
+        let nested = project.join("src/nested");
+        create_dir_all(project.join(".git")).unwrap();
+        create_dir_all(&nested).unwrap();
+
+        // Configuration files outside the repository are never used.
+        write(directory.join(DEFAULT_CONFIG_PATH), "").unwrap();
+        let outside = discover(&nested);
+        write(project.join(DEFAULT_CONFIG_PATH), "").unwrap();
+        let inside = discover(&nested);
+        let outside_repository = discover(&directory);
+        let _ = remove_dir_all(&directory);
+
+        assert!(outside.is_none());
+        assert_eq!(inside, Some(project));
+        assert!(outside_repository.is_none());
+    }
+}
@@ -0,0 +1,54 @@
+use {
+    crate::diagnostic::{self, Diagnostic, Severity},
+    std::path::PathBuf,
+};
+
+// This function reports the merge conflicts found while scanning, given their paths and starting
+// line numbers. They are errors if `fail` is set, and warnings otherwise.
+pub fn check(conflicts: &[(PathBuf, usize)], fail: bool) -> Vec<Diagnostic> {
+    conflicts
+        .iter()
+        .map(|(path, line_number)| Diagnostic {
+            code: diagnostic::CONFLICT_MARKER.to_owned(),
+            severity: if fail {
+                Severity::Error
+            } else {
+                Severity::Warning
+            },
+            message: format!(
+                "Merge conflict @ {}:{line_number}. The directives within it were ignored.",
+                path.to_string_lossy(),
+            ),
+            location: Some((path.clone(), *line_number)),
+            label: None,
+            context: None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{conflicts::check, diagnostic::Severity},
+        std::path::Path,
+    };
+
+    #[test]
+    fn check_empty() {
+        assert!(check(&[], true).is_empty());
+    }
+
+    #[test]
+    fn check_severity() {
+        let conflicts = [(Path::new("file1.rs").to_owned(), 3)];
+
+        let warnings = check(&conflicts, false);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].severity, Severity::Warning);
+        assert!(warnings[0].message.contains("file1.rs:3"));
+
+        let errors = check(&conflicts, true);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].severity, Severity::Error);
+    }
+}
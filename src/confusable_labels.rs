@@ -0,0 +1,101 @@
+use {
+    crate::{
+        diagnostic::{self, Diagnostic},
+        directive::Directive,
+    },
+    std::collections::{BTreeMap, HashMap},
+};
+
+// This function returns the skeleton of a label, as defined by Unicode Technical Standard #39.
+// Labels with the same skeleton look alike, e.g., `cache` written with a Latin `a` and with a
+// Cyrillic `а`.
+fn skeleton(label: &str) -> String {
+    unicode_security::skeleton(label).collect()
+}
+
+// This function finds tags with labels which are distinct but look alike because of non-ASCII
+// characters, since one of them was probably written by mistake. Lookalikes within ASCII, such as
+// `rn` and `m`, are left alone. The warning is located at each tag with a label other than the
+// first one alphabetically. It returns a vector of warnings.
+pub fn check(tags_map: &HashMap<String, Vec<Directive>>) -> Vec<Diagnostic> {
+    let mut warnings = Vec::<Diagnostic>::new();
+
+    // Group the labels by skeleton.
+    let mut labels_by_skeleton = BTreeMap::<String, Vec<&str>>::new();
+    for label in tags_map.keys() {
+        labels_by_skeleton
+            .entry(skeleton(label))
+            .or_default()
+            .push(label);
+    }
+
+    for mut labels in labels_by_skeleton.into_values() {
+        if labels.iter().all(|label| label.is_ascii()) {
+            continue;
+        }
+        labels.sort_unstable();
+
+        // The `[0]`s are safe because each label has at least one tag.
+        let first = &tags_map[labels[0]][0];
+        for label in &labels[1..] {
+            let tag = &tags_map[*label][0];
+            warnings.push(Diagnostic::warning(
+                diagnostic::CONFUSABLE_LABELS,
+                tag,
+                format!(
+                    "{tag} and {first} have labels which look alike but are written with different \
+                     characters.",
+                ),
+            ));
+        }
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            confusable_labels::check,
+            directive::{Directive, Type},
+        },
+        std::collections::HashMap,
+    };
+
+    fn tags(labels: &[&str]) -> HashMap<String, Vec<Directive>> {
+        labels
+            .iter()
+            .map(|label| {
+                (
+                    (*label).to_owned(),
+                    vec![Directive::new(Type::Tag, label, "file.rs", 1)],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn check_distinct() {
+        let tags = tags(&[
+            "cache",
+            "cache_v2",
+            "r\u{e9}gle_cache",
+            "regle_cache",
+            "burn",
+            "bum",
+        ]);
+
+        assert!(check(&tags).is_empty());
+    }
+
+    #[test]
+    fn check_confusable() {
+        // The second label has a Cyrillic `а`.
+        let tags = tags(&["cache", "c\u{430}che", "retry"]);
+
+        let warnings = check(&tags);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].label.as_deref(), Some("c\u{430}che"));
+    }
+}
@@ -0,0 +1,167 @@
+use {
+    crate::directive::Directive,
+    std::{collections::BTreeMap, fmt::Write, io, path::Path},
+};
+
+// This function sorts tags by label, then by location, as required for binary search by editors.
+fn sorted(tags: &[Directive]) -> Vec<&Directive> {
+    let mut tags = tags.iter().collect::<Vec<_>>();
+    tags.sort_by(|x, y| {
+        (&x.label, &x.path, x.line_number).cmp(&(&y.label, &y.path, y.line_number))
+    });
+    tags
+}
+
+// This function renders an index of the given tags in the format used by `ctags` (e.g., for Vim).
+// The `display` function determines how each path is written.
+pub fn ctags(tags: &[Directive], display: impl Fn(&Path) -> String) -> String {
+    let mut output = "\
+        !_TAG_FILE_FORMAT\t2\t/extended format/\n\
+        !_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted, 2=foldcase/\n\
+        !_TAG_PROGRAM_NAME\ttagref\t//\n\
+        "
+    .to_owned();
+
+    for tag in sorted(tags) {
+        let _ = writeln!(
+            output,
+            "{}\t{}\t{};\"\tt",
+            tag.label.replace('\t', " "),
+            display(&tag.path),
+            tag.line_number,
+        );
+    }
+
+    output
+}
+
+// This function renders an index of the given tags in the format used by `etags` (e.g., for
+// Emacs). Unlike `ctags`, this format includes the text and byte offset of each line, so the files
+// containing the tags are read with the given `read` function.
+pub fn etags(
+    tags: &[Directive],
+    display: impl Fn(&Path) -> String,
+    read: impl Fn(&Path) -> io::Result<Vec<u8>>,
+) -> io::Result<Vec<u8>> {
+    // Group the tags by file.
+    let mut tags_by_file = BTreeMap::<&Path, Vec<&Directive>>::new();
+    for tag in sorted(tags) {
+        tags_by_file.entry(&tag.path).or_default().push(tag);
+    }
+
+    let mut output = vec![];
+    for (path, mut file_tags) in tags_by_file {
+        file_tags.sort_by_key(|tag| tag.line_number);
+
+        // Find where each line starts.
+        let contents = read(path)?;
+        let mut line_starts = vec![0];
+        line_starts.extend(
+            contents
+                .iter()
+                .enumerate()
+                .filter(|(_, byte)| **byte == b'\n')
+                .map(|(i, _)| i + 1),
+        );
+
+        // Each entry consists of the text of the line, the name of the tag, the line number, and
+        // the offset of the start of the line.
+        let mut section = vec![];
+        for tag in file_tags {
+            let start = line_starts
+                .get(tag.line_number - 1)
+                .copied()
+                .unwrap_or(contents.len());
+            let end = contents[start..]
+                .iter()
+                .position(|byte| *byte == b'\n')
+                .map_or(contents.len(), |length| start + length);
+            let line = contents[start..end]
+                .strip_suffix(b"\r")
+                .unwrap_or(&contents[start..end]);
+            section.extend_from_slice(line);
+            section.push(0x7f);
+            section.extend_from_slice(tag.label.as_bytes());
+            section.push(0x01);
+            section.extend_from_slice(format!("{},{start}\n", tag.line_number).as_bytes());
+        }
+
+        output.extend_from_slice(format!("\x0c\n{},{}\n", display(path), section.len()).as_bytes());
+        output.extend_from_slice(&section);
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            ctags::{ctags, etags},
+            directive::{Directive, Type},
+        },
+        std::{io, path::Path},
+    };
+
+    fn tag(label: &str, path: &str, line_number: usize) -> Directive {
+        Directive::new(Type::Tag, label, path, line_number)
+    }
+
+    fn display(path: &Path) -> String {
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn ctags_sorted() {
+        assert_eq!(
+            ctags(
+                &[tag("tag2", "file1.rs", 3), tag("tag1", "file2.rs", 1)],
+                display,
+            ),
+            "!_TAG_FILE_FORMAT\t2\t/extended format/\n\
+             !_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted, 2=foldcase/\n\
+             !_TAG_PROGRAM_NAME\ttagref\t//\n\
+             tag1\tfile2.rs\t1;\"\tt\n\
+             tag2\tfile1.rs\t3;\"\tt\n",
+        );
+    }
+
+    #[test]
+    fn etags_sections() {
+        let output = etags(
+            &[
+                tag("tag2", "file1.rs", 3),
+                tag("tag1", "file1.rs", 1),
+                tag("tag3", "file2.rs", 2),
+            ],
+            display,
+            |path| {
+                Ok(if path == Path::new("file1.rs") {
+                    b"// tag1\r\nx\n// tag2\n".to_vec()
+                } else {
+                    b"\n// tag3".to_vec()
+                })
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\x0c\nfile1.rs,35\n\
+             // tag1\x7ftag1\x011,0\n\
+             // tag2\x7ftag2\x013,11\n\
+             \x0c\nfile2.rs,17\n\
+             // tag3\x7ftag3\x012,1\n",
+        );
+    }
+
+    #[test]
+    fn etags_unreadable() {
+        assert!(
+            etags(&[tag("tag1", "file1.rs", 1)], display, |_| {
+                Err(io::Error::new(io::ErrorKind::NotFound, "missing"))
+            })
+            .is_err(),
+        );
+    }
+}
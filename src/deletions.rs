@@ -0,0 +1,158 @@
+use {
+    crate::{
+        diagnostic::{self, Diagnostic},
+        directive::Directive,
+        path_style::display,
+        walk::{normalize, quote},
+    },
+    std::{
+        collections::{HashMap, HashSet},
+        path::{Path, PathBuf},
+    },
+};
+
+// This function describes what happened to a file since the revision.
+fn fate(path: &Path, renamed_to: Option<&PathBuf>, revision: &str) -> String {
+    match renamed_to {
+        Some(new_path) => format!(
+            "{}, which was renamed to {} since {revision}",
+            quote(&display(path)),
+            quote(&display(new_path)),
+        ),
+        None => format!(
+            "{}, which was deleted since {revision}",
+            quote(&display(path)),
+        ),
+    }
+}
+
+// This function finds the references which point into files that were deleted or renamed since a
+// revision: tag references to tags which were only defined in those files, and file references to
+// the files themselves. The tags are the ones which were in the removed files at the revision, and
+// the labels are the ones which exist now. References to files which exist again are skipped. It
+// returns a vector of errors, sorted by location.
+pub fn check(
+    revision: &str,
+    removed: &[(PathBuf, Option<PathBuf>)],
+    old_tags: &[Directive],
+    labels: &HashSet<String>,
+    refs: &[Directive],
+    files: &[Directive],
+    exists: impl Fn(&Path) -> bool,
+) -> Vec<Diagnostic> {
+    let removed = removed
+        .iter()
+        .map(|(path, renamed_to)| (normalize(path), (path, renamed_to.as_ref())))
+        .collect::<HashMap<_, _>>();
+    let mut errors = Vec::<Diagnostic>::new();
+
+    // Find where each removed tag was defined.
+    let mut old_locations = HashMap::<&str, &Path>::new();
+    for tag in old_tags {
+        old_locations.entry(&tag.label).or_insert(&tag.path);
+    }
+    for r#ref in refs {
+        if labels.contains(&r#ref.label) {
+            continue;
+        }
+        let Some((path, renamed_to)) = old_locations
+            .get(r#ref.label.as_str())
+            .and_then(|path| removed.get(&normalize(path)))
+        else {
+            continue;
+        };
+        errors.push(Diagnostic::error(
+            diagnostic::DANGLING_REF,
+            r#ref,
+            format!(
+                "{ref} points to a tag which was in {}.",
+                fate(path, *renamed_to, revision),
+            ),
+        ));
+    }
+
+    for file in files {
+        let target = Path::new(&file.label);
+        let Some((path, renamed_to)) = removed.get(&normalize(target)) else {
+            continue;
+        };
+        if !exists(target) {
+            errors.push(Diagnostic::error(
+                diagnostic::BAD_FILE_REF,
+                file,
+                format!("{file} points to {}.", fate(path, *renamed_to, revision)),
+            ));
+        }
+    }
+
+    errors.sort_by(|x, y| x.location.cmp(&y.location));
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            deletions::check,
+            directive::{Directive, Type},
+        },
+        std::{
+            collections::HashSet,
+            path::{Path, PathBuf},
+        },
+    };
+
+    #[test]
+    fn check_removed_files() {
+        let removed = [
+            (PathBuf::from("old.rs"), None),
+            (PathBuf::from("src/a.rs"), Some(PathBuf::from("src/b.rs"))),
+            (PathBuf::from("back.rs"), None),
+        ];
+        let old_tags = [
+            Directive::new(Type::Tag, "gone", "old.rs", 1),
+            Directive::new(Type::Tag, "moved", "src/a.rs", 2),
+            Directive::new(Type::Tag, "dropped", "src/a.rs", 3),
+        ];
+        let labels = HashSet::from(["moved".to_owned(), "other".to_owned()]);
+        let refs = [
+            Directive::new(Type::Ref, "moved", "./c.rs", 1),
+            Directive::new(Type::Ref, "gone", "./c.rs", 2),
+            Directive::new(Type::Ref, "dropped", "./b.rs", 1),
+            Directive::new(Type::Ref, "missing", "./b.rs", 2),
+        ];
+        let files = [
+            Directive::new(Type::File, "./old.rs", "./c.rs", 3),
+            Directive::new(Type::File, "src/a.rs", "./b.rs", 3),
+            Directive::new(Type::File, "back.rs", "./b.rs", 4),
+            Directive::new(Type::File, "src/c.rs", "./b.rs", 5),
+        ];
+
+        let errors = check(
+            "main",
+            &removed,
+            &old_tags,
+            &labels,
+            &refs,
+            &files,
+            |path| path == Path::new("back.rs"),
+        );
+
+        let messages = errors
+            .iter()
+            .map(|error| error.message.replace('[', "[?"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "[?ref:dropped] @ ./b.rs:1 points to a tag which was in src/a.rs, which was \
+                 renamed to src/b.rs since main.",
+                "[?file:src/a.rs] @ ./b.rs:3 points to src/a.rs, which was renamed to src/b.rs \
+                 since main.",
+                "[?ref:gone] @ ./c.rs:2 points to a tag which was in old.rs, which was deleted \
+                 since main.",
+                "[?file:./old.rs] @ ./c.rs:3 points to old.rs, which was deleted since main.",
+            ],
+        );
+    }
+}
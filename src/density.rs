@@ -0,0 +1,121 @@
+use {
+    crate::{
+        config::DirectiveDensity,
+        count::count,
+        diagnostic::{self, Diagnostic},
+        directive::Directive,
+    },
+    std::{collections::HashMap, path::PathBuf},
+};
+
+// This function adds the given directives to the number of directives in each file. The directives
+// can be counted in several batches.
+pub fn tally<'a>(
+    counts: &mut HashMap<PathBuf, usize>,
+    directives: impl IntoIterator<Item = &'a Directive>,
+) {
+    for directive in directives {
+        if let Some(count) = counts.get_mut(&directive.path) {
+            *count += 1;
+        } else {
+            counts.insert(directive.path.clone(), 1);
+        }
+    }
+}
+
+// This function checks that every file matching a `directive-density` entry contains at least as
+// many directives as the entry asks for. The `files` are the paths of all the scanned files, and
+// the `counts` are the number of directives in each file (see `tally`). It returns a vector of
+// warnings, since a file with few directives isn't necessarily wrong.
+pub fn check(
+    files: &[PathBuf],
+    counts: &HashMap<PathBuf, usize>,
+    expectations: &[DirectiveDensity],
+) -> Vec<Diagnostic> {
+    let mut warnings = Vec::<Diagnostic>::new();
+
+    for (i, expectation) in expectations.iter().enumerate() {
+        for path in files.iter().filter(|path| expectation.path.is_match(path)) {
+            let directives = counts.get(path).copied().unwrap_or(0);
+            if directives < expectation.min_directives {
+                warnings.push(Diagnostic::warning_at(
+                    diagnostic::DIRECTIVE_DENSITY,
+                    path,
+                    1,
+                    format!(
+                        "{} contains {}, but `directive-density` entry #{} (`{}`) expects at \
+                            least {}.",
+                        path.to_string_lossy(),
+                        count(directives, "directive"),
+                        i + 1,
+                        expectation.path,
+                        expectation.min_directives,
+                    ),
+                ));
+            }
+        }
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            config::parse,
+            density::{check, tally},
+            directive::{Directive, Type},
+        },
+        std::{collections::HashMap, path::PathBuf},
+    };
+
+    fn directive(r#type: Type, path: &str) -> Directive {
+        Directive::new(r#type, "label", path, 1)
+    }
+
+    #[test]
+    fn check_density() {
+        let config = parse(
+            "
+            [[directive-density]]
+            path = 'docs/**/*.md'
+            min-directives = 2
+            ",
+        )
+        .unwrap();
+
+        let files = [
+            "./docs/a.md",
+            "./docs/guide/b.md",
+            "./docs/c.md",
+            "./src/d.rs",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+        let directives = [
+            directive(Type::Tag, "./docs/a.md"),
+            directive(Type::Ref, "./docs/a.md"),
+            directive(Type::File, "./docs/guide/b.md"),
+        ];
+
+        let mut counts = HashMap::new();
+        tally(&mut counts, &directives[..2]);
+        tally(&mut counts, &directives[2..]);
+
+        let warnings = check(&files, &counts, &config.directive_density);
+        assert_eq!(warnings.len(), 2);
+        assert!(
+            warnings[0]
+                .message
+                .starts_with("./docs/guide/b.md contains 1 directive,"),
+        );
+        assert!(warnings[1].message.contains("c.md contains 0 directives"));
+        assert!(
+            warnings[1]
+                .message
+                .contains("entry #1 (`docs/**/*.md`) expects at least 2."),
+        );
+    }
+}
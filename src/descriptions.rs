@@ -0,0 +1,66 @@
+use {
+    crate::{
+        diagnostic::{self, Diagnostic},
+        directive::Directive,
+    },
+    regex::Regex,
+};
+
+// This function checks that tags with labels matching any of the given patterns have descriptions.
+// It returns a vector of errors.
+pub fn check(tags: &[Directive], patterns: &[Regex]) -> Vec<Diagnostic> {
+    let mut errors = Vec::<Diagnostic>::new();
+
+    for tag in tags {
+        if tag.description.is_empty() {
+            if let Some(pattern) = patterns.iter().find(|pattern| pattern.is_match(&tag.label)) {
+                errors.push(Diagnostic::error(
+                    diagnostic::MISSING_DESCRIPTION,
+                    tag,
+                    format!("{tag} has no description, but tags matching `{pattern}` require one."),
+                ));
+            }
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            descriptions::check,
+            directive::{Directive, Type},
+        },
+        regex::Regex,
+    };
+
+    fn tag(label: &str, description: &str) -> Directive {
+        Directive {
+            description: description.to_owned(),
+            ..Directive::new(Type::Tag, label, "file.rs", 1)
+        }
+    }
+
+    #[test]
+    fn check_empty() {
+        assert!(check(&[], &[Regex::new(".").unwrap()]).is_empty());
+    }
+
+    #[test]
+    fn check_ok() {
+        let tags = [tag("hack1", "This explains the hack."), tag("other", "")];
+
+        assert!(check(&tags, &[Regex::new("^hack").unwrap()]).is_empty());
+    }
+
+    #[test]
+    fn check_missing() {
+        let tags = [tag("hack1", "This explains the hack."), tag("hack2", "")];
+
+        let errors = check(&tags, &[Regex::new("^hack").unwrap()]);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("hack2"));
+    }
+}
@@ -0,0 +1,113 @@
+use {
+    crate::directive::Directive,
+    serde::{Deserialize, Serialize},
+    std::{
+        fmt,
+        path::{Path, PathBuf},
+    },
+};
+
+// These are the codes for the built-in checks. They are stable, so they can be referenced from
+// configuration files and by tools which consume the machine-readable output formats.
+pub const DUPLICATE_TAG: &str = "E001";
+pub const DANGLING_REF: &str = "E002";
+pub const BAD_FILE_REF: &str = "E003";
+pub const BAD_DIR_REF: &str = "E004";
+pub const BAD_SPEC_REF: &str = "E005";
+pub const BAD_URL_REF: &str = "E006";
+pub const OWNER_MISMATCH: &str = "E101";
+pub const MISSING_DESCRIPTION: &str = "E102";
+pub const RESERVED_LABEL: &str = "E103";
+pub const TAG_BUDGET: &str = "E104";
+pub const MISSING_REQUIRED_TAG: &str = "E105";
+pub const MISPLACED_REQUIRED_TAG: &str = "E106";
+pub const REF_PLACEMENT: &str = "E107";
+pub const CONFLICT_MARKER: &str = "E108";
+pub const SIMILAR_LABELS: &str = "E109";
+pub const DIRECTIVE_DENSITY: &str = "E110";
+pub const STALE_DESCRIPTION: &str = "E111";
+pub const UNREACHABLE_URL: &str = "E112";
+pub const MISSPELLING: &str = "E113";
+pub const REJECTED_LABEL: &str = "E114";
+pub const CLOSED_ISSUE: &str = "E115";
+pub const CONFUSABLE_LABELS: &str = "E116";
+pub const MALFORMED_DIRECTIVE: &str = "E117";
+pub const UNCHECKED_ISSUE: &str = "E118";
+
+// This enum represents how serious a problem is. Only errors cause a check to fail.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Warning => "warning",
+                Self::Error => "error",
+            },
+        )
+    }
+}
+
+// This struct represents a problem found by a check. The code identifies the check (or the policy
+// rule) which found it, and the location (if any) is where the problem should be reported. The
+// label (if any) is that of the directive with the problem, and the context (if any) is the line
+// containing it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Diagnostic {
+    pub code: String,
+    pub severity: Severity,
+    pub message: String,
+    pub location: Option<(PathBuf, usize)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+}
+
+// This function returns the context of a directive for a diagnostic, if it was captured.
+pub fn context(directive: &Directive) -> Option<String> {
+    (!directive.context.is_empty()).then(|| directive.context.clone())
+}
+
+impl Diagnostic {
+    // This function constructs an error located at the given directive.
+    pub fn error(code: &str, directive: &Directive, message: String) -> Self {
+        Self {
+            code: code.to_owned(),
+            severity: Severity::Error,
+            message,
+            location: Some((directive.path.clone(), directive.line_number)),
+            label: Some(directive.label.clone()),
+            context: context(directive),
+        }
+    }
+
+    // This function constructs a warning located at the given directive.
+    pub fn warning(code: &str, directive: &Directive, message: String) -> Self {
+        Self {
+            label: Some(directive.label.clone()),
+            context: context(directive),
+            ..Self::warning_at(code, &directive.path, directive.line_number, message)
+        }
+    }
+
+    // This function constructs a warning located at the given line of a file, for problems which
+    // aren't about any one directive.
+    pub fn warning_at(code: &str, path: &Path, line_number: usize, message: String) -> Self {
+        Self {
+            code: code.to_owned(),
+            severity: Severity::Warning,
+            message,
+            location: Some((path.to_owned(), line_number)),
+            label: None,
+            context: None,
+        }
+    }
+}
@@ -1,23 +1,74 @@
-use {crate::directive::Directive, std::fs::metadata};
+use {
+    crate::{
+        diagnostic::{self, Diagnostic},
+        directive::Directive,
+        stat::Target,
+    },
+    std::collections::{hash_map::Entry, HashMap},
+};
 
-// This function checks that directory references actually point to files. It returns a vector of
-// error strings.
-pub fn check(refs: &[Directive]) -> Vec<String> {
-    let mut errors = Vec::<String>::new();
+// This function checks that directory references actually point to directories, given what each
+// path points to (see `stat::stat_all`). Only the first reference to each bad path explains the
+// problem, and the others refer back to it. It returns a vector of errors.
+pub fn check(refs: &[Directive], targets: &HashMap<&str, Target>) -> Vec<Diagnostic> {
+    let mut errors = Vec::<Diagnostic>::new();
+    let mut first_refs = HashMap::<&str, &Directive>::new();
 
-    for dir in refs {
-        match metadata(&dir.label) {
-            Ok(metadata) => {
-                if !metadata.is_dir() {
-                    errors.push(format!("{dir} does not point to a directory."));
-                }
+    let mut sorted_refs = refs.iter().collect::<Vec<_>>();
+    sorted_refs.sort_by(|x, y| (&x.path, x.line_number).cmp(&(&y.path, y.line_number)));
+    for dir in sorted_refs {
+        // The `unwrap` is safe assuming every label was statted.
+        let message = match targets.get(dir.label.as_str()).unwrap() {
+            Target::Dir => continue,
+            Target::Error(error) => format!("Error when validating {dir}: {error}"),
+            _ => format!("{dir} does not point to a directory."),
+        };
+        let message = match first_refs.entry(&dir.label) {
+            Entry::Occupied(entry) => format!("{dir} has the same problem as {}.", entry.get()),
+            Entry::Vacant(entry) => {
+                entry.insert(dir);
+                message
             }
-            Err(error) => {
-                let error_string = error.to_string();
-                errors.push(format!("Error when validating {dir}: {error_string}"));
-            }
-        }
+        };
+        errors.push(Diagnostic::error(diagnostic::BAD_DIR_REF, dir, message));
     }
 
     errors
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        dir_references::check,
+        directive::{Directive, Type},
+        stat::stat_all,
+    };
+
+    fn dir(label: &str, line_number: usize) -> Directive {
+        Directive::new(Type::Dir, label, "file1.rs", line_number)
+    }
+
+    #[test]
+    fn check_repeated() {
+        let refs = [
+            dir("Cargo.toml", 3),
+            dir("src", 1),
+            dir("Cargo.toml", 2),
+            dir("nonexistent", 4),
+        ];
+        let targets = stat_all(refs.iter().map(|dir| dir.label.as_str()));
+
+        let errors = check(&refs, &targets);
+        assert_eq!(errors.len(), 3);
+        assert!(
+            errors[0]
+                .message
+                .ends_with("does not point to a directory."),
+        );
+        assert_eq!(
+            errors[1].message,
+            format!("{} has the same problem as {}.", refs[0], refs[2]),
+        );
+        assert!(errors[2].message.starts_with("Error when validating"));
+    }
+}
@@ -1,53 +1,196 @@
 use {
+    crate::{codeowners::OWNER_KEY, config, issues::UNTIL_KEY, path_style::display, walk::quote},
     regex::{escape, Regex},
+    serde::{Deserialize, Serialize, Serializer},
     std::{
+        collections::BTreeMap,
         fmt,
         io::BufRead,
+        iter::once,
+        ops::RangeInclusive,
         path::{Path, PathBuf},
+        sync::LazyLock,
+        thread,
     },
+    unicode_normalization::UnicodeNormalization,
 };
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+// This regular expression matches a `key=value` pair (such as `owner=@team`) at the end of a tag.
+// Only the known keys are matched, so any other text which looks like a pair is part of the label.
+static METADATA_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    // The `unwrap` is safe by manual inspection, since the keys don't contain special characters.
+    Regex::new(&format!("\\s+({OWNER_KEY}|{UNTIL_KEY})=(\\S+)$")).unwrap()
+});
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Type {
     Tag,
     Ref,
     File,
     Dir,
+    Spec,
+    Url,
+}
+
+impl Type {
+    // This function returns the name of the type, as it's written in directives.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Tag => "tag",
+            Self::Ref => "ref",
+            Self::File => "file",
+            Self::Dir => "dir",
+            Self::Spec => "spec",
+            Self::Url => "url",
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Directive {
     pub r#type: Type,
     pub label: String,
+    pub metadata: BTreeMap<String, String>,
+    pub description: String,
+    #[serde(serialize_with = "serialize_path")]
     pub path: PathBuf,
+    #[serde(rename = "line")]
     pub line_number: usize,
+
+    // The column (counting characters from 1) where the directive begins, or 0 if it isn't known
+    // (e.g., for tags in file names)
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub column: usize,
+
+    // The trimmed line containing the directive, for reports
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub context: String,
+
+    // For directives in Jupyter notebooks, the cell (counting from 1) that contains the directive.
+    // The line number is then relative to the cell.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cell: Option<usize>,
+
+    // For directives found with one of the configured sigil sets rather than the usual sigils, the
+    // name of the set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sigil_set: Option<String>,
+}
+
+// Unknown columns are left out when serializing.
+#[allow(clippy::trivially_copy_pass_by_ref)]
+pub fn is_zero(column: &usize) -> bool {
+    *column == 0
+}
+
+// Paths are serialized lossily, since not all paths are valid UTF-8.
+fn serialize_path<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&path.to_string_lossy())
 }
 
 // Sometimes we need to be able to print a directive.
 impl fmt::Display for Directive {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "[{}:{}] @ {}:{}",
-            match self.r#type {
-                Type::Tag => "tag",
-                Type::Ref => "ref",
-                Type::File => "file",
-                Type::Dir => "dir",
-            },
-            self.label,
-            self.path.to_string_lossy(),
-            self.line_number,
-        )
+        write!(f, "[{}:{}", self.r#type.name(), self.label)?;
+        for (key, value) in &self.metadata {
+            write!(f, " {key}={value}")?;
+        }
+        write!(f, "] @ {}:", quote(&display(&self.path)))?;
+        match self.cell {
+            Some(cell) => write!(f, "cell {cell}:line {}", self.line_number),
+            None => write!(f, "{}", self.line_number),
+        }?;
+        if self.column > 0 {
+            write!(f, ":{}", self.column)?;
+        }
+        match &self.sigil_set {
+            Some(sigil_set) => write!(f, " ({sigil_set} sigils)"),
+            None => Ok(()),
+        }
     }
 }
 
-#[derive(Clone, Debug)]
+// Tests build directives directly, so this constructor leaves the fields they don't care about
+// empty. Any of them can be set afterward.
+#[cfg(test)]
+impl Directive {
+    pub fn new(r#type: Type, label: &str, path: impl AsRef<Path>, line_number: usize) -> Self {
+        Self {
+            r#type,
+            label: label.to_owned(),
+            metadata: BTreeMap::new(),
+            description: String::new(),
+            path: path.as_ref().to_owned(),
+            line_number,
+            column: 0,
+            context: String::new(),
+            cell: None,
+            sigil_set: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Directives {
     pub tags: Vec<Directive>,
     pub refs: Vec<Directive>,
     pub files: Vec<Directive>,
     pub dirs: Vec<Directive>,
+    #[serde(default)]
+    pub specs: Vec<Directive>,
+    #[serde(default)]
+    pub urls: Vec<Directive>,
+
+    // The line numbers where merge conflicts begin. Directives within them are ignored.
+    pub conflicts: Vec<usize>,
+}
+
+impl Directives {
+    // This function returns every directive in the set.
+    pub fn all_mut(&mut self) -> impl Iterator<Item = &mut Directive> {
+        self.tags
+            .iter_mut()
+            .chain(self.refs.iter_mut())
+            .chain(self.files.iter_mut())
+            .chain(self.dirs.iter_mut())
+            .chain(self.specs.iter_mut())
+            .chain(self.urls.iter_mut())
+    }
+
+    // This function moves the directives from another set into this one, marking them as being in
+    // the given notebook cell. The other set's merge conflicts are dropped, since their line
+    // numbers are relative to the cell.
+    pub fn extend_with_cell(&mut self, mut other: Self, cell: usize) {
+        for directive in other.all_mut() {
+            directive.cell = Some(cell);
+        }
+        self.tags.append(&mut other.tags);
+        self.refs.append(&mut other.refs);
+        self.files.append(&mut other.files);
+        self.dirs.append(&mut other.dirs);
+        self.specs.append(&mut other.specs);
+        self.urls.append(&mut other.urls);
+    }
+
+    // This function shortens the context of each directive to at most `max_length` characters,
+    // marking where it was cut with an ellipsis. A maximum of zero removes the context entirely.
+    pub fn truncate_contexts(&mut self, max_length: usize) {
+        for directive in self.all_mut() {
+            if directive.context.chars().count() > max_length {
+                directive.context = if max_length == 0 {
+                    String::new()
+                } else {
+                    directive
+                        .context
+                        .chars()
+                        .take(max_length - 1)
+                        .chain(once('…'))
+                        .collect()
+                };
+            }
+        }
+    }
 }
 
 // This function compiles a regular expression for matching a directive.
@@ -59,87 +202,509 @@ pub fn compile_directive_regex(sigil: &str) -> Regex {
     .unwrap() // Safe by manual inspection
 }
 
-// This function returns all the directives in a file for a given type.
+// This struct represents the regular expressions for a sigil set from the configuration file. The
+// types of directives without a sigil in the set have a regular expression which never matches.
+#[derive(Clone)]
+pub struct SigilSet {
+    pub name: String,
+    pub tag_regex: Regex,
+    pub ref_regex: Regex,
+    pub file_regex: Regex,
+    pub dir_regex: Regex,
+    pub spec_regex: Regex,
+    pub url_regex: Regex,
+}
+
+impl SigilSet {
+    // This function compiles the regular expressions for a sigil set.
+    pub fn new(sigils: &config::SigilSet) -> Self {
+        let compile = |sigil: &Option<String>| {
+            sigil.as_deref().map_or_else(
+                || Regex::new("[^\\s\\S]").unwrap(), // Safe by manual inspection
+                compile_directive_regex,
+            )
+        };
+        Self {
+            name: sigils.name.clone(),
+            tag_regex: compile(&sigils.tag),
+            ref_regex: compile(&sigils.r#ref),
+            file_regex: compile(&sigils.file),
+            dir_regex: compile(&sigils.dir),
+            spec_regex: compile(&sigils.spec),
+            url_regex: compile(&sigils.url),
+        }
+    }
+
+    // This function returns the regular expressions in the same order as the arguments of `parse`.
+    pub fn regexes(&self) -> [&Regex; 6] {
+        [
+            &self.tag_regex,
+            &self.ref_regex,
+            &self.file_regex,
+            &self.dir_regex,
+            &self.spec_regex,
+            &self.url_regex,
+        ]
+    }
+}
+
+// This function splits the contents of a tag or tag reference into its label and any trailing
+// `owner` or `until` metadata. For example, `foo owner=@team` becomes `foo` with an `owner` of
+// `@team`, but `foo bar=baz` is left alone. A pair is only recognized if something precedes it, so
+// the label is never empty.
+pub fn split_metadata(contents: &str) -> (String, BTreeMap<String, String>) {
+    let mut label = contents;
+    let mut metadata = BTreeMap::new();
+
+    while let Some(captures) = METADATA_REGEX.captures(label) {
+        // The `unwrap`s are safe because these groups always participate in a match.
+        let whole = captures.get(0).unwrap();
+        if whole.start() == 0 {
+            break;
+        }
+        metadata
+            .entry(captures.get(1).unwrap().as_str().to_owned())
+            .or_insert_with(|| captures.get(2).unwrap().as_str().to_owned());
+        label = label[..whole.start()].trim_end();
+    }
+
+    (label.to_owned(), metadata)
+}
+
+// This function puts a label in Unicode Normalization Form C, so labels which look the same are
+// compared the same way even if they were written with different sequences of code points (e.g.,
+// an accented letter vs. a letter followed by a combining accent).
+pub fn normalize_label(label: &str) -> String {
+    if label.is_ascii() {
+        label.to_owned()
+    } else {
+        label.nfc().collect()
+    }
+}
+
+// This function finds the tag defined by the name of a file, if there is one: a part of the name
+// like `.tag-caching_strategy`, as in `0005-caching.tag-caching_strategy.md`. The label ends at the
+// extension, if there is one. Such a tag refers to the whole file, so files which can't contain
+// directives (e.g., diagrams) can still be referred to.
+pub fn path_tag(path: &Path, sigil: &str) -> Option<Directive> {
+    let name = path.file_name()?.to_str()?;
+    let marker = format!(".{sigil}-").to_ascii_lowercase();
+    let start = name.to_ascii_lowercase().find(&marker)? + marker.len();
+    let rest = &name[start..];
+    let label = rest.rsplit_once('.').map_or(rest, |(label, _)| label);
+    if label.is_empty() {
+        return None;
+    }
+
+    Some(Directive {
+        r#type: Type::Tag,
+        label: normalize_label(label),
+        metadata: BTreeMap::new(),
+        description: String::new(),
+        path: path.to_owned(),
+        line_number: 1,
+        column: 0,
+        context: name.to_owned(),
+        cell: None,
+        sigil_set: None,
+    })
+}
+
+// This function extracts the description which follows a directive on the same line. The
+// description ends where the next directive begins (if any), and common comment delimiters are
+// stripped from either end.
+fn description(line: &str, end: usize, starts: &[usize]) -> String {
+    let stop = starts
+        .iter()
+        .copied()
+        .filter(|start| *start >= end)
+        .min()
+        .unwrap_or(line.len());
+
+    line[end..stop]
+        .trim()
+        .trim_end_matches("*/")
+        .trim_end_matches("-->")
+        .trim_end()
+        .trim_start_matches([':', '-', '\u{2013}', '\u{2014}', '.', ',', ';'])
+        .trim_start()
+        .to_owned()
+}
+
+// This function determines whether a line is a merge conflict marker, and if so, which one. Markers
+// consist of exactly seven copies of the same character at the beginning of the line, optionally
+// followed by a space and a label (except for the separator).
+fn conflict_marker(line: &str) -> Option<char> {
+    ['<', '|', '=', '>'].into_iter().find(|&marker| {
+        let rest = line.trim_start_matches(marker);
+        line.len() - rest.len() == 7
+            && (rest.trim_end().is_empty() || (marker != '=' && rest.starts_with(' ')))
+    })
+}
+
+// This function finds the merge conflicts in a file, given the line numbers of its conflict
+// markers. A conflict must have a beginning, a separator, and an end, in that order.
+fn conflicts(markers: &[(usize, char)]) -> Vec<RangeInclusive<usize>> {
+    let mut conflicts = Vec::new();
+    let mut start = None;
+    let mut separated = false;
+    for &(line_number, marker) in markers {
+        match marker {
+            '<' => {
+                start = Some(line_number);
+                separated = false;
+            }
+            '=' => separated = start.is_some(),
+            '>' => {
+                if let (Some(start), true) = (start, separated) {
+                    conflicts.push(start..=line_number);
+                }
+                start = None;
+                separated = false;
+            }
+            _ => {}
+        }
+    }
+    conflicts
+}
+
+// The regular expressions for the usual sigils (with no sigil set) and those of each sigil set, in
+// the same order as the arguments of `parse`
+type Regexes<'a> = Vec<(Option<&'a String>, [&'a Regex; 6])>;
+
+// This function combines the regular expressions for the usual sigils with those of the sigil
+// sets. The usual sigils are searched for first, then those of each sigil set.
+fn with_sigil_sets<'a>(regexes: [&'a Regex; 6], sigil_sets: &'a [SigilSet]) -> Regexes<'a> {
+    once((None, regexes))
+        .chain(
+            sigil_sets
+                .iter()
+                .map(|sigil_set| (Some(&sigil_set.name), sigil_set.regexes())),
+        )
+        .collect()
+}
+
+// This function converts a byte offset within a line to a column, counting characters from 1.
+fn column(line: &str, offset: usize) -> usize {
+    line[..offset].chars().count() + 1
+}
+
+// This function returns an extended regular expression (as Git understands it) which matches the
+// lines which might contain a directive with one of the given sigils.
+pub fn line_pattern<'a>(sigils: impl IntoIterator<Item = &'a str>) -> String {
+    let sigils = sigils
+        .into_iter()
+        .map(|sigil| {
+            sigil
+                .chars()
+                .map(|c| {
+                    if "\\.[]{}()*+?^$|".contains(c) {
+                        format!("\\{c}")
+                    } else {
+                        c.to_string()
+                    }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>();
+    format!("\\[[[:space:]]*({})[[:space:]]*:", sigils.join("|"))
+}
+
+// This function finds the directives in a stream of lines, the first of which has the given line
+// number. It also returns the line numbers of the merge conflict markers, so the directives within
+// conflicts can be dropped once the whole file has been seen.
+fn parse_lines<R: BufRead>(
+    regexes: &Regexes,
+    path: &Path,
+    reader: R,
+    first_line_number: usize,
+) -> (Directives, Vec<(usize, char)>) {
+    let mut directives = Directives::default();
+    let mut markers = Vec::new();
+
+    for (i, line_result) in reader.lines().enumerate() {
+        let line_number = first_line_number + i;
+        if let Ok(line) = line_result {
+            // Remember where the merge conflict markers are.
+            if let Some(marker) = conflict_marker(&line) {
+                markers.push((line_number, marker));
+            }
+
+            // Find where each directive on the line begins, since that's where the description of
+            // the preceding directive ends.
+            let starts = regexes
+                .iter()
+                .flat_map(|(_, regexes)| regexes)
+                .flat_map(|regex| regex.find_iter(&line).map(|m| m.start()))
+                .collect::<Vec<_>>();
+
+            for (sigil_set, [tag_regex, ref_regex, file_regex, dir_regex, spec_regex, url_regex]) in
+                regexes
+            {
+                // Tags
+                for captures in tag_regex.captures_iter(&line) {
+                    // If we got a match, then `captures.get(0)` and `captures.get(1)` are
+                    // guaranteed to return a `Some`. Hence we are justified in unwrapping.
+                    let (label, metadata) = split_metadata(captures.get(1).unwrap().as_str());
+                    directives.tags.push(Directive {
+                        r#type: Type::Tag,
+                        label: normalize_label(&label),
+                        metadata,
+                        description: description(&line, captures.get(0).unwrap().end(), &starts),
+                        path: path.to_owned(),
+                        line_number,
+                        column: column(&line, captures.get(0).unwrap().start()),
+                        context: line.trim().to_owned(),
+                        cell: None,
+                        sigil_set: sigil_set.cloned(),
+                    });
+                }
+
+                // Tag references, file references, directory references, spec references, and URL
+                // references
+                for (r#type, regex, directives) in [
+                    (Type::Ref, ref_regex, &mut directives.refs),
+                    (Type::File, file_regex, &mut directives.files),
+                    (Type::Dir, dir_regex, &mut directives.dirs),
+                    (Type::Spec, spec_regex, &mut directives.specs),
+                    (Type::Url, url_regex, &mut directives.urls),
+                ] {
+                    for captures in regex.captures_iter(&line) {
+                        // If we got a match, then `captures.get(0)` and `captures.get(1)` are
+                        // guaranteed to return a `Some`. Hence we are justified in unwrapping. Only
+                        // the labels of tag references are normalized (and split from any metadata,
+                        // as for tags), since the others are paths, pointers, or URLs which must be
+                        // used as they were written.
+                        let label = captures.get(1).unwrap().as_str();
+                        directives.push(Directive {
+                            r#type,
+                            label: if r#type == Type::Ref {
+                                normalize_label(&split_metadata(label).0)
+                            } else {
+                                label.to_owned()
+                            },
+                            metadata: BTreeMap::new(),
+                            description: description(
+                                &line,
+                                captures.get(0).unwrap().end(),
+                                &starts,
+                            ),
+                            path: path.to_owned(),
+                            line_number,
+                            column: column(&line, captures.get(0).unwrap().start()),
+                            context: line.trim().to_owned(),
+                            cell: None,
+                            sigil_set: sigil_set.cloned(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    (directives, markers)
+}
+
+// This function drops the directives within the merge conflicts delimited by the given markers,
+// and records where the conflicts begin.
+fn drop_conflicts(mut directives: Directives, markers: &[(usize, char)]) -> Directives {
+    let conflicts = conflicts(markers);
+    let outside = |directive: &Directive| {
+        !conflicts
+            .iter()
+            .any(|conflict| conflict.contains(&directive.line_number))
+    };
+    directives.tags.retain(outside);
+    directives.refs.retain(outside);
+    directives.files.retain(outside);
+    directives.dirs.retain(outside);
+    directives.specs.retain(outside);
+    directives.urls.retain(outside);
+    directives.conflicts = conflicts.iter().map(|conflict| *conflict.start()).collect();
+    directives
+}
+
+// This function returns all the directives in a file for a given type, including those written with
+// the sigils of any sigil sets. Directives within merge conflicts are ignored, since they would
+// otherwise be found on both sides of each conflict.
+#[allow(clippy::too_many_arguments)]
 pub fn parse<R: BufRead>(
     tag_regex: &Regex,
     ref_regex: &Regex,
     file_regex: &Regex,
     dir_regex: &Regex,
+    spec_regex: &Regex,
+    url_regex: &Regex,
+    sigil_sets: &[SigilSet],
     path: &Path,
     reader: R,
 ) -> Directives {
-    let mut tags: Vec<Directive> = Vec::new();
-    let mut refs: Vec<Directive> = Vec::new();
-    let mut files: Vec<Directive> = Vec::new();
-    let mut dirs: Vec<Directive> = Vec::new();
+    let regexes = with_sigil_sets(
+        [
+            tag_regex, ref_regex, file_regex, dir_regex, spec_regex, url_regex,
+        ],
+        sigil_sets,
+    );
 
-    for (line_number, line_result) in reader.lines().enumerate() {
-        if let Ok(line) = line_result {
-            // Tags
-            for captures in tag_regex.captures_iter(&line) {
-                // If we got a match, then `captures.get(1)` is guaranteed to return a `Some`. Hence
-                // we are justified in unwrapping.
-                tags.push(Directive {
-                    r#type: Type::Tag,
-                    label: captures.get(1).unwrap().as_str().to_owned(),
-                    path: path.to_owned(),
-                    line_number: line_number + 1,
-                });
-            }
+    let (directives, markers) = parse_lines(&regexes, path, reader, 1);
+    drop_conflicts(directives, &markers)
+}
 
-            // Refs
-            for captures in ref_regex.captures_iter(&line) {
-                // If we got a match, then `captures.get(1)` is guaranteed to return a `Some`. Hence
-                // we are justified in unwrapping.
-                refs.push(Directive {
-                    r#type: Type::Ref,
-                    label: captures.get(1).unwrap().as_str().to_owned(),
-                    path: path.to_owned(),
-                    line_number: line_number + 1,
-                });
-            }
+// This function is like `parse`, but for the contents of a large file. The contents are split into
+// chunks of about `chunk_size` bytes at line boundaries, and the chunks are parsed in parallel. The
+// results are the same as parsing the whole file at once.
+#[allow(clippy::too_many_arguments)]
+pub fn parse_chunks(
+    tag_regex: &Regex,
+    ref_regex: &Regex,
+    file_regex: &Regex,
+    dir_regex: &Regex,
+    spec_regex: &Regex,
+    url_regex: &Regex,
+    sigil_sets: &[SigilSet],
+    path: &Path,
+    contents: &[u8],
+    chunk_size: usize,
+) -> Directives {
+    let regexes = with_sigil_sets(
+        [
+            tag_regex, ref_regex, file_regex, dir_regex, spec_regex, url_regex,
+        ],
+        sigil_sets,
+    );
 
-            // Files
-            for captures in file_regex.captures_iter(&line) {
-                // If we got a match, then `captures.get(1)` is guaranteed to return a `Some`. Hence
-                // we are justified in unwrapping.
-                files.push(Directive {
-                    r#type: Type::File,
-                    label: captures.get(1).unwrap().as_str().to_owned(),
-                    path: path.to_owned(),
-                    line_number: line_number + 1,
-                });
-            }
+    // Split the contents after the first line break past each multiple of the chunk size, and note
+    // the line number at which each chunk begins.
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut first_line_number = 1;
+    while start < contents.len() {
+        let end = (start + chunk_size.max(1)).min(contents.len());
+        let end = contents[end..]
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .map_or(contents.len(), |i| end + i + 1);
+        let chunk = &contents[start..end];
+        chunks.push((chunk, first_line_number));
+        first_line_number += chunk.split(|byte| *byte == b'\n').count() - 1;
+        start = end;
+    }
 
-            // Directories
-            for captures in dir_regex.captures_iter(&line) {
-                // If we got a match, then `captures.get(1)` is guaranteed to return a `Some`. Hence
-                // we are justified in unwrapping.
-                dirs.push(Directive {
-                    r#type: Type::Dir,
-                    label: captures.get(1).unwrap().as_str().to_owned(),
-                    path: path.to_owned(),
-                    line_number: line_number + 1,
-                });
-            }
+    // Parse the chunks in parallel, and combine the results in order. The `unwrap` is safe since
+    // parsing doesn't panic.
+    let mut directives = Directives::default();
+    let mut markers = Vec::new();
+    thread::scope(|scope| {
+        let handles = chunks
+            .into_iter()
+            .map(|(chunk, first_line_number)| {
+                let regexes = &regexes;
+                scope.spawn(move || parse_lines(regexes, path, chunk, first_line_number))
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            let (mut chunk_directives, mut chunk_markers) = handle.join().unwrap();
+            directives.tags.append(&mut chunk_directives.tags);
+            directives.refs.append(&mut chunk_directives.refs);
+            directives.files.append(&mut chunk_directives.files);
+            directives.dirs.append(&mut chunk_directives.dirs);
+            directives.specs.append(&mut chunk_directives.specs);
+            directives.urls.append(&mut chunk_directives.urls);
+            markers.append(&mut chunk_markers);
         }
-    }
+    });
 
-    Directives {
-        tags,
-        refs,
-        files,
-        dirs,
-    }
+    drop_conflicts(directives, &markers)
 }
 
 #[cfg(test)]
 mod tests {
     use {
-        crate::directive::{compile_directive_regex, parse, Type},
+        crate::{
+            config,
+            directive::{
+                compile_directive_regex, line_pattern, normalize_label, parse, parse_chunks,
+                path_tag, split_metadata, Directives, SigilSet, Type,
+            },
+        },
+        regex::Regex,
         std::path::Path,
     };
 
+    #[test]
+    fn split_metadata_none() {
+        let (label, metadata) = split_metadata("foo  bar");
+
+        assert_eq!(label, "foo  bar");
+        assert!(metadata.is_empty());
+    }
+
+    #[test]
+    fn split_metadata_pairs() {
+        let (label, metadata) = split_metadata("foo bar owner=@team  until=#12");
+
+        assert_eq!(label, "foo bar");
+        assert_eq!(metadata.len(), 2);
+        assert_eq!(metadata["owner"], "@team");
+        assert_eq!(metadata["until"], "#12");
+    }
+
+    #[test]
+    fn normalize_label_nfc() {
+        // The first label has a precomposed `é`, and the second has a combining accent.
+        assert_eq!(normalize_label("r\u{e9}gle_cache"), "r\u{e9}gle_cache");
+        assert_eq!(normalize_label("re\u{301}gle_cache"), "r\u{e9}gle_cache");
+        assert_eq!(normalize_label("regle_cache"), "regle_cache");
+    }
+
+    #[test]
+    fn path_tag_in_file_name() {
+        let tag = path_tag(
+            Path::new("./docs/adr/0005-caching.tag-caching_strategy.md"),
+            "tag",
+        )
+        .unwrap();
+        assert_eq!(tag.r#type, Type::Tag);
+        assert_eq!(tag.label, "caching_strategy");
+        assert_eq!(tag.line_number, 1);
+        assert_eq!(tag.context, "0005-caching.tag-caching_strategy.md");
+
+        assert_eq!(
+            path_tag(Path::new("diagram.TAG-auth.flow.svg"), "tag")
+                .unwrap()
+                .label,
+            "auth.flow",
+        );
+        assert_eq!(
+            path_tag(Path::new("notes.anchor-x"), "anchor")
+                .unwrap()
+                .label,
+            "x",
+        );
+        assert!(path_tag(Path::new("tag-cloud.js"), "tag").is_none());
+        assert!(path_tag(Path::new("docs.tag-x/a.md"), "tag").is_none());
+        assert!(path_tag(Path::new("a.tag-.md"), "tag").is_none());
+    }
+
+    #[test]
+    fn split_metadata_unknown_keys() {
+        let (label, metadata) = split_metadata("foo bar=baz");
+        assert_eq!(label, "foo bar=baz");
+        assert!(metadata.is_empty());
+
+        let (label, metadata) = split_metadata("foo bar=baz owner=@team");
+        assert_eq!(label, "foo bar=baz");
+        assert_eq!(metadata.len(), 1);
+    }
+
+    #[test]
+    fn split_metadata_label_only() {
+        let (label, metadata) = split_metadata("owner=@team");
+
+        assert_eq!(label, "owner=@team");
+        assert!(metadata.is_empty());
+    }
+
     #[test]
     fn parse_empty() {
         let path = Path::new("file.rs").to_owned();
@@ -149,12 +714,17 @@ mod tests {
         let ref_regex = compile_directive_regex("ref");
         let file_regex = compile_directive_regex("file");
         let dir_regex = compile_directive_regex("dir");
+        let spec_regex = compile_directive_regex("spec");
+        let url_regex = compile_directive_regex("url");
 
         let directives = parse(
             &tag_regex,
             &ref_regex,
             &file_regex,
             &dir_regex,
+            &spec_regex,
+            &url_regex,
+            &[],
             &path,
             contents,
         );
@@ -180,12 +750,17 @@ mod tests {
         let ref_regex = compile_directive_regex("ref");
         let file_regex = compile_directive_regex("file");
         let dir_regex = compile_directive_regex("dir");
+        let spec_regex = compile_directive_regex("spec");
+        let url_regex = compile_directive_regex("url");
 
         let directives = parse(
             &tag_regex,
             &ref_regex,
             &file_regex,
             &dir_regex,
+            &spec_regex,
+            &url_regex,
+            &[],
             &path,
             contents.as_ref(),
         );
@@ -200,6 +775,235 @@ mod tests {
         assert!(directives.dirs.is_empty());
     }
 
+    #[test]
+    fn parse_tag_metadata() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = r"
+      [?tag:label owner=@team]
+      [?ref:label owner=@team]
+    "
+        .trim()
+        .replace('?', "")
+        .as_bytes()
+        .to_owned();
+
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let spec_regex = compile_directive_regex("spec");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &spec_regex,
+            &url_regex,
+            &[],
+            &path,
+            contents.as_ref(),
+        );
+
+        assert_eq!(directives.tags.len(), 1);
+        assert_eq!(directives.tags[0].label, "label");
+        assert_eq!(directives.tags[0].metadata["owner"], "@team");
+        assert_eq!(
+            format!("{}", directives.tags[0]),
+            "[tag:label owner=@team] @ file.rs:1:1",
+        );
+        assert_eq!(directives.refs.len(), 1);
+        assert_eq!(directives.refs[0].label, "label");
+    }
+
+    #[test]
+    fn parse_columns() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = "// é [?tag:foo] [?ref:bar]".replace('?', "");
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let spec_regex = compile_directive_regex("spec");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &spec_regex,
+            &url_regex,
+            &[],
+            &path,
+            contents.as_bytes(),
+        );
+
+        assert_eq!(directives.tags.len(), 1);
+        assert_eq!(directives.refs.len(), 1);
+        assert!(directives.files.is_empty());
+        assert_eq!(directives.tags[0].column, 6);
+        assert_eq!(directives.refs[0].column, 16);
+        assert_eq!(
+            directives.refs[0].to_string(),
+            "[?ref:bar] @ file.rs:1:16".replace('?', ""),
+        );
+        assert!(
+            serde_json::to_string(&directives.tags[0])
+                .unwrap()
+                .contains(r#""line":1,"column":6,"#),
+        );
+    }
+
+    #[test]
+    fn parse_description() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = r"
+      // [?tag:foo] Foo is never empty.
+      /* [?tag:bar]: Bar is sorted. */
+      <!-- [?ref:baz] - See baz. [?file:qux.txt] -->
+      [?dir:foo]
+    "
+        .trim()
+        .replace('?', "")
+        .as_bytes()
+        .to_owned();
+
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let spec_regex = compile_directive_regex("spec");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &spec_regex,
+            &url_regex,
+            &[],
+            &path,
+            contents.as_ref(),
+        );
+
+        assert_eq!(directives.tags[0].description, "Foo is never empty.");
+        assert_eq!(directives.tags[1].description, "Bar is sorted.");
+        assert_eq!(directives.refs[0].description, "See baz.");
+        assert_eq!(directives.files[0].description, "");
+        assert_eq!(directives.dirs[0].description, "");
+    }
+
+    #[test]
+    fn parse_conflicts() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = "\
+            [?tag:before]\n\
+            <<<<<<< HEAD\n\
+            [?tag:ours]\n\
+            ||||||| base\n\
+            [?tag:base]\n\
+            =======\n\
+            [?tag:theirs]\n\
+            >>>>>>> feature\n\
+            [?tag:after]\n\
+            =======\n\
+            <<<<<<<< not a marker\n\
+            [?tag:unterminated]\n\
+            <<<<<<< HEAD\n\
+            [?tag:last]\n\
+        "
+        .replace('?', "");
+
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let spec_regex = compile_directive_regex("spec");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &spec_regex,
+            &url_regex,
+            &[],
+            &path,
+            contents.as_bytes(),
+        );
+
+        assert_eq!(
+            directives
+                .tags
+                .iter()
+                .map(|tag| tag.label.as_str())
+                .collect::<Vec<_>>(),
+            vec!["before", "after", "unterminated", "last"],
+        );
+        assert_eq!(directives.conflicts, vec![2]);
+    }
+
+    #[test]
+    fn parse_chunks_same_as_whole() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = "\
+            [?tag:first] The first tag.\r\n\
+            <<<<<<< HEAD\n\
+            [?ref:first]\n\
+            =======\n\
+            [?ref:second]\n\
+            >>>>>>> feature\n\
+            \n\
+            [?tag:second] [?file:src/main.rs] [?dir:src]\n\
+            [?tag:last]"
+            .replace('?', "");
+
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let spec_regex = compile_directive_regex("spec");
+        let url_regex = compile_directive_regex("url");
+
+        let whole = parse(
+            &tag_regex,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &spec_regex,
+            &url_regex,
+            &[],
+            &path,
+            contents.as_bytes(),
+        );
+        for chunk_size in [1, 10, 40, 1000] {
+            let chunked = parse_chunks(
+                &tag_regex,
+                &ref_regex,
+                &file_regex,
+                &dir_regex,
+                &spec_regex,
+                &url_regex,
+                &[],
+                &path,
+                contents.as_bytes(),
+                chunk_size,
+            );
+
+            assert_eq!(
+                serde_json::to_string(&chunked).unwrap(),
+                serde_json::to_string(&whole).unwrap(),
+            );
+        }
+        assert_eq!(whole.tags.len(), 3);
+        assert_eq!(whole.tags[2].line_number, 9);
+        assert_eq!(whole.conflicts, vec![2]);
+    }
+
     #[test]
     fn parse_ref_basic() {
         let path = Path::new("file.rs").to_owned();
@@ -215,12 +1019,17 @@ mod tests {
         let ref_regex = compile_directive_regex("ref");
         let file_regex = compile_directive_regex("file");
         let dir_regex = compile_directive_regex("dir");
+        let spec_regex = compile_directive_regex("spec");
+        let url_regex = compile_directive_regex("url");
 
         let directives = parse(
             &tag_regex,
             &ref_regex,
             &file_regex,
             &dir_regex,
+            &spec_regex,
+            &url_regex,
+            &[],
             &path,
             contents.as_ref(),
         );
@@ -250,12 +1059,17 @@ mod tests {
         let ref_regex = compile_directive_regex("ref");
         let file_regex = compile_directive_regex("file");
         let dir_regex = compile_directive_regex("dir");
+        let spec_regex = compile_directive_regex("spec");
+        let url_regex = compile_directive_regex("url");
 
         let directives = parse(
             &tag_regex,
             &ref_regex,
             &file_regex,
             &dir_regex,
+            &spec_regex,
+            &url_regex,
+            &[],
             &path,
             contents.as_ref(),
         );
@@ -285,12 +1099,17 @@ mod tests {
         let ref_regex = compile_directive_regex("ref");
         let file_regex = compile_directive_regex("file");
         let dir_regex = compile_directive_regex("dir");
+        let spec_regex = compile_directive_regex("spec");
+        let url_regex = compile_directive_regex("url");
 
         let directives = parse(
             &tag_regex,
             &ref_regex,
             &file_regex,
             &dir_regex,
+            &spec_regex,
+            &url_regex,
+            &[],
             &path,
             contents.as_ref(),
         );
@@ -305,6 +1124,92 @@ mod tests {
         assert_eq!(directives.dirs[0].line_number, 1);
     }
 
+    #[test]
+    fn parse_spec_basic() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = r"
+      [?spec:openapi.yaml#/components/schemas/User]
+    "
+        .trim()
+        .replace('?', "")
+        .as_bytes()
+        .to_owned();
+
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let spec_regex = compile_directive_regex("spec");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &spec_regex,
+            &url_regex,
+            &[],
+            &path,
+            contents.as_ref(),
+        );
+
+        assert!(directives.tags.is_empty());
+        assert!(directives.refs.is_empty());
+        assert!(directives.files.is_empty());
+        assert!(directives.dirs.is_empty());
+        assert_eq!(directives.specs.len(), 1);
+        assert_eq!(directives.specs[0].r#type, Type::Spec);
+        assert_eq!(
+            directives.specs[0].label,
+            "openapi.yaml#/components/schemas/User",
+        );
+        assert_eq!(directives.specs[0].path, path);
+        assert_eq!(directives.specs[0].line_number, 1);
+    }
+
+    #[test]
+    fn parse_url_basic() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = r"
+      // See [?url:https://example.com/docs/retries#backoff] for the backoff schedule.
+    "
+        .trim()
+        .replace('?', "")
+        .as_bytes()
+        .to_owned();
+
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let spec_regex = compile_directive_regex("spec");
+        let url_regex = compile_directive_regex("url");
+
+        let directives = parse(
+            &tag_regex,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &spec_regex,
+            &url_regex,
+            &[],
+            &path,
+            contents.as_ref(),
+        );
+
+        assert!(directives.tags.is_empty());
+        assert!(directives.specs.is_empty());
+        assert_eq!(directives.urls.len(), 1);
+        assert_eq!(directives.urls[0].r#type, Type::Url);
+        assert_eq!(
+            directives.urls[0].label,
+            "https://example.com/docs/retries#backoff",
+        );
+        assert_eq!(directives.urls[0].description, "for the backoff schedule.");
+        assert_eq!(directives.urls[0].line_number, 1);
+    }
+
     #[test]
     fn parse_multiple_per_line() {
         let path = Path::new("file.rs").to_owned();
@@ -320,12 +1225,17 @@ mod tests {
         let ref_regex = compile_directive_regex("ref");
         let file_regex = compile_directive_regex("file");
         let dir_regex = compile_directive_regex("dir");
+        let spec_regex = compile_directive_regex("spec");
+        let url_regex = compile_directive_regex("url");
 
         let directives = parse(
             &tag_regex,
             &ref_regex,
             &file_regex,
             &dir_regex,
+            &spec_regex,
+            &url_regex,
+            &[],
             &path,
             contents.as_ref(),
         );
@@ -373,12 +1283,17 @@ mod tests {
         let ref_regex = compile_directive_regex("ref");
         let file_regex = compile_directive_regex("file");
         let dir_regex = compile_directive_regex("dir");
+        let spec_regex = compile_directive_regex("spec");
+        let url_regex = compile_directive_regex("url");
 
         let directives = parse(
             &tag_regex,
             &ref_regex,
             &file_regex,
             &dir_regex,
+            &spec_regex,
+            &url_regex,
+            &[],
             &path,
             contents.as_ref(),
         );
@@ -426,12 +1341,17 @@ mod tests {
         let ref_regex = compile_directive_regex("ref");
         let file_regex = compile_directive_regex("file");
         let dir_regex = compile_directive_regex("dir");
+        let spec_regex = compile_directive_regex("spec");
+        let url_regex = compile_directive_regex("url");
 
         let directives = parse(
             &tag_regex,
             &ref_regex,
             &file_regex,
             &dir_regex,
+            &spec_regex,
+            &url_regex,
+            &[],
             &path,
             contents.as_ref(),
         );
@@ -483,12 +1403,17 @@ mod tests {
         let ref_regex = compile_directive_regex("ref");
         let file_regex = compile_directive_regex("file");
         let dir_regex = compile_directive_regex("dir");
+        let spec_regex = compile_directive_regex("spec");
+        let url_regex = compile_directive_regex("url");
 
         let directives = parse(
             &tag_regex,
             &ref_regex,
             &file_regex,
             &dir_regex,
+            &spec_regex,
+            &url_regex,
+            &[],
             &path,
             contents.as_ref(),
         );
@@ -533,4 +1458,89 @@ mod tests {
         assert_eq!(directives.dirs[1].path, path);
         assert_eq!(directives.dirs[1].line_number, 8);
     }
+
+    #[test]
+    fn parse_context() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = "  // See [?ref:label] for details.  \n".replace('?', "");
+
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let dir_regex = compile_directive_regex("dir");
+        let spec_regex = compile_directive_regex("spec");
+        let url_regex = compile_directive_regex("url");
+
+        let mut directives = parse(
+            &tag_regex,
+            &ref_regex,
+            &file_regex,
+            &dir_regex,
+            &spec_regex,
+            &url_regex,
+            &[],
+            &path,
+            contents.as_bytes(),
+        );
+
+        assert_eq!(
+            directives.refs[0].context,
+            "// See [?ref:label] for details.".replace('?', ""),
+        );
+
+        directives.truncate_contexts(8);
+        assert_eq!(directives.refs[0].context, "// See …");
+
+        directives.truncate_contexts(0);
+        assert!(directives.refs[0].context.is_empty());
+    }
+
+    #[test]
+    fn line_pattern_sigil_sets() {
+        let config = config::parse("[[sigil-sets]]\nname = 'legacy'\ntag = 'anchor'").unwrap();
+        let pattern =
+            Regex::new(&line_pattern(["tag", "a.b"].into_iter().chain(
+                config.sigil_sets.iter().flat_map(config::SigilSet::sigils),
+            )))
+            .unwrap();
+
+        assert!(pattern.is_match("// [ anchor:x]"));
+        assert!(pattern.is_match(&"// [?tag:x]".replace('?', "")));
+        assert!(pattern.is_match("// [a.b:x]"));
+        assert!(!pattern.is_match("// [axb:x] [anchor x]"));
+    }
+
+    #[test]
+    fn parse_sigil_set() {
+        let path = Path::new("file.rs").to_owned();
+        let contents = "[anchor:old] [?ref:old] [see:new] [?tag:new]\n".replace('?', "");
+        let config =
+            config::parse("[[sigil-sets]]\nname = 'legacy'\ntag = 'anchor'\nref = 'see'").unwrap();
+
+        let directives = parse(
+            &compile_directive_regex("tag"),
+            &compile_directive_regex("ref"),
+            &compile_directive_regex("file"),
+            &compile_directive_regex("dir"),
+            &compile_directive_regex("spec"),
+            &compile_directive_regex("url"),
+            &[SigilSet::new(&config.sigil_sets[0])],
+            &path,
+            contents.as_bytes(),
+        );
+
+        let Directives { tags, refs, .. } = directives;
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0].label, "new");
+        assert_eq!(tags[0].sigil_set, None);
+        assert_eq!(tags[1].label, "old");
+        assert_eq!(tags[1].sigil_set.as_deref(), Some("legacy"));
+        assert_eq!(tags[1].description, "");
+        assert_eq!(refs.len(), 2);
+        assert_eq!(refs[1].label, "new");
+        assert_eq!(
+            refs[1].to_string(),
+            "[?ref:new] @ file.rs:1:24 (legacy sigils)".replace('?', ""),
+        );
+    }
 }
@@ -0,0 +1,472 @@
+use {
+    crate::{
+        atomic, cache,
+        config::PathPattern,
+        directive::Directives,
+        schema::SCHEMA_VERSION,
+        walk::{self, normalize},
+    },
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::{hash_map::RandomState, BTreeMap},
+        fmt,
+        fs::{metadata, read, read_to_string},
+        hash::BuildHasher,
+        io::{ErrorKind, Read},
+        mem,
+        path::{Path, PathBuf},
+        str::FromStr,
+        sync::{Arc, Mutex},
+    },
+};
+
+// The default path of the directive index
+pub const DEFAULT_DIRECTIVE_INDEX_PATH: &str = ".tagref-directives.json";
+
+// The version of the directive index format, which is incremented whenever it changes incompatibly
+pub const DIRECTIVE_INDEX_VERSION: usize = 1;
+
+// This struct represents every directive in the scanned files, along with a hash of each file, so
+// it can be brought up to date without parsing the files which haven't changed. The generation is
+// incremented whenever the contents change, so consumers can tell when to reload it.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct DirectiveIndex {
+    #[serde(default)]
+    pub schema_version: usize,
+    pub version: usize,
+    pub generation: u64,
+    pub settings: String,
+    pub files: BTreeMap<String, FileEntry>,
+}
+
+// This struct represents the directives in a single file.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct FileEntry {
+    pub hash: String,
+    pub directives: Directives,
+}
+
+impl DirectiveIndex {
+    // This function constructs an empty index for the given parser settings (see `cache::key`).
+    pub fn new(settings: &[impl AsRef<str>]) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            version: DIRECTIVE_INDEX_VERSION,
+            generation: 0,
+            settings: cache::key(settings, b""),
+            files: BTreeMap::new(),
+        }
+    }
+
+    // This function brings the index up to date with the files in the given paths, skipping the
+    // excluded ones and the index itself. Only files whose contents changed are parsed again. It
+    // returns whether anything changed, in which case the generation is incremented.
+    pub fn update<F: 'static + Clone + Send + Fn(&Path, &[u8]) -> Directives>(
+        &mut self,
+        path: &Path,
+        paths: &[PathBuf],
+        excludes: &[PathPattern],
+        scan_copies: bool,
+        settings: &[impl AsRef<str>],
+        parse: F,
+    ) -> bool {
+        let previous = Arc::new(mem::take(&mut self.files));
+        let current = Arc::new(Mutex::new(BTreeMap::new()));
+        let previous_clone = previous.clone();
+        let current_clone = current.clone();
+        let settings = settings
+            .iter()
+            .map(|setting| setting.as_ref().to_owned())
+            .collect::<Vec<_>>();
+        let index_path = normalize(path);
+        walk::walk(
+            paths,
+            excludes,
+            None,
+            scan_copies,
+            move |file_path, mut file| {
+                let normalized = normalize(file_path);
+                if normalized == index_path {
+                    return;
+                }
+                let mut contents = Vec::new();
+                if file.read_to_end(&mut contents).is_err() {
+                    return;
+                }
+                let key = normalized.to_string_lossy().into_owned();
+                let hash = cache::key(&settings, &contents);
+                let directives = match previous_clone.get(&key) {
+                    Some(entry) if entry.hash == hash => entry.directives.clone(),
+                    _ => parse(file_path, &contents),
+                };
+                current_clone
+                    .lock()
+                    .unwrap() // Safe assuming no poisoning
+                    .insert(key, FileEntry { hash, directives });
+            },
+        );
+
+        // The `unwrap`s are safe because the walk is done, so there are no other references.
+        self.files = Arc::try_unwrap(current).ok().unwrap().into_inner().unwrap();
+        let changed = self.files.len() != previous.len()
+            || self.files.iter().any(|(key, entry)| {
+                previous
+                    .get(key)
+                    .is_none_or(|previous_entry| previous_entry.hash != entry.hash)
+            });
+        if changed {
+            self.generation += 1;
+        }
+        changed
+    }
+
+    // This function determines whether the index saved at the given path is fresh, i.e., none of
+    // the indexed files were modified or deleted after it was saved. This only involves looking at
+    // the metadata of the files, so it's much faster than updating the index. However, new files
+    // aren't noticed, so it's only suitable when a slightly stale answer is acceptable.
+    pub fn is_fresh(&self, path: &Path) -> bool {
+        let Ok(saved) = metadata(path).and_then(|metadata| metadata.modified()) else {
+            return false;
+        };
+
+        self.files.keys().all(|file| {
+            metadata(file)
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified <= saved)
+        })
+    }
+}
+
+// This struct represents the fraction of the indexed files to verify, such as `5%`. It's stored in
+// hundredths of a percent, so up to two decimal places are supported.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Sample {
+    hundredths: usize,
+}
+
+impl FromStr for Sample {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid sample `{s}`. Expected a percentage, e.g., `5%`.");
+        let percentage = s.trim().strip_suffix('%').unwrap_or(s.trim());
+        let (whole, fraction) = percentage.split_once('.').unwrap_or((percentage, ""));
+        if whole.is_empty()
+            || fraction.len() > 2
+            || !whole
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        let hundredths = format!("{whole}{fraction:0<2}")
+            .parse::<usize>()
+            .map_err(|_| invalid())?;
+        if hundredths > 10_000 {
+            return Err(invalid());
+        }
+
+        Ok(Self { hundredths })
+    }
+}
+
+impl fmt::Display for Sample {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.hundredths % 100 {
+            0 => write!(f, "{}%", self.hundredths / 100),
+            hundredths => write!(
+                f,
+                "{}.{}%",
+                self.hundredths / 100,
+                format!("{hundredths:02}").trim_end_matches('0'),
+            ),
+        }
+    }
+}
+
+impl Sample {
+    // This function returns how many of the given number of files are in the sample, rounding up
+    // so a nonzero percentage always verifies at least one file.
+    pub fn size(self, files: usize) -> usize {
+        files.saturating_mul(self.hundredths).div_ceil(10_000)
+    }
+}
+
+impl DirectiveIndex {
+    // This function parses a random sample of the indexed files again and compares the results
+    // with the index. It returns the number of files in the sample, along with a description of
+    // each discrepancy: files which changed or disappeared since they were indexed, and files whose
+    // recorded directives don't match their contents (e.g., because the index is corrupt).
+    pub fn verify<F: Fn(&Path, &[u8]) -> Directives>(
+        &self,
+        sample: Sample,
+        settings: &[impl AsRef<str>],
+        parse: F,
+    ) -> (usize, Vec<String>) {
+        // Order the files randomly, and take as many as the sample calls for.
+        let random = RandomState::new();
+        let mut files = self.files.iter().collect::<Vec<_>>();
+        files.sort_by_cached_key(|(path, _)| random.hash_one(path));
+        files.truncate(sample.size(files.len()));
+
+        let mut discrepancies = Vec::new();
+        for (path, entry) in &files {
+            let contents = match read(path) {
+                Ok(contents) => contents,
+                Err(error) => {
+                    discrepancies.push(format!("{path} can't be read ({error})."));
+                    continue;
+                }
+            };
+            if cache::key(settings, &contents) != entry.hash {
+                discrepancies.push(format!("{path} changed since it was indexed."));
+                continue;
+            }
+
+            // The paths in the index are relative to wherever the scan started, so they're
+            // compared after normalization.
+            let mut expected = entry.directives.clone();
+            let mut actual = parse(Path::new(path), &contents);
+            for directive in expected.all_mut().chain(actual.all_mut()) {
+                directive.path = normalize(&directive.path);
+            }
+            if expected != actual {
+                discrepancies.push(format!(
+                    "The directives recorded for {path} don't match its contents.",
+                ));
+            }
+        }
+
+        (files.len(), discrepancies)
+    }
+}
+
+// This function loads the directive index. It returns `None` if there is no index yet.
+pub fn load(path: &Path) -> Result<Option<DirectiveIndex>, String> {
+    let contents = match read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(error) => {
+            return Err(format!(
+                "Unable to read {}: {error}",
+                path.to_string_lossy(),
+            ));
+        }
+    };
+    let index: DirectiveIndex = serde_json::from_str(&contents)
+        .map_err(|error| format!("Error in {}: {error}", path.to_string_lossy()))?;
+
+    if index.version != DIRECTIVE_INDEX_VERSION {
+        return Err(format!(
+            "Error in {}: unsupported version {} (expected {DIRECTIVE_INDEX_VERSION}).",
+            path.to_string_lossy(),
+            index.version,
+        ));
+    }
+
+    Ok(Some(index))
+}
+
+// This function saves the directive index atomically, so readers never see a partial index.
+pub fn save(path: &Path, index: &DirectiveIndex) -> Result<(), String> {
+    // The `unwrap` is safe because the index always serializes successfully.
+    let mut json = serde_json::to_string(index).unwrap();
+    json.push('\n');
+
+    atomic::write(path, json)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            directive::{compile_directive_regex, parse, Directives},
+            directive_index::{
+                load, save, DirectiveIndex, Sample, DEFAULT_DIRECTIVE_INDEX_PATH,
+                DIRECTIVE_INDEX_VERSION,
+            },
+            walk,
+        },
+        std::{
+            env::temp_dir,
+            fs::{create_dir_all, metadata, remove_dir_all, write, File},
+            io::Read,
+            path::Path,
+            process,
+            sync::{Arc, Mutex},
+            time::Duration,
+        },
+    };
+
+    fn parse_tags(path: &Path, contents: &[u8]) -> Directives {
+        let regex = compile_directive_regex("tag");
+        parse(
+            &regex,
+            &regex,
+            &regex,
+            &regex,
+            &regex,
+            &regex,
+            &[],
+            path,
+            contents,
+        )
+    }
+
+    #[test]
+    fn update_incrementally() {
+        let directory = temp_dir().join(format!("tagref-directive-index-test-{}", process::id()));
+        let _ = remove_dir_all(&directory);
+        create_dir_all(&directory).unwrap();
+        let index_path = directory.join("index.json");
+        let paths = [directory.clone()];
+        write(directory.join("a.rs"), "[?tag:foo]".replace('?', "")).unwrap();
+
+        let mut index = DirectiveIndex::new(&["tag"]);
+        assert!(index.update(&index_path, &paths, &[], false, &["tag"], parse_tags));
+        assert_eq!(index.generation, 1);
+        save(&index_path, &index).unwrap();
+
+        // The index itself isn't indexed, and unchanged files don't change the generation.
+        assert!(!index.update(&index_path, &paths, &[], false, &["tag"], parse_tags));
+        assert_eq!(index.generation, 1);
+        assert_eq!(index.files.len(), 1);
+
+        write(directory.join("b.rs"), "[?tag:bar]".replace('?', "")).unwrap();
+        assert!(index.update(&index_path, &paths, &[], false, &["tag"], parse_tags));
+        assert_eq!(index.generation, 2);
+        save(&index_path, &index).unwrap();
+
+        let loaded = load(&index_path).unwrap().unwrap();
+        let _ = remove_dir_all(&directory);
+
+        assert_eq!(loaded.version, DIRECTIVE_INDEX_VERSION);
+        assert_eq!(loaded.generation, 2);
+        assert_eq!(loaded.settings, index.settings);
+        let mut labels = loaded
+            .files
+            .values()
+            .flat_map(|entry| entry.directives.tags.iter().map(|tag| tag.label.clone()))
+            .collect::<Vec<_>>();
+        labels.sort();
+        assert_eq!(labels, vec!["bar", "foo"]);
+    }
+
+    #[test]
+    fn check_after_update() {
+        let directory = temp_dir().join(format!("tagref-directive-index-check-{}", process::id()));
+        let _ = remove_dir_all(&directory);
+        create_dir_all(&directory).unwrap();
+        let index_path = directory.join(DEFAULT_DIRECTIVE_INDEX_PATH);
+        let paths = [directory.clone()];
+        write(directory.join("a.rs"), "[?tag:foo]".replace('?', "")).unwrap();
+        let mut index = DirectiveIndex::new(&["tag"]);
+        index.update(&index_path, &paths, &[], false, &["tag"], parse_tags);
+        save(&index_path, &index).unwrap();
+
+        // A later scan finds the tag once, not again in the index which quotes it.
+        let labels = Arc::new(Mutex::new(Vec::new()));
+        let labels_clone = labels.clone();
+        walk::walk(&paths, &[], None, false, move |path, mut file| {
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents).unwrap();
+            labels_clone.lock().unwrap().extend(
+                parse_tags(path, &contents)
+                    .tags
+                    .into_iter()
+                    .map(|tag| tag.label),
+            );
+        });
+        let _ = remove_dir_all(&directory);
+
+        assert_eq!(*labels.lock().unwrap(), ["foo"]);
+    }
+
+    #[test]
+    fn is_fresh_modified() {
+        let directory = temp_dir().join(format!("tagref-directive-index-fresh-{}", process::id()));
+        let _ = remove_dir_all(&directory);
+        create_dir_all(&directory).unwrap();
+        let index_path = directory.join("index.json");
+        let paths = [directory.clone()];
+        let file_path = directory.join("a.rs");
+        write(&file_path, "[?tag:foo]".replace('?', "")).unwrap();
+
+        let mut index = DirectiveIndex::new(&["tag"]);
+        index.update(&index_path, &paths, &[], false, &["tag"], parse_tags);
+        save(&index_path, &index).unwrap();
+        let saved = metadata(&index_path).unwrap().modified().unwrap();
+        let fresh = index.is_fresh(&index_path);
+
+        // Pretend the file was edited after the index was saved.
+        File::options()
+            .write(true)
+            .open(&file_path)
+            .unwrap()
+            .set_modified(saved + Duration::from_secs(1))
+            .unwrap();
+        let stale = !index.is_fresh(&index_path);
+        let _ = remove_dir_all(&directory);
+
+        assert!(fresh);
+        assert!(stale);
+        assert!(!index.is_fresh(Path::new("nonexistent.json")));
+    }
+
+    #[test]
+    fn sample_parse() {
+        assert_eq!("5%".parse::<Sample>().unwrap().to_string(), "5%");
+        assert_eq!("12.5".parse::<Sample>().unwrap().to_string(), "12.5%");
+        assert_eq!("0.05%".parse::<Sample>().unwrap().to_string(), "0.05%");
+        assert!("101%".parse::<Sample>().is_err());
+        assert!("0.001%".parse::<Sample>().is_err());
+        assert!("-5%".parse::<Sample>().is_err());
+        assert!("%".parse::<Sample>().is_err());
+
+        let sample = "5%".parse::<Sample>().unwrap();
+        assert_eq!(sample.size(0), 0);
+        assert_eq!(sample.size(1), 1);
+        assert_eq!(sample.size(100), 5);
+        assert_eq!(sample.size(101), 6);
+    }
+
+    #[test]
+    fn verify_discrepancies() {
+        let directory = temp_dir().join(format!("tagref-directive-index-verify-{}", process::id()));
+        let _ = remove_dir_all(&directory);
+        create_dir_all(&directory).unwrap();
+        let index_path = directory.join("index.json");
+        let paths = [directory.clone()];
+        for name in ["a.rs", "b.rs", "c.rs"] {
+            write(directory.join(name), "[?tag:foo]".replace('?', "")).unwrap();
+        }
+
+        let mut index = DirectiveIndex::new(&["tag"]);
+        index.update(&index_path, &paths, &[], false, &["tag"], parse_tags);
+        let all = "100%".parse::<Sample>().unwrap();
+        let (sampled, before) = index.verify(all, &["tag"], parse_tags);
+
+        // Corrupt the entry for one file, and change another.
+        let a = directory.join("a.rs").to_string_lossy().into_owned();
+        index.files.get_mut(&a).unwrap().directives.tags.clear();
+        write(directory.join("b.rs"), "[?tag:bar]".replace('?', "")).unwrap();
+        let (_, after) = index.verify(all, &["tag"], parse_tags);
+        let (none, _) = index.verify("0%".parse().unwrap(), &["tag"], parse_tags);
+        let _ = remove_dir_all(&directory);
+
+        assert_eq!(sampled, 3);
+        assert!(before.is_empty());
+        let mut after = after;
+        after.sort();
+        assert_eq!(after.len(), 2);
+        assert!(after[0].ends_with("b.rs changed since it was indexed."));
+        assert!(after[1].starts_with("The directives recorded for "));
+        assert_eq!(none, 0);
+    }
+
+    #[test]
+    fn load_missing() {
+        assert!(load(Path::new("nonexistent.json")).unwrap().is_none());
+    }
+}
@@ -0,0 +1,114 @@
+// This function computes the Levenshtein distance between two strings, i.e., the minimum number of
+// single-character insertions, deletions, and substitutions needed to turn one into the other.
+pub fn levenshtein(x: &str, y: &str) -> usize {
+    let y = y.chars().collect::<Vec<_>>();
+
+    // Only the previous row of the dynamic programming table is needed to compute the next one.
+    let mut row = (0..=y.len()).collect::<Vec<_>>();
+    for (i, x_char) in x.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, y_char) in y.iter().enumerate() {
+            let substitution = diagonal + usize::from(x_char != *y_char);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[y.len()]
+}
+
+// This function computes the optimal string alignment distance between two strings, which is like
+// the Levenshtein distance except that swapping two adjacent characters counts as a single edit.
+pub fn transposition_distance(x: &str, y: &str) -> usize {
+    let x = x.chars().collect::<Vec<_>>();
+    let y = y.chars().collect::<Vec<_>>();
+
+    // Only the previous two rows of the dynamic programming table are needed to compute the next
+    // one.
+    let mut previous = Vec::new();
+    let mut row = (0..=y.len()).collect::<Vec<_>>();
+    for i in 0..x.len() {
+        let mut next = vec![i + 1; y.len() + 1];
+        for j in 0..y.len() {
+            next[j + 1] = (row[j] + usize::from(x[i] != y[j]))
+                .min(row[j + 1] + 1)
+                .min(next[j] + 1);
+            if i > 0 && j > 0 && x[i] == y[j - 1] && x[i - 1] == y[j] {
+                next[j + 1] = next[j + 1].min(previous[j - 1] + 1);
+            }
+        }
+        previous = row;
+        row = next;
+    }
+
+    row[y.len()]
+}
+
+// This function returns the maximum distance at which a candidate is considered close to the given
+// string. Longer strings tolerate more typos.
+pub fn threshold(x: &str) -> usize {
+    (x.chars().count() / 4).max(1)
+}
+
+// This function finds the candidates which are close to the given string, sorted by distance and
+// then alphabetically.
+pub fn close_matches<'a>(x: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let threshold = threshold(x);
+    let mut matches = candidates
+        .into_iter()
+        .map(|candidate| (levenshtein(x, candidate), candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .collect::<Vec<_>>();
+    matches.sort_unstable();
+    matches.dedup();
+    matches
+        .into_iter()
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::distance::{close_matches, levenshtein, transposition_distance};
+
+    #[test]
+    fn levenshtein_empty() {
+        assert_eq!(levenshtein("", ""), 0);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("abc", ""), 3);
+    }
+
+    #[test]
+    fn levenshtein_edits() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("cache", "cache"), 0);
+        assert_eq!(levenshtein("cach", "cache"), 1);
+        assert_eq!(levenshtein("ünïcode", "unicode"), 2);
+    }
+
+    #[test]
+    fn transposition_distance_edits() {
+        assert_eq!(transposition_distance("", "abc"), 3);
+        assert_eq!(transposition_distance("recieve", "receive"), 1);
+        assert_eq!(transposition_distance("recieve", "relieve"), 1);
+        assert_eq!(transposition_distance("kitten", "sitting"), 3);
+        assert_eq!(transposition_distance("ca", "abc"), 3);
+    }
+
+    #[test]
+    fn close_matches_sorted() {
+        assert_eq!(
+            close_matches(
+                "cache_invalidation",
+                ["cache_invalidations", "cache_validation", "unrelated"],
+            ),
+            ["cache_invalidations", "cache_validation"],
+        );
+    }
+
+    #[test]
+    fn close_matches_none() {
+        assert!(close_matches("ab", ["xyz"]).is_empty());
+    }
+}
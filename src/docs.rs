@@ -0,0 +1,391 @@
+use {
+    crate::{
+        changelog::namespace, directive::Directive, links::url, report::escape_html,
+        walk::normalize,
+    },
+    std::{
+        collections::{BTreeMap, HashMap},
+        fmt::{self, Write},
+        str::FromStr,
+    },
+};
+
+// This enum represents the formats of the glossary.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DocsFormat {
+    Markdown,
+    Html,
+}
+
+// The names of the formats, for the command-line interface
+pub const DOCS_FORMATS: &[&str] = &["markdown", "html"];
+
+impl FromStr for DocsFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            _ => Err(format!("Unknown format `{s}`.")),
+        }
+    }
+}
+
+impl fmt::Display for DocsFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Markdown => "markdown",
+                Self::Html => "html",
+            },
+        )
+    }
+}
+
+// This struct represents a glossary entry: a label along with its tags and references.
+struct Entry<'a> {
+    label: &'a str,
+    description: &'a str,
+    tags: Vec<&'a Directive>,
+    refs: Vec<&'a Directive>,
+}
+
+impl Entry<'_> {
+    // This function returns the metadata of the entry. If the tags disagree, the first one wins.
+    fn metadata(&self) -> BTreeMap<&str, &str> {
+        let mut metadata = BTreeMap::new();
+        for tag in &self.tags {
+            for (key, value) in &tag.metadata {
+                metadata.entry(key.as_str()).or_insert(value.as_str());
+            }
+        }
+        metadata
+    }
+}
+
+// This function returns the anchor of the glossary entry for a label. It only depends on the label,
+// so links to the entry keep working as the glossary changes.
+pub fn anchor(label: &str) -> String {
+    let mut anchor = "tag-".to_owned();
+    anchor.extend(label.chars().map(|c| {
+        if c.is_alphanumeric() || "-_.".contains(c) {
+            c
+        } else {
+            '-'
+        }
+    }));
+    anchor
+}
+
+// This function describes the location of a directive, e.g., `src/main.rs:42`.
+fn location(directive: &Directive) -> String {
+    format!(
+        "{}:{}",
+        normalize(&directive.path).to_string_lossy(),
+        directive.line_number,
+    )
+}
+
+// This function groups the tags (and their references) into glossary entries by the namespace of
+// each label, with the entries sorted by label. Labels without a namespace are listed last.
+fn group<'a>(
+    tags: &'a HashMap<String, Vec<Directive>>,
+    refs: &'a [Directive],
+    separator: &str,
+) -> Vec<(Option<&'a str>, Vec<Entry<'a>>)> {
+    let mut refs_by_label = HashMap::<&str, Vec<&Directive>>::new();
+    for r#ref in refs {
+        refs_by_label.entry(&r#ref.label).or_default().push(r#ref);
+    }
+
+    let mut namespaces = BTreeMap::<Option<&str>, Vec<Entry>>::new();
+    for (label, tags) in tags {
+        let mut tags = tags.iter().collect::<Vec<_>>();
+        tags.sort_by(|x, y| (&x.path, x.line_number).cmp(&(&y.path, y.line_number)));
+        let mut refs = refs_by_label.remove(label.as_str()).unwrap_or_default();
+        refs.sort_by(|x, y| (&x.path, x.line_number).cmp(&(&y.path, y.line_number)));
+        namespaces
+            .entry(namespace(label, separator))
+            .or_default()
+            .push(Entry {
+                label,
+                description: tags
+                    .iter()
+                    .map(|tag| tag.description.as_str())
+                    .find(|description| !description.is_empty())
+                    .unwrap_or_default(),
+                tags,
+                refs,
+            });
+    }
+
+    // `None` sorts first, so it's moved to the end.
+    let mut groups = namespaces.into_iter().collect::<Vec<_>>();
+    for (_, entries) in &mut groups {
+        entries.sort_by_key(|entry| entry.label);
+    }
+    let without_namespace = usize::from(
+        groups
+            .first()
+            .is_some_and(|(namespace, _)| namespace.is_none()),
+    );
+    groups.rotate_left(without_namespace);
+    groups
+}
+
+// This function renders a glossary entry as Markdown.
+fn markdown_entry(output: &mut String, entry: &Entry, base_url: &str) {
+    let _ = write!(
+        output,
+        "\n<a id=\"{}\"></a>\n\n### `{}`\n\n",
+        anchor(entry.label),
+        entry.label,
+    );
+    if !entry.description.is_empty() {
+        let _ = write!(output, "{}\n\n", entry.description);
+    }
+    for (key, value) in entry.metadata() {
+        let _ = writeln!(output, "- `{key}`: {value}");
+    }
+    let links = |directives: &[&Directive]| {
+        directives
+            .iter()
+            .map(|directive| {
+                format!(
+                    "[{}]({})",
+                    location(directive),
+                    url(base_url, &directive.path, directive.line_number),
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let _ = writeln!(output, "- Defined in {}", links(&entry.tags));
+    if !entry.refs.is_empty() {
+        let _ = writeln!(output, "- Referenced in {}", links(&entry.refs));
+    }
+}
+
+// This function renders a glossary entry as HTML.
+fn html_entry(output: &mut String, entry: &Entry, base_url: &str) {
+    let anchor = anchor(entry.label);
+    let _ = writeln!(
+        output,
+        "<section id=\"{}\">\n<h3><a href=\"#{}\"><code>{}</code></a></h3>",
+        escape_html(&anchor),
+        escape_html(&anchor),
+        escape_html(entry.label),
+    );
+    if !entry.description.is_empty() {
+        let _ = writeln!(output, "<p>{}</p>", escape_html(entry.description));
+    }
+    output.push_str("<ul>\n");
+    for (key, value) in entry.metadata() {
+        let _ = writeln!(
+            output,
+            "<li><code>{}</code>: {}</li>",
+            escape_html(key),
+            escape_html(value),
+        );
+    }
+    let links = |directives: &[&Directive]| {
+        directives
+            .iter()
+            .map(|directive| {
+                format!(
+                    "<a href=\"{}\">{}</a>",
+                    escape_html(&url(base_url, &directive.path, directive.line_number)),
+                    escape_html(&location(directive)),
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let _ = writeln!(output, "<li>Defined in {}</li>", links(&entry.tags));
+    if !entry.refs.is_empty() {
+        let _ = writeln!(output, "<li>Referenced in {}</li>", links(&entry.refs));
+    }
+    output.push_str("</ul>\n</section>\n");
+}
+
+// This function renders the tags as a glossary in the given format, grouped by the namespace of
+// each label (the part before the first occurrence of the separator). Each entry links to the tags
+// and their references relative to the base URL, or relative to the root of the repository if
+// there isn't one.
+pub fn render(
+    format: DocsFormat,
+    tags: &HashMap<String, Vec<Directive>>,
+    refs: &[Directive],
+    base_url: Option<&str>,
+    separator: &str,
+) -> String {
+    let base_url = base_url.unwrap_or_default();
+    let groups = group(tags, refs, separator);
+
+    let mut body = String::new();
+    for (namespace, entries) in &groups {
+        match format {
+            DocsFormat::Markdown => {
+                let _ = writeln!(
+                    body,
+                    "\n## {}",
+                    namespace
+                        .map_or_else(|| "Other".to_owned(), |namespace| format!("`{namespace}`")),
+                );
+            }
+            DocsFormat::Html => {
+                let _ = writeln!(
+                    body,
+                    "<h2>{}</h2>",
+                    namespace.map_or_else(
+                        || "Other".to_owned(),
+                        |namespace| format!("<code>{}</code>", escape_html(namespace)),
+                    ),
+                );
+            }
+        }
+        for entry in entries {
+            match format {
+                DocsFormat::Markdown => markdown_entry(&mut body, entry, base_url),
+                DocsFormat::Html => html_entry(&mut body, entry, base_url),
+            }
+        }
+    }
+
+    match format {
+        DocsFormat::Markdown if groups.is_empty() => {
+            "# Glossary\n\nNo tags were found.\n".to_owned()
+        }
+        DocsFormat::Markdown => format!("# Glossary\n{body}"),
+        DocsFormat::Html => format!(
+            "<!DOCTYPE html>
+<html lang=\"en\">
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>Glossary</title>
+</head>
+<body>
+<h1>Glossary</h1>
+{}</body>
+</html>
+",
+            if groups.is_empty() {
+                "<p>No tags were found.</p>\n"
+            } else {
+                &body
+            },
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            directive::{Directive, Type},
+            docs::{anchor, render, DocsFormat},
+        },
+        std::collections::HashMap,
+    };
+
+    fn directive(r#type: Type, label: &str, description: &str, path: &str) -> Directive {
+        Directive {
+            description: description.to_owned(),
+            ..Directive::new(r#type, label, path, 3)
+        }
+    }
+
+    fn tags() -> HashMap<String, Vec<Directive>> {
+        let mut owned = directive(
+            Type::Tag,
+            "auth.tokens",
+            "Tokens are <hashed>.",
+            "./auth.rs",
+        );
+        owned
+            .metadata
+            .insert("owner".to_owned(), "@acme/auth".to_owned());
+        [
+            owned,
+            directive(Type::Tag, "cache", "", "./cache.rs"),
+            directive(Type::Tag, "auth.session", "", "./auth.rs"),
+        ]
+        .into_iter()
+        .map(|tag| (tag.label.clone(), vec![tag]))
+        .collect()
+    }
+
+    #[test]
+    fn anchor_stable() {
+        assert_eq!(anchor("auth.tokens"), "tag-auth.tokens");
+        assert_eq!(anchor("a b/c"), "tag-a-b-c");
+    }
+
+    #[test]
+    fn render_markdown() {
+        let refs = [directive(Type::Ref, "auth.tokens", "", "./api.rs")];
+
+        assert_eq!(
+            render(
+                DocsFormat::Markdown,
+                &tags(),
+                &refs,
+                Some("https://example.com/"),
+                ".",
+            ),
+            "# Glossary\n\
+             \n\
+             ## `auth`\n\
+             \n\
+             <a id=\"tag-auth.session\"></a>\n\
+             \n\
+             ### `auth.session`\n\
+             \n\
+             - Defined in [auth.rs:3](https://example.com/auth.rs#L3)\n\
+             \n\
+             <a id=\"tag-auth.tokens\"></a>\n\
+             \n\
+             ### `auth.tokens`\n\
+             \n\
+             Tokens are <hashed>.\n\
+             \n\
+             - `owner`: @acme/auth\n\
+             - Defined in [auth.rs:3](https://example.com/auth.rs#L3)\n\
+             - Referenced in [api.rs:3](https://example.com/api.rs#L3)\n\
+             \n\
+             ## Other\n\
+             \n\
+             <a id=\"tag-cache\"></a>\n\
+             \n\
+             ### `cache`\n\
+             \n\
+             - Defined in [cache.rs:3](https://example.com/cache.rs#L3)\n",
+        );
+    }
+
+    #[test]
+    fn render_html() {
+        let html = render(DocsFormat::Html, &tags(), &[], None, ".");
+
+        assert!(html.contains("<h2><code>auth</code></h2>"));
+        assert!(html.contains(
+            "<section id=\"tag-auth.tokens\">\n\
+             <h3><a href=\"#tag-auth.tokens\"><code>auth.tokens</code></a></h3>\n\
+             <p>Tokens are &lt;hashed&gt;.</p>",
+        ));
+        assert!(html.contains("<li>Defined in <a href=\"auth.rs#L3\">auth.rs:3</a></li>"));
+        assert!(!html.contains("Referenced in"));
+    }
+
+    #[test]
+    fn render_empty() {
+        assert_eq!(
+            render(DocsFormat::Markdown, &HashMap::new(), &[], None, "."),
+            "# Glossary\n\nNo tags were found.\n",
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use {
+    crate::config::PathPattern,
+    ignore::{
+        gitignore::{Gitignore, GitignoreBuilder, Glob},
+        Match,
+    },
+    std::{
+        fs,
+        path::{Path, PathBuf},
+    },
+};
+
+// These are the ignore files which are read in each directory, in order of precedence. The first
+// one which has a matching rule decides whether a path is ignored.
+const IGNORE_FILES: &[&str] = &[".ignore", ".gitignore", ".git/info/exclude"];
+
+// This function describes where an ignore rule came from, including the line number if it can be
+// found.
+fn origin(glob: &Glob) -> String {
+    let Some(from) = glob.from() else {
+        return format!("`{}`", glob.original());
+    };
+    let line_number = fs::read_to_string(from).ok().and_then(|contents| {
+        contents
+            .lines()
+            .position(|line| line.trim() == glob.original().trim())
+            .map(|i| i + 1)
+    });
+
+    match line_number {
+        Some(line_number) => format!(
+            "`{}` on line {line_number} of `{}`",
+            glob.original(),
+            from.display(),
+        ),
+        None => format!("`{}` in `{}`", glob.original(), from.display()),
+    }
+}
+
+// This function resolves a path to an absolute one so it can be compared to others, without
+// following a symlink at the end of it.
+fn resolve(path: &Path) -> PathBuf {
+    let path = Path::new(".").join(path);
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => parent.canonicalize().map(|parent| parent.join(name)),
+        _ => path.canonicalize(),
+    }
+    .unwrap_or(path)
+}
+
+// This function finds the ignore rule which decides whether a path is ignored, if there is one.
+// Like the traversal, it checks each directory on the way to the path before the path itself, and
+// it considers the ignore files in every directory containing them (including those above the
+// root) as well as the global ignore file. It returns the relative path which the rule matched,
+// whether the rule ignores it, and where the rule came from.
+fn ignore_rule(root: &Path, relative_path: &Path, is_dir: bool) -> Option<(PathBuf, bool, String)> {
+    let global = Gitignore::global().0;
+    let mut ancestors = relative_path.ancestors().collect::<Vec<_>>();
+    ancestors.pop(); // Skip the root itself.
+    for ancestor in ancestors.into_iter().rev() {
+        let path = root.join(ancestor);
+        let ancestor_is_dir = ancestor != relative_path || is_dir;
+
+        // The ignore files in deeper directories take precedence.
+        let rule = IGNORE_FILES
+            .iter()
+            .flat_map(|name| {
+                path.ancestors()
+                    .skip(1)
+                    .map(move |directory| (directory, directory.join(name)))
+            })
+            .filter(|(_, ignore_file)| ignore_file.is_file())
+            .find_map(|(directory, ignore_file)| {
+                let mut builder = GitignoreBuilder::new(directory);
+                builder.add(&ignore_file);
+                let gitignore = builder.build().ok()?;
+
+                // The `unwrap` is safe because the directory contains the path.
+                match gitignore.matched(path.strip_prefix(directory).unwrap(), ancestor_is_dir) {
+                    Match::None => None,
+                    Match::Ignore(glob) => Some((true, origin(glob))),
+                    Match::Whitelist(glob) => Some((false, origin(glob))),
+                }
+            })
+            .or_else(|| match global.matched(ancestor, ancestor_is_dir) {
+                Match::None => None,
+                Match::Ignore(glob) => Some((true, origin(glob))),
+                Match::Whitelist(glob) => Some((false, origin(glob))),
+            });
+
+        match rule {
+            Some((true, rule)) => return Some((ancestor.to_owned(), true, rule)),
+            Some((false, rule)) if ancestor == relative_path => {
+                return Some((ancestor.to_owned(), false, rule));
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+// This function explains whether a file or directory is scanned given the paths to scan and the
+// excluded patterns, and if not, which rule causes it to be skipped.
+pub fn why(path: &Path, roots: &[PathBuf], excludes: &[PathPattern]) -> String {
+    let display = path.display();
+    let Ok(metadata) = path.symlink_metadata() else {
+        return format!("`{display}` doesn't exist.");
+    };
+
+    // Find the path to scan which contains this one.
+    let resolved_path = resolve(path);
+    let Some((root, relative_path)) = roots.iter().find_map(|root| {
+        resolved_path
+            .strip_prefix(resolve(root))
+            .ok()
+            .map(|relative_path| (root, relative_path.to_owned()))
+    }) else {
+        return format!(
+            "`{display}` is skipped because it isn't inside any of the paths being scanned ({}).",
+            roots
+                .iter()
+                .map(|root| format!("`{}`", root.display()))
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+    };
+
+    // Check the path and each directory containing it against the excluded patterns, as they
+    // would be seen during the traversal.
+    for ancestor in relative_path
+        .ancestors()
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+    {
+        let traversed_path = root.join(ancestor);
+        if let Some(pattern) = excludes
+            .iter()
+            .find(|pattern| pattern.is_match(&traversed_path))
+        {
+            return if ancestor == relative_path {
+                format!(
+                    "`{display}` is skipped because it matches the excluded pattern `{pattern}`.",
+                )
+            } else {
+                format!(
+                    "`{display}` is skipped because `{}` matches the excluded pattern `{pattern}`.",
+                    traversed_path.display(),
+                )
+            };
+        }
+    }
+
+    // Version control directories are never scanned.
+    if let Some(directory) = relative_path.components().find(|component| {
+        [".git", ".hg"].contains(&component.as_os_str().to_string_lossy().as_ref())
+    }) {
+        return format!(
+            "`{display}` is skipped because it's inside a `{}` directory.",
+            directory.as_os_str().to_string_lossy(),
+        );
+    }
+
+    // Symlinks aren't followed.
+    if metadata.file_type().is_symlink() {
+        return format!("`{display}` is skipped because it's a symlink.");
+    }
+
+    // Check the ignore files.
+    match ignore_rule(&resolve(root), &relative_path, metadata.is_dir()) {
+        Some((ignored_path, true, rule)) if ignored_path == relative_path => {
+            format!("`{display}` is skipped because of the ignore rule {rule}.")
+        }
+        Some((ignored_path, true, rule)) => format!(
+            "`{display}` is skipped because `{}` is ignored by the rule {rule}.",
+            root.join(ignored_path).display(),
+        ),
+        Some((_, false, rule)) => {
+            format!("`{display}` is scanned, since the ignore rule {rule} includes it.")
+        }
+        None => format!("`{display}` is scanned."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{config::PathPattern, doctor::why},
+        std::{
+            env::temp_dir,
+            fs::{create_dir_all, remove_dir_all, write},
+            process,
+        },
+    };
+
+    #[test]
+    fn why_ignored() {
+        let directory = temp_dir().join(format!("tagref-doctor-{}", process::id()));
+        let _ = remove_dir_all(&directory);
+        create_dir_all(directory.join("build")).unwrap();
+        create_dir_all(directory.join("src")).unwrap();
+        write(
+            directory.join(".ignore"),
+            "# Outputs\nbuild/\n*.log\n!keep.log\n",
+        )
+        .unwrap();
+        for file in ["build/out.rs", "src/keep.log", "src/lib.rs", "src/gen.rs"] {
+            write(directory.join(file), "").unwrap();
+        }
+        let roots = [directory.clone()];
+        let excludes = ["**/gen.rs".parse::<PathPattern>().unwrap()];
+
+        let out = why(&directory.join("build/out.rs"), &roots, &excludes);
+        let keep = why(&directory.join("src/keep.log"), &roots, &excludes);
+        let lib = why(&directory.join("src/lib.rs"), &roots, &excludes);
+        let generated = why(&directory.join("src/gen.rs"), &roots, &excludes);
+        let missing = why(&directory.join("src/missing.rs"), &roots, &excludes);
+        let outside = why(
+            &directory.join("src/lib.rs"),
+            &[directory.join("build")],
+            &[],
+        );
+        let _ = remove_dir_all(&directory);
+
+        assert!(out.ends_with(&format!(
+            "build` is ignored by the rule `build/` on line 2 of `{}`.",
+            directory.join(".ignore").display(),
+        )));
+        assert!(keep.contains("is scanned, since the ignore rule `!keep.log` on line 4"));
+        assert!(lib.ends_with("lib.rs` is scanned."));
+        assert!(generated.ends_with("it matches the excluded pattern `**/gen.rs`."));
+        assert!(missing.ends_with("missing.rs` doesn't exist."));
+        assert!(outside.contains("isn't inside any of the paths being scanned"));
+    }
+}
@@ -1,21 +1,144 @@
 use {
-    crate::directive::Directive,
-    std::{collections::HashMap, fmt::Write},
+    crate::{
+        config::DuplicateTolerance,
+        count::count,
+        diagnostic::{self, Diagnostic},
+        directive::Directive,
+        packages::Packages,
+    },
+    std::{
+        collections::{BTreeMap, HashMap},
+        fmt::Write,
+        path::PathBuf,
+    },
 };
 
-// This function checks that all the vectors in `tags_map` have at most one element. It returns a
-// vector of error strings.
-pub fn check(tags_map: &HashMap<String, Vec<Directive>>) -> Vec<String> {
-    let mut errors = Vec::<String>::new();
-
+// This function groups the tags in `tags_map` which must have distinct labels: all the tags with
+// each label in the same package, or outside of any package. It returns the label, the package,
+// and the tags of each group. The groups are sorted by label and then package, and the tags in
+// each group are sorted by location, so the order doesn't depend on how the tags were found.
+pub fn groups<'a>(
+    tags_map: &'a HashMap<String, Vec<Directive>>,
+    packages: &Packages,
+) -> Vec<(&'a str, Option<PathBuf>, Vec<Directive>)> {
+    let mut groups = vec![];
     for (label, directives) in tags_map {
-        if directives.len() > 1 {
+        let mut by_package = BTreeMap::<Option<PathBuf>, Vec<Directive>>::new();
+        for directive in directives {
+            by_package
+                .entry(packages.package(&directive.path))
+                .or_default()
+                .push(directive.clone());
+        }
+        groups.extend(by_package.into_iter().map(|(package, mut directives)| {
+            directives.sort_by(|x, y| {
+                (&x.path, x.cell, x.line_number).cmp(&(&y.path, y.cell, y.line_number))
+            });
+            (label.as_str(), package, directives)
+        }));
+    }
+    groups.sort_by(|(x_label, x_package, _), (y_label, y_package, _)| {
+        (x_label, x_package).cmp(&(y_label, y_package))
+    });
+    groups
+}
+
+// This function finds the first `duplicate-tolerance` entry which matches a label, along with its
+// index.
+pub fn tolerance<'a>(
+    tolerances: &'a [DuplicateTolerance],
+    label: &str,
+) -> Option<(usize, &'a DuplicateTolerance)> {
+    tolerances
+        .iter()
+        .enumerate()
+        .find(|(_, tolerance)| tolerance.label.is_match(label))
+}
+
+// This function determines whether a group of tags sharing a label is a problem: a label may only
+// be used by one tag, unless a `duplicate-tolerance` entry says otherwise.
+pub fn is_violation(tolerances: &[DuplicateTolerance], label: &str, tags: usize) -> bool {
+    tolerance(tolerances, label).map_or(tags > 1, |(_, tolerance)| !tolerance.allows(tags))
+}
+
+// This function finds the original among duplicate tags, i.e., the one which was written first
+// according to `time`. Duplicates are usually copies of the original by someone who meant to write
+// a reference. It returns `None` if the time of any of the tags is unknown, or if there's a tie.
+pub fn original(
+    directives: &[Directive],
+    time: impl Fn(&Directive) -> Option<u64>,
+) -> Option<&Directive> {
+    let mut times = directives
+        .iter()
+        .map(|directive| time(directive).map(|time| (time, directive)))
+        .collect::<Option<Vec<_>>>()?;
+    times.sort_by_key(|(time, _)| *time);
+
+    match &times[..] {
+        [(first, original), (second, _), ..] if first < second => Some(original),
+        _ => None,
+    }
+}
+
+// This function checks that all the vectors in `tags_map` have at most one element, except that
+// tags in different packages may share a label, and labels matching a `duplicate-tolerance` entry
+// must have as many tags as the entry says. It returns a vector of errors, one per label (and
+// package) sorted by label, each listing the tags in order of location and located at the first of
+// them. If the original tag can be determined using `time`, the error suggests turning the others
+// into references.
+pub fn check(
+    tags_map: &HashMap<String, Vec<Directive>>,
+    packages: &Packages,
+    tolerances: &[DuplicateTolerance],
+    time: impl Fn(&Directive) -> Option<u64>,
+) -> Vec<Diagnostic> {
+    let mut errors = Vec::<Diagnostic>::new();
+
+    for (label, package, directives) in groups(tags_map, packages) {
+        if is_violation(tolerances, label, directives.len()) {
+            let in_package = package
+                .map(|package| format!(" in package `{}`", package.to_string_lossy()))
+                .unwrap_or_default();
+            let tolerance = tolerance(tolerances, label);
             let mut error = String::new();
-            let _ = writeln!(error, "Duplicate tags found for label `{label}`:");
-            for directive in directives {
+            match tolerance {
+                Some((i, tolerance)) => {
+                    let _ = writeln!(
+                        error,
+                        "Expected {} for label `{label}`{in_package} (see `duplicate-tolerance` \
+                            entry #{}), but found {}:",
+                        // The `unwrap` is safe due to [ref:duplicate_tolerance_count].
+                        tolerance.count.map_or_else(
+                            || format!("at most {}", count(tolerance.max_count.unwrap(), "tag")),
+                            |expected| format!("exactly {}", count(expected, "tag")),
+                        ),
+                        i + 1,
+                        directives.len(),
+                    );
+                }
+                None => {
+                    let _ = writeln!(
+                        error,
+                        "Duplicate tags found for label `{label}`{in_package} ({}):",
+                        count(directives.len(), "tag"),
+                    );
+                }
+            }
+            for directive in &directives {
                 let _ = writeln!(error, "  {directive}");
             }
-            errors.push(error);
+            if let (None, Some(original)) = (tolerance, original(&directives, &time)) {
+                let _ = writeln!(
+                    error,
+                    "The oldest is {original}, so the others may have been meant to be \
+                     references. Use `--fix` to turn them into references.",
+                );
+            }
+            errors.push(Diagnostic::error(
+                diagnostic::DUPLICATE_TAG,
+                &directives[0],
+                error,
+            ));
         }
     }
 
@@ -26,105 +149,184 @@ pub fn check(tags_map: &HashMap<String, Vec<Directive>>) -> Vec<String> {
 mod tests {
     use {
         crate::{
+            config::parse,
             directive::{Directive, Type},
-            duplicates::check,
+            duplicates::{check, is_violation, original},
+            packages::Packages,
         },
-        std::{collections::HashMap, path::Path},
+        std::collections::HashMap,
     };
 
     #[test]
     fn check_empty() {
-        assert!(check(&HashMap::new()).is_empty());
+        assert!(check(&HashMap::new(), &Packages::default(), &[], |_| None).is_empty());
     }
 
     #[test]
     fn check_no_dupes() {
         let mut tags_map = HashMap::new();
 
-        let tags_vec1 = vec![Directive {
-            r#type: Type::Tag,
-            label: "tag1".to_owned(),
-            path: Path::new("file1.rs").to_owned(),
-            line_number: 1,
-        }];
+        let tags_vec1 = vec![Directive::new(Type::Tag, "tag1", "file1.rs", 1)];
 
-        let tags_vec2 = vec![Directive {
-            r#type: Type::Tag,
-            label: "tag2".to_owned(),
-            path: Path::new("file2.rs").to_owned(),
-            line_number: 2,
-        }];
+        let tags_vec2 = vec![Directive::new(Type::Tag, "tag2", "file2.rs", 2)];
 
         tags_map.insert("tag1".to_owned(), tags_vec1);
         tags_map.insert("tag2".to_owned(), tags_vec2);
 
-        assert!(check(&tags_map).is_empty());
+        assert!(check(&tags_map, &Packages::default(), &[], |_| None).is_empty());
     }
 
     #[test]
     fn check_dupes() {
         let mut tags_map = HashMap::new();
 
-        let tags_vec1 = vec![Directive {
-            r#type: Type::Tag,
-            label: "tag1".to_owned(),
-            path: Path::new("file1.rs").to_owned(),
-            line_number: 1,
-        }];
+        let tags_vec1 = vec![Directive::new(Type::Tag, "tag1", "file1.rs", 1)];
 
         let tags_vec2 = vec![
-            Directive {
-                r#type: Type::Tag,
-                label: "tag2".to_owned(),
-                path: Path::new("file1.rs").to_owned(),
-                line_number: 1,
-            },
-            Directive {
-                r#type: Type::Tag,
-                label: "tag2".to_owned(),
-                path: Path::new("file2.rs").to_owned(),
-                line_number: 2,
-            },
+            Directive::new(Type::Tag, "tag2", "file1.rs", 1),
+            Directive::new(Type::Tag, "tag2", "file2.rs", 2),
         ];
 
-        let tags_vec3 = vec![
-            Directive {
-                r#type: Type::Tag,
-                label: "tag3".to_owned(),
-                path: Path::new("file1.rs").to_owned(),
-                line_number: 1,
-            },
-            Directive {
-                r#type: Type::Tag,
-                label: "tag3".to_owned(),
-                path: Path::new("file2.rs").to_owned(),
-                line_number: 2,
-            },
-            Directive {
-                r#type: Type::Tag,
-                label: "tag3".to_owned(),
-                path: Path::new("file3.rs").to_owned(),
-                line_number: 2,
-            },
+        let tags_vec3 = [
+            Directive::new(Type::Tag, "tag3", "file1.rs", 1),
+            Directive::new(Type::Tag, "tag3", "file2.rs", 2),
+            Directive::new(Type::Tag, "tag3", "file3.rs", 2),
         ];
 
         tags_map.insert("tag1".to_owned(), tags_vec1.clone());
         tags_map.insert("tag2".to_owned(), tags_vec2.clone());
-        tags_map.insert("tag3".to_owned(), tags_vec3.clone());
+        tags_map.insert(
+            "tag3".to_owned(),
+            tags_vec3.iter().rev().cloned().collect::<Vec<_>>(),
+        );
 
-        let errors = check(&tags_map);
+        let errors = check(&tags_map, &Packages::default(), &[], |_| None);
         assert_eq!(errors.len(), 2);
+        assert_eq!(
+            errors[0].message,
+            format!(
+                "Duplicate tags found for label `tag2` (2 tags):\n  {}\n  {}\n",
+                tags_vec2[0],
+                tags_vec2[1],
+            ),
+        );
+        assert_eq!(
+            errors[1].message,
+            format!(
+                "Duplicate tags found for label `tag3` (3 tags):\n  {}\n  {}\n  {}\n",
+                tags_vec3[0],
+                tags_vec3[1],
+                tags_vec3[2],
+            ),
+        );
+    }
+
+    #[test]
+    fn original_oldest() {
+        let directive = |line_number| Directive::new(Type::Tag, "tag1", "file1.rs", line_number);
+        let directives = [directive(1), directive(2), directive(3)];
+
+        let time = |directive: &Directive| Some([30_u64, 20, 10][directive.line_number - 1]);
+        assert_eq!(
+            original(&directives, time).map(|tag| tag.line_number),
+            Some(3),
+        );
+
+        let tied = |directive: &Directive| Some(u64::from(directive.line_number == 3));
+        assert!(original(&directives, tied).is_none());
+
+        let unknown = |directive: &Directive| (directive.line_number != 1).then_some(1_u64);
+        assert!(original(&directives, unknown).is_none());
+
+        let mut tags_map = HashMap::new();
+        tags_map.insert("tag1".to_owned(), directives.to_vec());
+        let errors = check(&tags_map, &Packages::default(), &[], time);
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0]
+                .message
+                .contains(&format!("The oldest is {}", directives[2])),
+        );
+    }
+
+    #[test]
+    fn check_dupes_in_packages() {
+        let directive = |path: &str| Directive::new(Type::Tag, "tag1", path, 1);
+        let config = parse("[packages]\npaths = ['crates/*']").unwrap();
+        let packages = Packages::new(&config.packages);
+
+        let mut tags_map = HashMap::new();
+        tags_map.insert(
+            "tag1".to_owned(),
+            vec![
+                directive("crates/a/lib.rs"),
+                directive("crates/b/lib.rs"),
+                directive("crates/b/src/main.rs"),
+            ],
+        );
+
+        let errors = check(&tags_map, &packages, &[], |_| None);
+        assert_eq!(errors.len(), 1);
         assert!(
-            (errors[0].contains(&format!("{}", tags_vec2[0]))
-                && errors[0].contains(&format!("{}", tags_vec2[1]))
-                && errors[1].contains(&format!("{}", tags_vec3[0]))
-                && errors[1].contains(&format!("{}", tags_vec3[1]))
-                && errors[1].contains(&format!("{}", tags_vec3[2])))
-                || (errors[0].contains(&format!("{}", tags_vec3[0]))
-                    && errors[0].contains(&format!("{}", tags_vec3[1]))
-                    && errors[0].contains(&format!("{}", tags_vec3[2]))
-                    && errors[1].contains(&format!("{}", tags_vec2[0]))
-                    && errors[1].contains(&format!("{}", tags_vec2[1]))),
+            errors[0].message.starts_with(
+                "Duplicate tags found for label `tag1` in package `crates/b` (2 tags):",
+            ),
         );
+        assert!(!errors[0].message.contains("crates/a"));
+    }
+
+    #[test]
+    fn check_dupes_tolerated() {
+        let directive = |label: &str, path: &str| Directive::new(Type::Tag, label, path, 1);
+        let config = parse(
+            "
+            [[duplicate-tolerance]]
+            label = '^header$'
+            count = 3
+
+            [[duplicate-tolerance]]
+            label = '^example_'
+            max-count = 2
+            ",
+        )
+        .unwrap();
+        let tolerances = &config.duplicate_tolerance;
+
+        let mut tags_map = HashMap::new();
+        tags_map.insert(
+            "header".to_owned(),
+            vec![directive("header", "a.rs"), directive("header", "b.rs")],
+        );
+        tags_map.insert(
+            "example_ok".to_owned(),
+            vec![
+                directive("example_ok", "a.rs"),
+                directive("example_ok", "b.rs"),
+            ],
+        );
+        tags_map.insert(
+            "example_many".to_owned(),
+            vec![
+                directive("example_many", "a.rs"),
+                directive("example_many", "b.rs"),
+                directive("example_many", "c.rs"),
+            ],
+        );
+
+        let errors = check(&tags_map, &Packages::default(), tolerances, |_| Some(1));
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].message.starts_with(
+            "Expected at most 2 tags for label `example_many` (see `duplicate-tolerance` entry \
+                #2), but found 3:",
+        ));
+        assert!(errors[1].message.starts_with(
+            "Expected exactly 3 tags for label `header` (see `duplicate-tolerance` entry #1), but \
+                found 2:",
+        ));
+        assert!(!errors[1].message.contains("--fix"));
+
+        assert!(!is_violation(tolerances, "header", 3));
+        assert!(is_violation(tolerances, "other", 2));
+        assert!(!is_violation(tolerances, "other", 1));
     }
 }
@@ -0,0 +1,167 @@
+use {
+    crate::{atomic, git},
+    std::{
+        env::current_dir,
+        fs::read,
+        path::{Component, Path, PathBuf},
+    },
+};
+
+// This is the directory where the original contents of edited files are saved with `--backup`. It's
+// never scanned, since the copies would duplicate every tag in them.
+pub const BACKUP_DIR: &str = ".tagref-backup";
+
+// This struct represents how the subcommands which edit files (e.g., `check --fix`) should do so.
+#[derive(Clone, Copy, Default)]
+pub struct Options {
+    // Only report the changes, without writing anything
+    pub dry_run: bool,
+
+    // Save the original contents of each file in the backup directory before editing it
+    pub backup: bool,
+
+    // Edit files even if they have uncommitted changes
+    pub allow_dirty: bool,
+}
+
+// This struct represents the new contents of a file.
+pub struct Edit {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+// This function returns where the original contents of a file are saved by `--backup`. The path is
+// resolved against the root of the repository, which is assumed to be the working directory, so
+// different files never share a backup. Files outside of the repository can't be backed up.
+pub fn backup_path(path: &Path) -> Result<PathBuf, String> {
+    let root = current_dir()
+        .map_err(|error| format!("Unable to determine the working directory: {error}"))?;
+
+    // Resolve `.` and `..` without touching the file system, since the file may be a symlink.
+    let mut resolved = PathBuf::new();
+    for component in root.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            _ => resolved.push(component),
+        }
+    }
+
+    resolved
+        .strip_prefix(&root)
+        .map(|relative| Path::new(BACKUP_DIR).join(relative))
+        .map_err(|_| {
+            format!(
+                "Unable to back up {}, since it's outside of the repository.",
+                path.to_string_lossy(),
+            )
+        })
+}
+
+// This function writes the edited files. Unless editing dirty files is allowed, it refuses to edit
+// anything if any of the files have uncommitted changes, since those changes couldn't be recovered
+// from Git. Each file is replaced atomically. Nothing is written for a dry run.
+pub fn write_all(edits: &[Edit], options: Options) -> Result<(), String> {
+    if options.dry_run {
+        return Ok(());
+    }
+
+    // Check every file before editing any of them.
+    if !options.allow_dirty {
+        let dirty = edits
+            .iter()
+            .filter(|edit| git::is_dirty(&edit.path))
+            .map(|edit| format!("  {}", edit.path.to_string_lossy()))
+            .collect::<Vec<_>>();
+        if !dirty.is_empty() {
+            return Err(format!(
+                "Refusing to edit files with uncommitted changes (use `--allow-dirty` to edit \
+                 them anyway, or `--dry-run` to see the changes):\n{}",
+                dirty.join("\n"),
+            ));
+        }
+    }
+
+    // Find where every file will be backed up before editing any of them, too.
+    let backup_paths = if options.backup {
+        edits
+            .iter()
+            .map(|edit| backup_path(&edit.path).map(Some))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        vec![None; edits.len()]
+    };
+
+    for (edit, backup_path) in edits.iter().zip(backup_paths) {
+        if let Some(backup_path) = backup_path {
+            let original = read(&edit.path).map_err(|error| {
+                format!("Unable to read {}: {error}", edit.path.to_string_lossy())
+            })?;
+            atomic::write(&backup_path, original)?;
+        }
+        atomic::write(&edit.path, &edit.contents)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::edit::{backup_path, write_all, Edit, Options},
+        std::{
+            env::{current_dir, temp_dir},
+            fs::{create_dir_all, read_to_string, remove_dir_all, write},
+            path::Path,
+            process,
+        },
+    };
+
+    #[test]
+    fn backup_path_relative() {
+        assert_eq!(
+            backup_path(Path::new("./src/../lib/a.rs")).unwrap(),
+            Path::new(".tagref-backup/lib/a.rs"),
+        );
+    }
+
+    #[test]
+    fn backup_path_absolute() {
+        let root = current_dir().unwrap();
+
+        assert_eq!(
+            backup_path(&root.join("lib/a.rs")).unwrap(),
+            Path::new(".tagref-backup/lib/a.rs"),
+        );
+        assert!(backup_path(&root.join("../a.rs")).is_err());
+        assert!(backup_path(Path::new("/a.rs")).is_err());
+    }
+
+    #[test]
+    fn write_all_dry_run() {
+        let directory = temp_dir().join(format!("tagref-edit-{}", process::id()));
+        let _ = remove_dir_all(&directory);
+        create_dir_all(&directory).unwrap();
+        let path = directory.join("a.rs");
+        write(&path, "old").unwrap();
+        let edits = [Edit {
+            path: path.clone(),
+            contents: "new".to_owned(),
+        }];
+
+        let dry_run = Options {
+            dry_run: true,
+            ..Options::default()
+        };
+        write_all(&edits, dry_run).unwrap();
+        let after_dry_run = read_to_string(&path).unwrap();
+        write_all(&edits, Options::default()).unwrap();
+        let after_edit = read_to_string(&path).unwrap();
+        let _ = remove_dir_all(&directory);
+
+        assert_eq!(after_dry_run, "old");
+        assert_eq!(after_edit, "new");
+    }
+}
@@ -0,0 +1,115 @@
+use {
+    crate::{diagnostic::Diagnostic, directive::Directive},
+    serde::{Deserialize, Serialize},
+};
+
+// The version of the export format, which is incremented whenever it changes incompatibly
+pub const EXPORT_VERSION: usize = 1;
+
+// This struct represents the results of scanning some files, so they can be combined with the
+// results of other scans (e.g., of other shards). It includes the problems found by the checks
+// which depend on the filesystem. The checks which depend on all the directives are left for
+// whoever combines the results.
+#[derive(Deserialize, Serialize)]
+pub struct Export {
+    #[serde(default)]
+    pub schema_version: usize,
+    pub version: usize,
+    pub shard: Option<String>,
+    pub files_scanned: usize,
+    pub scanned: Vec<String>,
+    pub directives: Vec<Directive>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+// This function renders an export as compact JSON.
+pub fn render(export: &Export) -> String {
+    // The `unwrap` is safe because exports always serialize successfully.
+    let mut json = serde_json::to_string(export).unwrap();
+    json.push('\n');
+    json
+}
+
+// This function parses an export.
+pub fn parse(contents: &str) -> Result<Export, String> {
+    let export: Export = serde_json::from_str(contents).map_err(|error| error.to_string())?;
+
+    if export.version != EXPORT_VERSION {
+        return Err(format!(
+            "Unsupported version {} (expected {EXPORT_VERSION}).",
+            export.version,
+        ));
+    }
+
+    Ok(export)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            diagnostic::{Diagnostic, Severity},
+            directive::{Directive, Type},
+            export::{parse, render, Export, EXPORT_VERSION},
+            schema::SCHEMA_VERSION,
+        },
+        std::path::Path,
+    };
+
+    fn example() -> Export {
+        Export {
+            schema_version: SCHEMA_VERSION,
+            version: EXPORT_VERSION,
+            shard: Some("1/2".to_owned()),
+            files_scanned: 1,
+            scanned: vec!["file1.rs".to_owned()],
+            directives: vec![Directive::new(Type::File, "missing.rs", "./file1.rs", 2)],
+            diagnostics: vec![Diagnostic {
+                code: "E003".to_owned(),
+                severity: Severity::Error,
+                message: "Missing.".to_owned(),
+                location: Some((Path::new("./file1.rs").to_owned(), 2)),
+                label: None,
+                context: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn render_export() {
+        let json: serde_json::Value = serde_json::from_str(&render(&example())).unwrap();
+        assert_eq!(json["version"], 1_u64);
+        assert_eq!(json["shard"], "1/2");
+        assert_eq!(json["files_scanned"], 1_u64);
+        assert_eq!(json["scanned"][0], "file1.rs");
+        assert_eq!(json["directives"][0]["type"], "file");
+        assert_eq!(json["directives"][0]["line"], 2_u64);
+        assert_eq!(json["diagnostics"][0]["code"], "E003");
+        assert_eq!(json["diagnostics"][0]["severity"], "error");
+        assert_eq!(json["diagnostics"][0]["location"][0], "./file1.rs");
+        assert_eq!(json["diagnostics"][0]["location"][1], 2_u64);
+    }
+
+    #[test]
+    fn parse_round_trip() {
+        let export = parse(&render(&example())).unwrap();
+
+        assert_eq!(export.shard.as_deref(), Some("1/2"));
+        assert_eq!(export.files_scanned, 1);
+        assert_eq!(export.scanned, ["file1.rs"]);
+        assert_eq!(export.directives[0].r#type, Type::File);
+        assert_eq!(export.directives[0].label, "missing.rs");
+        assert_eq!(export.directives[0].path, Path::new("./file1.rs"));
+        assert_eq!(export.directives[0].line_number, 2);
+        assert_eq!(export.diagnostics[0].severity, Severity::Error);
+        assert_eq!(
+            export.diagnostics[0].location,
+            Some((Path::new("./file1.rs").to_owned(), 2)),
+        );
+    }
+
+    #[test]
+    fn parse_wrong_version() {
+        assert!(parse(&render(&example()).replace("\"version\":1", "\"version\":2")).is_err());
+    }
+}
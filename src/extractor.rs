@@ -0,0 +1,57 @@
+use std::{path::Path, process::Command};
+
+// This function runs an extractor command on a file and returns what it printed. The path of the
+// file is appended to the command, which must not be empty [ref:extractor_command_nonempty].
+pub fn run(command: &[String], path: &Path) -> Result<Vec<u8>, String> {
+    let output = Command::new(&command[0])
+        .args(&command[1..])
+        .arg(path)
+        .output()
+        .map_err(|error| format!("Unable to run `{}`: {error}", command[0]))?;
+
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(format!(
+            "`{}` failed on {}: {}",
+            command.join(" "),
+            path.to_string_lossy(),
+            String::from_utf8_lossy(&output.stderr).trim(),
+        ))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use {
+        crate::extractor::run,
+        std::{
+            env::temp_dir,
+            fs::{remove_file, write},
+            path::Path,
+            process,
+        },
+    };
+
+    #[test]
+    fn run_success() {
+        let path = temp_dir().join(format!("tagref-extractor-{}.txt", process::id()));
+        write(&path, "[?ref:foo]".replace('?', "")).unwrap();
+        let output = run(&["cat".to_owned()], &path);
+        let _ = remove_file(&path);
+
+        assert_eq!(output.unwrap(), "[?ref:foo]".replace('?', "").into_bytes());
+    }
+
+    #[test]
+    fn run_failure() {
+        assert!(run(&["false".to_owned()], Path::new("file.pdf")).is_err());
+        assert!(
+            run(
+                &["tagref-no-such-command".to_owned()],
+                Path::new("file.pdf"),
+            )
+            .is_err(),
+        );
+    }
+}
@@ -1,23 +1,70 @@
-use {crate::directive::Directive, std::fs::metadata};
+use {
+    crate::{
+        diagnostic::{self, Diagnostic},
+        directive::Directive,
+        stat::Target,
+    },
+    std::collections::{hash_map::Entry, HashMap},
+};
 
-// This function checks that file references actually point to files. It returns a vector of error
-// strings.
-pub fn check(refs: &[Directive]) -> Vec<String> {
-    let mut errors = Vec::<String>::new();
+// This function checks that file references actually point to files, given what each path points
+// to (see `stat::stat_all`). Only the first reference to each bad path explains the problem, and
+// the others refer back to it. It returns a vector of errors.
+pub fn check(refs: &[Directive], targets: &HashMap<&str, Target>) -> Vec<Diagnostic> {
+    let mut errors = Vec::<Diagnostic>::new();
+    let mut first_refs = HashMap::<&str, &Directive>::new();
 
-    for file in refs {
-        match metadata(&file.label) {
-            Ok(metadata) => {
-                if !metadata.is_file() {
-                    errors.push(format!("{file} does not point to a file."));
-                }
+    let mut sorted_refs = refs.iter().collect::<Vec<_>>();
+    sorted_refs.sort_by(|x, y| (&x.path, x.line_number).cmp(&(&y.path, y.line_number)));
+    for file in sorted_refs {
+        // The `unwrap` is safe assuming every label was statted.
+        let message = match targets.get(file.label.as_str()).unwrap() {
+            Target::File => continue,
+            Target::Error(error) => format!("Error when validating {file}: {error}"),
+            _ => format!("{file} does not point to a file."),
+        };
+        let message = match first_refs.entry(&file.label) {
+            Entry::Occupied(entry) => format!("{file} has the same problem as {}.", entry.get()),
+            Entry::Vacant(entry) => {
+                entry.insert(file);
+                message
             }
-            Err(error) => {
-                let error_string = error.to_string();
-                errors.push(format!("Error when validating {file}: {error_string}"));
-            }
-        }
+        };
+        errors.push(Diagnostic::error(diagnostic::BAD_FILE_REF, file, message));
     }
 
     errors
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        file_references::check,
+        stat::stat_all,
+    };
+
+    fn file(label: &str, line_number: usize) -> Directive {
+        Directive::new(Type::File, label, "file1.rs", line_number)
+    }
+
+    #[test]
+    fn check_repeated() {
+        let refs = [
+            file("src", 3),
+            file("src/main.rs", 1),
+            file("src", 2),
+            file("nonexistent", 4),
+        ];
+        let targets = stat_all(refs.iter().map(|file| file.label.as_str()));
+
+        let errors = check(&refs, &targets);
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].message.ends_with("does not point to a file."));
+        assert_eq!(
+            errors[1].message,
+            format!("{} has the same problem as {}.", refs[0], refs[2]),
+        );
+        assert!(errors[2].message.starts_with("Error when validating"));
+    }
+}
@@ -0,0 +1,270 @@
+use {
+    crate::{
+        directive::{Directive, Type},
+        packages::Packages,
+        path_style::display,
+        stat::Target,
+        walk::normalize,
+    },
+    std::{
+        collections::{HashMap, HashSet},
+        fmt::Write,
+        path::Path,
+        ptr,
+    },
+};
+
+// This struct summarizes how the directives in a single file relate to the rest of the code.
+pub struct FileReport<'a> {
+    // The tags defined in the file, each with the references to it
+    pub tags: Vec<(&'a Directive, Vec<&'a Directive>)>,
+
+    // The tag references in the file, each with the tags it resolves to
+    pub refs: Vec<(&'a Directive, Resolution<'a>)>,
+
+    // The file and directory references in the file, each with what its path points to
+    pub paths: Vec<(&'a Directive, Target)>,
+}
+
+// This enum represents what a tag reference resolves to.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Resolution<'a> {
+    Tags(Vec<&'a Directive>),
+    Provided,
+    Missing,
+}
+
+// This function sorts directives by location.
+fn sort_by_location(directives: &mut [&Directive]) {
+    directives
+        .sort_by(|x, y| (&x.path, x.cell, x.line_number).cmp(&(&y.path, y.cell, y.line_number)));
+}
+
+// This function gathers the report for the file at `path`. References resolve to tags as they do
+// for the check: tags in the same package come first (see `Packages::resolve`), and the labels in
+// `provided` come from the providers. The `targets` give what each file or directory reference
+// points to (see `stat::stat_all`).
+pub fn report<'a>(
+    path: &Path,
+    tags: &[&'a Directive],
+    refs: &'a [Directive],
+    paths: impl IntoIterator<Item = &'a Directive>,
+    packages: &Packages,
+    provided: &HashSet<String>,
+    targets: &HashMap<&str, Target>,
+) -> FileReport<'a> {
+    let path = normalize(path);
+    let in_file = |directive: &Directive| normalize(&directive.path) == path;
+
+    // Index the tags by label.
+    let mut tags_by_label = HashMap::<&str, Vec<&Directive>>::new();
+    for tag in tags {
+        tags_by_label.entry(&tag.label).or_default().push(tag);
+    }
+    let resolve = |r#ref: &Directive| {
+        packages.resolve(
+            tags_by_label
+                .get(r#ref.label.as_str())
+                .map_or(&[][..], Vec::as_slice),
+            r#ref,
+        )
+    };
+
+    // Find the references to each tag in the file.
+    let mut defined = tags
+        .iter()
+        .copied()
+        .filter(|tag| in_file(tag))
+        .collect::<Vec<_>>();
+    sort_by_location(&mut defined);
+    let tags = defined
+        .into_iter()
+        .map(|tag| {
+            let mut inbound = refs
+                .iter()
+                .filter(|r#ref| {
+                    r#ref.label == tag.label
+                        && resolve(r#ref)
+                            .iter()
+                            .any(|resolved| ptr::eq(*resolved, tag))
+                })
+                .collect::<Vec<_>>();
+            sort_by_location(&mut inbound);
+            (tag, inbound)
+        })
+        .collect();
+
+    // Find what each reference in the file resolves to.
+    let mut outbound = refs
+        .iter()
+        .filter(|r#ref| in_file(r#ref))
+        .collect::<Vec<_>>();
+    sort_by_location(&mut outbound);
+    let refs = outbound
+        .into_iter()
+        .map(|r#ref| {
+            let mut resolved = resolve(r#ref);
+            let resolution = if !resolved.is_empty() {
+                sort_by_location(&mut resolved);
+                Resolution::Tags(resolved)
+            } else if provided.contains(&r#ref.label) {
+                Resolution::Provided
+            } else {
+                Resolution::Missing
+            };
+            (r#ref, resolution)
+        })
+        .collect();
+
+    // Find what each file or directory reference in the file points to.
+    let mut path_refs = paths
+        .into_iter()
+        .filter(|r#ref| in_file(r#ref))
+        .collect::<Vec<_>>();
+    sort_by_location(&mut path_refs);
+    let paths = path_refs
+        .into_iter()
+        .map(|r#ref| {
+            (
+                r#ref,
+                targets
+                    .get(r#ref.label.as_str())
+                    .cloned()
+                    .unwrap_or_else(|| Target::Error("not checked".to_owned())),
+            )
+        })
+        .collect();
+
+    FileReport { tags, refs, paths }
+}
+
+// This function describes whether a file or directory reference points to the right kind of thing.
+fn status(r#ref: &Directive, target: &Target) -> String {
+    match (r#ref.r#type, target) {
+        (Type::File, Target::File) => "points to a file".to_owned(),
+        (Type::Dir, Target::Dir) => "points to a directory".to_owned(),
+        (_, Target::Error(error)) => format!("is broken: {error}"),
+        (Type::Dir, _) => "is broken: not a directory".to_owned(),
+        _ => "is broken: not a file".to_owned(),
+    }
+}
+
+// This function renders the report for humans.
+pub fn render(path: &Path, report: &FileReport) -> String {
+    let mut output = String::new();
+    let _ = writeln!(output, "{}", display(&normalize(path)).to_string_lossy());
+
+    let _ = writeln!(output, "\nTags defined ({}):", report.tags.len());
+    for (tag, inbound) in &report.tags {
+        let _ = writeln!(output, "  {tag}");
+        if inbound.is_empty() {
+            output.push_str("    (no references)\n");
+        }
+        for r#ref in inbound {
+            let _ = writeln!(output, "    referenced by {ref}");
+        }
+    }
+
+    let _ = writeln!(output, "\nReferences made ({}):", report.refs.len());
+    for (r#ref, resolution) in &report.refs {
+        let _ = writeln!(output, "  {ref}");
+        match resolution {
+            Resolution::Tags(tags) => {
+                for tag in tags {
+                    let _ = writeln!(output, "    resolves to {tag}");
+                }
+            }
+            Resolution::Provided => output.push_str("    resolves to a tag from a provider\n"),
+            Resolution::Missing => output.push_str("    (no tag found)\n"),
+        }
+    }
+
+    let _ = writeln!(
+        output,
+        "\nFile and directory references ({}):",
+        report.paths.len(),
+    );
+    for (r#ref, target) in &report.paths {
+        let _ = writeln!(output, "  {ref}\n    {}", status(r#ref, target));
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            directive::{Directive, Type},
+            file_report::{render, report, Resolution},
+            packages::Packages,
+            stat::Target,
+        },
+        std::{
+            collections::{HashMap, HashSet},
+            path::Path,
+        },
+    };
+
+    #[test]
+    fn report_file() {
+        let tags = [
+            Directive::new(Type::Tag, "evict", "./src/cache.rs", 15),
+            Directive::new(Type::Tag, "schema", "./src/db.rs", 3),
+        ];
+        let refs = [
+            Directive::new(Type::Ref, "evict", "./src/db.rs", 40),
+            Directive::new(Type::Ref, "schema", "./src/cache.rs", 20),
+            Directive::new(Type::Ref, "symbol", "./src/cache.rs", 21),
+            Directive::new(Type::Ref, "gone", "./src/cache.rs", 22),
+        ];
+        let files = [
+            Directive::new(Type::File, "README.md", "./src/cache.rs", 5),
+            Directive::new(Type::File, "missing.md", "./src/db.rs", 5),
+        ];
+        let provided = HashSet::from(["symbol".to_owned()]);
+        let targets = HashMap::from([
+            ("README.md", Target::File),
+            ("missing.md", Target::Error("not found".to_owned())),
+        ]);
+
+        let report = report(
+            Path::new("src/cache.rs"),
+            &tags.iter().collect::<Vec<_>>(),
+            &refs,
+            &files,
+            &Packages::default(),
+            &provided,
+            &targets,
+        );
+
+        assert_eq!(report.tags.len(), 1);
+        assert_eq!(report.tags[0].1, [&refs[0]]);
+        assert_eq!(
+            report
+                .refs
+                .iter()
+                .map(|(_, resolution)| resolution)
+                .collect::<Vec<_>>(),
+            [
+                &Resolution::Tags(vec![&tags[1]]),
+                &Resolution::Provided,
+                &Resolution::Missing,
+            ],
+        );
+        assert_eq!(report.paths, [(&files[0], Target::File)]);
+
+        let rendered = render(Path::new("./src/cache.rs"), &report);
+        assert!(rendered.starts_with("src/cache.rs\n\nTags defined (1):\n"));
+        assert!(rendered.contains("\n    resolves to a tag from a provider\n"));
+        assert!(rendered.contains("\n    (no tag found)\n"));
+        assert!(
+            rendered.ends_with(
+                "File and directory references (1):\n  [?file:README.md] @ ./src/cache.rs:5\n    \
+                points to a file\n"
+                    .replace('?', "")
+                    .as_str(),
+            ),
+        );
+    }
+}
@@ -0,0 +1,348 @@
+use {
+    crate::{
+        directive::{normalize_label, split_metadata, Directive, Type},
+        distance::close_matches,
+        edit::{self, Edit},
+        walk::normalize,
+    },
+    regex::{Captures, Regex},
+    std::{
+        collections::{BTreeSet, HashMap, HashSet},
+        fs::{metadata, read_to_string},
+        io::ErrorKind,
+        path::{Path, PathBuf},
+    },
+};
+
+// This struct represents the repairs which can be made unambiguously. Each map takes the contents
+// of a directive to its replacement.
+#[derive(Default)]
+pub struct Corrections {
+    // Labels of dangling tag references which are close to exactly one tag
+    pub labels: HashMap<String, String>,
+
+    // Paths of file references to missing files which share a name with exactly one scanned file
+    pub files: HashMap<String, String>,
+
+    // Locations and labels of duplicate tags which should be references to the original tags
+    pub duplicates: HashSet<(PathBuf, usize, String)>,
+
+    // The sigil for writing the references which replace duplicate tags
+    pub ref_sigil: String,
+}
+
+// This struct represents a change to a single directive.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Change {
+    pub path: PathBuf,
+    pub line_number: usize,
+    pub before: String,
+    pub after: String,
+}
+
+// This function determines which tag references and file references can be repaired. The `scanned`
+// files are the candidates for the new locations of files which have moved.
+pub fn corrections(
+    tags: &HashSet<String>,
+    refs: &[Directive],
+    files: &[Directive],
+    scanned: &[PathBuf],
+) -> Corrections {
+    let mut corrections = Corrections::default();
+
+    // Find the dangling references with exactly one close match.
+    for r#ref in refs {
+        if !tags.contains(&r#ref.label) && !corrections.labels.contains_key(&r#ref.label) {
+            if let [label] = close_matches(&r#ref.label, tags.iter().map(String::as_str))[..] {
+                corrections
+                    .labels
+                    .insert(r#ref.label.clone(), label.to_owned());
+            }
+        }
+    }
+
+    // Find the references to missing files which share a name with exactly one scanned file.
+    for file in files {
+        if metadata(&file.label).is_err_and(|error| error.kind() == ErrorKind::NotFound) {
+            let name = Path::new(&file.label).file_name();
+            let candidates = scanned
+                .iter()
+                .filter(|path| name.is_some() && path.file_name() == name)
+                .collect::<Vec<_>>();
+            if let [path] = candidates[..] {
+                corrections.files.insert(
+                    file.label.clone(),
+                    normalize(path).to_string_lossy().into_owned(),
+                );
+            }
+        }
+    }
+
+    corrections
+}
+
+// This function rewrites the directives in the contents of a file. Directives are written without
+// any extraneous whitespace, and the contents of tag references and file references are replaced
+// according to the corrections. It returns the new contents and a list of the changes.
+pub fn rewrite(
+    path: &Path,
+    contents: &str,
+    regexes: &[(Type, &Regex)],
+    corrections: &Corrections,
+) -> (String, Vec<Change>) {
+    let mut changes = Vec::new();
+    let mut new_contents = String::with_capacity(contents.len());
+
+    for (i, line) in contents.split_inclusive('\n').enumerate() {
+        let mut line = line.to_owned();
+
+        for (r#type, regex) in regexes {
+            line = regex
+                .replace_all(&line, |captures: &Captures| {
+                    // The `unwrap`s are safe because these groups always participate in a match.
+                    let whole = captures.get(0).unwrap();
+                    let inner = captures.get(1).unwrap();
+
+                    // Recover the sigil as it was written, since the match is case-insensitive.
+                    let prefix = &whole.as_str()[1..inner.start() - whole.start()];
+                    let sigil = prefix.trim_end().strip_suffix(':').unwrap_or(prefix).trim();
+
+                    let replacement = match r#type {
+                        Type::Ref => {
+                            // Keep any metadata after the label.
+                            let label = split_metadata(inner.as_str()).0;
+                            corrections
+                                .labels
+                                .get(&normalize_label(&label))
+                                .map(|new| format!("{new}{}", &inner.as_str()[label.len()..]))
+                        }
+                        Type::File => corrections.files.get(inner.as_str()).cloned(),
+                        Type::Tag | Type::Dir | Type::Spec | Type::Url => None,
+                    };
+                    let duplicate = (*r#type == Type::Tag)
+                        .then(|| normalize_label(&split_metadata(inner.as_str()).0))
+                        .filter(|label| {
+                            corrections.duplicates.contains(&(
+                                path.to_owned(),
+                                i + 1,
+                                label.clone(),
+                            ))
+                        });
+                    let after = if let Some(label) = duplicate {
+                        format!("[{}:{label}]", corrections.ref_sigil)
+                    } else {
+                        format!(
+                            "[{sigil}:{}]",
+                            replacement.as_deref().unwrap_or(inner.as_str()),
+                        )
+                    };
+
+                    if after != whole.as_str() {
+                        changes.push(Change {
+                            path: path.to_owned(),
+                            line_number: i + 1,
+                            before: whole.as_str().to_owned(),
+                            after: after.clone(),
+                        });
+                    }
+
+                    after
+                })
+                .into_owned();
+        }
+
+        new_contents.push_str(&line);
+    }
+
+    (new_contents, changes)
+}
+
+// This function applies the repairs to the files containing the given directives, as the options
+// allow. Files which aren't valid UTF-8 are skipped. It returns a list of the changes.
+pub fn apply<'a>(
+    directives: impl IntoIterator<Item = &'a Directive>,
+    regexes: &[(Type, &Regex)],
+    corrections: &Corrections,
+    options: edit::Options,
+) -> Result<Vec<Change>, String> {
+    let mut changes = Vec::new();
+    let mut edits = Vec::new();
+
+    // Visit each file once, in a deterministic order.
+    let paths = directives
+        .into_iter()
+        .map(|directive| &directive.path)
+        .collect::<BTreeSet<_>>();
+
+    for path in paths {
+        let Ok(contents) = read_to_string(path) else {
+            continue;
+        };
+
+        let (new_contents, file_changes) = rewrite(path, &contents, regexes, corrections);
+        if !file_changes.is_empty() {
+            edits.push(Edit {
+                path: path.clone(),
+                contents: new_contents,
+            });
+            changes.extend(file_changes);
+        }
+    }
+
+    edit::write_all(&edits, options)?;
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            directive::{compile_directive_regex, Directive, Type},
+            fix::{corrections, rewrite, Change, Corrections},
+        },
+        std::{
+            collections::HashSet,
+            path::{Path, PathBuf},
+        },
+    };
+
+    fn directive(r#type: Type, label: &str) -> Directive {
+        Directive::new(r#type, label, "file.rs", 1)
+    }
+
+    #[test]
+    fn corrections_labels() {
+        let tags = [
+            "cache_invalidation",
+            "cache_eviction",
+            "schema_v1",
+            "schema_v2",
+        ]
+        .into_iter()
+        .map(ToOwned::to_owned)
+        .collect::<HashSet<_>>();
+        let refs = [
+            directive(Type::Ref, "cache_invalidation"),
+            directive(Type::Ref, "cach_invalidation"),
+            directive(Type::Ref, "schema_v3"),
+            directive(Type::Ref, "unrelated"),
+        ];
+
+        let corrections = corrections(&tags, &refs, &[], &[]);
+        assert_eq!(corrections.labels.len(), 1);
+        assert_eq!(
+            corrections.labels["cach_invalidation"],
+            "cache_invalidation",
+        );
+    }
+
+    #[test]
+    fn corrections_files() {
+        let files = [
+            directive(Type::File, "old/moved.rs"),
+            directive(Type::File, "old/ambiguous.rs"),
+            directive(Type::File, "old/gone.rs"),
+        ];
+        let scanned = [
+            PathBuf::from("./new/moved.rs"),
+            PathBuf::from("./a/ambiguous.rs"),
+            PathBuf::from("./b/ambiguous.rs"),
+        ];
+
+        let corrections = corrections(&HashSet::new(), &[], &files, &scanned);
+        assert_eq!(corrections.files.len(), 1);
+        assert_eq!(corrections.files["old/moved.rs"], "new/moved.rs");
+    }
+
+    #[test]
+    fn rewrite_directives() {
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let file_regex = compile_directive_regex("file");
+        let regexes = [
+            (Type::Tag, &tag_regex),
+            (Type::Ref, &ref_regex),
+            (Type::File, &file_regex),
+        ];
+        let mut corrections = Corrections::default();
+        corrections
+            .labels
+            .insert("cach".to_owned(), "cache".to_owned());
+        corrections
+            .files
+            .insert("old.rs".to_owned(), "new.rs".to_owned());
+
+        let contents = "
+            [?tag:cache] Unchanged.\r
+            [?TAG : spaced ] and [?Ref: cach]
+            [?file:old.rs]
+        "
+        .replace('?', "");
+
+        let (new_contents, changes) =
+            rewrite(Path::new("file.rs"), &contents, &regexes, &corrections);
+        assert_eq!(
+            new_contents,
+            "
+            [?tag:cache] Unchanged.\r
+            [?TAG:spaced] and [?Ref:cache]
+            [?file:new.rs]
+        "
+            .replace('?', ""),
+        );
+        assert_eq!(
+            changes,
+            [
+                Change {
+                    path: Path::new("file.rs").to_owned(),
+                    line_number: 3,
+                    before: "[?TAG : spaced ]".replace('?', ""),
+                    after: "[?TAG:spaced]".replace('?', ""),
+                },
+                Change {
+                    path: Path::new("file.rs").to_owned(),
+                    line_number: 3,
+                    before: "[?Ref: cach]".replace('?', ""),
+                    after: "[?Ref:cache]".replace('?', ""),
+                },
+                Change {
+                    path: Path::new("file.rs").to_owned(),
+                    line_number: 4,
+                    before: "[?file:old.rs]".replace('?', ""),
+                    after: "[?file:new.rs]".replace('?', ""),
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn rewrite_duplicates() {
+        let tag_regex = compile_directive_regex("tag");
+        let regexes = [(Type::Tag, &tag_regex)];
+        let mut corrections = Corrections {
+            ref_sigil: "ref".to_owned(),
+            ..Corrections::default()
+        };
+        corrections
+            .duplicates
+            .insert((Path::new("file.rs").to_owned(), 2, "cache".to_owned()));
+
+        let contents = "
+            [?tag:cache owner=@storage] Copied.
+            [?tag:cache] Not a duplicate on this line.
+        "
+        .replace('?', "");
+
+        let (new_contents, changes) =
+            rewrite(Path::new("file.rs"), &contents, &regexes, &corrections);
+        assert_eq!(
+            new_contents,
+            "
+            [?ref:cache] Copied.
+            [?tag:cache] Not a duplicate on this line.
+        "
+            .replace('?', ""),
+        );
+        assert_eq!(changes.len(), 1);
+    }
+}
@@ -0,0 +1,363 @@
+use {
+    crate::walk::normalize,
+    std::{
+        path::{Path, PathBuf},
+        process::{Command, Output},
+    },
+};
+
+// This function returns the directory containing a file, for running Git there.
+fn directory(path: &Path) -> &Path {
+    path.parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."))
+}
+
+// This function returns when a line of a file was written, as a Unix timestamp, according to
+// `git blame`. Lines which haven't been committed yet (including those in untracked files) are
+// treated as newer than any commit. It returns `None` if the file isn't in a Git repository or Git
+// isn't available.
+pub fn author_time(path: &Path, line_number: usize) -> Option<u64> {
+    let directory = directory(path);
+    let output = blame(path, line_number)?;
+    if !output.status.success() {
+        // The file may be untracked, in which case it's newer than anything in the repository.
+        return Command::new("git")
+            .arg("-C")
+            .arg(directory)
+            .args(["rev-parse", "--is-inside-work-tree"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|_| u64::MAX);
+    }
+
+    let output = String::from_utf8_lossy(&output.stdout);
+    let mut lines = output.lines();
+
+    // The first line starts with the commit hash, which is all zeros for uncommitted changes.
+    if lines
+        .next()?
+        .split(' ')
+        .next()?
+        .bytes()
+        .all(|byte| byte == b'0')
+    {
+        return Some(u64::MAX);
+    }
+
+    lines
+        .find_map(|line| line.strip_prefix("author-time "))
+        .and_then(|time| time.parse().ok())
+}
+
+// This function returns who wrote a line of a file according to `git blame`, as their email address
+// if it's known and their name otherwise. It returns `None` if the line hasn't been committed, the
+// file isn't in a Git repository, or Git isn't available.
+pub fn author(path: &Path, line_number: usize) -> Option<String> {
+    let output = blame(path, line_number)?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let output = String::from_utf8_lossy(&output.stdout);
+    let mut lines = output.lines();
+
+    // The first line starts with the commit hash, which is all zeros for uncommitted changes.
+    if lines
+        .next()?
+        .split(' ')
+        .next()?
+        .bytes()
+        .all(|byte| byte == b'0')
+    {
+        return None;
+    }
+
+    let lines = lines.collect::<Vec<_>>();
+    lines
+        .iter()
+        .find_map(|line| line.strip_prefix("author-mail "))
+        .map(|mail| mail.trim_start_matches('<').trim_end_matches('>'))
+        .filter(|mail| !mail.is_empty())
+        .or_else(|| lines.iter().find_map(|line| line.strip_prefix("author ")))
+        .map(ToOwned::to_owned)
+}
+
+// This function runs `git blame` for a single line of a file.
+fn blame(path: &Path, line_number: usize) -> Option<Output> {
+    Command::new("git")
+        .arg("-C")
+        .arg(directory(path))
+        .args(["blame", "--porcelain", "-L"])
+        .arg(format!("{line_number},{line_number}"))
+        .arg("--")
+        .arg(path.file_name()?)
+        .output()
+        .ok()
+}
+
+// This function determines whether a file has changes which haven't been committed, including if
+// it's untracked. It returns `false` if the file isn't in a Git repository or Git isn't available.
+pub fn is_dirty(path: &Path) -> bool {
+    let directory = directory(path);
+    let Some(name) = path.file_name() else {
+        return false;
+    };
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .arg("-C")
+            .arg(directory)
+            .args(args)
+            .arg("--")
+            .arg(name)
+            .output()
+            .ok()
+    };
+
+    // Untracked files are dirty, as long as they're in a repository.
+    if !git(&["ls-files", "--error-unmatch"]).is_some_and(|output| output.status.success()) {
+        return git(&["rev-parse", "--is-inside-work-tree"])
+            .is_some_and(|output| output.status.success());
+    }
+
+    // `git diff --quiet` exits with 1 if there are differences, whether they're staged or not.
+    git(&["diff", "--quiet", "HEAD"]).is_some_and(|output| output.status.code() == Some(1_i32))
+}
+
+// This function resolves a revision (e.g., a Git tag) to the abbreviated hash of its commit.
+pub fn commit(revision: &str) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--verify", "--quiet", "--short"])
+        .arg(format!("{revision}^{{commit}}"))
+        .output()
+        .map_err(|error| format!("Unable to run Git: {error}"))?;
+    if !output.status.success() {
+        return Err(format!("Unknown revision `{revision}`."));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+// This function runs Git to list paths, which are separated by NULs in its output.
+fn list(args: &[&str]) -> Result<Vec<PathBuf>, String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|error| format!("Unable to run Git: {error}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Unable to read the Git index: {}",
+            String::from_utf8_lossy(&output.stderr).trim(),
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .split('\0')
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+// This function lists the files in the working directory whose contents differ from what's staged
+// in the Git index: tracked files with unstaged changes (including deletions), and untracked files
+// which aren't ignored. The paths are relative to the working directory, and the second list is of
+// the untracked files.
+pub fn unstaged() -> Result<(Vec<PathBuf>, Vec<PathBuf>), String> {
+    Ok((
+        list(&["diff", "--name-only", "--no-renames", "--relative", "-z"])?,
+        list(&["ls-files", "--others", "--exclude-standard", "-z"])?,
+    ))
+}
+
+// This function lists the files which were deleted or renamed between a revision and the working
+// directory, along with the new path of each renamed file. The paths are relative to the working
+// directory.
+pub fn removed(revision: &str) -> Result<Vec<(PathBuf, Option<PathBuf>)>, String> {
+    let output = Command::new("git")
+        .args([
+            "diff",
+            "--name-status",
+            "--find-renames",
+            "--relative",
+            "-z",
+        ])
+        .arg(revision)
+        .arg("--")
+        .output()
+        .map_err(|error| format!("Unable to run Git: {error}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Unable to compare with {revision}: {}",
+            String::from_utf8_lossy(&output.stderr).trim(),
+        ));
+    }
+
+    // Each change is a status followed by its paths, all separated by NULs. Renames (and copies)
+    // have two paths, and the status of a rename starts with `R`.
+    let output = String::from_utf8_lossy(&output.stdout);
+    let mut fields = output.split('\0').filter(|field| !field.is_empty());
+    let mut removed = vec![];
+    while let Some(status) = fields.next() {
+        let Some(path) = fields.next() else {
+            break;
+        };
+        if status.starts_with(['R', 'C']) {
+            let new_path = fields.next().map(PathBuf::from);
+            if status.starts_with('R') {
+                removed.push((PathBuf::from(path), new_path));
+            }
+        } else if status == "D" {
+            removed.push((PathBuf::from(path), None));
+        }
+    }
+
+    Ok(removed)
+}
+
+// This function returns the contents of a file as staged in the Git index, or `None` if it isn't
+// in the index. The path is relative to the working directory.
+pub fn staged(path: &Path) -> Result<Option<Vec<u8>>, String> {
+    let output = Command::new("git")
+        .arg("cat-file")
+        .arg("blob")
+        .arg(format!(":./{}", normalize(path).to_string_lossy()))
+        .output()
+        .map_err(|error| format!("Unable to run Git: {error}"))?;
+
+    Ok(output.status.success().then_some(output.stdout))
+}
+
+// This function returns the root of the Git repository containing the working directory.
+pub fn root() -> Result<PathBuf, String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--show-toplevel"])
+        .output()
+        .map_err(|error| format!("Unable to run Git: {error}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Unable to find the root of the Git repository: {}",
+            String::from_utf8_lossy(&output.stderr).trim(),
+        ));
+    }
+
+    Ok(PathBuf::from(
+        String::from_utf8_lossy(&output.stdout).trim_end_matches('\n'),
+    ))
+}
+
+// This function finds the last commit reachable from `HEAD` which was made before the given time
+// (e.g., `2023-01-01 23:59:59`), as an abbreviated hash. It returns `None` if there isn't one.
+pub fn commit_before(time: &str) -> Result<Option<String>, String> {
+    let output = Command::new("git")
+        .args(["rev-list", "-1", "--abbrev-commit"])
+        .arg(format!("--before={time}"))
+        .arg("HEAD")
+        .output()
+        .map_err(|error| format!("Unable to run Git: {error}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Unable to read the Git history: {}",
+            String::from_utf8_lossy(&output.stderr).trim(),
+        ));
+    }
+
+    let commit = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    Ok((!commit.is_empty()).then_some(commit))
+}
+
+// This function finds the lines matching an extended regular expression (case-insensitively) in
+// the files at a revision, within the given paths. It returns the path, line number, and contents
+// of each line.
+pub fn grep(
+    revision: &str,
+    pattern: &str,
+    paths: &[PathBuf],
+) -> Result<Vec<(PathBuf, usize, String)>, String> {
+    let output = Command::new("git")
+        .args([
+            "grep", "-z", "-n", "-I", "-i", "-E", "-e", pattern, revision, "--",
+        ])
+        .args(paths)
+        .output()
+        .map_err(|error| format!("Unable to run Git: {error}"))?;
+
+    // Git exits with 1 if nothing matches.
+    if !output.status.success() && output.status.code() != Some(1_i32) {
+        return Err(format!(
+            "Unable to search {revision}: {}",
+            String::from_utf8_lossy(&output.stderr).trim(),
+        ));
+    }
+
+    // Each line is `<revision>:<path>`, the line number, and the contents, separated by NULs.
+    let prefix = format!("{revision}:");
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\0');
+            let path = fields.next()?;
+            let line_number = fields.next()?.parse().ok()?;
+            let contents = fields.next()?;
+            Some((
+                Path::new(path.strip_prefix(&prefix).unwrap_or(path)).to_owned(),
+                line_number,
+                contents.to_owned(),
+            ))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::git::{author, author_time, commit, commit_before, is_dirty, removed, staged},
+        std::{
+            env::temp_dir,
+            fs::{remove_file, write},
+            path::Path,
+            process,
+        },
+    };
+
+    #[test]
+    fn author_time_missing() {
+        assert_eq!(author_time(Path::new("does/not/exist.rs"), 1), None);
+    }
+
+    #[test]
+    fn author_missing() {
+        assert_eq!(author(Path::new("does/not/exist.rs"), 1), None);
+    }
+
+    #[test]
+    fn is_dirty_outside_repository() {
+        let path = temp_dir().join(format!("tagref-git-{}.rs", process::id()));
+        write(&path, "").unwrap();
+        let dirty = is_dirty(&path);
+        let _ = remove_file(&path);
+
+        assert!(!dirty);
+    }
+
+    #[test]
+    fn commit_unknown() {
+        assert!(commit("tagref-no-such-revision").is_err());
+    }
+
+    #[test]
+    fn removed_unknown() {
+        assert!(removed("tagref-no-such-revision").is_err());
+    }
+
+    #[test]
+    fn staged_missing() {
+        assert_eq!(staged(Path::new("does/not/exist.rs")).ok().flatten(), None);
+    }
+
+    #[test]
+    fn commit_before_history() {
+        // Nothing in this repository was committed before Unix time.
+        assert_eq!(commit_before("1970-01-02 00:00:00").ok().flatten(), None);
+    }
+}
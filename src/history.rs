@@ -0,0 +1,333 @@
+use {
+    crate::{directive::Directives, schema::SCHEMA_VERSION},
+    serde::Serialize,
+    std::{
+        collections::HashSet,
+        fmt::{self, Write},
+        str::FromStr,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+// This enum represents how far apart the snapshots are.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Interval {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+// The names of the intervals, for the command-line interface
+pub const INTERVALS: &[&str] = &["day", "week", "month", "year"];
+
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "day" => Ok(Self::Day),
+            "week" => Ok(Self::Week),
+            "month" => Ok(Self::Month),
+            "year" => Ok(Self::Year),
+            _ => Err(format!("Unknown interval `{s}`.")),
+        }
+    }
+}
+
+// This enum represents the output formats for the history.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HistoryFormat {
+    Csv,
+    Json,
+}
+
+// The names of the formats, for the command-line interface
+pub const HISTORY_FORMATS: &[&str] = &["csv", "json"];
+
+impl FromStr for HistoryFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Unknown format `{s}`.")),
+        }
+    }
+}
+
+// This struct represents a calendar date, such as `2023-01-01`.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Date {
+    year: i64,
+    month: i64,
+    day: i64,
+}
+
+// This function returns the number of days in a month.
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl FromStr for Date {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid date `{s}`. Dates are written like `2023-01-01`.");
+        let mut parts = s.splitn(3, '-').map(|part| {
+            if part.is_empty() || !part.bytes().all(|byte| byte.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            part.parse::<i64>().map_err(|_| invalid())
+        });
+        let mut next = || parts.next().unwrap_or_else(|| Err(invalid()));
+        let (year, month, day) = (next()?, next()?, next()?);
+        if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+            return Err(invalid());
+        }
+
+        Ok(Self { year, month, day })
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl Date {
+    // This function returns the date which is the given number of days after 1970-01-01, using
+    // the algorithm from http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+    fn from_days(days: i64) -> Self {
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Self { year, month, day }
+    }
+
+    // This function returns the number of days since 1970-01-01 (the inverse of `from_days`).
+    fn days(self) -> i64 {
+        let year = if self.month <= 2 {
+            self.year - 1
+        } else {
+            self.year
+        };
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let shifted_month = (self.month + 9) % 12;
+        let day_of_year = (153 * shifted_month + 2) / 5 + self.day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    // This function returns the current date in UTC.
+    pub fn today() -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        Self::from_days(i64::try_from(seconds / 86_400).unwrap_or(0))
+    }
+
+    // This function returns the date which is `n` intervals after this one. Adding months or years
+    // to the end of a month stays within the target month (e.g., January 31 becomes February 28).
+    fn add(self, interval: Interval, n: i64) -> Self {
+        let months = match interval {
+            Interval::Day => return Self::from_days(self.days() + n),
+            Interval::Week => return Self::from_days(self.days() + 7 * n),
+            Interval::Month => n,
+            Interval::Year => 12 * n,
+        };
+        let index = self.year * 12 + self.month - 1 + months;
+        let (year, month) = (index.div_euclid(12), index.rem_euclid(12) + 1);
+        Self {
+            year,
+            month,
+            day: self.day.min(days_in_month(year, month)),
+        }
+    }
+}
+
+// This function returns the dates of the snapshots: `since`, then one per interval up to `until`,
+// and finally `until` itself (if it isn't already included), so the last snapshot is always
+// current.
+pub fn dates(since: Date, until: Date, interval: Interval) -> Vec<Date> {
+    let mut dates = vec![];
+    let mut date = since;
+    while date <= until {
+        dates.push(date);
+        date = since.add(interval, i64::try_from(dates.len()).unwrap_or(i64::MAX));
+    }
+    if dates.last().is_some_and(|last| *last < until) {
+        dates.push(until);
+    }
+    dates
+}
+
+// This struct represents the counts for a snapshot of the history.
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct Snapshot {
+    pub date: String,
+    pub commit: String,
+    pub tags: usize,
+    pub tag_references: usize,
+    pub dangling_tag_references: usize,
+}
+
+impl Snapshot {
+    // This function counts the directives found at a commit. A reference is dangling if no tag in
+    // the snapshot has its label.
+    pub fn new(date: Date, commit: &str, directives: &Directives) -> Self {
+        let labels = directives
+            .tags
+            .iter()
+            .map(|tag| tag.label.as_str())
+            .collect::<HashSet<_>>();
+
+        Self {
+            date: date.to_string(),
+            commit: commit.to_owned(),
+            tags: directives.tags.len(),
+            tag_references: directives.refs.len(),
+            dangling_tag_references: directives
+                .refs
+                .iter()
+                .filter(|r#ref| !labels.contains(r#ref.label.as_str()))
+                .count(),
+        }
+    }
+}
+
+// This struct represents the history in the `json` format.
+#[derive(Serialize)]
+struct History<'a> {
+    schema_version: usize,
+    snapshots: &'a [Snapshot],
+}
+
+// This function renders the snapshots in the given format.
+pub fn render(format: HistoryFormat, snapshots: &[Snapshot]) -> String {
+    match format {
+        HistoryFormat::Csv => {
+            let mut output = "date,commit,tags,tag_references,dangling_tag_references\n".to_owned();
+            for snapshot in snapshots {
+                let _ = writeln!(
+                    output,
+                    "{},{},{},{},{}",
+                    snapshot.date,
+                    snapshot.commit,
+                    snapshot.tags,
+                    snapshot.tag_references,
+                    snapshot.dangling_tag_references,
+                );
+            }
+            output
+        }
+        HistoryFormat::Json => {
+            // The `unwrap` is safe because the history always serializes successfully.
+            let mut output = serde_json::to_string(&History {
+                schema_version: SCHEMA_VERSION,
+                snapshots,
+            })
+            .unwrap();
+            output.push('\n');
+            output
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Directives, Type},
+        history::{dates, render, Date, HistoryFormat, Interval, Snapshot},
+    };
+
+    fn directive(r#type: Type, label: &str) -> Directive {
+        Directive::new(r#type, label, "file.rs", 1)
+    }
+
+    #[test]
+    fn date_parse() {
+        assert_eq!(
+            "2024-02-29".parse::<Date>().unwrap().to_string(),
+            "2024-02-29",
+        );
+        assert!("2023-02-29".parse::<Date>().is_err());
+        assert!("2023-13-01".parse::<Date>().is_err());
+        assert!("2023-01".parse::<Date>().is_err());
+        assert!("2023-01-01-01".parse::<Date>().is_err());
+        assert!("2023-+1-01".parse::<Date>().is_err());
+    }
+
+    #[test]
+    fn date_days_round_trip() {
+        for s in ["1970-01-01", "2000-02-29", "2023-12-31", "1969-07-20"] {
+            let date = s.parse::<Date>().unwrap();
+            assert_eq!(Date::from_days(date.days()), date);
+        }
+        assert_eq!("1970-01-02".parse::<Date>().unwrap().days(), 1);
+    }
+
+    #[test]
+    fn dates_by_interval() {
+        let since = "2023-01-31".parse::<Date>().unwrap();
+        let until = "2023-04-30".parse::<Date>().unwrap();
+
+        assert_eq!(
+            dates(since, until, Interval::Month)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["2023-01-31", "2023-02-28", "2023-03-31", "2023-04-30"],
+        );
+        assert_eq!(dates(since, until, Interval::Week).len(), 14);
+        assert_eq!(
+            dates(since, until, Interval::Year)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["2023-01-31", "2023-04-30"],
+        );
+        assert!(dates(until, since, Interval::Day).is_empty());
+    }
+
+    #[test]
+    fn snapshot_counts() {
+        let directives = Directives {
+            tags: vec![directive(Type::Tag, "a")],
+            refs: vec![directive(Type::Ref, "a"), directive(Type::Ref, "b")],
+            ..Directives::default()
+        };
+        let snapshot = Snapshot::new(
+            "2023-01-01".parse::<Date>().unwrap(),
+            "abc1234",
+            &directives,
+        );
+
+        assert_eq!(
+            render(HistoryFormat::Csv, &[snapshot]),
+            "date,commit,tags,tag_references,dangling_tag_references\n\
+             2023-01-01,abc1234,1,2,1\n",
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use {
+    crate::{directive::Directive, walk::normalize},
+    std::{
+        fmt,
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+
+// This struct represents a region of the source: a file or directory, optionally narrowed to a
+// range of lines (inclusive), such as `src/cache.rs:10-20`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Region {
+    path: PathBuf,
+    lines: Option<(usize, usize)>,
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // A suffix after the last `:` is only a line range if it looks like one, so paths which
+        // contain colons still work.
+        let Some((path, range)) = s
+            .rsplit_once(':')
+            .filter(|(_, range)| range.starts_with(|c: char| c.is_ascii_digit()))
+        else {
+            return Ok(Self {
+                path: normalize(Path::new(s)),
+                lines: None,
+            });
+        };
+
+        let invalid = || format!("Invalid line range `{range}` in `{s}`.");
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let start = start.parse::<usize>().map_err(|_| invalid())?;
+        let end = end.parse::<usize>().map_err(|_| invalid())?;
+        if start == 0 || end < start {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            path: normalize(Path::new(path)),
+            lines: Some((start, end)),
+        })
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.path.to_string_lossy())?;
+        match self.lines {
+            Some((start, end)) if start == end => write!(f, ":{start}"),
+            Some((start, end)) => write!(f, ":{start}-{end}"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Region {
+    // This function determines whether a directive is in the region. A region without a line range
+    // contains everything in the file, or everything under the directory.
+    pub fn contains(&self, directive: &Directive) -> bool {
+        normalize(&directive.path).starts_with(&self.path)
+            && self
+                .lines
+                .is_none_or(|(start, end)| (start..=end).contains(&directive.line_number))
+    }
+}
+
+// This function finds the tags defined in the region, along with every reference to each of them
+// (including those within the region). The tags and references are sorted by location.
+pub fn impact<'a>(
+    region: &Region,
+    tags: impl IntoIterator<Item = &'a Directive>,
+    refs: &'a [Directive],
+) -> Vec<(&'a Directive, Vec<&'a Directive>)> {
+    let by_location =
+        |x: &&Directive, y: &&Directive| (&x.path, x.line_number).cmp(&(&y.path, y.line_number));
+
+    let mut tags = tags
+        .into_iter()
+        .filter(|tag| region.contains(tag))
+        .collect::<Vec<_>>();
+    tags.sort_by(by_location);
+
+    tags.into_iter()
+        .map(|tag| {
+            let mut dependents = refs
+                .iter()
+                .filter(|r#ref| r#ref.label == tag.label)
+                .collect::<Vec<_>>();
+            dependents.sort_by(by_location);
+            (tag, dependents)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        impact::{impact, Region},
+    };
+
+    #[test]
+    fn region_parse() {
+        assert_eq!(
+            "./src/cache.rs:10-20"
+                .parse::<Region>()
+                .unwrap()
+                .to_string(),
+            "src/cache.rs:10-20",
+        );
+        assert_eq!(
+            "src/cache.rs:7".parse::<Region>().unwrap().to_string(),
+            "src/cache.rs:7",
+        );
+        assert_eq!("src".parse::<Region>().unwrap().to_string(), "src");
+        assert_eq!("a:b.rs".parse::<Region>().unwrap().to_string(), "a:b.rs");
+        assert!("src/cache.rs:20-10".parse::<Region>().is_err());
+        assert!("src/cache.rs:0".parse::<Region>().is_err());
+        assert!("src/cache.rs:1-x".parse::<Region>().is_err());
+    }
+
+    #[test]
+    fn impact_in_range() {
+        let tags = [
+            Directive::new(Type::Tag, "before", "./src/cache.rs", 5),
+            Directive::new(Type::Tag, "evict", "./src/cache.rs", 15),
+            Directive::new(Type::Tag, "expire", "./src/cache.rs", 12),
+            Directive::new(Type::Tag, "elsewhere", "./src/db.rs", 12),
+        ];
+        let refs = [
+            Directive::new(Type::Ref, "evict", "./src/db.rs", 40),
+            Directive::new(Type::Ref, "evict", "./src/api.rs", 3),
+            Directive::new(Type::Ref, "evict", "./src/cache.rs", 18),
+            Directive::new(Type::Ref, "evict", "./src/cache.rs", 30),
+            Directive::new(Type::Ref, "before", "./src/db.rs", 1),
+        ];
+
+        let region = "src/cache.rs:10-20".parse::<Region>().unwrap();
+        let summary = impact(&region, &tags, &refs)
+            .into_iter()
+            .map(|(tag, refs)| {
+                (
+                    tag.label.as_str(),
+                    refs.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            summary,
+            [
+                ("expire", vec![]),
+                (
+                    "evict",
+                    vec![
+                        "[?ref:evict] @ ./src/api.rs:3".replace('?', ""),
+                        "[?ref:evict] @ ./src/cache.rs:18".replace('?', ""),
+                        "[?ref:evict] @ ./src/cache.rs:30".replace('?', ""),
+                        "[?ref:evict] @ ./src/db.rs:40".replace('?', ""),
+                    ],
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn impact_directory() {
+        let tags = [
+            Directive::new(Type::Tag, "a", "./src/auth/a.rs", 1),
+            Directive::new(Type::Tag, "b", "./src/authz.rs", 1),
+        ];
+
+        let region = "src/auth".parse::<Region>().unwrap();
+        let found = impact(&region, &tags, &[]);
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0.label, "a");
+    }
+}
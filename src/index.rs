@@ -0,0 +1,173 @@
+use {
+    crate::{
+        directive::{is_zero, Directive},
+        links,
+        schema::SCHEMA_VERSION,
+        walk::normalize,
+    },
+    serde::Serialize,
+    std::collections::BTreeMap,
+};
+
+// The default path of the index
+pub const DEFAULT_INDEX_PATH: &str = ".tagref-index.json";
+
+// The version of the index format, which is incremented whenever it changes incompatibly
+const INDEX_VERSION: usize = 1;
+
+// This struct represents the index, which lets editor extensions look up tags without scanning.
+#[derive(Serialize)]
+struct Index<'a> {
+    schema_version: usize,
+    version: usize,
+    labels: BTreeMap<&'a str, Entry<'a>>,
+}
+
+// This struct represents everything known about a label. A label with references but no
+// definitions is a dangling reference.
+#[derive(Default, Serialize)]
+struct Entry<'a> {
+    definitions: Vec<Definition<'a>>,
+    references: Vec<Location>,
+}
+
+// This struct represents a tag.
+#[derive(Serialize)]
+struct Definition<'a> {
+    #[serde(flatten)]
+    location: Location,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    description: &'a str,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: &'a BTreeMap<String, String>,
+}
+
+// This struct represents the location of a directive, and a link to it if there's a base URL.
+// Locations are ordered by path, line, and column.
+#[derive(Eq, Ord, PartialEq, PartialOrd, Serialize)]
+struct Location {
+    path: String,
+    line: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    column: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+}
+
+impl Location {
+    fn new(directive: &Directive, base_url: Option<&str>) -> Self {
+        Self {
+            path: normalize(&directive.path).to_string_lossy().into_owned(),
+            line: directive.line_number,
+            column: directive.column,
+            url: base_url
+                .map(|base_url| links::url(base_url, &directive.path, directive.line_number)),
+        }
+    }
+}
+
+// This function renders the index of the given tags and references as compact JSON. If a base URL
+// is given, every location includes a link.
+pub fn render(tags: &[Directive], refs: &[Directive], base_url: Option<&str>) -> String {
+    let mut labels = BTreeMap::<&str, Entry>::new();
+    for tag in tags {
+        labels
+            .entry(&tag.label)
+            .or_default()
+            .definitions
+            .push(Definition {
+                location: Location::new(tag, base_url),
+                description: &tag.description,
+                metadata: &tag.metadata,
+            });
+    }
+    for r#ref in refs {
+        labels
+            .entry(&r#ref.label)
+            .or_default()
+            .references
+            .push(Location::new(r#ref, base_url));
+    }
+
+    // Sort the locations so the index is deterministic.
+    for entry in labels.values_mut() {
+        entry
+            .definitions
+            .sort_by(|x, y| x.location.cmp(&y.location));
+        entry.references.sort();
+    }
+
+    // The `unwrap` is safe because the index always serializes successfully.
+    let mut json = serde_json::to_string(&Index {
+        schema_version: SCHEMA_VERSION,
+        version: INDEX_VERSION,
+        labels,
+    })
+    .unwrap();
+    json.push('\n');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        index::render,
+    };
+
+    #[test]
+    fn render_empty() {
+        assert_eq!(
+            render(&[], &[], None),
+            "{\"schema_version\":1,\"version\":1,\"labels\":{}}\n",
+        );
+    }
+
+    #[test]
+    fn render_labels() {
+        let mut tag = Directive::new(Type::Tag, "tag1", "./file1.rs", 1);
+        tag.description = "Description".to_owned();
+        tag.metadata.insert("owner".to_owned(), "@core".to_owned());
+        let refs = [
+            Directive::new(Type::Ref, "tag1", "./file2.rs", 5),
+            Directive::new(Type::Ref, "tag1", "./file1.rs", 9),
+            Directive::new(Type::Ref, "tag2", "./file2.rs", 1),
+        ];
+
+        let json: serde_json::Value = serde_json::from_str(&render(&[tag], &refs, None)).unwrap();
+
+        assert_eq!(json["version"], 1_u64);
+        let tag1 = &json["labels"]["tag1"];
+        assert_eq!(tag1["definitions"][0]["path"], "file1.rs");
+        assert_eq!(tag1["definitions"][0]["line"], 1_u64);
+        assert_eq!(tag1["definitions"][0]["description"], "Description");
+        assert_eq!(tag1["definitions"][0]["metadata"]["owner"], "@core");
+        assert_eq!(tag1["references"][0]["path"], "file1.rs");
+        assert_eq!(tag1["references"][1]["path"], "file2.rs");
+        let tag2 = &json["labels"]["tag2"];
+        assert!(tag2["definitions"].as_array().unwrap().is_empty());
+        assert_eq!(tag2["references"][0]["line"], 1_u64);
+        assert!(tag2["references"][0].get("description").is_none());
+        assert!(tag2["references"][0].get("url").is_none());
+    }
+
+    #[test]
+    fn render_urls() {
+        let tag = Directive::new(Type::Tag, "tag1", "./file1.rs", 3);
+        let r#ref = Directive::new(Type::Ref, "tag1", "./file2.rs", 4);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&[tag], &[r#ref], Some("https://example.com/repo/")))
+                .unwrap();
+
+        let tag1 = &json["labels"]["tag1"];
+        assert_eq!(
+            tag1["definitions"][0]["url"],
+            "https://example.com/repo/file1.rs#L3",
+        );
+        assert_eq!(
+            tag1["references"][0]["url"],
+            "https://example.com/repo/file2.rs#L4",
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use {
+    crate::{
+        config::{Issues, ISSUE_PLACEHOLDER},
+        diagnostic::{self, Diagnostic},
+        directive::Directive,
+    },
+    serde::Deserialize,
+    std::{collections::BTreeMap, env, time::Duration},
+};
+
+// The environment variable which holds a bearer token for the issue tracker
+pub const TOKEN_VARIABLE: &str = "TAGREF_ISSUES_TOKEN";
+
+// The metadata key used to declare when a tag should be removed, as in `until=#1234`.
+pub const UNTIL_KEY: &str = "until";
+
+// This struct represents the parts of an issue returned by the tracker which matter here. GitHub
+// and GitLab both describe an issue's state this way.
+#[derive(Deserialize)]
+struct Issue {
+    state: String,
+}
+
+// This function returns the issue number in the `until` metadata of a tag, if it has one.
+fn until(tag: &Directive) -> Option<u64> {
+    tag.metadata
+        .get(UNTIL_KEY)?
+        .strip_prefix('#')
+        .filter(|number| number.bytes().all(|byte| byte.is_ascii_digit()))?
+        .parse()
+        .ok()
+}
+
+// This function asks the tracker whether an issue is closed. If it can't, it returns the reason.
+fn fetch(agent: &ureq::Agent, url: &str, token: &str, issue: u64) -> Result<bool, String> {
+    let url = url.replace(ISSUE_PLACEHOLDER, &issue.to_string());
+    let response = agent
+        .get(&url)
+        .set("Accept", "application/json")
+        .set("Authorization", &format!("Bearer {token}"))
+        .call()
+        .map_err(|error| error.to_string())?
+        .into_string()
+        .map_err(|error| error.to_string())?;
+    let response =
+        serde_json::from_str::<Issue>(&response).map_err(|error| format!("{url}: {error}"))?;
+
+    Ok(response.state.eq_ignore_ascii_case("closed"))
+}
+
+// This function looks up the issues named by the `until` metadata of the given tags, if there's an
+// issue tracker and a token for it. It returns a vector of warnings for tags whose issues are
+// closed or couldn't be looked up.
+pub fn check<'a>(
+    tags: impl IntoIterator<Item = &'a Directive>,
+    settings: &Issues,
+) -> Vec<Diagnostic> {
+    let (Some(url), Ok(token)) = (&settings.url, env::var(TOKEN_VARIABLE)) else {
+        return vec![];
+    };
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(settings.timeout_seconds))
+        .build();
+
+    check_with(tags, |issue| fetch(&agent, url, &token, issue))
+}
+
+// This function checks the `until` metadata of tags, using the given function to find out whether
+// each issue is closed. Each issue is only looked up once, no matter how many tags name it. An
+// issue which can't be looked up (e.g., because the tracker is down) is only worth a warning, since
+// the tags may well still be needed.
+fn check_with<'a, F: Fn(u64) -> Result<bool, String>>(
+    tags: impl IntoIterator<Item = &'a Directive>,
+    is_closed: F,
+) -> Vec<Diagnostic> {
+    let mut warnings = Vec::<Diagnostic>::new();
+
+    // Group the tags by issue.
+    let mut tags_by_issue = BTreeMap::<u64, Vec<&Directive>>::new();
+    for tag in tags {
+        if let Some(issue) = until(tag) {
+            tags_by_issue.entry(issue).or_default().push(tag);
+        }
+    }
+
+    for (issue, tags) in tags_by_issue {
+        match is_closed(issue) {
+            Ok(false) => {}
+            Ok(true) => {
+                for tag in tags {
+                    warnings.push(Diagnostic::warning(
+                        diagnostic::CLOSED_ISSUE,
+                        tag,
+                        format!(
+                            "{tag} is only needed until issue #{issue}, which is closed. Consider \
+                             removing it along with the code it describes.",
+                        ),
+                    ));
+                }
+            }
+            Err(reason) => {
+                for tag in tags {
+                    warnings.push(Diagnostic::warning(
+                        diagnostic::UNCHECKED_ISSUE,
+                        tag,
+                        format!(
+                            "{tag} is only needed until issue #{issue}, which could not be checked \
+                             ({reason}).",
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    warnings.sort_by(|x, y| x.location.cmp(&y.location));
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            diagnostic::Severity,
+            directive::{Directive, Type},
+            issues::{check_with, until},
+        },
+        std::{cell::RefCell, collections::BTreeMap},
+    };
+
+    fn tag(label: &str, until: Option<&str>) -> Directive {
+        let mut metadata = BTreeMap::new();
+        if let Some(until) = until {
+            metadata.insert("until".to_owned(), until.to_owned());
+        }
+        Directive {
+            metadata,
+            ..Directive::new(Type::Tag, label, "file.rs", 1)
+        }
+    }
+
+    #[test]
+    fn until_issue_numbers() {
+        assert_eq!(until(&tag("a", Some("#1234"))), Some(1234));
+        assert_eq!(until(&tag("a", Some("1234"))), None);
+        assert_eq!(until(&tag("a", Some("#+1"))), None);
+        assert_eq!(until(&tag("a", None)), None);
+    }
+
+    #[test]
+    fn check_closed_issues() {
+        let tags = [
+            tag("workaround_a", Some("#1")),
+            tag("workaround_b", Some("#2")),
+            tag("workaround_c", Some("#1")),
+            tag("permanent", None),
+        ];
+        let requested = RefCell::new(vec![]);
+
+        let warnings = check_with(&tags, |issue| {
+            requested.borrow_mut().push(issue);
+            Ok(issue == 1)
+        });
+
+        assert_eq!(requested.into_inner(), [1, 2]);
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|warning| warning.label.as_deref().unwrap())
+                .collect::<Vec<_>>(),
+            ["workaround_a", "workaround_c"],
+        );
+        assert!(
+            warnings
+                .iter()
+                .all(|warning| warning.severity == Severity::Warning),
+        );
+    }
+
+    #[test]
+    fn check_lookup_failure() {
+        let tags = [
+            tag("a", Some("#1")),
+            tag("b", Some("#2")),
+            tag("c", Some("#1")),
+        ];
+
+        let warnings = check_with(&tags, |issue| {
+            if issue == 1 {
+                Err("offline".to_owned())
+            } else {
+                Ok(true)
+            }
+        });
+
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|warning| (warning.code.as_str(), warning.label.as_deref().unwrap()))
+                .collect::<Vec<_>>(),
+            [("E118", "a"), ("E118", "c"), ("E115", "b")],
+        );
+        assert!(
+            warnings[0]
+                .message
+                .ends_with("could not be checked (offline)."),
+        );
+        assert!(
+            warnings
+                .iter()
+                .all(|warning| warning.severity == Severity::Warning),
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use {
+    crate::{
+        config::LabelValidator,
+        diagnostic::{self, Diagnostic},
+        directive::Directive,
+        schema::SCHEMA_VERSION,
+    },
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::BTreeMap,
+        io::Write,
+        process::{Command, Stdio},
+        thread,
+    },
+};
+
+// This struct represents what's written to the STDIN of a label validator.
+#[derive(Serialize)]
+struct Request<'a> {
+    schema_version: usize,
+    labels: &'a [&'a str],
+}
+
+// This struct represents what a label validator writes to its STDOUT: a verdict for each label.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Response {
+    verdicts: BTreeMap<String, Verdict>,
+}
+
+// This struct represents whether a label is acceptable, and if not, why.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Verdict {
+    valid: bool,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+// This function runs a label validator on a batch of labels, and returns the verdicts. The command
+// must not be empty [ref:label_validator_command_nonempty].
+fn run(command: &[String], labels: &[&str]) -> Result<BTreeMap<String, Verdict>, String> {
+    let name = command.join(" ");
+    let mut child = Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("Unable to run `{name}`: {error}"))?;
+
+    // The `unwrap`s are safe because STDIN is piped and the request always serializes successfully.
+    let mut stdin = child.stdin.take().unwrap();
+    let request = serde_json::to_vec(&Request {
+        schema_version: SCHEMA_VERSION,
+        labels,
+    })
+    .unwrap();
+
+    // The input is written from another thread, so a validator which starts writing its output
+    // before it reads all of its input can't deadlock. Errors are ignored, since the validator may
+    // exit without reading everything (e.g., if it fails), which is reported below.
+    let writer = thread::spawn(move || {
+        let _ = stdin.write_all(&request);
+    });
+    let output = child
+        .wait_with_output()
+        .map_err(|error| format!("Unable to run `{name}`: {error}"))?;
+    let _ = writer.join();
+
+    if !output.status.success() {
+        return Err(format!(
+            "`{name}` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim(),
+        ));
+    }
+    let response = serde_json::from_slice::<Response>(&output.stdout)
+        .map_err(|error| format!("`{name}` returned invalid verdicts: {error}"))?;
+    if let Some(label) = labels
+        .iter()
+        .find(|label| !response.verdicts.contains_key(**label))
+    {
+        return Err(format!("`{name}` didn't return a verdict for `{label}`."));
+    }
+
+    Ok(response.verdicts)
+}
+
+// This function asks the label validators about the labels of the given tags. Each validator is run
+// once per batch of the labels it's interested in, and every tag with a label it rejects is
+// reported. It returns a vector of errors, or an error if a validator couldn't be run or returned
+// something unexpected.
+pub fn check<'a>(
+    tags: impl IntoIterator<Item = &'a Directive>,
+    validators: &[LabelValidator],
+) -> Result<Vec<Diagnostic>, String> {
+    let mut errors = Vec::<Diagnostic>::new();
+
+    // Group the tags by label, so each label is only validated once.
+    let mut tags_by_label = BTreeMap::<&str, Vec<&Directive>>::new();
+    for tag in tags {
+        tags_by_label.entry(&tag.label).or_default().push(tag);
+    }
+
+    for (i, validator) in validators.iter().enumerate() {
+        let labels = tags_by_label
+            .keys()
+            .copied()
+            .filter(|label| {
+                validator
+                    .label
+                    .as_ref()
+                    .is_none_or(|pattern| pattern.is_match(label))
+            })
+            .collect::<Vec<_>>();
+
+        for batch in labels.chunks(validator.batch_size) {
+            for (label, verdict) in run(&validator.command, batch)? {
+                if verdict.valid {
+                    continue;
+                }
+                for tag in tags_by_label.get(label.as_str()).into_iter().flatten() {
+                    errors.push(Diagnostic::error(
+                        diagnostic::REJECTED_LABEL,
+                        tag,
+                        format!(
+                            "{tag} was rejected by `label-validators` entry #{}{}",
+                            i + 1,
+                            verdict
+                                .message
+                                .as_ref()
+                                .map_or_else(|| ".".to_owned(), |message| format!(": {message}")),
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(errors)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::{
+        config::parse,
+        directive::{Directive, Type},
+        label_validators::check,
+    };
+
+    fn tag(label: &str) -> Directive {
+        Directive::new(Type::Tag, label, "file.rs", 1)
+    }
+
+    #[test]
+    fn check_verdicts() {
+        let config = parse(
+            r#"
+            [[label-validators]]
+            label = '^svc_'
+            command = [
+                'sh',
+                '-c',
+                '''
+                grep -q '"labels":\["svc_auth","svc_billing"\]' || exit 1
+                echo '{"verdicts":{"svc_auth":{"valid":true},'
+                echo '"svc_billing":{"valid":false,"message":"Unknown service."}}}'
+                ''',
+            ]
+            "#,
+        )
+        .unwrap();
+        let tags = [tag("svc_billing"), tag("other"), tag("svc_auth")];
+
+        let errors = check(&tags, &config.label_validators).unwrap();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].label.as_deref(), Some("svc_billing"));
+        assert!(
+            errors[0]
+                .message
+                .ends_with("was rejected by `label-validators` entry #1: Unknown service."),
+        );
+    }
+
+    #[test]
+    fn check_missing_verdict() {
+        let config = parse(
+            r#"
+            [[label-validators]]
+            command = ['sh', '-c', '''cat > /dev/null; echo '{"verdicts":{}}' ''']
+            "#,
+        )
+        .unwrap();
+
+        assert!(
+            check(&[tag("foo")], &config.label_validators)
+                .unwrap_err()
+                .contains("didn't return a verdict for `foo`"),
+        );
+    }
+}
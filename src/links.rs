@@ -0,0 +1,221 @@
+use {
+    crate::{
+        diagnostic::Diagnostic,
+        directive::Directive,
+        output::{self, Format, Listing},
+        schema::SCHEMA_VERSION,
+        walk::normalize,
+    },
+    serde::Serialize,
+    std::{fmt::Write, path::Path},
+};
+
+// This struct represents a directive along with a link to it.
+#[derive(Serialize)]
+struct Link<'a> {
+    #[serde(flatten)]
+    directive: &'a Directive,
+    url: String,
+}
+
+// This function returns a link to a line of a file, relative to the given base URL (e.g.,
+// `https://github.com/org/repo/blob/main/`). The path is percent-encoded as needed. An empty base
+// URL results in a relative link.
+pub fn url(base_url: &str, path: &Path, line_number: usize) -> String {
+    let mut url = base_url.to_owned();
+    if !url.is_empty() && !url.ends_with('/') {
+        url.push('/');
+    }
+    let normalized = normalize(path);
+    for (i, component) in normalized.iter().enumerate() {
+        if i > 0 {
+            url.push('/');
+        }
+        for byte in component.as_encoded_bytes() {
+            if byte.is_ascii_alphanumeric() || b"-._~".contains(byte) {
+                url.push(char::from(*byte));
+            } else {
+                let _ = write!(url, "%{byte:02X}");
+            }
+        }
+    }
+    let _ = write!(url, "#L{line_number}");
+    url
+}
+
+// This function renders a list of directives with links to them in the given format.
+pub fn render(format: &Format, base_url: &str, directives: &[Directive]) -> String {
+    let links = directives
+        .iter()
+        .map(|directive| Link {
+            directive,
+            url: url(base_url, &directive.path, directive.line_number),
+        })
+        .collect::<Vec<_>>();
+
+    match format {
+        Format::Human => {
+            let mut output = String::new();
+            for link in links {
+                let _ = writeln!(output, "{} {}", link.directive, link.url);
+            }
+            output
+        }
+        Format::Json => {
+            // The `unwrap` is safe because links always serialize successfully.
+            let mut json = serde_json::to_string_pretty(&Listing {
+                schema_version: SCHEMA_VERSION,
+                directives: &links,
+            })
+            .unwrap();
+            json.push('\n');
+            json
+        }
+        Format::Template(template) => {
+            let mut output = String::new();
+            for link in links {
+                let _ = writeln!(
+                    output,
+                    "{}",
+                    template.render(link.directive, Some(&link.url)),
+                );
+            }
+            output
+        }
+        Format::Exec { template, .. } => {
+            let mut output = String::new();
+            for link in links {
+                let _ = writeln!(
+                    output,
+                    "{}",
+                    output::shell_command(&template.command(link.directive, Some(&link.url))),
+                );
+            }
+            output
+        }
+    }
+}
+
+// This function prints a list of directives with links to them in the given format, or runs the
+// command for each one if the format says to.
+pub fn emit(format: &Format, base_url: &str, directives: &[Directive]) -> Result<(), String> {
+    if let Format::Exec {
+        template,
+        run: true,
+    } = format
+    {
+        return output::run(directives.iter().map(|directive| {
+            template.command(
+                directive,
+                Some(&url(base_url, &directive.path, directive.line_number)),
+            )
+        }));
+    }
+
+    print!("{}", render(format, base_url, directives));
+    Ok(())
+}
+
+// This function adds a link to the location of each problem, if it has one, to its message.
+pub fn annotate(base_url: &str, diagnostics: &mut [Diagnostic]) {
+    for diagnostic in diagnostics {
+        if let Some((path, line_number)) = &diagnostic.location {
+            let url = url(base_url, path, *line_number);
+            let _ = write!(diagnostic.message, "\n{url}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            diagnostic::{Diagnostic, Severity},
+            directive::{Directive, Type},
+            links::{annotate, render, url},
+            output::Format,
+        },
+        std::{path::Path, slice},
+    };
+
+    const BASE_URL: &str = "https://github.com/org/repo/blob/main/";
+
+    #[test]
+    fn url_simple() {
+        assert_eq!(
+            url(BASE_URL, Path::new("./src/main.rs"), 42),
+            "https://github.com/org/repo/blob/main/src/main.rs#L42",
+        );
+    }
+
+    #[test]
+    fn url_without_trailing_slash() {
+        assert_eq!(
+            url("https://example.com/repo", Path::new("main.rs"), 1),
+            "https://example.com/repo/main.rs#L1",
+        );
+    }
+
+    #[test]
+    fn url_relative() {
+        assert_eq!(url("", Path::new("./src/main.rs"), 7), "src/main.rs#L7");
+    }
+
+    #[test]
+    fn url_escaped() {
+        assert_eq!(
+            url(BASE_URL, Path::new("docs/a b#c%.md"), 3),
+            "https://github.com/org/repo/blob/main/docs/a%20b%23c%25.md#L3",
+        );
+    }
+
+    #[test]
+    fn render_human_and_json() {
+        let tag = Directive::new(Type::Tag, "tag1", "file1.rs", 7);
+
+        assert_eq!(
+            render(&Format::Human, BASE_URL, slice::from_ref(&tag)),
+            "[?tag:tag1] @ file1.rs:7 https://github.com/org/repo/blob/main/file1.rs#L7\n"
+                .replace('?', ""),
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&Format::Json, BASE_URL, &[tag])).unwrap();
+        assert_eq!(json["schema_version"], 1_u64);
+        assert_eq!(json["directives"][0]["label"], "tag1");
+        assert_eq!(json["directives"][0]["line"], 7_u64);
+        assert_eq!(
+            json["directives"][0]["url"],
+            "https://github.com/org/repo/blob/main/file1.rs#L7",
+        );
+    }
+
+    #[test]
+    fn annotate_located_only() {
+        let mut diagnostics = vec![
+            Diagnostic {
+                code: "E002".to_owned(),
+                severity: Severity::Error,
+                message: "Dangling.".to_owned(),
+                location: Some((Path::new("file1.rs").to_owned(), 2)),
+                label: None,
+                context: None,
+            },
+            Diagnostic {
+                code: "E000".to_owned(),
+                severity: Severity::Error,
+                message: "Nowhere.".to_owned(),
+                location: None,
+                label: None,
+                context: None,
+            },
+        ];
+        annotate(BASE_URL, &mut diagnostics);
+
+        assert_eq!(
+            diagnostics[0].message,
+            "Dangling.\nhttps://github.com/org/repo/blob/main/file1.rs#L2",
+        );
+        assert_eq!(diagnostics[1].message, "Nowhere.");
+    }
+}
@@ -0,0 +1,808 @@
+use {
+    crate::{
+        count::count,
+        diagnostic::{Diagnostic, Severity},
+        directive::{normalize_label, split_metadata, Directive, Directives, Type},
+        walk::normalize,
+    },
+    regex::Regex,
+    serde::Serialize,
+    serde_json::Value,
+    std::{
+        collections::{BTreeMap, BTreeSet, HashMap},
+        fmt::Write as _,
+        fs::read,
+        io::{BufRead, Write},
+        path::{Path, PathBuf},
+        sync::LazyLock,
+    },
+};
+
+// The JSON-RPC error code for requests the server doesn't support
+const METHOD_NOT_FOUND: i64 = -32601;
+
+// The LSP text document sync kind for sending the whole document on each change
+const FULL_SYNC: u64 = 1;
+
+// The LSP diagnostic severities
+const ERROR: u64 = 1;
+const WARNING: u64 = 2;
+
+// The function which finds the problems with the tags and references
+type Check<'a> = &'a dyn Fn(&HashMap<String, Vec<Directive>>, &[Directive]) -> Vec<Diagnostic>;
+
+// A directive of any kind, with the part after the sigil captured. This is deliberately looser
+// than the regular expressions for the configured sigils, since it's only used to find where on a
+// line a known directive is.
+static DIRECTIVE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[\s*[^\[\]:\s]+\s*:([^\]]*)\]").unwrap() // Safe by manual inspection
+});
+
+// This struct represents a position in a document. Lines and characters count from zero, and the
+// characters are UTF-16 code units.
+#[derive(Debug, Serialize)]
+struct Position {
+    line: usize,
+    character: usize,
+}
+
+// This struct represents a range of a document.
+#[derive(Debug, Serialize)]
+struct Range {
+    start: Position,
+    end: Position,
+}
+
+// This struct represents a range of a document, along with the document.
+#[derive(Serialize)]
+struct Location {
+    uri: String,
+    range: Range,
+}
+
+// This struct represents a problem, as the editor shows it.
+#[derive(Serialize)]
+struct LspDiagnostic {
+    range: Range,
+    severity: u64,
+    code: String,
+    source: &'static str,
+    message: String,
+}
+
+// This struct represents the problems in a document.
+#[derive(Serialize)]
+struct PublishDiagnosticsParams {
+    uri: String,
+    diagnostics: Vec<LspDiagnostic>,
+}
+
+// This struct represents the features the server supports.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Capabilities {
+    text_document_sync: u64,
+    definition_provider: bool,
+    references_provider: bool,
+    hover_provider: bool,
+}
+
+// This struct represents the server's reply to `initialize`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InitializeResult {
+    capabilities: Capabilities,
+    server_info: ServerInfo,
+}
+
+// This struct identifies the server.
+#[derive(Serialize)]
+struct ServerInfo {
+    name: &'static str,
+    version: &'static str,
+}
+
+// This struct represents the text shown when hovering over a directive.
+#[derive(Serialize)]
+struct Hover {
+    contents: MarkupContent,
+}
+
+// This struct represents Markdown text.
+#[derive(Serialize)]
+struct MarkupContent {
+    kind: &'static str,
+    value: String,
+}
+
+// This struct represents a reply to a request.
+#[derive(Serialize)]
+struct Response<'a> {
+    jsonrpc: &'static str,
+    id: &'a Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ResponseError>,
+}
+
+// This struct represents why a request failed.
+#[derive(Serialize)]
+struct ResponseError {
+    code: i64,
+    message: String,
+}
+
+// This struct represents a message which doesn't expect a reply.
+#[derive(Serialize)]
+struct Notification<T> {
+    jsonrpc: &'static str,
+    method: &'static str,
+    params: T,
+}
+
+// This function converts a reply to JSON. The `unwrap` is safe because the replies above always
+// serialize successfully.
+fn to_value(value: impl Serialize) -> Value {
+    serde_json::to_value(value).unwrap()
+}
+
+// This struct holds the state of the language server: the tags and references in each file (keyed
+// by the path relative to the workspace root), kept up to date as files are edited.
+pub struct Server<'a> {
+    root: PathBuf,
+    files: BTreeMap<PathBuf, Directives>,
+    open: HashMap<PathBuf, String>,
+    published: BTreeSet<PathBuf>,
+    parse: &'a dyn Fn(&Path, &[u8]) -> Directives,
+    check: Check<'a>,
+    track: &'a dyn Fn(&Path) -> bool,
+}
+
+// This function reads a message. It returns `None` at the end of the input.
+fn read_message(input: &mut impl BufRead) -> Result<Option<Value>, String> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input
+            .read_line(&mut header)
+            .map_err(|error| format!("Unable to read a message: {error}"))?
+            == 0
+        {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let length = length.ok_or("A message is missing its `Content-Length`.")?;
+    let mut body = vec![0; length];
+    input
+        .read_exact(&mut body)
+        .map_err(|error| format!("Unable to read a message: {error}"))?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|error| format!("Unable to parse a message: {error}"))
+}
+
+// This function writes a message.
+fn write_message(output: &mut impl Write, message: &impl Serialize) -> Result<(), String> {
+    let body = to_value(message).to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())
+        .and_then(|()| output.flush())
+        .map_err(|error| format!("Unable to write a message: {error}"))
+}
+
+// This function returns the number of UTF-16 code units in a string, which is how LSP positions
+// count characters.
+fn utf16_len(s: &str) -> usize {
+    s.chars().map(char::len_utf16).sum()
+}
+
+// This function finds the span (in UTF-16 code units) of each directive on a line, along with its
+// label.
+fn spans(line: &str) -> Vec<(usize, usize, String)> {
+    DIRECTIVE_REGEX
+        .captures_iter(line)
+        .map(|captures| {
+            // The `unwrap`s are safe because the whole match and the group always participate.
+            let whole = captures.get(0).unwrap();
+            let contents = captures.get(1).unwrap().as_str().trim();
+            let start = utf16_len(&line[..whole.start()]);
+            let end = start + utf16_len(whole.as_str());
+            (start, end, normalize_label(&split_metadata(contents).0))
+        })
+        .collect()
+}
+
+// This function converts a URI to a path relative to the workspace root, if it names a file there.
+fn uri_path(root: &Path, uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let escape = (encoded[i] == b'%')
+            .then(|| encoded.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        if let Some(byte) = escape {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(encoded[i]);
+            i += 1;
+        }
+    }
+
+    let path = PathBuf::from(String::from_utf8(decoded).ok()?);
+    path.strip_prefix(root).ok().map(normalize)
+}
+
+// This function converts a path relative to the workspace root to a URI.
+fn path_uri(root: &Path, path: &Path) -> String {
+    let mut uri = "file://".to_owned();
+    for byte in root.join(normalize(path)).to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            let _ = write!(uri, "%{byte:02X}");
+        }
+    }
+    uri
+}
+
+impl<'a> Server<'a> {
+    // This function creates a server for the directives found by scanning the workspace. Files
+    // opened in the editor are parsed with `parse` if `track` accepts them, and problems are found
+    // with `check`.
+    pub fn new(
+        root: PathBuf,
+        directives: impl IntoIterator<Item = Directive>,
+        parse: &'a dyn Fn(&Path, &[u8]) -> Directives,
+        check: Check<'a>,
+        track: &'a dyn Fn(&Path) -> bool,
+    ) -> Self {
+        let mut files = BTreeMap::<PathBuf, Directives>::new();
+        for directive in directives {
+            let file = files.entry(normalize(&directive.path)).or_default();
+            if directive.r#type == Type::Tag {
+                file.tags.push(directive);
+            } else {
+                file.refs.push(directive);
+            }
+        }
+
+        Self {
+            root,
+            files,
+            open: HashMap::new(),
+            published: BTreeSet::new(),
+            parse,
+            check,
+            track,
+        }
+    }
+
+    // This function handles messages until the client asks the server to exit.
+    pub fn serve(
+        &mut self,
+        input: &mut impl BufRead,
+        output: &mut impl Write,
+    ) -> Result<(), String> {
+        while let Some(message) = read_message(input)? {
+            let method = message["method"].as_str().unwrap_or_default();
+            let params = &message["params"];
+            let id = message.get("id");
+
+            let result = match method {
+                "exit" => return Ok(()),
+                "initialize" => Some(to_value(InitializeResult {
+                    capabilities: Capabilities {
+                        text_document_sync: FULL_SYNC,
+                        definition_provider: true,
+                        references_provider: true,
+                        hover_provider: true,
+                    },
+                    server_info: ServerInfo {
+                        name: "tagref",
+                        version: env!("CARGO_PKG_VERSION"),
+                    },
+                })),
+                "initialized" => {
+                    self.publish(output)?;
+                    None
+                }
+                "textDocument/didOpen" | "textDocument/didChange" | "textDocument/didClose" => {
+                    let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                    if let Some(path) = uri_path(&self.root, uri) {
+                        let text = match method {
+                            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+                            "textDocument/didChange" => params["contentChanges"]
+                                .as_array()
+                                .and_then(|changes| changes.last())
+                                .and_then(|change| change["text"].as_str()),
+                            _ => None,
+                        };
+                        self.update(&path, text.map(ToOwned::to_owned));
+                        self.publish(output)?;
+                    }
+                    None
+                }
+                "textDocument/definition" => Some(self.locate(params, |label, tags, _| {
+                    tags.get(label).cloned().unwrap_or_default()
+                })),
+                "textDocument/references" => {
+                    let declarations = params["context"]["includeDeclaration"]
+                        .as_bool()
+                        .unwrap_or(false);
+                    Some(self.locate(params, |label, tags, refs| {
+                        let mut found = if declarations {
+                            tags.get(label).cloned().unwrap_or_default()
+                        } else {
+                            vec![]
+                        };
+                        found.extend(refs.iter().filter(|r#ref| r#ref.label == label).cloned());
+                        found
+                    }))
+                }
+                "textDocument/hover" => Some(to_value(self.hover(params))),
+                "shutdown" => Some(Value::Null),
+                _ => {
+                    // Unknown notifications are ignored, and unknown requests are refused.
+                    if let Some(id) = id {
+                        write_message(
+                            output,
+                            &Response {
+                                jsonrpc: "2.0",
+                                id,
+                                result: None,
+                                error: Some(ResponseError {
+                                    code: METHOD_NOT_FOUND,
+                                    message: format!("Unsupported method `{method}`."),
+                                }),
+                            },
+                        )?;
+                    }
+                    None
+                }
+            };
+
+            if let (Some(id), Some(result)) = (id, result) {
+                write_message(
+                    output,
+                    &Response {
+                        jsonrpc: "2.0",
+                        id,
+                        result: Some(result),
+                        error: None,
+                    },
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // This function updates the directives in a file, given its text in the editor, or from the
+    // disk if the editor closed it.
+    fn update(&mut self, path: &Path, text: Option<String>) {
+        match text {
+            Some(text) => {
+                self.open.insert(path.to_owned(), text);
+            }
+            None => {
+                self.open.remove(path);
+            }
+        }
+
+        if !self.files.contains_key(path) && !(self.track)(path) {
+            return;
+        }
+        let contents = self
+            .open
+            .get(path)
+            .map(|text| text.as_bytes().to_owned())
+            .or_else(|| read(self.root.join(path)).ok());
+        match contents {
+            Some(contents) => {
+                // Keep the paths written the way the scan wrote them, e.g., with a leading `./`.
+                let written = self
+                    .files
+                    .get(path)
+                    .and_then(|file| file.tags.iter().chain(&file.refs).next())
+                    .map_or_else(|| path.to_owned(), |directive| directive.path.clone());
+                self.files
+                    .insert(path.to_owned(), (self.parse)(&written, &contents));
+            }
+            None => {
+                self.files.remove(path);
+            }
+        }
+    }
+
+    // This function gathers the tags and references in all the files.
+    fn directives(&self) -> (HashMap<String, Vec<Directive>>, Vec<Directive>) {
+        let mut tags = HashMap::<String, Vec<Directive>>::new();
+        let mut refs = vec![];
+        for file in self.files.values() {
+            for tag in &file.tags {
+                tags.entry(tag.label.clone()).or_default().push(tag.clone());
+            }
+            refs.extend(file.refs.iter().cloned());
+        }
+        (tags, refs)
+    }
+
+    // This function returns a line of a file, as the editor has it.
+    fn line(&self, path: &Path, line_number: usize) -> Option<String> {
+        let text = match self.open.get(path) {
+            Some(text) => text.clone(),
+            None => String::from_utf8(read(self.root.join(path)).ok()?).ok()?,
+        };
+        text.lines()
+            .nth(line_number.checked_sub(1)?)
+            .map(ToOwned::to_owned)
+    }
+
+    // This function returns the range of a directive with the given label on a line, or the whole
+    // line if it can't be found there.
+    fn range(&self, path: &Path, line_number: usize, label: Option<&str>) -> Range {
+        let line = self.line(path, line_number).unwrap_or_default();
+        let (start, end) = spans(&line)
+            .into_iter()
+            .find(|(_, _, span_label)| Some(span_label.as_str()) == label)
+            .map_or((0, utf16_len(&line)), |(start, end, _)| (start, end));
+        let line = line_number.saturating_sub(1);
+        Range {
+            start: Position {
+                line,
+                character: start,
+            },
+            end: Position {
+                line,
+                character: end,
+            },
+        }
+    }
+
+    // This function returns the location of a directive.
+    fn location(&self, directive: &Directive) -> Location {
+        let path = normalize(&directive.path);
+        Location {
+            uri: path_uri(&self.root, &path),
+            range: self.range(&path, directive.line_number, Some(&directive.label)),
+        }
+    }
+
+    // This function finds the label of the directive at the position in the request, if any.
+    fn label_at(&self, params: &Value) -> Option<String> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let path = uri_path(&self.root, uri)?;
+        let line_number = usize::try_from(params["position"]["line"].as_u64()?).ok()? + 1;
+        let character = usize::try_from(params["position"]["character"].as_u64()?).ok()?;
+
+        spans(&self.line(&path, line_number)?)
+            .into_iter()
+            .find(|(start, end, _)| (*start..=*end).contains(&character))
+            .map(|(_, _, label)| label)
+    }
+
+    // This function answers a request for the locations of the directives related to the one at
+    // the position in the request.
+    fn locate(
+        &self,
+        params: &Value,
+        find: impl Fn(&str, &HashMap<String, Vec<Directive>>, &[Directive]) -> Vec<Directive>,
+    ) -> Value {
+        let Some(label) = self.label_at(params) else {
+            return Value::Null;
+        };
+        let (tags, refs) = self.directives();
+        to_value(
+            find(&label, &tags, &refs)
+                .iter()
+                .map(|directive| self.location(directive))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    // This function describes the tag for the directive at the position in the request.
+    fn hover(&self, params: &Value) -> Option<Hover> {
+        let label = self.label_at(params)?;
+        let (tags, refs) = self.directives();
+
+        let mut contents = String::new();
+        match tags.get(&label) {
+            Some(tags) => {
+                for tag in tags {
+                    let _ = writeln!(contents, "`{tag}`\n");
+                    if !tag.description.is_empty() {
+                        let _ = writeln!(contents, "{}\n", tag.description);
+                    }
+                }
+            }
+            None => {
+                let _ = writeln!(contents, "No tag found for `{label}`.\n");
+            }
+        }
+        contents.push_str(&count(
+            refs.iter().filter(|r#ref| r#ref.label == label).count(),
+            "reference",
+        ));
+
+        Some(Hover {
+            contents: MarkupContent {
+                kind: "markdown",
+                value: contents,
+            },
+        })
+    }
+
+    // This function reports the problems in each file, and clears the ones which were fixed.
+    fn publish(&mut self, output: &mut impl Write) -> Result<(), String> {
+        let (tags, refs) = self.directives();
+        let mut by_path = BTreeMap::<PathBuf, Vec<LspDiagnostic>>::new();
+        for diagnostic in (self.check)(&tags, &refs) {
+            let Some((path, line_number)) = &diagnostic.location else {
+                continue;
+            };
+            let path = normalize(path);
+            let lsp_diagnostic = LspDiagnostic {
+                range: self.range(&path, *line_number, diagnostic.label.as_deref()),
+                severity: match diagnostic.severity {
+                    Severity::Error => ERROR,
+                    Severity::Warning => WARNING,
+                },
+                code: diagnostic.code.clone(),
+                source: "tagref",
+                message: diagnostic.message.clone(),
+            };
+            by_path.entry(path).or_default().push(lsp_diagnostic);
+        }
+
+        let fixed = self
+            .published
+            .iter()
+            .filter(|path| !by_path.contains_key(*path))
+            .map(|path| (path.clone(), vec![]))
+            .collect::<Vec<_>>();
+        self.published = by_path.keys().cloned().collect();
+        for (path, diagnostics) in by_path.into_iter().chain(fixed) {
+            write_message(
+                output,
+                &Notification {
+                    jsonrpc: "2.0",
+                    method: "textDocument/publishDiagnostics",
+                    params: PublishDiagnosticsParams {
+                        uri: path_uri(&self.root, &path),
+                        diagnostics,
+                    },
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            directive::{compile_directive_regex, parse, Directive, Directives},
+            lsp::{path_uri, read_message, spans, uri_path, Server},
+            tag_references,
+        },
+        serde_json::Value,
+        std::{
+            collections::HashMap,
+            path::{Path, PathBuf},
+        },
+    };
+
+    fn parse_directives(path: &Path, contents: &[u8]) -> Directives {
+        parse(
+            &compile_directive_regex("tag"),
+            &compile_directive_regex("ref"),
+            &compile_directive_regex("file"),
+            &compile_directive_regex("dir"),
+            &compile_directive_regex("spec"),
+            &compile_directive_regex("url"),
+            &[],
+            path,
+            contents,
+        )
+    }
+
+    fn value(json: &str) -> Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    // This function frames a message from the client. Requests have an ID, and notifications
+    // don't.
+    fn message(id: Option<u64>, method: &str, params: &str) -> Vec<u8> {
+        let id = id.map_or_else(String::new, |id| format!(r#""id": {id}, "#));
+        let body = value(&format!(
+            r#"{{"jsonrpc": "2.0", {id}"method": "{method}", "params": {params}}}"#,
+        ))
+        .to_string();
+        format!("Content-Length: {}\r\n\r\n{body}", body.len()).into_bytes()
+    }
+
+    // This function returns the parameters for a request about a position in a document.
+    fn position(uri: &str, line: usize, character: usize) -> String {
+        format!(
+            r#"{{
+                "textDocument": {{"uri": "{uri}"}},
+                "position": {{"line": {line}, "character": {character}}},
+                "context": {{"includeDeclaration": true}}
+            }}"#,
+        )
+    }
+
+    // This function runs a server for the given files on the given messages, and returns the
+    // messages it sends back.
+    fn serve(files: &[(&str, &str)], input: &[u8]) -> Vec<Value> {
+        let directives = files
+            .iter()
+            .flat_map(|(path, contents)| {
+                let directives = parse_directives(Path::new(path), contents.as_bytes());
+                directives.tags.into_iter().chain(directives.refs)
+            })
+            .collect::<Vec<_>>();
+        let check = |tags: &HashMap<String, Vec<Directive>>, refs: &[Directive]| {
+            tag_references::check(&tags.keys().cloned().collect(), refs)
+        };
+
+        let mut output = vec![];
+        Server::new(
+            PathBuf::from("/workspace"),
+            directives,
+            &parse_directives,
+            &check,
+            &|_| true,
+        )
+        .serve(&mut &input[..], &mut output)
+        .unwrap();
+
+        let mut output = output.as_slice();
+        let mut messages = vec![];
+        while let Some(message) = read_message(&mut output).unwrap() {
+            messages.push(message);
+        }
+        messages
+    }
+
+    #[test]
+    fn spans_of_directives() {
+        assert_eq!(
+            spans(&"// \u{e9} [?tag:a owner=@x] and [?ref: b ]".replace('?', "")),
+            [(5, 21, "a".to_owned()), (26, 35, "b".to_owned())],
+        );
+    }
+
+    #[test]
+    fn uri_round_trip() {
+        let root = Path::new("/work space");
+        let uri = path_uri(root, Path::new("./src/caf\u{e9}.rs"));
+
+        assert_eq!(uri, "file:///work%20space/src/caf%C3%A9.rs");
+        assert_eq!(
+            uri_path(root, &uri),
+            Some(PathBuf::from("src/caf\u{e9}.rs")),
+        );
+        assert_eq!(uri_path(root, "file:///elsewhere/a.rs"), None);
+    }
+
+    #[test]
+    fn serve_session() {
+        let a = "// [?tag:cache] Entries expire.\n".replace('?', "");
+        let b = "// See [?ref:cache].\n// [?ref:gone]\n".replace('?', "");
+
+        let a_uri = "file:///workspace/a.rs";
+        let b_uri = "file:///workspace/b.rs";
+        let open = |uri: &str, text: &str| {
+            format!(
+                r#"{{"textDocument": {{"uri": "{uri}", "text": {}}}}}"#,
+                Value::from(text),
+            )
+        };
+        let change = format!(
+            r#"{{"textDocument": {{"uri": "{b_uri}"}}, "contentChanges": [{{"text": {}}}]}}"#,
+            Value::from("// See [?ref:cache].\n".replace('?', "")),
+        );
+        let input = [
+            message(Some(1), "initialize", "{}"),
+            message(None, "initialized", "{}"),
+            message(None, "textDocument/didOpen", &open(a_uri, &a)),
+            message(None, "textDocument/didOpen", &open(b_uri, &b)),
+            message(Some(2), "textDocument/definition", &position(b_uri, 0, 10)),
+            message(Some(3), "textDocument/references", &position(a_uri, 0, 4)),
+            message(Some(4), "textDocument/hover", &position(b_uri, 0, 10)),
+            message(None, "textDocument/didChange", &change),
+            message(Some(5), "textDocument/rename", "{}"),
+            message(Some(6), "shutdown", "null"),
+            message(None, "exit", "null"),
+            message(Some(7), "shutdown", "null"),
+        ]
+        .concat();
+
+        let messages = serve(&[("./a.rs", &a), ("./b.rs", &b)], &input);
+
+        // The server describes what it can do.
+        assert_eq!(messages[0]["id"], 1_u64);
+        assert_eq!(
+            messages[0]["result"]["capabilities"]["definitionProvider"],
+            true,
+        );
+
+        // The dangling reference is reported when the client is ready, and each time a file is
+        // opened. Once the file is open, the problem is located at the reference.
+        for message in &messages[1..4] {
+            assert_eq!(message["method"], "textDocument/publishDiagnostics");
+            assert_eq!(message["params"]["uri"], b_uri);
+            assert_eq!(
+                message["params"]["diagnostics"].as_array().unwrap().len(),
+                1,
+            );
+        }
+        assert_eq!(
+            messages[3]["params"]["diagnostics"][0]["range"],
+            value(
+                r#"{
+                    "start": {"line": 1, "character": 3},
+                    "end": {"line": 1, "character": 13}
+                }"#,
+            ),
+        );
+
+        // The reference leads to the tag, and the tag to itself and the reference.
+        assert_eq!(messages[4]["id"], 2_u64);
+        assert_eq!(
+            messages[4]["result"],
+            value(&format!(
+                r#"[{{
+                    "uri": "{a_uri}",
+                    "range": {{
+                        "start": {{"line": 0, "character": 3}},
+                        "end": {{"line": 0, "character": 14}}
+                    }}
+                }}]"#,
+            )),
+        );
+        assert_eq!(messages[5]["id"], 3_u64);
+        assert_eq!(
+            messages[5]["result"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|location| location["uri"].as_str().unwrap())
+                .collect::<Vec<_>>(),
+            [a_uri, b_uri],
+        );
+
+        // Hovering over the reference describes the tag.
+        assert_eq!(messages[6]["id"], 4_u64);
+        let hover = messages[6]["result"]["contents"]["value"].as_str().unwrap();
+        assert!(hover.contains("Entries expire."));
+        assert!(hover.ends_with("1 reference"));
+
+        // Fixing the dangling reference clears the problem.
+        assert_eq!(messages[7]["params"]["uri"], b_uri);
+        assert_eq!(messages[7]["params"]["diagnostics"], value("[]"));
+
+        // Unsupported requests are refused, and nothing is handled after `exit`.
+        assert_eq!(messages[8]["id"], 5_u64);
+        assert!(messages[8]["error"].is_object());
+        assert_eq!(messages[9]["id"], 6_u64);
+        assert_eq!(messages[9]["result"], Value::Null);
+        assert_eq!(messages.len(), 10);
+    }
+}
@@ -1,90 +1,633 @@
+mod atomic;
+mod badge;
+mod budgets;
+mod cache;
+mod changelog;
+mod codeowners;
+mod comments;
+mod config;
+mod conflicts;
+mod confusable_labels;
 mod count;
+mod ctags;
+mod deletions;
+mod density;
+mod descriptions;
+mod diagnostic;
 mod dir_references;
 mod directive;
+mod directive_index;
+mod distance;
+mod docs;
+mod doctor;
 mod duplicates;
+mod edit;
+mod export;
+mod extractor;
 mod file_references;
+mod file_report;
+mod fix;
+mod git;
+mod history;
+mod impact;
+mod index;
+mod issues;
+mod label_validators;
+mod links;
+mod lsp;
+mod malformed_directives;
+mod notebook;
+mod output;
+mod packages;
+mod path_style;
+mod placement;
+mod policy;
+mod progress;
+mod provenance;
+mod providers;
+mod query;
+mod reachable;
+mod readahead;
+mod rename;
+mod report;
+mod report_diff;
+mod required_tags;
+mod reserved;
+mod schema;
+mod severity;
+mod shard;
+mod similar_labels;
+mod spec_references;
+mod spelling;
+mod spill;
+mod stale_descriptions;
+mod stat;
+mod stats;
+mod structured;
+mod suppressions;
 mod tag_references;
+mod url_references;
 mod walk;
+mod watch;
 
 use {
     atty::Stream,
-    clap::{App, AppSettings, Arg, SubCommand},
+    cache::Cache,
+    clap::{value_t, values_t, App, AppSettings, Arg, ArgGroup, ArgMatches, ErrorKind, SubCommand},
     colored::Colorize,
-    directive::compile_directive_regex,
+    comments::Extraction,
+    config::{Config, Extractor, PathPattern},
+    diagnostic::{Diagnostic, Severity},
+    directive::{compile_directive_regex, normalize_label, Directive, Directives, SigilSet, Type},
+    directive_index::{DirectiveIndex, Sample},
+    docs::DocsFormat,
+    history::{Date, HistoryFormat, Interval},
+    impact::Region,
+    output::{ExecTemplate, Format, Template},
+    packages::Packages,
+    path_style::PathStyle,
+    policy::Policy,
+    progress::{Progress, ProgressFormat},
+    reachable::{Edges, Graph, EDGES},
+    regex::Regex,
+    report::{Configuration, ReportFormat, Totals},
+    report_diff::DiffFormat,
+    schema::SCHEMA_VERSION,
+    shard::Shard,
+    spill::Spill,
     std::{
-        collections::{HashMap, HashSet},
-        io::BufReader,
+        collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+        env::{args_os, current_dir, set_current_dir},
+        ffi::OsString,
+        fmt::Write,
+        fs::{read, read_to_string, File},
+        io::{stdin, stdout, BufRead, BufReader, Read},
+        iter::once,
+        mem,
+        num::NonZeroUsize,
         path::{Path, PathBuf},
         process::exit,
+        ptr,
         sync::{Arc, Mutex},
+        thread::available_parallelism,
+        time::Instant,
     },
+    walk::normalize,
 };
 
 // The program version
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// Files at least this large (in bytes) are split into chunks which are parsed in parallel
+const PARALLEL_PARSE_THRESHOLD: u64 = 64 * 1024 * 1024;
+
 // Command-line option and subcommand names
 const CHECK_SUBCOMMAND: &str = "check";
+const CHECK_FIX_OPTION: &str = "fix";
+const CHECK_FAIL_ON_CONFLICTS_OPTION: &str = "fail-on-conflict-markers";
+const CHECK_USE_INDEX_OPTION: &str = "use-index";
+const CHECK_STAGED_OPTION: &str = "staged";
+const CHECK_VERIFY_SAMPLE_OPTION: &str = "verify-sample";
+const CHECK_EXPLAIN_PASS_OPTION: &str = "explain-pass";
+const CHECK_SCANNED_FILES_ONLY_OPTION: &str = "scanned-files-only";
+const CHECK_LINT_DESCRIPTIONS_OPTION: &str = "lint-descriptions";
+const CHECK_QUIET_OPTION: &str = "check-quiet";
+const CHECK_WATCH_OPTION: &str = "watch";
 const LIST_TAGS_SUBCOMMAND: &str = "list-tags";
 const LIST_REFS_SUBCOMMAND: &str = "list-refs";
 const LIST_FILES_SUBCOMMAND: &str = "list-files";
 const LIST_DIRS_SUBCOMMAND: &str = "list-dirs";
 const LIST_UNUSED_SUBCOMMAND: &str = "list-unused";
-const LIST_UNUSED_ERROR_OPTION: &str = "fail-if-any"; // [tag:fail_if_any]
+const LIST_DUPES_SUBCOMMAND: &str = "list-dupes";
+const LIST_TAGS_BY_OWNER_OPTION: &str = "by-owner"; // [tag:by_owner]
+const LIST_TAGS_GREP_OPTION: &str = "grep";
+const LIST_REFS_LABEL_OPTION: &str = "label";
+const LIST_ERROR_OPTION: &str = "fail-if-any"; // [tag:fail_if_any]
+const LIST_WHERE_OPTION: &str = "where";
+const LIST_UNUSED_IGNORE_OPTION: &str = "ignore";
+const LIST_UNUSED_ONLY_OPTION: &str = "only";
+const QUERY_SUBCOMMAND: &str = "query";
+const QUERY_ARGUMENT: &str = "QUERY";
+const FORMAT_OPTION: &str = "format";
+const TEMPLATE_OPTION: &str = "template";
+const EXEC_TEMPLATE_OPTION: &str = "exec-template";
+const RUN_OPTION: &str = "run";
+const REACHABLE_SUBCOMMAND: &str = "reachable";
+const REACHABLE_FROM_OPTION: &str = "from";
+const REACHABLE_PATH_BETWEEN_OPTION: &str = "path-between";
+const REACHABLE_EDGES_OPTION: &str = "edges";
+const STATS_SUBCOMMAND: &str = "stats";
+const STATS_HOTSPOTS_OPTION: &str = "hotspots";
+const STATS_OWNERS_OPTION: &str = "owners";
+const STATS_THRESHOLD_OPTION: &str = "threshold";
+const STATS_TOP_OPTION: &str = "top";
+const CTAGS_SUBCOMMAND: &str = "ctags";
+const CTAGS_OUTPUT_OPTION: &str = "output";
+const CTAGS_ETAGS_OPTION: &str = "etags";
+const INDEX_SUBCOMMAND: &str = "index";
+const INDEX_OUTPUT_OPTION: &str = "output";
+const BADGE_SUBCOMMAND: &str = "badge";
+const EXPORT_SUBCOMMAND: &str = "export";
+const EXPORT_SHARD_OPTION: &str = "shard";
+const EXPORT_OUTPUT_OPTION: &str = "output";
+const MERGE_SUBCOMMAND: &str = "merge";
+const MERGE_ARGUMENT: &str = "EXPORTS";
+const REPORT_DIFF_SUBCOMMAND: &str = "report-diff";
+const REPORT_DIFF_OLD_ARGUMENT: &str = "OLD";
+const REPORT_DIFF_NEW_ARGUMENT: &str = "NEW";
+const LINKS_SUBCOMMAND: &str = "links";
+const UPDATE_INDEX_SUBCOMMAND: &str = "update-index";
+const COMPLETE_LABEL_SUBCOMMAND: &str = "complete-label";
+const COMPLETE_LABEL_ARGUMENT: &str = "PREFIX";
+const CHECK_DELETIONS_SUBCOMMAND: &str = "check-deletions";
+const CHECK_DELETIONS_SINCE_OPTION: &str = "changed-since";
+const CHANGELOG_SUBCOMMAND: &str = "changelog";
+const CHANGELOG_SINCE_OPTION: &str = "since";
+const CHANGELOG_SEPARATOR_OPTION: &str = "namespace-separator";
+const HISTORY_SUBCOMMAND: &str = "history";
+const HISTORY_SINCE_OPTION: &str = "since";
+const HISTORY_INTERVAL_OPTION: &str = "interval";
+const DOCS_SUBCOMMAND: &str = "docs";
+const DOCS_OUTPUT_OPTION: &str = "output";
+const DOCS_SEPARATOR_OPTION: &str = "namespace-separator";
+const IMPACT_SUBCOMMAND: &str = "impact";
+const IMPACT_ARGUMENT: &str = "REGION";
+const FILE_REPORT_SUBCOMMAND: &str = "file-report";
+const FILE_REPORT_ARGUMENT: &str = "PATH";
+const LSP_SUBCOMMAND: &str = "lsp";
+const RENAME_SUBCOMMAND: &str = "rename";
+const RENAME_OLD_ARGUMENT: &str = "OLD";
+const RENAME_NEW_ARGUMENT: &str = "NEW";
+const RENAME_BATCH_SUBCOMMAND: &str = "rename-batch";
+const RENAME_BATCH_ARGUMENT: &str = "MAPPING";
+const DOCTOR_SUBCOMMAND: &str = "doctor";
+const DOCTOR_WHY_OPTION: &str = "why";
+const BASE_URL_OPTION: &str = "base-url";
+const MAX_WARNINGS_OPTION: &str = "max-warnings";
+const REPORT_OUTPUT_OPTION: &str = "output";
+const CONFIG_OPTION: &str = "config";
+const POLICY_OPTION: &str = "policy";
+const CACHE_OPTION: &str = "cache";
+const COMMENTS_ONLY_OPTION: &str = "comments-only";
+const INCLUDE_STRINGS_OPTION: &str = "include-strings";
+const STRUCTURED_VALUES_OPTION: &str = "structured-values";
+const SCAN_COPIES_OPTION: &str = "scan-copies";
+const DRY_RUN_OPTION: &str = "dry-run";
+const BACKUP_OPTION: &str = "backup";
+const ALLOW_DIRTY_OPTION: &str = "allow-dirty";
 const PATH_OPTION: &str = "path";
+const EXCLUDE_OPTION: &str = "exclude";
+const PROFILE_OPTION: &str = "profile";
 const TAG_SIGIL_OPTION: &str = "tag-sigil";
 const REF_SIGIL_OPTION: &str = "ref-sigil";
 const FILE_SIGIL_OPTION: &str = "file-sigil";
 const DIR_SIGIL_OPTION: &str = "dir-sigil";
+const SPEC_SIGIL_OPTION: &str = "spec-sigil";
+const URL_SIGIL_OPTION: &str = "url-sigil";
+const CONTEXT_LENGTH_OPTION: &str = "context-length";
+const MAX_MEMORY_OPTION: &str = "max-memory";
+const PROGRESS_FORMAT_OPTION: &str = "progress-format";
+const PATH_STYLE_OPTION: &str = "path-style";
+const SCHEMA_OPTION: &str = "schema";
+
+// This struct represents the options shared by the subcommands which list directives.
+struct Listing {
+    fail_if_any: bool, // [ref:fail_if_any]
+    filter: Option<String>,
+    format: Format,
+}
+
+// This struct represents a report of the result of a check: its format, and the file to write it to
+// (or STDOUT).
+struct Report {
+    format: ReportFormat,
+    output: Option<PathBuf>,
+}
 
 // This enum represents the subcommands.
 enum Subcommand {
-    Check,
-    ListTags,
-    ListRefs,
-    ListFiles,
-    ListDirs,
-    ListUnused(bool), // [ref:fail_if_any]
+    Check {
+        fix: bool,
+        fail_on_conflicts: bool,
+        use_index: bool,
+        verify_sample: Option<Sample>,
+        staged: bool,
+        watch: bool,
+        explain_pass: bool,
+        scanned_files_only: bool,
+        lint_descriptions: bool,
+        quiet: bool,
+        reports: Vec<Report>,
+        base_url: Option<String>,
+        max_warnings: Option<usize>,
+    },
+    ListTags {
+        by_owner: bool, // [ref:by_owner]
+        grep: Option<String>,
+        listing: Listing,
+    },
+    ListRefs {
+        label: Option<String>,
+        listing: Listing,
+    },
+    ListFiles(Listing),
+    ListDirs(Listing),
+    ListUnused {
+        ignore: Vec<Regex>,
+        only: Vec<Regex>,
+        listing: Listing,
+    },
+    ListDupes(Listing),
+    Query {
+        query: String,
+        format: Format,
+    },
+    Reachable {
+        target: Reachability,
+        edges: Edges,
+        format: Format,
+    },
+    Stats {
+        hotspots: bool,
+        owners: bool,
+        threshold: usize,
+        top: usize,
+    },
+    Ctags {
+        output: Option<PathBuf>,
+        etags: bool,
+    },
+    Index {
+        output: PathBuf,
+        base_url: Option<String>,
+    },
+    Badge,
+    Export {
+        shard: Option<Shard>,
+        output: Option<PathBuf>,
+    },
+    Merge {
+        exports: Vec<PathBuf>,
+        reports: Vec<Report>,
+        base_url: Option<String>,
+        max_warnings: Option<usize>,
+    },
+    ReportDiff {
+        old: PathBuf,
+        new: PathBuf,
+        format: DiffFormat,
+    },
+    Links {
+        base_url: String,
+        format: Format,
+    },
+    UpdateIndex,
+    CompleteLabel {
+        prefix: String,
+    },
+    CheckDeletions {
+        changed_since: String,
+    },
+    Changelog {
+        since: String,
+        separator: String,
+    },
+    History {
+        since: Date,
+        interval: Interval,
+        format: HistoryFormat,
+    },
+    Docs {
+        format: DocsFormat,
+        output: Option<PathBuf>,
+        base_url: Option<String>,
+        separator: String,
+    },
+    Impact {
+        region: Region,
+        format: Format,
+    },
+    FileReport {
+        path: PathBuf,
+    },
+    Lsp,
+    Rename {
+        old: String,
+        new: String,
+    },
+    RenameBatch {
+        mapping: PathBuf,
+    },
+    Doctor {
+        why: PathBuf,
+    },
+}
+
+// This enum represents what the `reachable` subcommand should find.
+enum Reachability {
+    From(String),
+    PathBetween(String, String),
 }
 
 // This struct represents the command-line arguments.
 #[allow(clippy::struct_excessive_bools)]
 pub struct Settings {
+    config_path: Option<PathBuf>,
+    policy_path: Option<PathBuf>,
+    cache: Option<Cache>,
+    extraction: Extraction,
+    structured_values: bool,
+    scan_copies: bool,
+    edit_options: edit::Options,
+    profile: Option<String>,
     paths: Vec<PathBuf>,
+    default_paths: bool,
+    excludes: Vec<PathPattern>,
     tag_sigil: String,
     ref_sigil: String,
     file_sigil: String,
     dir_sigil: String,
+    spec_sigil: String,
+    url_sigil: String,
+    context_length: usize,
+    max_memory: Option<usize>, // In bytes
+    progress_format: ProgressFormat,
+    path_style: Option<PathStyle>,
+    schema: bool,
     subcommand: Subcommand,
 }
 
+// This function returns the option for choosing the output format of a listing.
+fn format_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(FORMAT_OPTION)
+        .value_name("FORMAT")
+        .long(FORMAT_OPTION)
+        .help("Sets the output format")
+        .possible_values(output::FORMATS)
+        .default_value("human")
+}
+
+// This function returns the option for giving the template of the `template` output format.
+fn template_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(TEMPLATE_OPTION)
+        .value_name("TEMPLATE")
+        .long(TEMPLATE_OPTION)
+        .help(
+            "Sets the template for each directive with `--format template`, e.g., \
+             `{path}:{line}: [{type}:{label}] {description}`",
+        )
+        .required_if(FORMAT_OPTION, "template")
+        .validator(|template| template.parse::<Template>().map(|_| ()))
+}
+
+// This function returns the option for giving the command of the `exec` output format.
+fn exec_template_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(EXEC_TEMPLATE_OPTION)
+        .value_name("TEMPLATE")
+        .long(EXEC_TEMPLATE_OPTION)
+        .help(
+            "Sets the command for each directive with `--format exec`, with the same \
+             placeholders as `--template`, e.g., `code -g {path}:{line}`",
+        )
+        .required_if(FORMAT_OPTION, "exec")
+        .validator(|template| template.parse::<ExecTemplate>().map(|_| ()))
+}
+
+// This function returns the option for running the commands of the `exec` output format.
+fn run_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(RUN_OPTION)
+        .long(RUN_OPTION)
+        .help("Runs the command for each directive with `--format exec`, rather than printing it")
+        .requires(EXEC_TEMPLATE_OPTION)
+}
+
+// This function returns the option for failing when a listing is nonempty.
+fn fail_if_any_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(LIST_ERROR_OPTION)
+        .long(LIST_ERROR_OPTION)
+        .help("Exits with an error status code if anything is listed")
+}
+
+// This function returns the option for filtering a listing with a query.
+fn where_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(LIST_WHERE_OPTION)
+        .value_name("QUERY")
+        .long(LIST_WHERE_OPTION)
+        .help("Only lists the directives which match this query (see `query`)")
+}
+
+// This function returns the option for writing the report of a check to a file.
+fn report_output_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(REPORT_OUTPUT_OPTION)
+        .value_name("PATH")
+        .short("o")
+        .long(REPORT_OUTPUT_OPTION)
+        .multiple(true)
+        .number_of_values(1)
+        .help(
+            "Writes the report to a file rather than STDOUT (can be repeated to write a report in \
+             each `--format`)",
+        )
+}
+
+// This function returns the option for choosing the output format of a check.
+fn report_format_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(FORMAT_OPTION)
+        .value_name("FORMAT")
+        .long(FORMAT_OPTION)
+        .help(
+            "Sets the output format (see the README for details); can be repeated along with \
+             `--output`",
+        )
+        .multiple(true)
+        .number_of_values(1)
+        .possible_values(report::REPORT_FORMATS)
+        .default_value("human")
+}
+
+// This function returns the option for linking to directives.
+fn base_url_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(BASE_URL_OPTION)
+        .value_name("URL")
+        .long(BASE_URL_OPTION)
+        .help(
+            "Links to the location of each directive relative to this URL (e.g., \
+             https://github.com/org/repo/blob/main/)",
+        )
+}
+
+// This function returns the option for limiting the number of warnings a check tolerates.
+fn max_warnings_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(MAX_WARNINGS_OPTION)
+        .value_name("N")
+        .long(MAX_WARNINGS_OPTION)
+        .help("Fails if there are more than this many warnings")
+}
+
 // Parse the command-line arguments.
 #[allow(clippy::too_many_lines)]
 fn settings() -> Settings {
+    // Prepare the help text which isn't a string literal.
+    let about = "\
+                 Tagref helps you maintain cross-references in your code.\n\
+                 \n\
+                 You can annotate your code with tags like [tag?:foo] and reference them like \
+                 [ref?:foo]. You can also reference files like [file:src/main.rs] and directories \
+                 like [dir:src].\n\
+                 \n\
+                 Tagref checks that tags are unique and that references are not dangling.\n\
+                 \n\
+                 For more information, visit https://github.com/stepchowfun/tagref.\
+                 "
+    .replace('?', "");
+    let config_help = format!(
+        "Sets the path of the configuration file [default: {}]",
+        config::DEFAULT_CONFIG_PATH,
+    );
+    let policy_help = format!(
+        "Sets the path of the policy rules file [default: {}]",
+        policy::DEFAULT_POLICY_PATH,
+    );
+    let use_index_help = format!(
+        "Reads the directives from {} (see `update-index`) instead of scanning",
+        directive_index::DEFAULT_DIRECTIVE_INDEX_PATH,
+    );
+
     // Set up the command-line interface.
-    let matches = App::new("Tagref")
+    let app = App::new("Tagref")
         .version(VERSION)
         .version_short("v")
         .author("Stephan Boyer <stephan@stephanboyer.com>")
-        .about(
-            "\
-             Tagref helps you maintain cross-references in your code.\n\
-             \n\
-             You can annotate your code with tags like [tag?:foo] and reference them like \
-             [ref?:foo]. You can also reference files like [file:src/main.rs] and directories like \
-             [dir:src].\n\
-             \n\
-             Tagref checks that tags are unique and that references are not dangling.\n\
-             \n\
-             For more information, visit https://github.com/stepchowfun/tagref.\
-             "
-            .replace('?', "")
-            .trim(),
-        )
+        .about(about.trim())
         .setting(AppSettings::ColoredHelp)
         .setting(AppSettings::NextLineHelp)
         .setting(AppSettings::UnifiedHelpMessage)
         .setting(AppSettings::VersionlessSubcommands)
+        .arg(
+            Arg::with_name(CONFIG_OPTION)
+                .value_name("CONFIG")
+                .short("c")
+                .long(CONFIG_OPTION)
+                .help(&config_help),
+        )
+        .arg(
+            Arg::with_name(POLICY_OPTION)
+                .value_name("POLICY")
+                .long(POLICY_OPTION)
+                .help(&policy_help),
+        )
+        .arg(
+            Arg::with_name(CACHE_OPTION)
+                .value_name("CACHE")
+                .long(CACHE_OPTION)
+                .help(
+                    "Caches parse results in a directory or at an HTTP(S) URL, keyed by file \
+                     contents",
+                ),
+        )
+        .arg(
+            Arg::with_name(COMMENTS_ONLY_OPTION)
+                .long(COMMENTS_ONLY_OPTION)
+                .help(
+                    "Only searches comments for directives, in languages with a tree-sitter \
+                     grammar (requires the `tree-sitter` feature)",
+                ),
+        )
+        .arg(
+            Arg::with_name(INCLUDE_STRINGS_OPTION)
+                .long(INCLUDE_STRINGS_OPTION)
+                .help("Also searches string literals when using --comments-only")
+                .requires(COMMENTS_ONLY_OPTION),
+        )
+        .arg(
+            Arg::with_name(STRUCTURED_VALUES_OPTION)
+                .long(STRUCTURED_VALUES_OPTION)
+                .help(
+                    "Searches the decoded string values in YAML, JSON, and TOML files, so \
+                     directives split across the lines of a multi-line value are found",
+                ),
+        )
+        .arg(
+            Arg::with_name(SCAN_COPIES_OPTION)
+                .long(SCAN_COPIES_OPTION)
+                .help(
+                    "Scans files again when they are reachable via more than one path (e.g., \
+                     overlapping paths or hard links)",
+                ),
+        )
+        .arg(
+            Arg::with_name(DRY_RUN_OPTION)
+                .long(DRY_RUN_OPTION)
+                .help(
+                    "Prints the changes which subcommands that edit files (e.g., `check --fix`) \
+                     would make, without making them",
+                ),
+        )
+        .arg(
+            Arg::with_name(BACKUP_OPTION)
+                .long(BACKUP_OPTION)
+                .help(
+                    "Saves the original contents of each file which is edited to the \
+                     `.tagref-backup` directory first",
+                ),
+        )
+        .arg(
+            Arg::with_name(ALLOW_DIRTY_OPTION)
+                .long(ALLOW_DIRTY_OPTION)
+                .help(
+                    "Allows subcommands to edit files with changes which haven't been committed to \
+                     Git",
+                ),
+        )
+        .arg(
+            Arg::with_name(PROFILE_OPTION)
+                .value_name("PROFILE")
+                .long(PROFILE_OPTION)
+                .help(
+                    "Uses the paths, excluded paths, and severity overrides of a profile in the \
+                     configuration file",
+                ),
+        )
         .arg(
             Arg::with_name(PATH_OPTION)
                 .value_name("PATH")
@@ -95,6 +638,14 @@ fn settings() -> Settings {
                 .multiple(true)
                 .number_of_values(1),
         )
+        .arg(
+            Arg::with_name(EXCLUDE_OPTION)
+                .value_name("GLOB")
+                .long(EXCLUDE_OPTION)
+                .help("Skips the files and directories matching a glob")
+                .multiple(true)
+                .number_of_values(1),
+        )
         .arg(
             Arg::with_name(TAG_SIGIL_OPTION)
                 .value_name("TAG_SIGIL")
@@ -127,30 +678,713 @@ fn settings() -> Settings {
                 .help("Sets the sigil used for directory references")
                 .default_value("dir"), // [tag:dir_sigil_default]
         )
+        .arg(
+            Arg::with_name(SPEC_SIGIL_OPTION)
+                .value_name("SPEC_SIGIL")
+                .short("s")
+                .long(SPEC_SIGIL_OPTION)
+                .help(
+                    "Sets the sigil used for references into YAML or JSON documents via JSON \
+                     Pointers",
+                )
+                .default_value("spec"), // [tag:spec_sigil_default]
+        )
+        .arg(
+            Arg::with_name(URL_SIGIL_OPTION)
+                .value_name("URL_SIGIL")
+                .short("u")
+                .long(URL_SIGIL_OPTION)
+                .help("Sets the sigil used for URL references")
+                .default_value("url"), // [tag:url_sigil_default]
+        )
+        .arg(
+            Arg::with_name(CONTEXT_LENGTH_OPTION)
+                .value_name("LENGTH")
+                .long(CONTEXT_LENGTH_OPTION)
+                .help(
+                    "Sets the maximum number of characters of the line containing each directive \
+                     to include in reports (0 to include none)",
+                )
+                .default_value("120"), // [tag:context_length_default]
+        )
+        .arg(
+            Arg::with_name(MAX_MEMORY_OPTION)
+                .value_name("MB")
+                .long(MAX_MEMORY_OPTION)
+                .help(
+                    "Sets how many megabytes of tag references to keep in memory before spilling \
+                     them to temporary files",
+                ),
+        )
+        .arg(
+            Arg::with_name(PROGRESS_FORMAT_OPTION)
+                .value_name("FORMAT")
+                .long(PROGRESS_FORMAT_OPTION)
+                .help(
+                    "Sets how to report progress while scanning (`json` writes an event with the \
+                     counts so far to STDERR a few times per second)",
+                )
+                .possible_values(progress::PROGRESS_FORMATS)
+                .default_value("none"), // [tag:progress_format_default]
+        )
+        .arg(
+            Arg::with_name(PATH_STYLE_OPTION)
+                .value_name("STYLE")
+                .long(PATH_STYLE_OPTION)
+                .help(
+                    "Prints the paths of files relative to the working directory, as absolute \
+                     paths, or relative to the root of the Git repository, in every output format \
+                     (by default, they're printed as they were scanned)",
+                )
+                .possible_values(path_style::PATH_STYLES),
+        )
+        .arg(
+            Arg::with_name(SCHEMA_OPTION)
+                .long(SCHEMA_OPTION)
+                .help("Prints the JSON Schema of the JSON documents which Tagref writes"),
+        )
         .subcommand(
             SubCommand::with_name(CHECK_SUBCOMMAND)
-                .about("Checks all the tags and references (default)"),
+                .about("Checks all the tags and references (default)")
+                .arg(
+                    Arg::with_name(CHECK_FIX_OPTION)
+                        .long(CHECK_FIX_OPTION)
+                        .help(
+                            "Repairs dangling references with exactly one similar tag, file \
+                             references to files which moved to a unique new location, and \
+                             extraneous whitespace in directives before checking",
+                        ),
+                )
+                .arg(
+                    Arg::with_name(CHECK_FAIL_ON_CONFLICTS_OPTION)
+                        .long(CHECK_FAIL_ON_CONFLICTS_OPTION)
+                        .help(
+                            "Fails if any files contain merge conflicts (otherwise, they are \
+                             reported as warnings)",
+                        ),
+                )
+                .arg(
+                    Arg::with_name(CHECK_USE_INDEX_OPTION)
+                        .long(CHECK_USE_INDEX_OPTION)
+                        .visible_alias("trust-index")
+                        .help(&use_index_help)
+                        .conflicts_with(CHECK_FIX_OPTION),
+                )
+                .arg(
+                    Arg::with_name(CHECK_VERIFY_SAMPLE_OPTION)
+                        .value_name("PERCENT")
+                        .long(CHECK_VERIFY_SAMPLE_OPTION)
+                        .help(
+                            "With `--use-index`, parses a random sample of the indexed files \
+                             (e.g., `5%`) again and fails if the index doesn't match them",
+                        )
+                        .requires(CHECK_USE_INDEX_OPTION)
+                        .validator(|sample| sample.parse::<Sample>().map(|_| ())),
+                )
+                .arg(
+                    Arg::with_name(CHECK_STAGED_OPTION)
+                        .long(CHECK_STAGED_OPTION)
+                        .help(
+                            "Checks the files as they're staged in the Git index rather than as \
+                             they are in the working tree, leaving out untracked files, so a \
+                             pre-commit hook checks what's actually being committed",
+                        )
+                        .conflicts_with_all(&[CHECK_FIX_OPTION, CHECK_USE_INDEX_OPTION]),
+                )
+                .arg(
+                    Arg::with_name(CHECK_WATCH_OPTION)
+                        .long(CHECK_WATCH_OPTION)
+                        .help(
+                            "Keeps running and checks again whenever files change, parsing only \
+                             the files which changed",
+                        )
+                        .conflicts_with_all(&[
+                            CHECK_FIX_OPTION,
+                            CHECK_USE_INDEX_OPTION,
+                            CHECK_STAGED_OPTION,
+                        ]),
+                )
+                .arg(
+                    Arg::with_name(CHECK_EXPLAIN_PASS_OPTION)
+                        .long(CHECK_EXPLAIN_PASS_OPTION)
+                        .help(
+                            "Lists the totals for each kind of directive and the settings in \
+                             effect (paths, excluded paths, sigils, etc.) when the check passes, \
+                             and adds the settings to the `json-summary` format",
+                        ),
+                )
+                .arg(
+                    Arg::with_name(CHECK_SCANNED_FILES_ONLY_OPTION)
+                        .long(CHECK_SCANNED_FILES_ONLY_OPTION)
+                        .help(
+                            "Validates file and directory references against the files which \
+                             were scanned rather than the filesystem, so references to ignored or \
+                             excluded files are reported",
+                        ),
+                )
+                .arg(
+                    Arg::with_name(CHECK_LINT_DESCRIPTIONS_OPTION)
+                        .long(CHECK_LINT_DESCRIPTIONS_OPTION)
+                        .help(
+                            "Warns about words in descriptions which aren't in the bundled word \
+                             list or the `dictionary` in the configuration file",
+                        ),
+                )
+                .arg(
+                    Arg::with_name(CHECK_QUIET_OPTION)
+                        .long(CHECK_QUIET_OPTION)
+                        .help(
+                            "Prints nothing to STDOUT, so only the exit status reports the result \
+                             (the problems and the summary are printed to STDERR)",
+                        ),
+                )
+                .arg(report_format_arg())
+                .arg(report_output_arg())
+                .arg(base_url_arg())
+                .arg(max_warnings_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name(LIST_TAGS_SUBCOMMAND)
+                .about("Lists all the tags")
+                .arg(
+                    Arg::with_name(LIST_TAGS_BY_OWNER_OPTION)
+                        .long(LIST_TAGS_BY_OWNER_OPTION)
+                        .help("Groups the tags by their `owner` metadata")
+                        .conflicts_with(FORMAT_OPTION),
+                )
+                .arg(
+                    Arg::with_name(LIST_TAGS_GREP_OPTION)
+                        .value_name("REGEX")
+                        .long(LIST_TAGS_GREP_OPTION)
+                        .help(
+                            "Only lists the tags with a label or description matching the given \
+                             regular expression",
+                        ),
+                )
+                .arg(where_arg())
+                .arg(fail_if_any_arg())
+                .arg(format_arg())
+                .arg(template_arg())
+                .arg(exec_template_arg())
+                .arg(run_arg()),
         )
-        .subcommand(SubCommand::with_name(LIST_TAGS_SUBCOMMAND).about("Lists all the tags"))
         .subcommand(
-            SubCommand::with_name(LIST_REFS_SUBCOMMAND).about("Lists all the tag references"),
+            SubCommand::with_name(LIST_REFS_SUBCOMMAND)
+                .about("Lists all the tag references")
+                .arg(
+                    Arg::with_name(LIST_REFS_LABEL_OPTION)
+                        .value_name("LABEL")
+                        .long(LIST_REFS_LABEL_OPTION)
+                        .help("Only lists the references to the tag with the given label"),
+                )
+                .arg(where_arg())
+                .arg(fail_if_any_arg())
+                .arg(format_arg())
+                .arg(template_arg())
+                .arg(exec_template_arg())
+                .arg(run_arg()),
         )
         .subcommand(
-            SubCommand::with_name(LIST_FILES_SUBCOMMAND).about("Lists all the file references"),
+            SubCommand::with_name(LIST_FILES_SUBCOMMAND)
+                .about("Lists all the file references")
+                .arg(where_arg())
+                .arg(fail_if_any_arg())
+                .arg(format_arg())
+                .arg(template_arg())
+                .arg(exec_template_arg())
+                .arg(run_arg()),
         )
         .subcommand(
-            SubCommand::with_name(LIST_DIRS_SUBCOMMAND).about("Lists all the directory references"),
+            SubCommand::with_name(LIST_DIRS_SUBCOMMAND)
+                .about("Lists all the directory references")
+                .arg(where_arg())
+                .arg(fail_if_any_arg())
+                .arg(format_arg())
+                .arg(template_arg())
+                .arg(exec_template_arg())
+                .arg(run_arg()),
         )
         .subcommand(
             SubCommand::with_name(LIST_UNUSED_SUBCOMMAND)
                 .about("Lists the unreferenced tags")
                 .arg(
-                    Arg::with_name(LIST_UNUSED_ERROR_OPTION)
-                        .long(LIST_UNUSED_ERROR_OPTION)
-                        .help("Exits with an error status code if any tags are unreferenced"),
+                    Arg::with_name(LIST_UNUSED_IGNORE_OPTION)
+                        .value_name("REGEX")
+                        .long(LIST_UNUSED_IGNORE_OPTION)
+                        .help(
+                            "Skips tags with labels matching this regular expression (e.g., tags \
+                             which are only referenced from outside the codebase)",
+                        )
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name(LIST_UNUSED_ONLY_OPTION)
+                        .value_name("REGEX")
+                        .long(LIST_UNUSED_ONLY_OPTION)
+                        .help("Only considers tags with labels matching this regular expression")
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(where_arg())
+                .arg(fail_if_any_arg())
+                .arg(format_arg())
+                .arg(template_arg())
+                .arg(exec_template_arg())
+                .arg(run_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name(LIST_DUPES_SUBCOMMAND)
+                .about("Lists the tags which share a label with another tag")
+                .arg(where_arg())
+                .arg(fail_if_any_arg())
+                .arg(format_arg())
+                .arg(template_arg())
+                .arg(exec_template_arg())
+                .arg(run_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name(QUERY_SUBCOMMAND)
+                .about(
+                    "Lists the tags and references which match a query, such as \
+                     `type == tag && refs == 0 && path ~ \"src/**\" && label ~ \"^temp_\"`",
+                )
+                .arg(
+                    Arg::with_name(QUERY_ARGUMENT)
+                        .help(
+                            "The query, which compares the fields `type`, `label`, `path`, \
+                             `description`, `metadata.<key>`, `line`, and `refs` using `==`, \
+                             `!=`, `<`, `<=`, `>`, `>=`, `~` (matches a regular expression, or a \
+                             glob for paths), and `!~`, combined with `&&`, `||`, `!`, and \
+                             parentheses",
+                        )
+                        .required(true), // [tag:query_required]
+                )
+                .arg(format_arg())
+                .arg(template_arg())
+                .arg(exec_template_arg())
+                .arg(run_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name(REACHABLE_SUBCOMMAND)
+                .about(
+                    "Follows references between tags transitively, treating the references which \
+                     belong to a tag as edges to the tags they point to",
+                )
+                .arg(
+                    Arg::with_name(REACHABLE_FROM_OPTION)
+                        .value_name("LABEL")
+                        .long(REACHABLE_FROM_OPTION)
+                        .help("Lists the tags reachable from the tag with this label"),
+                )
+                .arg(
+                    Arg::with_name(REACHABLE_PATH_BETWEEN_OPTION)
+                        .value_names(&["FROM", "TO"])
+                        .long(REACHABLE_PATH_BETWEEN_OPTION)
+                        .help("Lists the tags along a shortest path between two tags"),
+                )
+                .group(
+                    ArgGroup::with_name("target")
+                        .args(&[REACHABLE_FROM_OPTION, REACHABLE_PATH_BETWEEN_OPTION])
+                        .required(true), // [tag:reachable_target_required]
+                )
+                .arg(
+                    Arg::with_name(REACHABLE_EDGES_OPTION)
+                        .value_name("EDGES")
+                        .long(REACHABLE_EDGES_OPTION)
+                        .help(
+                            "Decides which references belong to a tag: those in the same file, \
+                             those in the same section (up to the next tag in the file), or \
+                             those on the same line",
+                        )
+                        .possible_values(EDGES)
+                        .default_value("file"),
+                )
+                .arg(format_arg())
+                .arg(template_arg())
+                .arg(exec_template_arg())
+                .arg(run_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name(STATS_SUBCOMMAND)
+                .about("Prints statistics about the tags and references")
+                .arg(
+                    Arg::with_name(STATS_HOTSPOTS_OPTION)
+                        .long(STATS_HOTSPOTS_OPTION)
+                        .help("Lists the most heavily referenced tags and files"),
+                )
+                .arg(
+                    Arg::with_name(STATS_OWNERS_OPTION)
+                        .long(STATS_OWNERS_OPTION)
+                        .help(
+                            "Counts the tags, unused tags, and dangling references of each owner \
+                             (from `owner=` metadata, CODEOWNERS, or `git blame`)",
+                        ),
+                )
+                .arg(
+                    Arg::with_name(STATS_THRESHOLD_OPTION)
+                        .value_name("THRESHOLD")
+                        .long(STATS_THRESHOLD_OPTION)
+                        .help("Lists tags with more than this many references as hotspots")
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::with_name(STATS_TOP_OPTION)
+                        .value_name("TOP")
+                        .long(STATS_TOP_OPTION)
+                        .help("Sets the number of files to list as hotspots")
+                        .default_value("10"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(CTAGS_SUBCOMMAND)
+                .about("Writes an index of the tags for editors which support `ctags` or `etags`")
+                .arg(
+                    Arg::with_name(CTAGS_OUTPUT_OPTION)
+                        .value_name("PATH")
+                        .short("o")
+                        .long(CTAGS_OUTPUT_OPTION)
+                        .help("Sets the path of the index [default: tags, or TAGS with --etags]"),
+                )
+                .arg(
+                    Arg::with_name(CTAGS_ETAGS_OPTION)
+                        .long(CTAGS_ETAGS_OPTION)
+                        .help("Writes the index in the `etags` format (e.g., for Emacs)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(INDEX_SUBCOMMAND)
+                .about(
+                    "Writes a JSON index of the tags, their references, and their descriptions \
+                     for editor extensions",
+                )
+                .arg(
+                    Arg::with_name(INDEX_OUTPUT_OPTION)
+                        .value_name("PATH")
+                        .short("o")
+                        .long(INDEX_OUTPUT_OPTION)
+                        .help("Sets the path of the index")
+                        .default_value(index::DEFAULT_INDEX_PATH), // [tag:index_output_default]
+                )
+                .arg(base_url_arg()),
+        )
+        .subcommand(SubCommand::with_name(BADGE_SUBCOMMAND).about(
+            "Prints a shields.io endpoint badge with the number of tags and dangling references",
+        ))
+        .subcommand(
+            SubCommand::with_name(EXPORT_SUBCOMMAND)
+                .about(
+                    "Prints the directives and the results of the checks which depend on the \
+                     filesystem as JSON, so the checks which depend on all the directives can be \
+                     run later",
+                )
+                .arg(
+                    Arg::with_name(EXPORT_SHARD_OPTION)
+                        .value_name("K/N")
+                        .long(EXPORT_SHARD_OPTION)
+                        .help(
+                            "Only scans the K-th of N disjoint slices of the files, so the work \
+                             can be split across N jobs",
+                        ),
+                )
+                .arg(
+                    Arg::with_name(EXPORT_OUTPUT_OPTION)
+                        .value_name("PATH")
+                        .short("o")
+                        .long(EXPORT_OUTPUT_OPTION)
+                        .help("Writes the results to a file rather than STDOUT"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(MERGE_SUBCOMMAND)
+                .about(
+                    "Combines the results of `export` (e.g., from several shards) and finishes \
+                     checking them",
+                )
+                .arg(
+                    Arg::with_name(MERGE_ARGUMENT)
+                        .help("Sets the paths of the files written by `export`")
+                        .multiple(true)
+                        .required(true), // [tag:merge_exports_required]
+                )
+                .arg(report_format_arg())
+                .arg(report_output_arg())
+                .arg(base_url_arg())
+                .arg(max_warnings_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name(REPORT_DIFF_SUBCOMMAND)
+                .about(
+                    "Compares the problems in two check results (written with `--format json`) or \
+                     exports, and fails if there are any new errors",
+                )
+                .arg(
+                    Arg::with_name(REPORT_DIFF_OLD_ARGUMENT)
+                        .help("Sets the path of the earlier result")
+                        .required(true), // [tag:report_diff_old_required]
+                )
+                .arg(
+                    Arg::with_name(REPORT_DIFF_NEW_ARGUMENT)
+                        .help("Sets the path of the later result")
+                        .required(true), // [tag:report_diff_new_required]
+                )
+                .arg(
+                    Arg::with_name(FORMAT_OPTION)
+                        .value_name("FORMAT")
+                        .long(FORMAT_OPTION)
+                        .help("Sets the output format")
+                        .possible_values(report_diff::DIFF_FORMATS)
+                        .default_value("human"), // [tag:report_diff_format_default]
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(LINKS_SUBCOMMAND)
+                .about("Lists all the directives with links to them")
+                .arg(base_url_arg().required(true)) // [tag:links_base_url_required]
+                .arg(format_arg())
+                .arg(template_arg())
+                .arg(exec_template_arg())
+                .arg(run_arg()),
+        )
+        .subcommand(SubCommand::with_name(UPDATE_INDEX_SUBCOMMAND).about(
+            "Records every directive in a directive index so `check --use-index` can skip \
+             scanning (only the files which changed since the last update are parsed)",
+        ))
+        .subcommand(
+            SubCommand::with_name(COMPLETE_LABEL_SUBCOMMAND)
+                .about(
+                    "Lists the labels of the tags which start with a prefix, for shell completion \
+                     and editor plugins (uses the directive index instead of scanning if it's \
+                     fresh)",
+                )
+                .arg(
+                    Arg::with_name(COMPLETE_LABEL_ARGUMENT)
+                        .help("Sets the prefix of the labels to list")
+                        .default_value(""), // [tag:complete_label_prefix_default]
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(CHECK_DELETIONS_SUBCOMMAND)
+                .about(
+                    "Checks only for references to files which were deleted or renamed since a \
+                     Git revision, and to the tags which were in them",
+                )
+                .arg(
+                    Arg::with_name(CHECK_DELETIONS_SINCE_OPTION)
+                        .value_name("REVISION")
+                        .long(CHECK_DELETIONS_SINCE_OPTION)
+                        .help("Sets the revision (e.g., the base branch of a pull request)")
+                        .required(true), // [tag:check_deletions_since_required]
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(CHANGELOG_SUBCOMMAND)
+                .about(
+                    "Lists the tags which were added, removed, or renamed since a Git revision, as \
+                     Markdown for release notes",
+                )
+                .arg(
+                    Arg::with_name(CHANGELOG_SINCE_OPTION)
+                        .value_name("REVISION")
+                        .long(CHANGELOG_SINCE_OPTION)
+                        .help("Sets the revision (e.g., the Git tag of the last release)")
+                        .required(true), // [tag:changelog_since_required]
+                )
+                .arg(
+                    Arg::with_name(CHANGELOG_SEPARATOR_OPTION)
+                        .value_name("SEPARATOR")
+                        .long(CHANGELOG_SEPARATOR_OPTION)
+                        .help(
+                            "Sets the separator between the namespace of a label and the rest of \
+                             it, for grouping the tags",
+                        )
+                        .default_value("."), // [tag:changelog_separator_default]
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(HISTORY_SUBCOMMAND)
+                .about(
+                    "Counts the tags, references, and dangling references at regular intervals in \
+                     the Git history, as CSV or JSON for charting trends",
+                )
+                .arg(
+                    Arg::with_name(HISTORY_SINCE_OPTION)
+                        .value_name("DATE")
+                        .long(HISTORY_SINCE_OPTION)
+                        .help("Sets the date of the first snapshot (e.g., 2023-01-01)")
+                        .required(true) // [tag:history_since_required]
+                        .validator(|date| date.parse::<Date>().map(|_| ())),
+                )
+                .arg(
+                    Arg::with_name(HISTORY_INTERVAL_OPTION)
+                        .value_name("INTERVAL")
+                        .long(HISTORY_INTERVAL_OPTION)
+                        .help("Sets the time between snapshots")
+                        .possible_values(history::INTERVALS)
+                        .default_value("month"), // [tag:history_interval_default]
+                )
+                .arg(
+                    Arg::with_name(FORMAT_OPTION)
+                        .value_name("FORMAT")
+                        .long(FORMAT_OPTION)
+                        .help("Sets the output format")
+                        .possible_values(history::HISTORY_FORMATS)
+                        .default_value("csv"), // [tag:history_format_default]
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(DOCS_SUBCOMMAND)
+                .about(
+                    "Renders the tags as a Markdown or HTML glossary, with their descriptions and \
+                     links to where they're defined and referenced",
+                )
+                .arg(
+                    Arg::with_name(FORMAT_OPTION)
+                        .value_name("FORMAT")
+                        .long(FORMAT_OPTION)
+                        .help("Sets the output format")
+                        .possible_values(docs::DOCS_FORMATS)
+                        .default_value("markdown"), // [tag:docs_format_default]
+                )
+                .arg(
+                    Arg::with_name(DOCS_OUTPUT_OPTION)
+                        .value_name("PATH")
+                        .short("o")
+                        .long(DOCS_OUTPUT_OPTION)
+                        .help("Writes the glossary to a file rather than STDOUT"),
+                )
+                .arg(base_url_arg())
+                .arg(
+                    Arg::with_name(DOCS_SEPARATOR_OPTION)
+                        .value_name("SEPARATOR")
+                        .long(DOCS_SEPARATOR_OPTION)
+                        .help(
+                            "Sets the separator between the namespace of a label and the rest of \
+                             it, for grouping the tags",
+                        )
+                        .default_value("."), // [tag:docs_separator_default]
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(IMPACT_SUBCOMMAND)
+                .about(
+                    "Lists the tags defined in a file, directory, or range of lines, along with \
+                     all the references to them",
+                )
+                .arg(
+                    Arg::with_name(IMPACT_ARGUMENT)
+                        .help("Sets the region, e.g., `src/cache.rs:10-20`")
+                        .required(true) // [tag:impact_region_required]
+                        .validator(|region| region.parse::<Region>().map(|_| ())),
+                )
+                .arg(format_arg())
+                .arg(template_arg())
+                .arg(exec_template_arg())
+                .arg(run_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name(FILE_REPORT_SUBCOMMAND)
+                .about(
+                    "Summarizes a file: the tags it defines and their references, the references \
+                     it makes and the tags they resolve to, and its file and directory references",
+                )
+                .arg(
+                    Arg::with_name(FILE_REPORT_ARGUMENT)
+                        .help("Sets the path of the file")
+                        .required(true), // [tag:file_report_path_required]
+                ),
+        )
+        .subcommand(SubCommand::with_name(LSP_SUBCOMMAND).about(
+            "Runs a language server on STDIN and STDOUT, so editors can jump between tags and \
+             references and show problems while you type",
+        ))
+        .subcommand(
+            SubCommand::with_name(RENAME_SUBCOMMAND)
+                .about(
+                    "Renames a tag and rewrites its references, unless there is already a tag \
+                     with the new label",
+                )
+                .arg(
+                    Arg::with_name(RENAME_OLD_ARGUMENT)
+                        .help("Sets the label of the tag to rename")
+                        .required(true), // [tag:rename_old_required]
+                )
+                .arg(
+                    Arg::with_name(RENAME_NEW_ARGUMENT)
+                        .help("Sets the new label")
+                        .required(true), // [tag:rename_new_required]
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(RENAME_BATCH_SUBCOMMAND)
+                .about(
+                    "Renames many tags at once (along with their references), given a CSV file \
+                     with an old label and a new label on each line",
+                )
+                .arg(
+                    Arg::with_name(RENAME_BATCH_ARGUMENT)
+                        .help("Sets the path of the mapping file")
+                        .required(true), // [tag:rename_batch_mapping_required]
                 ),
         )
-        .get_matches();
+        .subcommand(
+            SubCommand::with_name(DOCTOR_SUBCOMMAND)
+                .about("Diagnoses problems with which files are scanned")
+                .arg(
+                    Arg::with_name(DOCTOR_WHY_OPTION)
+                        .value_name("PATH")
+                        .long(DOCTOR_WHY_OPTION)
+                        .help(
+                            "Explains whether a file is scanned, and if not, which rule (e.g., a \
+                             line in an ignore file) skips it",
+                        )
+                        .required(true), // [tag:doctor_why_required]
+                ),
+        );
+
+    // Parse the command-line arguments, and parse them again with any defaults from the
+    // configuration file.
+    let args = args_os().collect::<Vec<_>>();
+    let matches = app.clone().get_matches_from(&args);
+    let matches = match config_arguments(&args, &matches) {
+        Some(args) => app.get_matches_from(args),
+        None => matches,
+    };
+
+    // Determine which configuration file to load, if any.
+    let config_path = matches
+        .value_of(CONFIG_OPTION)
+        .map(|path| Path::new(path).to_owned());
+
+    // Determine which policy file to load, if any.
+    let policy_path = matches
+        .value_of(POLICY_OPTION)
+        .map(|path| Path::new(path).to_owned());
+
+    // Determine where to cache parse results, if anywhere.
+    let cache = matches.value_of(CACHE_OPTION).map(Cache::new);
+
+    // Determine which parts of files to search for directives.
+    let extraction = if !matches.is_present(COMMENTS_ONLY_OPTION) {
+        Extraction::Everything
+    } else if matches.is_present(INCLUDE_STRINGS_OPTION) {
+        Extraction::CommentsAndStrings
+    } else {
+        Extraction::Comments
+    };
+
+    // Determine whether to decode the string values in structured files.
+    let structured_values = matches.is_present(STRUCTURED_VALUES_OPTION);
+
+    // Determine whether to scan files more than once if they are reachable via several paths.
+    let scan_copies = matches.is_present(SCAN_COPIES_OPTION);
+
+    // Determine how files may be edited.
+    let edit_options = edit::Options {
+        dry_run: matches.is_present(DRY_RUN_OPTION),
+        backup: matches.is_present(BACKUP_OPTION),
+        allow_dirty: matches.is_present(ALLOW_DIRTY_OPTION),
+    };
 
     // Determine which paths to scan. The `unwrap` is safe due to [ref:path_default].
     let paths = matches
@@ -158,6 +1392,17 @@ fn settings() -> Settings {
         .unwrap()
         .map(|path| Path::new(path).to_owned())
         .collect::<Vec<_>>();
+    let default_paths = matches.occurrences_of(PATH_OPTION) == 0;
+
+    // Determine which paths to skip.
+    let excludes = if matches.is_present(EXCLUDE_OPTION) {
+        values_t!(matches, EXCLUDE_OPTION, PathPattern).unwrap_or_else(|error| error.exit())
+    } else {
+        vec![]
+    };
+
+    // Determine which profile to use, if any.
+    let profile = matches.value_of(PROFILE_OPTION).map(ToOwned::to_owned);
 
     // Determine the tag sigil. The `unwrap` is safe due to [ref:tag_sigil_default].
     let tag_sigil = matches.value_of(TAG_SIGIL_OPTION).unwrap().to_owned();
@@ -171,183 +1416,2996 @@ fn settings() -> Settings {
     // Determine the directory sigil. The `unwrap` is safe due to [ref:dir_sigil_default].
     let dir_sigil = matches.value_of(DIR_SIGIL_OPTION).unwrap().to_owned();
 
-    // Determine the subcommand.
-    let subcommand = match matches.subcommand_name() {
-        Some(CHECK_SUBCOMMAND) | None => Subcommand::Check,
-        Some(LIST_TAGS_SUBCOMMAND) => Subcommand::ListTags,
-        Some(LIST_REFS_SUBCOMMAND) => Subcommand::ListRefs,
-        Some(LIST_FILES_SUBCOMMAND) => Subcommand::ListFiles,
-        Some(LIST_DIRS_SUBCOMMAND) => Subcommand::ListDirs,
-        Some(LIST_UNUSED_SUBCOMMAND) => Subcommand::ListUnused(
-            matches
-                .subcommand
-                .unwrap() // Safe because we're _in_ a subcommand
-                .matches
-                .is_present(LIST_UNUSED_ERROR_OPTION),
-        ),
-        Some(&_) => panic!("Unimplemented subcommand."),
-    };
+    // Determine the spec sigil. The `unwrap` is safe due to [ref:spec_sigil_default].
+    let spec_sigil = matches.value_of(SPEC_SIGIL_OPTION).unwrap().to_owned();
 
-    // Return the command-line options.
-    Settings {
-        paths,
-        tag_sigil,
-        ref_sigil,
-        file_sigil,
-        dir_sigil,
-        subcommand,
-    }
-}
+    // Determine the URL sigil. The `unwrap` is safe due to [ref:url_sigil_default].
+    let url_sigil = matches.value_of(URL_SIGIL_OPTION).unwrap().to_owned();
 
-// Program entrypoint
-#[allow(clippy::too_many_lines)]
-fn entry() -> Result<(), String> {
-    // Determine whether to print colored output.
-    colored::control::set_override(atty::is(Stream::Stdout));
+    // Determine how much of the line containing each directive to keep. The default is given by
+    // [ref:context_length_default].
+    let context_length =
+        value_t!(matches, CONTEXT_LENGTH_OPTION, usize).unwrap_or_else(|error| error.exit());
 
-    // Parse the command-line options.
-    let settings = settings();
+    // Determine how much memory the tag references may take up, if it's limited.
+    let max_memory = matches.is_present(MAX_MEMORY_OPTION).then(|| {
+        value_t!(matches, MAX_MEMORY_OPTION, usize)
+            .unwrap_or_else(|error| error.exit())
+            .saturating_mul(1 << 20)
+    });
 
-    // Compile the regular expressions in advance.
-    let tag_regex = compile_directive_regex(&settings.tag_sigil);
-    let ref_regex = compile_directive_regex(&settings.ref_sigil);
-    let file_regex = compile_directive_regex(&settings.file_sigil);
-    let dir_regex = compile_directive_regex(&settings.dir_sigil);
+    // Determine how to report progress. The default is given by [ref:progress_format_default].
+    let progress_format = value_t!(matches, PROGRESS_FORMAT_OPTION, ProgressFormat)
+        .unwrap_or_else(|error| error.exit());
 
-    // Parse all the tags and references.
-    let tags = Arc::new(Mutex::new(HashMap::new()));
-    let refs = Arc::new(Mutex::new(Vec::new()));
-    let files = Arc::new(Mutex::new(Vec::new()));
-    let dirs = Arc::new(Mutex::new(Vec::new()));
-    let tags_clone = tags.clone();
-    let refs_clone = refs.clone();
-    let files_clone = files.clone();
-    let dirs_clone = dirs.clone();
-    let tag_regex_clone = tag_regex.clone();
-    let ref_regex_clone = ref_regex.clone();
-    let file_regex_clone = file_regex.clone();
-    let dir_regex_clone = dir_regex.clone();
-    let files_scanned = walk::walk(&settings.paths, move |file_path, file| {
-        let directives = directive::parse(
-            &tag_regex_clone,
-            &ref_regex_clone,
-            &file_regex_clone,
-            &dir_regex_clone,
-            file_path,
-            BufReader::new(file),
-        );
-        for tag in directives.tags {
-            tags_clone
-                .lock()
-                .unwrap() // Safe assuming no poisoning
-                .entry(tag.label.clone())
-                .or_insert_with(Vec::new)
-                .push(tag.clone());
-        }
-        refs_clone.lock().unwrap().extend(directives.refs); // Safe assuming no poisoning
-        files_clone.lock().unwrap().extend(directives.files); // Safe assuming no poisoning
-        dirs_clone.lock().unwrap().extend(directives.dirs); // Safe assuming no poisoning
+    // Determine how to print paths, if they should be printed differently than they were scanned.
+    let path_style = matches.is_present(PATH_STYLE_OPTION).then(|| {
+        value_t!(matches, PATH_STYLE_OPTION, PathStyle).unwrap_or_else(|error| error.exit())
     });
 
-    // Decide what to do based on the subcommand.
+    // Determine whether to just print the JSON Schema.
+    let schema = matches.is_present(SCHEMA_OPTION);
+
+    // Determine the subcommand.
+    let (subcommand_name, subcommand_matches) = matches.subcommand();
+    let format = || {
+        // The `unwrap` is safe because every subcommand with this option has a default value.
+        subcommand_matches.map_or(Format::Human, |matches| {
+            // The templates are given separately [ref:template_format_name].
+            match matches.value_of(FORMAT_OPTION) {
+                Some("template") => Format::Template(
+                    value_t!(matches, TEMPLATE_OPTION, Template)
+                        .unwrap_or_else(|error| error.exit()),
+                ),
+                Some("exec") => Format::Exec {
+                    template: value_t!(matches, EXEC_TEMPLATE_OPTION, ExecTemplate)
+                        .unwrap_or_else(|error| error.exit()),
+                    run: matches.is_present(RUN_OPTION),
+                },
+                _ => value_t!(matches, FORMAT_OPTION, Format).unwrap_or_else(|error| error.exit()),
+            }
+        })
+    };
+    let reports = || {
+        let Some(matches) = subcommand_matches else {
+            return vec![Report {
+                format: ReportFormat::Human,
+                output: None,
+            }];
+        };
+
+        // The formats and outputs are paired up in order. The last format can go without an
+        // output, in which case the report is printed to STDOUT. The `values_t!` is safe because
+        // every subcommand with these options has a default format.
+        let formats =
+            values_t!(matches, FORMAT_OPTION, ReportFormat).unwrap_or_else(|error| error.exit());
+        let outputs = matches
+            .values_of(REPORT_OUTPUT_OPTION)
+            .map_or_else(Vec::new, |paths| paths.map(PathBuf::from).collect());
+        if outputs.len() > formats.len() || formats.len() > outputs.len() + 1 {
+            clap::Error::with_description(
+                &format!(
+                    "Each `--{FORMAT_OPTION}` except the last needs its own \
+                     `--{REPORT_OUTPUT_OPTION}` (got {} and {}).",
+                    count::count(formats.len(), "format"),
+                    count::count(outputs.len(), "output"),
+                ),
+                ErrorKind::ArgumentConflict,
+            )
+            .exit();
+        }
+        let mut outputs = outputs.into_iter();
+        formats
+            .into_iter()
+            .map(|format| Report {
+                format,
+                output: outputs.next(),
+            })
+            .collect()
+    };
+    let is_present = |name| subcommand_matches.is_some_and(|matches| matches.is_present(name));
+    let regexes = |name| {
+        subcommand_matches
+            .filter(|matches| matches.is_present(name))
+            .map_or_else(Vec::new, |matches| {
+                values_t!(matches, name, Regex).unwrap_or_else(|error| error.exit())
+            })
+    };
+    let listing = || Listing {
+        fail_if_any: is_present(LIST_ERROR_OPTION),
+        filter: subcommand_matches
+            .and_then(|matches| matches.value_of(LIST_WHERE_OPTION))
+            .map(ToOwned::to_owned),
+        format: format(),
+    };
+    let base_url = || {
+        subcommand_matches
+            .and_then(|matches| matches.value_of(BASE_URL_OPTION))
+            .map(ToOwned::to_owned)
+    };
+    let max_warnings = || {
+        subcommand_matches
+            .filter(|matches| matches.is_present(MAX_WARNINGS_OPTION))
+            .map(|matches| {
+                value_t!(matches, MAX_WARNINGS_OPTION, usize).unwrap_or_else(|error| error.exit())
+            })
+    };
+    let subcommand = match subcommand_name {
+        CHECK_SUBCOMMAND | "" => Subcommand::Check {
+            fix: is_present(CHECK_FIX_OPTION),
+            fail_on_conflicts: is_present(CHECK_FAIL_ON_CONFLICTS_OPTION),
+            use_index: is_present(CHECK_USE_INDEX_OPTION),
+            verify_sample: subcommand_matches
+                .and_then(|matches| matches.value_of(CHECK_VERIFY_SAMPLE_OPTION))
+                .map(|sample| {
+                    // The `unwrap` is safe due to the validator.
+                    sample.parse::<Sample>().unwrap()
+                }),
+            staged: is_present(CHECK_STAGED_OPTION),
+            watch: is_present(CHECK_WATCH_OPTION),
+            explain_pass: is_present(CHECK_EXPLAIN_PASS_OPTION),
+            scanned_files_only: is_present(CHECK_SCANNED_FILES_ONLY_OPTION),
+            lint_descriptions: is_present(CHECK_LINT_DESCRIPTIONS_OPTION),
+            quiet: is_present(CHECK_QUIET_OPTION),
+            reports: reports(),
+            base_url: base_url(),
+            max_warnings: max_warnings(),
+        },
+        LIST_TAGS_SUBCOMMAND => Subcommand::ListTags {
+            by_owner: is_present(LIST_TAGS_BY_OWNER_OPTION),
+            grep: subcommand_matches
+                .and_then(|matches| matches.value_of(LIST_TAGS_GREP_OPTION))
+                .map(ToOwned::to_owned),
+            listing: listing(),
+        },
+        LIST_REFS_SUBCOMMAND => Subcommand::ListRefs {
+            label: subcommand_matches
+                .and_then(|matches| matches.value_of(LIST_REFS_LABEL_OPTION))
+                .map(normalize_label),
+            listing: listing(),
+        },
+        LIST_FILES_SUBCOMMAND => Subcommand::ListFiles(listing()),
+        LIST_DIRS_SUBCOMMAND => Subcommand::ListDirs(listing()),
+        LIST_UNUSED_SUBCOMMAND => Subcommand::ListUnused {
+            ignore: regexes(LIST_UNUSED_IGNORE_OPTION),
+            only: regexes(LIST_UNUSED_ONLY_OPTION),
+            listing: listing(),
+        },
+        LIST_DUPES_SUBCOMMAND => Subcommand::ListDupes(listing()),
+        QUERY_SUBCOMMAND => Subcommand::Query {
+            // The `unwrap`s are safe due to [ref:query_required].
+            query: subcommand_matches
+                .unwrap()
+                .value_of(QUERY_ARGUMENT)
+                .unwrap()
+                .to_owned(),
+            format: format(),
+        },
+        REACHABLE_SUBCOMMAND => {
+            // The `unwrap` is safe because we're _in_ a subcommand.
+            let matches = subcommand_matches.unwrap();
+            Subcommand::Reachable {
+                // The `unwrap`s are safe due to [ref:reachable_target_required].
+                target: if let Some(mut labels) = matches.values_of(REACHABLE_PATH_BETWEEN_OPTION) {
+                    Reachability::PathBetween(
+                        labels.next().unwrap().to_owned(),
+                        labels.next().unwrap().to_owned(),
+                    )
+                } else {
+                    Reachability::From(matches.value_of(REACHABLE_FROM_OPTION).unwrap().to_owned())
+                },
+                edges: value_t!(matches, REACHABLE_EDGES_OPTION, Edges)
+                    .unwrap_or_else(|error| error.exit()),
+                format: format(),
+            }
+        }
+        STATS_SUBCOMMAND => {
+            // The `unwrap` is safe because we're _in_ a subcommand.
+            let matches = subcommand_matches.unwrap();
+            Subcommand::Stats {
+                hotspots: matches.is_present(STATS_HOTSPOTS_OPTION),
+                owners: matches.is_present(STATS_OWNERS_OPTION),
+                threshold: value_t!(matches, STATS_THRESHOLD_OPTION, usize)
+                    .unwrap_or_else(|error| error.exit()),
+                top: value_t!(matches, STATS_TOP_OPTION, usize)
+                    .unwrap_or_else(|error| error.exit()),
+            }
+        }
+        CTAGS_SUBCOMMAND => Subcommand::Ctags {
+            output: subcommand_matches
+                .and_then(|matches| matches.value_of(CTAGS_OUTPUT_OPTION))
+                .map(|path| Path::new(path).to_owned()),
+            etags: is_present(CTAGS_ETAGS_OPTION),
+        },
+        BADGE_SUBCOMMAND => Subcommand::Badge,
+        MERGE_SUBCOMMAND => Subcommand::Merge {
+            // The `unwrap`s are safe due to [ref:merge_exports_required].
+            exports: subcommand_matches
+                .unwrap()
+                .values_of(MERGE_ARGUMENT)
+                .unwrap()
+                .map(|path| Path::new(path).to_owned())
+                .collect(),
+            reports: reports(),
+            base_url: base_url(),
+            max_warnings: max_warnings(),
+        },
+        UPDATE_INDEX_SUBCOMMAND => Subcommand::UpdateIndex,
+        CHECK_DELETIONS_SUBCOMMAND => Subcommand::CheckDeletions {
+            // The `unwrap`s are safe due to [ref:check_deletions_since_required].
+            changed_since: subcommand_matches
+                .unwrap()
+                .value_of(CHECK_DELETIONS_SINCE_OPTION)
+                .unwrap()
+                .to_owned(),
+        },
+        CHANGELOG_SUBCOMMAND => {
+            // The `unwrap`s are safe due to [ref:changelog_since_required] and
+            // [ref:changelog_separator_default].
+            let matches = subcommand_matches.unwrap();
+            Subcommand::Changelog {
+                since: matches.value_of(CHANGELOG_SINCE_OPTION).unwrap().to_owned(),
+                separator: matches
+                    .value_of(CHANGELOG_SEPARATOR_OPTION)
+                    .unwrap()
+                    .to_owned(),
+            }
+        }
+        REPORT_DIFF_SUBCOMMAND => {
+            // The `unwrap`s are safe due to [ref:report_diff_old_required],
+            // [ref:report_diff_new_required], and [ref:report_diff_format_default].
+            let matches = subcommand_matches.unwrap();
+            Subcommand::ReportDiff {
+                old: Path::new(matches.value_of(REPORT_DIFF_OLD_ARGUMENT).unwrap()).to_owned(),
+                new: Path::new(matches.value_of(REPORT_DIFF_NEW_ARGUMENT).unwrap()).to_owned(),
+                format: matches.value_of(FORMAT_OPTION).unwrap().parse().unwrap(),
+            }
+        }
+        HISTORY_SUBCOMMAND => {
+            // The `unwrap`s are safe due to [ref:history_since_required] (and the validator),
+            // [ref:history_interval_default], and [ref:history_format_default].
+            let matches = subcommand_matches.unwrap();
+            Subcommand::History {
+                since: matches
+                    .value_of(HISTORY_SINCE_OPTION)
+                    .unwrap()
+                    .parse()
+                    .unwrap(),
+                interval: matches
+                    .value_of(HISTORY_INTERVAL_OPTION)
+                    .unwrap()
+                    .parse()
+                    .unwrap(),
+                format: matches.value_of(FORMAT_OPTION).unwrap().parse().unwrap(),
+            }
+        }
+        DOCS_SUBCOMMAND => {
+            // The `unwrap`s are safe due to [ref:docs_format_default] and
+            // [ref:docs_separator_default].
+            let matches = subcommand_matches.unwrap();
+            Subcommand::Docs {
+                format: value_t!(matches, FORMAT_OPTION, DocsFormat)
+                    .unwrap_or_else(|error| error.exit()),
+                output: matches
+                    .value_of(DOCS_OUTPUT_OPTION)
+                    .map(|path| Path::new(path).to_owned()),
+                base_url: base_url(),
+                separator: matches.value_of(DOCS_SEPARATOR_OPTION).unwrap().to_owned(),
+            }
+        }
+        IMPACT_SUBCOMMAND => Subcommand::Impact {
+            // The `unwrap`s are safe due to [ref:impact_region_required] and the validator.
+            region: subcommand_matches
+                .unwrap()
+                .value_of(IMPACT_ARGUMENT)
+                .unwrap()
+                .parse()
+                .unwrap(),
+            format: format(),
+        },
+        FILE_REPORT_SUBCOMMAND => Subcommand::FileReport {
+            // The `unwrap`s are safe due to [ref:file_report_path_required].
+            path: PathBuf::from(
+                subcommand_matches
+                    .unwrap()
+                    .value_of(FILE_REPORT_ARGUMENT)
+                    .unwrap(),
+            ),
+        },
+        LSP_SUBCOMMAND => Subcommand::Lsp,
+        RENAME_SUBCOMMAND => Subcommand::Rename {
+            // The `unwrap`s are safe due to [ref:rename_old_required] and
+            // [ref:rename_new_required].
+            old: subcommand_matches
+                .unwrap()
+                .value_of(RENAME_OLD_ARGUMENT)
+                .unwrap()
+                .to_owned(),
+            new: subcommand_matches
+                .unwrap()
+                .value_of(RENAME_NEW_ARGUMENT)
+                .unwrap()
+                .to_owned(),
+        },
+        RENAME_BATCH_SUBCOMMAND => Subcommand::RenameBatch {
+            // The `unwrap`s are safe due to [ref:rename_batch_mapping_required].
+            mapping: Path::new(
+                subcommand_matches
+                    .unwrap()
+                    .value_of(RENAME_BATCH_ARGUMENT)
+                    .unwrap(),
+            )
+            .to_owned(),
+        },
+        DOCTOR_SUBCOMMAND => Subcommand::Doctor {
+            // The `unwrap`s are safe due to [ref:doctor_why_required].
+            why: Path::new(
+                subcommand_matches
+                    .unwrap()
+                    .value_of(DOCTOR_WHY_OPTION)
+                    .unwrap(),
+            )
+            .to_owned(),
+        },
+        COMPLETE_LABEL_SUBCOMMAND => Subcommand::CompleteLabel {
+            // The `unwrap`s are safe due to [ref:complete_label_prefix_default].
+            prefix: subcommand_matches
+                .unwrap()
+                .value_of(COMPLETE_LABEL_ARGUMENT)
+                .unwrap()
+                .to_owned(),
+        },
+        LINKS_SUBCOMMAND => Subcommand::Links {
+            // The `unwrap` is safe due to [ref:links_base_url_required].
+            base_url: base_url().unwrap(),
+            format: format(),
+        },
+        EXPORT_SUBCOMMAND => {
+            // The `unwrap` is safe because we're _in_ a subcommand.
+            let matches = subcommand_matches.unwrap();
+            Subcommand::Export {
+                shard: matches.is_present(EXPORT_SHARD_OPTION).then(|| {
+                    value_t!(matches, EXPORT_SHARD_OPTION, Shard)
+                        .unwrap_or_else(|error| error.exit())
+                }),
+                output: matches
+                    .value_of(EXPORT_OUTPUT_OPTION)
+                    .map(|path| Path::new(path).to_owned()),
+            }
+        }
+        INDEX_SUBCOMMAND => Subcommand::Index {
+            // The `unwrap`s are safe due to [ref:index_output_default].
+            output: Path::new(
+                subcommand_matches
+                    .unwrap()
+                    .value_of(INDEX_OUTPUT_OPTION)
+                    .unwrap(),
+            )
+            .to_owned(),
+            base_url: base_url(),
+        },
+        _ => panic!("Unimplemented subcommand."),
+    };
+
+    // Return the command-line options.
+    Settings {
+        config_path,
+        policy_path,
+        cache,
+        extraction,
+        structured_values,
+        scan_copies,
+        edit_options,
+        profile,
+        paths,
+        default_paths,
+        excludes,
+        tag_sigil,
+        ref_sigil,
+        file_sigil,
+        dir_sigil,
+        spec_sigil,
+        url_sigil,
+        context_length,
+        max_memory,
+        progress_format,
+        path_style,
+        schema,
+        subcommand,
+    }
+}
+
+// This function returns the command-line arguments with the options from the configuration file
+// which weren't given on the command line: the sigils (see `sigils`) and the options for the
+// subcommand (see `arguments`). It returns `None` if there's nothing to add. Problems with the
+// configuration file are ignored here, since they're reported when it's loaded later.
+fn config_arguments(args: &[OsString], matches: &ArgMatches) -> Option<Vec<OsString>> {
+    let path = matches
+        .value_of(CONFIG_OPTION)
+        .map(PathBuf::from)
+        .or_else(|| {
+            current_dir()
+                .ok()
+                .and_then(|directory| config::discover(&directory))
+                .map(|root| root.join(config::DEFAULT_CONFIG_PATH))
+        });
+    let config = config::load(path.as_deref()).ok()?;
+
+    // Options may be given by a short name or an alias, which `occurrences_of` accounts for, or
+    // as `--name=value`, which it only does if `name` is also what the option is called here.
+    let given = |matches: &ArgMatches, name: &str| {
+        matches.occurrences_of(name) > 0
+            || args.iter().any(|arg| {
+                arg.to_str()
+                    .and_then(|arg| arg.strip_prefix("--")?.strip_prefix(name))
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('='))
+            })
+    };
+
+    // Determine which sigils to add.
+    let mut global = vec![];
+    for (name, sigil) in [
+        (TAG_SIGIL_OPTION, &config.sigils.tag),
+        (REF_SIGIL_OPTION, &config.sigils.r#ref),
+        (FILE_SIGIL_OPTION, &config.sigils.file),
+        (DIR_SIGIL_OPTION, &config.sigils.dir),
+        (SPEC_SIGIL_OPTION, &config.sigils.spec),
+        (URL_SIGIL_OPTION, &config.sigils.url),
+    ] {
+        if let Some(sigil) = sigil.as_ref().filter(|_| !given(matches, name)) {
+            global.push(OsString::from(format!("--{name}={sigil}")));
+        }
+    }
+
+    // Determine which options to add to the subcommand.
+    let mut local = vec![];
+    if let (subcommand, Some(subcommand_matches)) = matches.subcommand() {
+        for (name, value) in config.arguments.get(subcommand).into_iter().flatten() {
+            if !given(subcommand_matches, name) {
+                local.extend(value.to_args(name).into_iter().map(OsString::from));
+            }
+        }
+    }
+
+    if global.is_empty() && local.is_empty() {
+        return None;
+    }
+
+    // The sigils go before the subcommand, and the options for the subcommand go at the end (but
+    // before any `--`, so they aren't taken as positional arguments).
+    let end = args
+        .iter()
+        .position(|arg| arg == "--")
+        .unwrap_or(args.len());
+    Some(
+        args[..1]
+            .iter()
+            .cloned()
+            .chain(global)
+            .chain(args[1..end].iter().cloned())
+            .chain(local)
+            .chain(args[end..].iter().cloned())
+            .collect(),
+    )
+}
+
+// This function changes the working directory to a project root, so that's what's scanned by
+// default and the paths in its configuration file are resolved as usual. Relative paths given on
+// the command line are still interpreted relative to the original working directory.
+fn relocate(settings: &mut Settings, from: &Path, to: &Path) -> Result<(), String> {
+    let rebase = |path: &mut PathBuf| {
+        if path.is_relative() {
+            *path = from.join(&*path);
+        }
+    };
+
+    if let Some(path) = &mut settings.policy_path {
+        rebase(path);
+    }
+    if let Some(Cache::Local(directory)) = &mut settings.cache {
+        rebase(directory);
+    }
+    match &mut settings.subcommand {
+        Subcommand::Check { reports, .. } => {
+            reports
+                .iter_mut()
+                .filter_map(|report| report.output.as_mut())
+                .for_each(rebase);
+        }
+        Subcommand::Ctags { output, .. }
+        | Subcommand::Docs { output, .. }
+        | Subcommand::Export { output, .. } => {
+            if let Some(path) = output {
+                rebase(path);
+            }
+        }
+        Subcommand::Merge {
+            exports, reports, ..
+        } => {
+            exports.iter_mut().for_each(rebase);
+            reports
+                .iter_mut()
+                .filter_map(|report| report.output.as_mut())
+                .for_each(rebase);
+        }
+        Subcommand::ReportDiff { old, new, .. } => {
+            rebase(old);
+            rebase(new);
+        }
+        Subcommand::Index { output, .. } => rebase(output),
+        _ => {}
+    }
+
+    set_current_dir(to).map_err(|error| {
+        format!(
+            "Unable to change the working directory to {}: {error}",
+            to.to_string_lossy(),
+        )
+    })
+}
+
+// This struct represents the results of scanning the filesystem.
+struct Scan {
+    tags: Arc<Mutex<HashMap<String, Vec<Directive>>>>,
+    refs: Arc<Mutex<Vec<Directive>>>,
+    files: Arc<Mutex<Vec<Directive>>>,
+    dirs: Arc<Mutex<Vec<Directive>>>,
+    specs: Arc<Mutex<Vec<Directive>>>,
+    urls: Arc<Mutex<Vec<Directive>>>,
+    scanned: Arc<Mutex<Vec<PathBuf>>>,
+    conflicts: Arc<Mutex<Vec<(PathBuf, usize)>>>,
+    files_scanned: usize,
+
+    // The tag references which didn't fit in memory
+    spill: Arc<Mutex<Spill>>,
+}
+
+// This struct holds everything needed to find the directives in a file.
+#[derive(Clone)]
+struct Parser {
+    tag_regex: Regex,
+    ref_regex: Regex,
+    file_regex: Regex,
+    dir_regex: Regex,
+    spec_regex: Regex,
+    url_regex: Regex,
+    sigil_sets: Vec<SigilSet>,
+    extraction: Extraction,
+    structured_values: bool,
+    extractors: Vec<Extractor>,
+    context_length: usize,
+    cache: Option<Cache>,
+
+    // The sigil for the tags in file names, if the configuration asks for them
+    path_tag_sigil: Option<String>,
+}
+
+impl Parser {
+    // This function returns the settings which affect how files are parsed.
+    fn settings(&self) -> [String; 12] {
+        [
+            self.tag_regex.to_string(),
+            self.ref_regex.to_string(),
+            self.file_regex.to_string(),
+            self.dir_regex.to_string(),
+            self.spec_regex.to_string(),
+            self.url_regex.to_string(),
+            self.sigil_sets
+                .iter()
+                .map(|sigil_set| {
+                    format!(
+                        "{} {} {} {} {} {} {}",
+                        sigil_set.name,
+                        sigil_set.tag_regex,
+                        sigil_set.ref_regex,
+                        sigil_set.file_regex,
+                        sigil_set.dir_regex,
+                        sigil_set.spec_regex,
+                        sigil_set.url_regex,
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            self.extraction.name().to_owned(),
+            self.structured_values.to_string(),
+            self.extractors
+                .iter()
+                .map(|extractor| format!("{} {}", extractor.path, extractor.command.join(" ")))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            self.context_length.to_string(),
+            self.path_tag_sigil.clone().unwrap_or_default(),
+        ]
+    }
+
+    // This function returns the regular expressions for each type of directive, including those of
+    // the sigil sets.
+    fn regexes(&self) -> Vec<(Type, &Regex)> {
+        let mut regexes = vec![
+            (Type::Tag, &self.tag_regex),
+            (Type::Ref, &self.ref_regex),
+            (Type::File, &self.file_regex),
+            (Type::Dir, &self.dir_regex),
+            (Type::Spec, &self.spec_regex),
+            (Type::Url, &self.url_regex),
+        ];
+        for sigil_set in &self.sigil_sets {
+            regexes.extend([
+                (Type::Tag, &sigil_set.tag_regex),
+                (Type::Ref, &sigil_set.ref_regex),
+                (Type::File, &sigil_set.file_regex),
+                (Type::Dir, &sigil_set.dir_regex),
+                (Type::Spec, &sigil_set.spec_regex),
+                (Type::Url, &sigil_set.url_regex),
+            ]);
+        }
+        regexes
+    }
+
+    // This function finds the directives in a file, including the tag in its name (if any).
+    fn parse(&self, path: &Path, file: File) -> Directives {
+        self.with_path_tag(path, self.parse_file(path, file))
+    }
+
+    // This function finds the directives in the contents of a file, including the tag in its name
+    // (if any).
+    fn parse_contents(&self, path: &Path, contents: &[u8]) -> Directives {
+        self.with_path_tag(path, self.parse_text(path, contents))
+    }
+
+    // This function adds the tag defined by the name of a file to its directives, if the
+    // configuration asks for such tags and there is one. It isn't cached with the other
+    // directives, since the cache only depends on the contents of the file.
+    fn with_path_tag(&self, path: &Path, mut directives: Directives) -> Directives {
+        if let Some(tag) = self
+            .path_tag_sigil
+            .as_deref()
+            .and_then(|sigil| directive::path_tag(path, sigil))
+        {
+            directives.tags.insert(0, tag);
+        }
+        directives
+    }
+
+    // This function finds the directives in a file, consulting the cache if there is one.
+    fn parse_file(&self, path: &Path, mut file: File) -> Directives {
+        // In the common case, the file is streamed rather than read all at once. Large files are
+        // read all at once so they can be parsed in parallel.
+        if self.cache.is_none()
+            && self.extraction == Extraction::Everything
+            && !self.structured_values
+            && !notebook::is_notebook(path)
+            && self.extractor(path).is_none()
+            && file
+                .metadata()
+                .is_ok_and(|metadata| metadata.len() < PARALLEL_PARSE_THRESHOLD)
+        {
+            return self.parse_reader(path, BufReader::new(file));
+        }
+
+        // Files which can't be read in full are parsed as far as possible, but not cached.
+        let mut contents = Vec::new();
+        if file.read_to_end(&mut contents).is_err() {
+            return self.parse_text(path, &contents);
+        }
+        let Some(cache) = &self.cache else {
+            return self.parse_text(path, &contents);
+        };
+        let key = cache::key(&self.settings(), &contents);
+        cache.get(&key, path).unwrap_or_else(|| {
+            let directives = self.parse_text(path, &contents);
+            cache.put(&key, &directives);
+            directives
+        })
+    }
+
+    // This function finds the directives in the contents of a file, after blanking out the parts
+    // which shouldn't be searched (or decoding the string values, for structured files).
+    fn parse_text(&self, path: &Path, contents: &[u8]) -> Directives {
+        // Files with an extractor are searched via the text it produces. If it fails, the file is
+        // searched as is.
+        if let Some(extractor) = self.extractor(path) {
+            match extractor::run(&extractor.command, path) {
+                Ok(text) => return self.parse_bytes(path, &text),
+                Err(error) => eprintln!("{}", error.yellow()),
+            }
+        }
+
+        // Notebooks are searched cell by cell, so the line numbers are relative to each cell.
+        if notebook::is_notebook(path) {
+            if let Some(cells) = std::str::from_utf8(contents).ok().and_then(notebook::cells) {
+                let mut directives = Directives::default();
+                for (i, source) in cells.iter().enumerate() {
+                    directives.extend_with_cell(self.parse_reader(path, source.as_bytes()), i + 1);
+                }
+                return directives;
+            }
+        }
+
+        match std::str::from_utf8(contents).ok().and_then(|text| {
+            comments::extract(path, text, self.extraction).or_else(|| {
+                if self.structured_values {
+                    structured::extract(path, text)
+                } else {
+                    None
+                }
+            })
+        }) {
+            Some(extracted) => self.parse_bytes(path, extracted.as_bytes()),
+            None => self.parse_bytes(path, contents),
+        }
+    }
+
+    // This function returns the extractor for a file, if there is one.
+    fn extractor(&self, path: &Path) -> Option<&Extractor> {
+        self.extractors
+            .iter()
+            .find(|extractor| extractor.path.is_match(path))
+    }
+
+    // This function finds the directives in the contents of a file. Large files are split into
+    // chunks which are parsed in parallel, one per available thread.
+    fn parse_bytes(&self, path: &Path, contents: &[u8]) -> Directives {
+        if (contents.len() as u64) < PARALLEL_PARSE_THRESHOLD {
+            return self.parse_reader(path, contents);
+        }
+
+        let threads = available_parallelism().map_or(1, NonZeroUsize::get);
+        let mut directives = directive::parse_chunks(
+            &self.tag_regex,
+            &self.ref_regex,
+            &self.file_regex,
+            &self.dir_regex,
+            &self.spec_regex,
+            &self.url_regex,
+            &self.sigil_sets,
+            path,
+            contents,
+            contents.len().div_ceil(threads),
+        );
+        directives.truncate_contexts(self.context_length);
+        directives
+    }
+
+    // This function finds the directives in a stream.
+    fn parse_reader<R: BufRead>(&self, path: &Path, reader: R) -> Directives {
+        let mut directives = directive::parse(
+            &self.tag_regex,
+            &self.ref_regex,
+            &self.file_regex,
+            &self.dir_regex,
+            &self.spec_regex,
+            &self.url_regex,
+            &self.sigil_sets,
+            path,
+            reader,
+        );
+        directives.truncate_contexts(self.context_length);
+        directives
+    }
+}
+
+// This function parses all the directives in the given paths, or in the given shard of them. Unless
+// `scan_copies` is set, each file is only scanned once, even if it's reachable via several paths.
+// If the tag references take up more than `max_memory` bytes, they're spilled to disk. Progress is
+// reported in the given format.
+fn scan(
+    paths: &[PathBuf],
+    excludes: &[PathPattern],
+    shard: Option<Shard>,
+    scan_copies: bool,
+    max_memory: Option<usize>,
+    progress_format: ProgressFormat,
+    parser: &Parser,
+) -> Scan {
+    let tags = Arc::new(Mutex::new(HashMap::new()));
+    let refs = Arc::new(Mutex::new(Vec::new()));
+    let files = Arc::new(Mutex::new(Vec::new()));
+    let dirs = Arc::new(Mutex::new(Vec::new()));
+    let specs = Arc::new(Mutex::new(Vec::new()));
+    let urls = Arc::new(Mutex::new(Vec::new()));
+    let scanned = Arc::new(Mutex::new(Vec::new()));
+    let conflicts = Arc::new(Mutex::new(Vec::new()));
+    let spill = Arc::new(Mutex::new(Spill::new(max_memory)));
+    let tags_clone = tags.clone();
+    let refs_clone = refs.clone();
+    let files_clone = files.clone();
+    let dirs_clone = dirs.clone();
+    let specs_clone = specs.clone();
+    let urls_clone = urls.clone();
+    let scanned_clone = scanned.clone();
+    let conflicts_clone = conflicts.clone();
+    let spill_clone = spill.clone();
+    let progress = Arc::new(Progress::new(progress_format));
+    let progress_clone = progress.clone();
+    let parser_clone = parser.clone();
+    let files_scanned = walk::walk(
+        paths,
+        excludes,
+        shard,
+        scan_copies,
+        move |file_path, file| {
+            let directives = parser_clone.parse(file_path, file);
+            progress_clone.file_scanned(
+                directives.tags.len()
+                    + directives.refs.len()
+                    + directives.files.len()
+                    + directives.dirs.len()
+                    + directives.specs.len()
+                    + directives.urls.len(),
+            );
+            for tag in directives.tags {
+                tags_clone
+                    .lock()
+                    .unwrap() // Safe assuming no poisoning
+                    .entry(tag.label.clone())
+                    .or_insert_with(Vec::new)
+                    .push(tag.clone());
+            }
+            {
+                // The `unwrap`s are safe assuming no poisoning.
+                let mut refs = refs_clone.lock().unwrap();
+                refs.extend_from_slice(&directives.refs);
+                spill_clone
+                    .lock()
+                    .unwrap()
+                    .track(&directives.refs, &mut refs);
+            }
+            files_clone.lock().unwrap().extend(directives.files); // Safe assuming no poisoning
+            dirs_clone.lock().unwrap().extend(directives.dirs); // Safe assuming no poisoning
+            specs_clone.lock().unwrap().extend(directives.specs); // Safe assuming no poisoning
+            urls_clone.lock().unwrap().extend(directives.urls); // Safe assuming no poisoning
+            scanned_clone.lock().unwrap().push(file_path.to_owned()); // Safe assuming no poisoning
+            conflicts_clone
+                .lock()
+                .unwrap() // Safe assuming no poisoning
+                .extend(
+                    directives
+                        .conflicts
+                        .into_iter()
+                        .map(|line_number| (file_path.to_owned(), line_number)),
+                );
+        },
+    );
+    progress.finish();
+
+    Scan {
+        tags,
+        refs,
+        files,
+        dirs,
+        specs,
+        urls,
+        scanned,
+        conflicts,
+        files_scanned,
+        spill,
+    }
+}
+
+// This function replaces the directives found in the files which differ from what's staged in the
+// Git index with those in the staged contents, so the check sees what would be committed. Untracked
+// files are left out entirely. Only the files within the given paths which aren't excluded are
+// read from the index, as if they were scanned.
+fn stage(
+    scan: &Scan,
+    paths: &[PathBuf],
+    excludes: &[PathPattern],
+    parser: &Parser,
+) -> Result<(), String> {
+    let (unstaged, untracked) = git::unstaged()?;
+    let replaced = unstaged
+        .iter()
+        .chain(&untracked)
+        .map(|path| normalize(path))
+        .collect::<HashSet<_>>();
+    let keep = |directive: &Directive| !replaced.contains(&normalize(&directive.path));
+
+    // The tag references need to be in memory to be replaced. The `unwrap`s are safe assuming no
+    // poisoning.
+    let mut refs = scan.refs.lock().unwrap();
+    let mut spill = scan.spill.lock().unwrap();
+    spill.reload(&mut refs)?;
+    *spill = Spill::new(None);
+
+    // Forget what was found in the working tree. The `unwrap`s are safe assuming no poisoning.
+    let mut tags = scan.tags.lock().unwrap();
+    for tags in tags.values_mut() {
+        tags.retain(keep);
+    }
+    tags.retain(|_, tags| !tags.is_empty());
+    refs.retain(keep);
+    let mut files = scan.files.lock().unwrap();
+    files.retain(keep);
+    let mut dirs = scan.dirs.lock().unwrap();
+    dirs.retain(keep);
+    let mut specs = scan.specs.lock().unwrap();
+    specs.retain(keep);
+    let mut urls = scan.urls.lock().unwrap();
+    urls.retain(keep);
+    let mut scanned = scan.scanned.lock().unwrap();
+    scanned.retain(|path| !replaced.contains(&normalize(path)));
+    let mut conflicts = scan.conflicts.lock().unwrap();
+    conflicts.retain(|(path, _)| !replaced.contains(&normalize(path)));
+
+    // Read the staged contents instead. Each path is written as if it were found by walking the
+    // path which contains it.
+    for path in unstaged {
+        let Some(path) = paths.iter().find_map(|root| {
+            normalize(&path)
+                .strip_prefix(normalize(root))
+                .ok()
+                .map(|relative| root.join(relative))
+        }) else {
+            continue;
+        };
+        if path
+            .ancestors()
+            .any(|ancestor| excludes.iter().any(|exclude| exclude.is_match(ancestor)))
+        {
+            continue;
+        }
+        let Some(contents) = git::staged(&path)? else {
+            continue;
+        };
+
+        let directives = parser.parse_contents(&path, &contents);
+        for tag in directives.tags {
+            tags.entry(tag.label.clone()).or_default().push(tag);
+        }
+        refs.extend(directives.refs);
+        files.extend(directives.files);
+        dirs.extend(directives.dirs);
+        specs.extend(directives.specs);
+        urls.extend(directives.urls);
+        conflicts.extend(
+            directives
+                .conflicts
+                .into_iter()
+                .map(|line_number| (path.clone(), line_number)),
+        );
+        scanned.push(path);
+    }
+
+    Ok(())
+}
+
+// This function finds the directives with the given sigils (and those in the sigil sets) in the
+// given paths as of a Git revision. Only the lines which look like they contain such a directive
+// are read, which is much faster than checking out the revision.
+fn scan_revision(
+    revision: &str,
+    paths: &[PathBuf],
+    excludes: &[PathPattern],
+    sigils: &[&str],
+    sigil_sets: &[config::SigilSet],
+    parser: &Parser,
+) -> Result<Directives, String> {
+    let pattern = directive::line_pattern(
+        sigils
+            .iter()
+            .copied()
+            .chain(sigil_sets.iter().flat_map(config::SigilSet::sigils)),
+    );
+
+    let mut directives = Directives::default();
+    for (path, line_number, contents) in git::grep(revision, &pattern, paths)? {
+        if excludes.iter().any(|exclude| exclude.is_match(&path)) {
+            continue;
+        }
+        let mut found = parser.parse_reader(&path, contents.as_bytes());
+        for directive in found.all_mut() {
+            directive.line_number = line_number;
+        }
+        directives.tags.append(&mut found.tags);
+        directives.refs.append(&mut found.refs);
+    }
+
+    Ok(directives)
+}
+
+// This function gathers the directives recorded in the directive index, as if they were scanned.
+fn scan_index(index: DirectiveIndex) -> Scan {
+    let mut tags = HashMap::<String, Vec<Directive>>::new();
+    let mut refs = Vec::new();
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    let mut specs = Vec::new();
+    let mut urls = Vec::new();
+    let mut scanned = Vec::new();
+    let mut conflicts = Vec::new();
+    let files_scanned = index.files.len();
+    for (path, entry) in index.files {
+        for tag in entry.directives.tags {
+            tags.entry(tag.label.clone()).or_default().push(tag);
+        }
+        refs.extend(entry.directives.refs);
+        files.extend(entry.directives.files);
+        dirs.extend(entry.directives.dirs);
+        specs.extend(entry.directives.specs);
+        urls.extend(entry.directives.urls);
+        let path = PathBuf::from(path);
+        conflicts.extend(
+            entry
+                .directives
+                .conflicts
+                .into_iter()
+                .map(|line_number| (path.clone(), line_number)),
+        );
+        scanned.push(path);
+    }
+
+    Scan {
+        tags: Arc::new(Mutex::new(tags)),
+        refs: Arc::new(Mutex::new(refs)),
+        files: Arc::new(Mutex::new(files)),
+        dirs: Arc::new(Mutex::new(dirs)),
+        specs: Arc::new(Mutex::new(specs)),
+        urls: Arc::new(Mutex::new(urls)),
+        scanned: Arc::new(Mutex::new(scanned)),
+        conflicts: Arc::new(Mutex::new(conflicts)),
+        files_scanned,
+        spill: Arc::new(Mutex::new(Spill::new(None))),
+    }
+}
+
+// This function compiles the patterns for finding text which looks like a directive but isn't one,
+// using the sigils from the command line and the configuration.
+fn malformed_directive_patterns(
+    settings: &Settings,
+    config: &Config,
+) -> malformed_directives::Patterns {
+    let sigils = [
+        &settings.tag_sigil,
+        &settings.ref_sigil,
+        &settings.file_sigil,
+        &settings.dir_sigil,
+        &settings.spec_sigil,
+        &settings.url_sigil,
+    ]
+    .into_iter()
+    .map(String::as_str)
+    .chain(config.sigil_sets.iter().flat_map(config::SigilSet::sigils));
+    malformed_directives::Patterns::new(sigils)
+}
+
+// This type represents a way to visit the tag references in batches, since they might not fit in
+// memory. It calls the given function with each batch in turn (see `Spill::batches`).
+type RefBatches<'a> = dyn FnMut(&mut dyn FnMut(&[Directive])) -> Result<(), String> + 'a;
+
+// This function runs the checks which only depend on the directives (and the paths of the scanned
+// files), rather than on the filesystem. References may also point to the tags from the providers.
+#[allow(clippy::too_many_arguments)]
+fn check_directives(
+    config: &Config,
+    policy: &Policy,
+    tags: &HashMap<String, Vec<Directive>>,
+    provided: &HashSet<String>,
+    ref_batches: &mut RefBatches,
+    files: &[Directive],
+    dirs: &[Directive],
+    specs: &[Directive],
+    urls: &[Directive],
+    scanned: &[PathBuf],
+) -> Result<Vec<Diagnostic>, String> {
+    // Problems will be accumulated in this vector.
+    let mut diagnostics = Vec::<Diagnostic>::new();
+
+    // Check for duplicate tags, using the Git history to find the originals.
+    let packages = Packages::new(&config.packages);
+    diagnostics.extend(duplicates::check(
+        tags,
+        &packages,
+        &config.duplicate_tolerance,
+        |tag| git::author_time(&tag.path, tag.line_number),
+    ));
+
+    // Gather all the tags for the checks below.
+    let all_tags = tags.values().flatten().cloned().collect::<Vec<_>>();
+    let labels = tags.keys().chain(provided).cloned().collect::<HashSet<_>>();
+
+    // Count the references to each label, and the directives in each file.
+    let mut label_counts = HashMap::<String, usize>::new();
+    let mut file_counts = HashMap::new();
+    density::tally(
+        &mut file_counts,
+        all_tags
+            .iter()
+            .chain(files)
+            .chain(dirs)
+            .chain(specs)
+            .chain(urls),
+    );
+    ref_batches(&mut |batch| {
+        for r#ref in batch {
+            *label_counts.entry(r#ref.label.clone()).or_default() += 1;
+        }
+        density::tally(&mut file_counts, batch);
+    })?;
+    let ref_counts = label_counts
+        .iter()
+        .map(|(label, count)| (label.as_str(), *count))
+        .collect::<HashMap<_, _>>();
+
+    // Check that tags have descriptions where the configuration requires them.
+    diagnostics.extend(descriptions::check(&all_tags, &config.require_descriptions));
+
+    // Check that no tags use reserved labels.
+    diagnostics.extend(reserved::check(&all_tags, &config.reserved_labels));
+
+    // Check for labels which are probably typos of other labels, if the configuration asks for it.
+    if config.warn_similar_labels {
+        diagnostics.extend(similar_labels::check(tags, &ref_counts));
+    }
+
+    // Check for labels which look alike but aren't the same, if the configuration asks for it.
+    if config.warn_confusable_labels {
+        diagnostics.extend(confusable_labels::check(tags));
+    }
+
+    // Check that no file has more tags than its budgets allow.
+    diagnostics.extend(budgets::check(&all_tags, &config.tag_budgets));
+
+    // Check that files contain the tags the configuration requires.
+    diagnostics.extend(required_tags::check(
+        scanned,
+        &all_tags,
+        &config.required_tags,
+    ));
+
+    // Check that files contain as many directives as the configuration expects.
+    diagnostics.extend(density::check(
+        scanned,
+        &file_counts,
+        &config.directive_density,
+    ));
+
+    // Evaluate the policy rules for everything but the tag references, which are handled below.
+    diagnostics.extend(policy::check(
+        policy,
+        all_tags
+            .iter()
+            .chain(files.iter())
+            .chain(dirs.iter())
+            .chain(specs.iter())
+            .chain(urls.iter()),
+        &ref_counts,
+    ));
+
+    ref_batches(&mut |refs| {
+        // Check for references with descriptions which mention labels which were probably
+        // renamed, if the configuration asks for it.
+        if config.warn_stale_descriptions {
+            diagnostics.extend(stale_descriptions::check_labels(refs, &labels));
+        }
+
+        // Check the tag references.
+        diagnostics.extend(tag_references::check(&labels, refs));
+
+        // Check where the references are placed relative to their tags.
+        diagnostics.extend(placement::check(
+            &all_tags,
+            refs,
+            &packages,
+            config.ref_placement,
+        ));
+
+        // Evaluate the policy rules for the tag references.
+        diagnostics.extend(policy::check(policy, refs, &ref_counts));
+    })?;
+
+    Ok(diagnostics)
+}
+
+// This function applies the suppressions, severity overrides, and generated file rules from the
+// configuration file to the problems found by the checks. It prints how many problems each
+// suppression rule suppressed.
+fn adjust_diagnostics(config: &Config, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    let (diagnostics, counts) = suppressions::apply(&config.suppressions, diagnostics);
+    for (i, (suppression, count)) in config.suppressions.iter().zip(counts).enumerate() {
+        eprintln!(
+            "Suppression #{} ({}) suppressed {}.",
+            i + 1,
+            suppression.path,
+            count::count(count, "problem"),
+        );
+    }
+
+    // Report the problems in generated files where they can be fixed.
+    provenance::apply(
+        &config.generated,
+        severity::apply(&config.severity, diagnostics),
+    )
+}
+
+// This function returns the directives which match the filter of a listing (if any).
+fn select<'a>(
+    listing: &Listing,
+    directives: impl IntoIterator<Item = &'a Directive>,
+    ref_counts: &HashMap<&str, usize>,
+) -> Result<Vec<Directive>, String> {
+    let filter = listing.filter.as_deref().map(query::parse).transpose()?;
+
+    Ok(directives
+        .into_iter()
+        .filter(|directive| {
+            filter
+                .as_ref()
+                .is_none_or(|filter| filter.matches(directive, ref_counts))
+        })
+        .cloned()
+        .collect())
+}
+
+// This function fails if the listing is supposed to be empty but isn't.
+fn fail_if_any(listing: &Listing, noun: &str, directives: &[Directive]) -> Result<(), String> {
+    if listing.fail_if_any && !directives.is_empty() {
+        return Err(format!("Found {noun} while using --{LIST_ERROR_OPTION}"));
+    }
+
+    Ok(())
+}
+
+// This function prints the directives which match the filter of a listing (if any).
+fn list<'a>(
+    listing: &Listing,
+    noun: &str,
+    directives: impl IntoIterator<Item = &'a Directive>,
+    ref_counts: &HashMap<&str, usize>,
+) -> Result<(), String> {
+    let directives = select(listing, directives, ref_counts)?;
+    output::emit(&listing.format, &directives)?;
+    fail_if_any(listing, noun, &directives)
+}
+
+// This function writes the data shown in an HTML report to a file next to it, with the same name
+// and a `.json` extension.
+fn write_html_data(
+    output: &Path,
+    diagnostics: &[Diagnostic],
+    totals: &Totals,
+) -> Result<(), String> {
+    let data_path = output.with_extension("json");
+    if data_path == output {
+        return Err(format!(
+            "The HTML report can't be written to {}, since its data is written to a file with the \
+             same name and a `.json` extension.",
+            output.to_string_lossy(),
+        ));
+    }
+    atomic::write(&data_path, report::html_data(diagnostics, totals))
+}
+
+// This function reports the problems found by the checks in each of the given reports, writing the
+// ones with outputs to files before printing the one without (if any). Only errors cause the check
+// to fail, unless there are more than `max_warnings` warnings. If the check passes, the
+// summary is printed in the human-readable format. Either way, the human-readable format ends with
+// the number of errors and warnings, if there are any. If a base URL is given, each problem links
+// to its location. If `quiet` is set, everything which would be printed to STDOUT is printed to
+// STDERR instead.
+#[allow(clippy::too_many_arguments)]
+fn report_results(
+    reports: &[Report],
+    base_url: Option<&str>,
+    quiet: bool,
+    max_warnings: Option<usize>,
+    mut diagnostics: Vec<Diagnostic>,
+    totals: &Totals,
+    summary: &str,
+) -> Result<(), String> {
+    // Sort the problems by location so the output is deterministic.
+    diagnostics.sort_by(|x, y| (&x.location, &x.code).cmp(&(&y.location, &y.code)));
+
+    // Add the links, if requested.
+    if let Some(base_url) = base_url {
+        links::annotate(base_url, &mut diagnostics);
+    }
+
+    // Count the errors and warnings. Warnings don't cause the check to fail, unless there are too
+    // many of them.
+    let (errors, warnings): (Vec<_>, Vec<_>) = diagnostics
+        .iter()
+        .cloned()
+        .partition(|diagnostic| diagnostic.severity == Severity::Error);
+    let tally = report::tally(errors.len(), warnings.len());
+    let failure = if errors.is_empty() {
+        max_warnings
+            .filter(|max| warnings.len() > *max)
+            .map(|max| format!("{tally} The maximum is {max} (see --{MAX_WARNINGS_OPTION})."))
+    } else {
+        Some(tally.clone())
+    };
+
+    // The summary includes the number of problems, if there are any.
+    let summary = if diagnostics.is_empty() {
+        summary.to_owned()
+    } else {
+        format!("{summary} {tally}")
+    };
+
+    // If the check passes, explain what was validated, if requested.
+    let explanation = totals
+        .configuration
+        .as_ref()
+        .filter(|_| failure.is_none())
+        .map(|configuration| report::explain(totals, configuration, warnings.len()));
+
+    // Write the reports to files, if requested. The human-readable format ends with the summary
+    // (and the explanation, if any) if the check passes, or the number of problems if it doesn't.
+    let mut stdout_format = None;
+    for Report { format, output } in reports {
+        let (format, Some(output)) = (*format, output) else {
+            stdout_format = Some(*format);
+            continue;
+        };
+        let mut report = report::render(format, &diagnostics, totals);
+        if format == ReportFormat::Human {
+            if !report.is_empty() {
+                report.push_str("\n\n");
+            }
+            if let Some(failure) = &failure {
+                report.push_str(failure);
+            } else {
+                if let Some(explanation) = &explanation {
+                    report.push_str(explanation);
+                    report.push_str("\n\n");
+                }
+                report.push_str(&summary);
+            }
+            report.push('\n');
+        }
+
+        // The data shown in the HTML report is written alongside it, for other frontends.
+        if format == ReportFormat::Html {
+            write_html_data(output, &diagnostics, totals)?;
+        }
+
+        atomic::write(output, report)?;
+    }
+    let Some(format) = stdout_format else {
+        return failure.map_or(Ok(()), Err);
+    };
+
+    // Print the report for other programs, if requested.
+    if format != ReportFormat::Human {
+        let report = report::render(format, &diagnostics, totals);
+        if quiet {
+            eprint!("{report}");
+        } else {
+            print!("{report}");
+        }
+
+        return failure.map_or(Ok(()), Err);
+    }
+
+    // Report any warnings in yellow.
+    for warning in &warnings {
+        eprintln!("{}\n", warning.message.yellow());
+    }
+
+    // Report the result. Errors are printed in red (by `main`), followed by the number of problems.
+    // The summary is green if there were no problems at all, and yellow if there were warnings.
+    if let Some(failure) = failure {
+        if errors.is_empty() {
+            Err(failure)
+        } else {
+            Err(format!(
+                "{}\n\n{failure}",
+                report::render(format, &errors, totals),
+            ))
+        }
+    } else {
+        let summary = if warnings.is_empty() {
+            summary.green()
+        } else {
+            summary.yellow()
+        };
+        if quiet {
+            if let Some(explanation) = explanation {
+                eprintln!("{explanation}\n");
+            }
+            eprintln!("{summary}");
+        } else {
+            if let Some(explanation) = explanation {
+                println!("{explanation}\n");
+            }
+            println!("{summary}");
+        }
+        Ok(())
+    }
+}
+
+// This function rewrites each file containing a renamed tag or a reference to one, and prints a
+// summary of the changes.
+fn rename_tags(
+    parser: &Parser,
+    tags: &HashMap<String, Vec<Directive>>,
+    refs: &[Directive],
+    renames: &HashMap<String, String>,
+    edit_options: edit::Options,
+) -> Result<(), String> {
+    let changes = rename::apply(
+        tags.values()
+            .flatten()
+            .chain(refs.iter())
+            .filter(|directive| renames.contains_key(&directive.label))
+            .map(|directive| &directive.path),
+        &parser
+            .regexes()
+            .into_iter()
+            .filter(|(r#type, _)| [Type::Tag, Type::Ref].contains(r#type))
+            .collect::<Vec<_>>(),
+        renames,
+        edit_options,
+    )?;
+
+    for change in &changes {
+        println!(
+            "{}:{}\n  {}\n  {}",
+            change.path.to_string_lossy(),
+            change.line_number,
+            format!("- {}", change.before).red(),
+            format!("+ {}", change.after).green(),
+        );
+    }
+    println!(
+        "{} {} with {} in {}.",
+        if edit_options.dry_run {
+            "Would rename"
+        } else {
+            "Renamed"
+        },
+        count::count(renames.len(), "tag"),
+        count::count(changes.len(), "change"),
+        count::count(
+            changes
+                .iter()
+                .map(|change| &change.path)
+                .collect::<HashSet<_>>()
+                .len(),
+            "file",
+        ),
+    );
+
+    Ok(())
+}
+
+// Program entrypoint
+#[allow(clippy::too_many_lines)]
+fn entry() -> Result<(), String> {
+    // Keep track of how long everything takes.
+    let start = Instant::now();
+
+    // Determine whether to print colored output.
+    colored::control::set_override(atty::is(Stream::Stdout));
+
+    // Parse the command-line options.
+    let mut settings = settings();
+
+    // The JSON Schema doesn't depend on anything else.
+    if settings.schema {
+        print!("{}", schema::SCHEMA);
+        return Ok(());
+    }
+
+    // If the configuration file is in a parent directory (within the Git repository), use it. If no
+    // paths were given, scan the whole project rather than just the working directory.
+    let invocation_dir = current_dir()
+        .map_err(|error| format!("Unable to determine the working directory: {error}"))?;
+    if settings.config_path.is_none() {
+        if let Some(root) = config::discover(&invocation_dir).filter(|root| *root != invocation_dir)
+        {
+            if settings.default_paths {
+                relocate(&mut settings, &invocation_dir, &root)?;
+            } else {
+                settings.config_path = Some(root.join(config::DEFAULT_CONFIG_PATH));
+            }
+        }
+    }
+
+    // Choose how to print paths, if requested. Relative paths are relative to the directory Tagref
+    // was run in, even if it moved to the root of the project above.
+    if let Some(style) = settings.path_style {
+        let working_dir = current_dir()
+            .map_err(|error| format!("Unable to determine the working directory: {error}"))?;
+        let base = if style == PathStyle::GitRoot {
+            git::root()?
+        } else {
+            invocation_dir.clone()
+        };
+        path_style::set(style, working_dir, base);
+    }
+
+    // Load the configuration file.
+    let mut config = config::load(settings.config_path.as_deref())?;
+
+    // Apply the paths and excluded paths from the configuration file, if any.
+    if settings.default_paths && !config.paths.is_empty() {
+        settings.paths = mem::take(&mut config.paths);
+    }
+    settings.excludes.append(&mut config.excludes);
+
+    // Apply the profile, if any.
+    if let Some(name) = settings.profile.clone() {
+        let profile = config.profiles.remove(&name).ok_or_else(|| {
+            format!("There is no profile named `{name}` in the configuration file.")
+        })?;
+        if settings.default_paths && !profile.paths.is_empty() {
+            settings.paths = profile.paths;
+        }
+        settings.excludes.extend(profile.excludes);
+        config.severity.extend(profile.severity);
+    }
+
+    // Don't traverse any paths twice, unless files reachable via several paths should be scanned
+    // again anyway.
+    if !settings.scan_copies {
+        let (roots, removals) = walk::roots(&settings.paths);
+        for removal in removals {
+            eprintln!("{}", removal.yellow());
+        }
+        settings.paths = roots;
+    }
+
+    // Only some builds can extract comments.
+    if settings.extraction != Extraction::Everything && !cfg!(feature = "tree-sitter") {
+        return Err(format!(
+            "This build of Tagref doesn't support `--{COMMENTS_ONLY_OPTION}`. Install it with \
+             `cargo install tagref --features tree-sitter` to enable it.",
+        ));
+    }
+
+    // Compile the regular expressions in advance.
+    let parser = Parser {
+        tag_regex: compile_directive_regex(&settings.tag_sigil),
+        ref_regex: compile_directive_regex(&settings.ref_sigil),
+        file_regex: compile_directive_regex(&settings.file_sigil),
+        dir_regex: compile_directive_regex(&settings.dir_sigil),
+        spec_regex: compile_directive_regex(&settings.spec_sigil),
+        url_regex: compile_directive_regex(&settings.url_sigil),
+        sigil_sets: config.sigil_sets.iter().map(SigilSet::new).collect(),
+        extraction: settings.extraction,
+        structured_values: settings.structured_values,
+        extractors: config.extractors.clone(),
+        context_length: settings.context_length,
+        cache: settings.cache.clone(),
+        path_tag_sigil: config.path_tags.then(|| settings.tag_sigil.clone()),
+    };
+    let malformed_patterns = config
+        .warn_malformed_directives
+        .then(|| malformed_directive_patterns(&settings, &config));
+
+    // Parse all the tags and references, or just those in the requested shard, or read them from
+    // the directive index. Merging, updating the index, diagnosing, and counting directives in the
+    // Git history don't involve scanning here at all.
+    let directive_index_path = Path::new(directive_index::DEFAULT_DIRECTIVE_INDEX_PATH);
+    let (paths, shard) = match settings.subcommand {
+        Subcommand::Export { shard, .. } => (&settings.paths[..], shard),
+        Subcommand::Merge { .. }
+        | Subcommand::UpdateIndex
+        | Subcommand::Doctor { .. }
+        | Subcommand::History { .. }
+        | Subcommand::ReportDiff { .. } => (&[][..], None),
+        _ => (&settings.paths[..], None),
+    };
+    let mut watch_index = None;
+    let found = match settings.subcommand {
+        Subcommand::Check {
+            use_index: true,
+            verify_sample,
+            ..
+        } => match directive_index::load(directive_index_path)? {
+            Some(index) if index.settings == DirectiveIndex::new(&parser.settings()).settings => {
+                // Make sure the index can be trusted, if requested.
+                if let Some(sample) = verify_sample {
+                    let (sampled, discrepancies) =
+                        index.verify(sample, &parser.settings(), |path, contents| {
+                            parser.parse_contents(path, contents)
+                        });
+                    if !discrepancies.is_empty() {
+                        return Err(format!(
+                            "{} doesn't match {} of the {} sampled:\n{}\nRun `tagref \
+                             {UPDATE_INDEX_SUBCOMMAND}` to bring it up to date.",
+                            directive_index_path.to_string_lossy(),
+                            discrepancies.len(),
+                            count::count(sampled, "file"),
+                            discrepancies
+                                .iter()
+                                .map(|discrepancy| format!("  {discrepancy}"))
+                                .collect::<Vec<_>>()
+                                .join("\n"),
+                        ));
+                    }
+                }
+
+                scan_index(index)
+            }
+            _ => {
+                return Err(format!(
+                    "{} is missing or was created with different settings. Run `tagref \
+                     {UPDATE_INDEX_SUBCOMMAND}` to create it.",
+                    directive_index_path.to_string_lossy(),
+                ));
+            }
+        },
+
+        // Watching keeps the directives in a directive index in memory, so each file can be parsed
+        // again on its own when it changes.
+        Subcommand::Check { watch: true, .. } => {
+            let mut index = DirectiveIndex::new(&parser.settings());
+            let parser_clone = parser.clone();
+            index.update(
+                directive_index_path,
+                paths,
+                &settings.excludes,
+                settings.scan_copies,
+                &parser.settings(),
+                move |path, contents| parser_clone.parse_contents(path, contents),
+            );
+            watch_index = Some(index.clone());
+            scan_index(index)
+        }
+
+        // Completion needs to be fast, so it uses the directive index if it's fresh and scans
+        // otherwise.
+        Subcommand::CompleteLabel { .. } => match directive_index::load(directive_index_path) {
+            Ok(Some(index))
+                if index.settings == DirectiveIndex::new(&parser.settings()).settings
+                    && index.is_fresh(directive_index_path) =>
+            {
+                scan_index(index)
+            }
+            _ => scan(
+                paths,
+                &settings.excludes,
+                shard,
+                settings.scan_copies,
+                settings.max_memory,
+                settings.progress_format,
+                &parser,
+            ),
+        },
+
+        _ => scan(
+            paths,
+            &settings.excludes,
+            shard,
+            settings.scan_copies,
+            settings.max_memory,
+            settings.progress_format,
+            &parser,
+        ),
+    };
+
+    // Check what's staged rather than what's in the working tree, if requested.
+    if let Subcommand::Check { staged: true, .. } = settings.subcommand {
+        stage(&found, &settings.paths, &settings.excludes, &parser)?;
+    }
+
+    let Scan {
+        mut tags,
+        mut refs,
+        mut files,
+        mut dirs,
+        mut specs,
+        mut urls,
+        mut scanned,
+        mut conflicts,
+        mut files_scanned,
+        mut spill,
+    } = found;
+
+    // Only the checks can work with tag references that were spilled to disk, so everything else
+    // needs them back in memory. The `unwrap`s are safe assuming no poisoning.
+    if !matches!(settings.subcommand, Subcommand::Check { fix: false, .. }) {
+        spill.lock().unwrap().reload(&mut refs.lock().unwrap())?;
+    }
+
+    // Describe the settings in effect, in case the check needs to explain itself.
+    let configuration = Configuration {
+        paths: settings
+            .paths
+            .iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect(),
+        excludes: settings.excludes.iter().map(ToString::to_string).collect(),
+        sigils: [
+            ("tag", &settings.tag_sigil),
+            ("ref", &settings.ref_sigil),
+            ("file", &settings.file_sigil),
+            ("dir", &settings.dir_sigil),
+            ("spec", &settings.spec_sigil),
+            ("url", &settings.url_sigil),
+        ]
+        .into_iter()
+        .map(|(kind, sigil)| (kind.to_owned(), sigil.clone()))
+        .collect(),
+        config_file: settings
+            .config_path
+            .clone()
+            .or_else(|| {
+                let path = Path::new(config::DEFAULT_CONFIG_PATH);
+                path.is_file().then(|| path.to_owned())
+            })
+            .map(|path| path.to_string_lossy().into_owned()),
+        policy_file: settings
+            .policy_path
+            .as_ref()
+            .map(|path| path.to_string_lossy().into_owned()),
+        profile: settings.profile.clone(),
+        search: settings.extraction.name().to_owned(),
+        structured_values: settings.structured_values,
+    };
+
+    // Decide what to do based on the subcommand.
     match settings.subcommand {
-        Subcommand::Check => {
-            // Errors will be accumulated in this vector.
-            let mut errors = Vec::<String>::new();
+        Subcommand::Check {
+            fix,
+            fail_on_conflicts,
+            use_index: _,
+            verify_sample: _,
+            staged: _,
+            watch: _,
+            explain_pass,
+            scanned_files_only,
+            lint_descriptions,
+            quiet,
+            reports,
+            base_url,
+            max_warnings,
+        } => {
+            // Load the policy file, and gather the tags from the providers.
+            let policy = policy::load(settings.policy_path.as_deref())?;
+            let provided =
+                providers::labels(&config.providers, &settings.paths, &settings.excludes)?;
+
+            // Repair what can be repaired, and scan again to pick up the changes.
+            if fix {
+                // The `unwrap`s are safe assuming no poisoning.
+                let tags_map = tags.lock().unwrap();
+                let refs_vec = refs.lock().unwrap();
+                let files_vec = files.lock().unwrap();
+                let dirs_vec = dirs.lock().unwrap();
+                let specs_vec = specs.lock().unwrap();
+                let mut corrections = fix::corrections(
+                    &tags_map.keys().chain(&provided).cloned().collect(),
+                    &refs_vec,
+                    &files_vec,
+                    &scanned.lock().unwrap(),
+                );
+
+                // Turn duplicate tags into references to the originals, if they're known. Labels
+                // which are meant to be used by several tags are left alone.
+                for (_, _, dupes) in duplicates::groups(&tags_map, &Packages::new(&config.packages))
+                    .iter()
+                    .filter(|(label, _, dupes)| {
+                        dupes.len() > 1
+                            && duplicates::tolerance(&config.duplicate_tolerance, label).is_none()
+                    })
+                {
+                    if let Some(original) = duplicates::original(dupes, |tag| {
+                        git::author_time(&tag.path, tag.line_number)
+                    }) {
+                        corrections.duplicates.extend(
+                            dupes
+                                .iter()
+                                .filter(|tag| !ptr::eq(*tag, original))
+                                .map(|tag| (tag.path.clone(), tag.line_number, tag.label.clone())),
+                        );
+                    }
+                }
+                corrections.ref_sigil.clone_from(&settings.ref_sigil);
+                let changes = fix::apply(
+                    tags_map
+                        .values()
+                        .flatten()
+                        .chain(refs_vec.iter())
+                        .chain(files_vec.iter())
+                        .chain(dirs_vec.iter())
+                        .chain(specs_vec.iter()),
+                    &parser.regexes(),
+                    &corrections,
+                    settings.edit_options,
+                )?;
+                drop((tags_map, refs_vec, files_vec, dirs_vec, specs_vec));
+
+                // Print a summary of the changes. If the report is meant for another program or
+                // nothing should be printed to STDOUT, the summary goes to STDERR instead.
+                let mut summary = String::new();
+                for change in &changes {
+                    let _ = writeln!(
+                        summary,
+                        "{}:{}\n  {}\n  {}",
+                        change.path.to_string_lossy(),
+                        change.line_number,
+                        format!("- {}", change.before).red(),
+                        format!("+ {}", change.after).green(),
+                    );
+                }
+                let _ = writeln!(
+                    summary,
+                    "{} {} in {}.",
+                    if settings.edit_options.dry_run {
+                        "Would fix"
+                    } else {
+                        "Fixed"
+                    },
+                    count::count(changes.len(), "directive"),
+                    count::count(
+                        changes
+                            .iter()
+                            .map(|change| &change.path)
+                            .collect::<HashSet<_>>()
+                            .len(),
+                        "file",
+                    ),
+                );
+                if reports
+                    .iter()
+                    .all(|report| report.format == ReportFormat::Human)
+                    && !quiet
+                {
+                    println!("{summary}");
+                } else {
+                    eprintln!("{summary}");
+                }
+
+                if !changes.is_empty() && !settings.edit_options.dry_run {
+                    Scan {
+                        tags,
+                        refs,
+                        files,
+                        dirs,
+                        specs,
+                        urls,
+                        scanned,
+                        conflicts,
+                        files_scanned,
+                        spill,
+                    } = scan(
+                        &settings.paths,
+                        &settings.excludes,
+                        None,
+                        settings.scan_copies,
+                        settings.max_memory,
+                        settings.progress_format,
+                        &parser,
+                    );
+                }
+            }
+
+            // Run the checks on the directives found by a scan, and report the result.
+            let check = |scan: &Scan, start: Instant| {
+                let Scan {
+                    tags,
+                    refs,
+                    files,
+                    dirs,
+                    specs,
+                    urls,
+                    scanned,
+                    conflicts,
+                    files_scanned,
+                    spill,
+                } = scan;
+
+                // Run the checks. The `unwrap`s are safe assuming no poisoning.
+                let tags = tags.lock().unwrap();
+                let mut refs = refs.lock().unwrap();
+                let spill = spill.lock().unwrap();
+                let mut ref_batches =
+                    |visit: &mut dyn FnMut(&[Directive])| spill.batches(&mut refs, visit);
+                let files = files.lock().unwrap();
+                let dirs = dirs.lock().unwrap();
+                let specs = specs.lock().unwrap();
+                let urls = urls.lock().unwrap();
+                let all_tags = tags.values().flatten().cloned().collect::<Vec<_>>();
+                let mut diagnostics = Vec::<Diagnostic>::new();
 
-            // Convert the `tags` map into a set and check for duplicates. The `unwrap` is safe
+                // Check that tag owners agree with CODEOWNERS, if there is such a file.
+                if let Some(code_owners) = codeowners::load()? {
+                    diagnostics.extend(codeowners::check(&code_owners, &all_tags));
+                }
+
+                // Ask the label validators about the labels, if there are any.
+                diagnostics.extend(label_validators::check(
+                    &all_tags,
+                    &config.label_validators,
+                )?);
+
+                // Look up the issues which tags are waiting for, if there's a tracker to ask.
+                diagnostics.extend(issues::check(&all_tags, &config.issues));
+
+                // Check the file and directory references. Each path is only statted once, even if
+                // it appears in both. The `unwrap` is safe assuming no poisoning.
+                let labels = files.iter().chain(dirs.iter()).map(|r#ref| &*r#ref.label);
+                let targets = if scanned_files_only {
+                    stat::scanned_targets(labels, &scanned.lock().unwrap())
+                } else {
+                    stat::stat_all(labels)
+                };
+                diagnostics.extend(file_references::check(&files, &targets));
+                diagnostics.extend(dir_references::check(&dirs, &targets));
+
+                // Check the spec references.
+                diagnostics.extend(spec_references::check(&specs));
+
+                // Check the URL references, if the configuration asks for it.
+                if config.urls.check {
+                    diagnostics.extend(url_references::check(&urls, &config.urls)?);
+                }
+
+                // Check for text which looks like a directive but isn't one, if the configuration
+                // asks for it. The `unwrap` is safe assuming no poisoning.
+                if let Some(patterns) = &malformed_patterns {
+                    diagnostics.extend(malformed_directives::check_files(
+                        &scanned.lock().unwrap(),
+                        patterns,
+                    ));
+                }
+
+                // Spell-check the descriptions, if requested.
+                let dictionary = lint_descriptions
+                    .then(|| spelling::Dictionary::load(config.dictionary.as_deref()))
+                    .transpose()?;
+                if let Some(dictionary) = &dictionary {
+                    diagnostics.extend(spelling::check(
+                        all_tags
+                            .iter()
+                            .chain(files.iter())
+                            .chain(dirs.iter())
+                            .chain(specs.iter())
+                            .chain(urls.iter()),
+                        dictionary,
+                    ));
+                }
+
+                // Count the tag references. Check for references with descriptions which mention
+                // paths which don't exist, if the configuration asks for it, and spell-check their
+                // descriptions if requested.
+                let mut ref_count = 0;
+                ref_batches(&mut |refs| {
+                    ref_count += refs.len();
+                    if config.warn_stale_descriptions {
+                        diagnostics.extend(stale_descriptions::check_paths(refs));
+                    }
+                    if let Some(dictionary) = &dictionary {
+                        diagnostics.extend(spelling::check(refs, dictionary));
+                    }
+                })?;
+
+                // Report any merge conflicts. The `unwrap` is safe assuming no poisoning.
+                diagnostics.extend(conflicts::check(
+                    &conflicts.lock().unwrap(),
+                    fail_on_conflicts,
+                ));
+
+                // Run the checks which don't depend on the filesystem. The `unwrap` is safe
+                // assuming no poisoning.
+                diagnostics.extend(check_directives(
+                    &config,
+                    &policy,
+                    &tags,
+                    &provided,
+                    &mut ref_batches,
+                    &files,
+                    &dirs,
+                    &specs,
+                    &urls,
+                    &scanned.lock().unwrap(),
+                )?);
+
+                // Report the result, as adjusted by the configuration file.
+                report_results(
+                    &reports,
+                    base_url.as_deref(),
+                    quiet,
+                    max_warnings,
+                    adjust_diagnostics(&config, diagnostics),
+                    &Totals {
+                        tags: tags.len(),
+                        tag_references: ref_count,
+                        file_references: files.len(),
+                        directory_references: dirs.len(),
+                        spec_references: specs.len(),
+                        url_references: urls.len(),
+                        files_scanned: *files_scanned,
+                        duration_seconds: start.elapsed().as_secs_f64(),
+                        configuration: explain_pass.then(|| configuration.clone()),
+                    },
+                    &format!(
+                        "{}, {}, {}, and {} validated in {}.",
+                        count::count(tags.len(), "tag"),
+                        count::count(ref_count, "tag reference"),
+                        count::count(files.len(), "file reference"),
+                        count::count(dirs.len(), "directory reference"),
+                        count::count(*files_scanned, "file"),
+                    ),
+                )
+            };
+
+            // Check once, or keep checking as files change. Problems found while watching are
+            // reported, but they don't stop the watch.
+            let scan = Scan {
+                tags,
+                refs,
+                files,
+                dirs,
+                specs,
+                urls,
+                scanned,
+                conflicts,
+                files_scanned,
+                spill,
+            };
+            let Some(mut index) = watch_index else {
+                return check(&scan, start);
+            };
+            let mut watch = watch::Watch::new(&settings.paths, &index)?;
+            let mut result = check(&scan, start);
+            loop {
+                if let Err(error) = result {
+                    eprintln!("{}", error.red());
+                }
+                let changed = watch.wait()?;
+                let start = Instant::now();
+                result = if watch.refresh(
+                    &mut index,
+                    &changed,
+                    &settings.excludes,
+                    &parser.settings(),
+                    |path, contents| parser.parse_contents(path, contents),
+                ) {
+                    check(&scan_index(index.clone()), start)
+                } else {
+                    Ok(())
+                };
+            }
+        }
+
+        Subcommand::ListTags {
+            by_owner,
+            grep,
+            listing,
+        } => {
+            // Compile the search pattern, if any.
+            let grep = grep
+                .map(|pattern| {
+                    Regex::new(&pattern)
+                        .map_err(|error| format!("Invalid pattern `{pattern}`: {error}"))
+                })
+                .transpose()?;
+
+            // Find the tags which match the search pattern and the filter. The `unwrap`s are safe
             // assuming no poisoning.
-            errors.extend(duplicates::check(&tags.lock().unwrap()));
+            let tags = select(
+                &listing,
+                tags.lock().unwrap().values().flatten().filter(|tag| {
+                    grep.as_ref().is_none_or(|grep| {
+                        grep.is_match(&tag.label) || grep.is_match(&tag.description)
+                    })
+                }),
+                &stats::ref_counts(&refs.lock().unwrap()),
+            )?;
 
-            // Check the tag references. The `unwrap`s are safe assuming no poisoning.
-            let tags = tags
+            if by_owner {
+                // Group the tags by owner.
+                let mut owners = BTreeMap::<Option<String>, Vec<Directive>>::new();
+                for tag in &tags {
+                    owners
+                        .entry(tag.metadata.get(codeowners::OWNER_KEY).cloned())
+                        .or_default()
+                        .push(tag.clone());
+                }
+
+                // Print the groups, with the unowned tags last.
+                let (unowned, owned): (Vec<_>, Vec<_>) =
+                    owners.into_iter().partition(|(owner, _)| owner.is_none());
+                for (owner, tags) in owned.into_iter().chain(unowned) {
+                    println!("{}", owner.as_deref().unwrap_or("(no owner)"));
+                    for tag in tags {
+                        println!("  {tag}");
+                    }
+                }
+            } else {
+                // Print the tags.
+                output::emit(&listing.format, &tags)?;
+            }
+
+            fail_if_any(&listing, "tags", &tags)?;
+        }
+
+        Subcommand::ListRefs { label, listing } => {
+            // Print the tag references, or just the ones to the given tag. The `unwrap` is safe
+            // assuming no poisoning.
+            let refs = refs.lock().unwrap();
+            list(
+                &listing,
+                "tag references",
+                refs.iter()
+                    .filter(|r#ref| label.as_ref().is_none_or(|label| r#ref.label == *label)),
+                &stats::ref_counts(&refs),
+            )?;
+        }
+
+        Subcommand::ListFiles(listing) => {
+            // Print the file references. The `unwrap`s are safe assuming no poisoning.
+            list(
+                &listing,
+                "file references",
+                files.lock().unwrap().iter(),
+                &stats::ref_counts(&refs.lock().unwrap()),
+            )?;
+        }
+
+        Subcommand::ListDirs(listing) => {
+            // Print the directory references. The `unwrap`s are safe assuming no poisoning.
+            list(
+                &listing,
+                "directory references",
+                dirs.lock().unwrap().iter(),
+                &stats::ref_counts(&refs.lock().unwrap()),
+            )?;
+        }
+
+        Subcommand::ListDupes(listing) => {
+            // Group the duplicate tags by label, so the ones with the same label are listed
+            // together. Tags in different packages aren't duplicates, nor are tags which the
+            // `duplicate-tolerance` setting allows. The `unwrap` is safe assuming no poisoning.
+            let tags = tags.lock().unwrap();
+            let groups = duplicates::groups(&tags, &Packages::new(&config.packages));
+            let mut dupes = groups
+                .iter()
+                .filter(|(label, _, tags)| {
+                    tags.len() > 1
+                        && duplicates::is_violation(&config.duplicate_tolerance, label, tags.len())
+                })
+                .flat_map(|(_, _, tags)| tags)
+                .collect::<Vec<_>>();
+            dupes.sort_by(|x, y| {
+                (&x.label, &x.path, x.line_number).cmp(&(&y.label, &y.path, y.line_number))
+            });
+
+            // Print the duplicate tags. The `unwrap` is safe assuming no poisoning.
+            list(
+                &listing,
+                "duplicate tags",
+                dupes,
+                &stats::ref_counts(&refs.lock().unwrap()),
+            )?;
+        }
+
+        Subcommand::ListUnused {
+            ignore,
+            only,
+            listing,
+        } => {
+            // Remove all the referenced tags. The `unwrap` is safe assuming no poisoning.
+            for r#ref in refs.lock().unwrap().iter() {
+                tags.lock()
+                    .unwrap() // Safe assuming no poisoning
+                    .remove(&r#ref.label);
+            }
+
+            // Print the remaining tags, except for the ones which are filtered out. The `unwrap`s
+            // are safe assuming no poisoning.
+            list(
+                &listing,
+                "unused tags",
+                tags.lock().unwrap().values().flatten().filter(|tag| {
+                    !ignore.iter().any(|regex| regex.is_match(&tag.label))
+                        && (only.is_empty() || only.iter().any(|regex| regex.is_match(&tag.label)))
+                }),
+                &stats::ref_counts(&refs.lock().unwrap()),
+            )?;
+        }
+
+        Subcommand::Query { query, format } => {
+            // Parse the query.
+            let query = query::parse(&query)?;
+
+            // Find the matching directives. The `unwrap`s are safe assuming no poisoning.
+            let refs = refs.lock().unwrap();
+            let ref_counts = stats::ref_counts(&refs);
+            let matches = tags
                 .lock()
                 .unwrap()
-                .keys()
+                .values()
+                .flatten()
+                .chain(refs.iter())
+                .chain(files.lock().unwrap().iter())
+                .chain(dirs.lock().unwrap().iter())
+                .chain(specs.lock().unwrap().iter())
+                .chain(urls.lock().unwrap().iter())
+                .filter(|directive| query.matches(directive, &ref_counts))
                 .cloned()
-                .collect::<HashSet<String>>();
+                .collect::<Vec<_>>();
+            output::emit(&format, &matches)?;
+        }
+
+        Subcommand::Reachable {
+            target,
+            edges,
+            format,
+        } => {
+            // Build the graph. The `unwrap`s are safe assuming no poisoning.
+            let tags = tags.lock().unwrap();
+            let refs = refs.lock().unwrap();
+            let graph = Graph::new(&tags, &refs, edges);
+
+            // Find the labels to print.
+            let tag = |label: &str| {
+                tags.get(label)
+                    .ok_or_else(|| format!("No tag found for `{label}`."))
+            };
+            let labels = match &target {
+                Reachability::From(from) => {
+                    tag(from)?;
+                    graph.reachable(from)
+                }
+                Reachability::PathBetween(from, to) => {
+                    tag(from)?;
+                    tag(to)?;
+                    graph
+                        .path(from, to)
+                        .ok_or_else(|| format!("There is no path from `{from}` to `{to}`."))?
+                }
+            };
+
+            // Print the tags with those labels.
+            let mut directives = vec![];
+            for label in labels {
+                directives.extend(tag(label)?.iter().cloned());
+            }
+            output::emit(&format, &directives)?;
+        }
+
+        Subcommand::Stats {
+            hotspots,
+            owners,
+            threshold,
+            top,
+        } => {
+            // The `unwrap`s are safe assuming no poisoning.
+            let tags = tags.lock().unwrap();
             let refs = refs.lock().unwrap();
-            errors.extend(tag_references::check(&tags, &refs));
 
-            // Check the file references. The `unwrap` is safe assuming no poisoning.
-            errors.extend(file_references::check(&files.lock().unwrap()));
+            // Print the totals. As with `badge` and `--owners`, each tag counts, even if another
+            // one has the same label.
+            let counts = stats::ref_counts(&refs);
+            println!("Tags: {}", tags.values().map(Vec::len).sum::<usize>());
+            println!("Tag references: {}", refs.len());
+            println!("File references: {}", files.lock().unwrap().len());
+            println!("Directory references: {}", dirs.lock().unwrap().len());
+            println!(
+                "Unused tags: {}",
+                tags.iter()
+                    .filter(|(label, _)| !counts.contains_key(label.as_str()))
+                    .map(|(_, dupes)| dupes.len())
+                    .sum::<usize>(),
+            );
+            println!("Files scanned: {files_scanned}");
 
-            // Check the directory references. The `unwrap` is safe assuming no poisoning.
-            errors.extend(dir_references::check(&dirs.lock().unwrap()));
+            // Print the hotspots, if requested.
+            if hotspots {
+                let hotspots = stats::hotspots(&tags, &refs, threshold, top);
 
-            // Check for any errors and report the result.
-            if errors.is_empty() {
+                println!();
                 println!(
-                    "{}",
-                    format!(
-                        "{}, {}, {}, and {} validated in {}.",
-                        count::count(tags.len(), "tag"),
-                        count::count(refs.len(), "tag reference"),
-                        // The `unwrap` is safe assuming no poisoning.
-                        count::count(files.lock().unwrap().len(), "file reference"),
-                        // The `unwrap` is safe assuming no poisoning.
-                        count::count(dirs.lock().unwrap().len(), "directory reference"),
-                        count::count(files_scanned, "file"),
-                    )
-                    .green(),
+                    "Tags with more than {}:",
+                    count::count(threshold, "reference"),
                 );
+                for (tag, count) in hotspots.tags {
+                    println!("  {count:>6}  {tag}");
+                }
+
+                println!();
+                println!("Files with the most inbound references:");
+                for (path, count) in hotspots.inbound {
+                    println!(
+                        "  {count:>6}  {}",
+                        path_style::display(&path).to_string_lossy(),
+                    );
+                }
+
+                println!();
+                println!("Files with the most outbound references:");
+                for (path, count) in hotspots.outbound {
+                    println!(
+                        "  {count:>6}  {}",
+                        path_style::display(&path).to_string_lossy(),
+                    );
+                }
+            }
+
+            // Print the owners, if requested. A tag's owner is declared in its metadata; otherwise,
+            // the owners of a directive are those of its file according to CODEOWNERS, or failing
+            // that, whoever wrote its line according to `git blame`.
+            if owners {
+                let code_owners = codeowners::load()?;
+                let owners = stats::owners(&tags, &refs, |directive| {
+                    if let Some(owner) = directive.metadata.get(codeowners::OWNER_KEY) {
+                        return vec![owner.clone()];
+                    }
+                    if let Some(owners) = code_owners
+                        .as_ref()
+                        .map(|code_owners| code_owners.owners_of(&directive.path))
+                        .filter(|owners| !owners.is_empty())
+                    {
+                        return owners.to_vec();
+                    }
+                    git::author(&directive.path, directive.line_number)
+                        .into_iter()
+                        .collect()
+                });
+
+                println!();
+                println!("Owners:");
+                println!("  {:>6}  {:>6}  {:>8}  Owner", "Tags", "Unused", "Dangling");
+                for (owner, ownership) in owners {
+                    println!(
+                        "  {:>6}  {:>6}  {:>8}  {}",
+                        ownership.tags,
+                        ownership.unused,
+                        ownership.dangling,
+                        owner.as_deref().unwrap_or("(no owner)"),
+                    );
+                }
+            }
+        }
+
+        Subcommand::Ctags { output, etags } => {
+            let output =
+                output.unwrap_or_else(|| Path::new(if etags { "TAGS" } else { "tags" }).to_owned());
+
+            // Editors interpret relative paths in the index as relative to the index itself. If
+            // the index is somewhere other than the working directory, use absolute paths.
+            let current_dir = current_dir()
+                .map_err(|error| format!("Unable to determine the working directory: {error}"))?;
+            let relative = output
+                .parent()
+                .is_none_or(|parent| walk::normalize(parent).as_os_str().is_empty());
+            let display = |path: &Path| {
+                if relative {
+                    walk::normalize(path).to_string_lossy().into_owned()
+                } else {
+                    walk::normalize(&current_dir.join(path))
+                        .to_string_lossy()
+                        .into_owned()
+                }
+            };
+
+            // Render the index. The `unwrap` is safe assuming no poisoning.
+            let tags = tags
+                .lock()
+                .unwrap()
+                .values()
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>();
+            let index = if etags {
+                ctags::etags(&tags, display, |path| read(path)).map_err(|error| {
+                    format!("Unable to read the files containing the tags: {error}")
+                })?
+            } else {
+                ctags::ctags(&tags, display).into_bytes()
+            };
+
+            // Write the index.
+            atomic::write(&output, index)?;
+            println!(
+                "Wrote {} to {}.",
+                count::count(tags.len(), "tag"),
+                output.to_string_lossy(),
+            );
+        }
+
+        Subcommand::Badge => {
+            // Count the tags and dangling references. As with `check`, references to the tags from
+            // the providers aren't dangling. The `unwrap`s are safe assuming no poisoning.
+            let provided =
+                providers::labels(&config.providers, &settings.paths, &settings.excludes)?;
+            let tags = tags.lock().unwrap();
+            let labels = tags.keys().chain(&provided).collect::<HashSet<_>>();
+            let dangling = refs
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|r#ref| !labels.contains(&r#ref.label))
+                .count();
+            print!(
+                "{}",
+                badge::render(tags.values().map(Vec::len).sum(), dangling),
+            );
+        }
+
+        Subcommand::Export { shard, output } => {
+            // Run the checks which depend on the filesystem. The `unwrap`s are safe assuming no
+            // poisoning.
+            let tags = tags
+                .lock()
+                .unwrap()
+                .values()
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>();
+            let refs = refs.lock().unwrap();
+            let files = files.lock().unwrap();
+            let dirs = dirs.lock().unwrap();
+            let specs = specs.lock().unwrap();
+            let urls = urls.lock().unwrap();
+            let mut diagnostics = Vec::<Diagnostic>::new();
+            if let Some(code_owners) = codeowners::load()? {
+                diagnostics.extend(codeowners::check(&code_owners, &tags));
+            }
+            diagnostics.extend(label_validators::check(&tags, &config.label_validators)?);
+            diagnostics.extend(issues::check(&tags, &config.issues));
+            let targets =
+                stat::stat_all(files.iter().chain(dirs.iter()).map(|r#ref| &*r#ref.label));
+            diagnostics.extend(file_references::check(&files, &targets));
+            diagnostics.extend(dir_references::check(&dirs, &targets));
+            diagnostics.extend(spec_references::check(&specs));
+            if config.urls.check {
+                diagnostics.extend(url_references::check(&urls, &config.urls)?);
+            }
+            if config.warn_stale_descriptions {
+                diagnostics.extend(stale_descriptions::check_paths(&refs));
+            }
+            if let Some(patterns) = &malformed_patterns {
+                diagnostics.extend(malformed_directives::check_files(
+                    &scanned.lock().unwrap(),
+                    patterns,
+                ));
+            }
+            diagnostics.extend(conflicts::check(&conflicts.lock().unwrap(), false));
+
+            // Gather everything else for later. The `unwrap` is safe assuming no poisoning.
+            let mut scanned = scanned
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|path| walk::normalize(path).to_string_lossy().into_owned())
+                .collect::<Vec<_>>();
+            scanned.sort();
+            let mut directives = tags
+                .into_iter()
+                .chain(refs.iter().cloned())
+                .chain(files.iter().cloned())
+                .chain(dirs.iter().cloned())
+                .chain(specs.iter().cloned())
+                .chain(urls.iter().cloned())
+                .collect::<Vec<_>>();
+            directives.sort_by(|x, y| (&x.path, x.line_number).cmp(&(&y.path, y.line_number)));
+            let export = export::render(&export::Export {
+                schema_version: SCHEMA_VERSION,
+                version: export::EXPORT_VERSION,
+                shard: shard.map(|shard| shard.to_string()),
+                files_scanned,
+                scanned,
+                directives,
+                diagnostics,
+            });
+
+            // Print or write the results.
+            if let Some(output) = output {
+                atomic::write(&output, export)?;
             } else {
-                return Err(errors.join("\n\n"));
+                print!("{export}");
             }
         }
 
-        Subcommand::ListTags => {
-            // Print all the tags. The `unwrap` is safe assuming no poisoning.
-            for dupes in tags.lock().unwrap().values() {
-                for dupe in dupes {
-                    println!("{dupe}");
+        Subcommand::Merge {
+            exports,
+            reports,
+            base_url,
+            max_warnings,
+        } => {
+            // Load the policy file, and gather the tags from the providers.
+            let policy = policy::load(settings.policy_path.as_deref())?;
+            let provided =
+                providers::labels(&config.providers, &settings.paths, &settings.excludes)?;
+
+            // Combine the exports.
+            let mut tags = HashMap::<String, Vec<Directive>>::new();
+            let mut refs = vec![];
+            let mut files = vec![];
+            let mut dirs = vec![];
+            let mut specs = vec![];
+            let mut urls = vec![];
+            let mut scanned = vec![];
+            let mut files_scanned = 0;
+            let mut diagnostics = vec![];
+            for path in &exports {
+                let export = read_to_string(path)
+                    .map_err(|error| format!("Unable to read {}: {error}", path.to_string_lossy()))
+                    .and_then(|contents| {
+                        export::parse(&contents).map_err(|error| {
+                            format!("Error in {}: {error}", path.to_string_lossy())
+                        })
+                    })?;
+
+                for directive in export.directives {
+                    match directive.r#type {
+                        Type::Tag => tags
+                            .entry(directive.label.clone())
+                            .or_default()
+                            .push(directive),
+                        Type::Ref => refs.push(directive),
+                        Type::File => files.push(directive),
+                        Type::Dir => dirs.push(directive),
+                        Type::Spec => specs.push(directive),
+                        Type::Url => urls.push(directive),
+                    }
                 }
+                scanned.extend(export.scanned.into_iter().map(PathBuf::from));
+                files_scanned += export.files_scanned;
+                diagnostics.extend(export.diagnostics);
             }
+
+            // Run the checks which depend on all the directives, and report the result as adjusted
+            // by the configuration file.
+            diagnostics.extend(check_directives(
+                &config,
+                &policy,
+                &tags,
+                &provided,
+                &mut |visit| {
+                    visit(&refs);
+                    Ok(())
+                },
+                &files,
+                &dirs,
+                &specs,
+                &urls,
+                &scanned,
+            )?);
+            report_results(
+                &reports,
+                base_url.as_deref(),
+                false,
+                max_warnings,
+                adjust_diagnostics(&config, diagnostics),
+                &Totals {
+                    tags: tags.len(),
+                    tag_references: refs.len(),
+                    file_references: files.len(),
+                    directory_references: dirs.len(),
+                    spec_references: specs.len(),
+                    url_references: urls.len(),
+                    files_scanned,
+                    duration_seconds: start.elapsed().as_secs_f64(),
+                    configuration: None,
+                },
+                &format!(
+                    "{}, {}, {}, and {} validated in {} from {}.",
+                    count::count(tags.len(), "tag"),
+                    count::count(refs.len(), "tag reference"),
+                    count::count(files.len(), "file reference"),
+                    count::count(dirs.len(), "directory reference"),
+                    count::count(files_scanned, "file"),
+                    count::count(exports.len(), "export"),
+                ),
+            )?;
         }
 
-        Subcommand::ListRefs => {
-            // Print all the tag references. The `unwrap` is safe assuming no poisoning.
-            for r#ref in refs.lock().unwrap().iter() {
-                println!("{ref}");
+        Subcommand::Links { base_url, format } => {
+            // Gather all the directives in order. The `unwrap`s are safe assuming no poisoning.
+            let mut directives = tags
+                .lock()
+                .unwrap()
+                .values()
+                .flatten()
+                .chain(refs.lock().unwrap().iter())
+                .chain(files.lock().unwrap().iter())
+                .chain(dirs.lock().unwrap().iter())
+                .chain(specs.lock().unwrap().iter())
+                .chain(urls.lock().unwrap().iter())
+                .cloned()
+                .collect::<Vec<_>>();
+            directives.sort_by(|x, y| (&x.path, x.line_number).cmp(&(&y.path, y.line_number)));
+
+            // Print them with their links.
+            links::emit(&format, &base_url, &directives)?;
+        }
+
+        Subcommand::CompleteLabel { prefix } => {
+            // The `BTreeSet` sorts the labels. The `unwrap` is safe assuming no poisoning.
+            for label in tags
+                .lock()
+                .unwrap()
+                .keys()
+                .filter(|label| label.starts_with(&prefix))
+                .collect::<BTreeSet<_>>()
+            {
+                println!("{label}");
+            }
+        }
+
+        Subcommand::CheckDeletions { changed_since } => {
+            // Find the files which were deleted or renamed, and the tags which were in them. The
+            // revision is only searched if something was removed, since Git would otherwise search
+            // everything.
+            let removed = git::removed(&changed_since)?;
+            let old_tags = if removed.is_empty() {
+                vec![]
+            } else {
+                scan_revision(
+                    &changed_since,
+                    &removed
+                        .iter()
+                        .map(|(path, _)| path.clone())
+                        .collect::<Vec<_>>(),
+                    &settings.excludes,
+                    &[&settings.tag_sigil],
+                    &config.sigil_sets,
+                    &parser,
+                )?
+                .tags
+            };
+
+            // Find the references which point into them. The `unwrap`s are safe assuming no
+            // poisoning.
+            let labels = tags
+                .lock()
+                .unwrap()
+                .keys()
+                .cloned()
+                .chain(providers::labels(
+                    &config.providers,
+                    &settings.paths,
+                    &settings.excludes,
+                )?)
+                .collect::<HashSet<_>>();
+            let diagnostics = adjust_diagnostics(
+                &config,
+                deletions::check(
+                    &changed_since,
+                    &removed,
+                    &old_tags,
+                    &labels,
+                    &refs.lock().unwrap(),
+                    &files.lock().unwrap(),
+                    Path::is_file,
+                ),
+            );
+
+            // Report them like the check does.
+            let (errors, warnings): (Vec<_>, Vec<_>) = diagnostics
+                .into_iter()
+                .partition(|diagnostic| diagnostic.severity == Severity::Error);
+            for warning in &warnings {
+                eprintln!("{}\n", warning.message.yellow());
             }
+            if !errors.is_empty() {
+                return Err(errors
+                    .iter()
+                    .map(|error| error.message.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n\n"));
+            }
+            println!(
+                "Nothing points into the {} deleted or renamed since {changed_since}.",
+                count::count(removed.len(), "file"),
+            );
         }
 
-        Subcommand::ListFiles => {
-            // Print all the file references. The `unwrap` is safe assuming no poisoning.
-            for file in files.lock().unwrap().iter() {
-                println!("{file}");
+        Subcommand::Changelog { since, separator } => {
+            // Find the tags as of the revision, and compare them to the current ones. The `unwrap`
+            // is safe assuming no poisoning.
+            let commit = git::commit(&since)?;
+            let mut old = HashMap::<String, Vec<Directive>>::new();
+            for tag in scan_revision(
+                &since,
+                &settings.paths,
+                &settings.excludes,
+                &[&settings.tag_sigil],
+                &config.sigil_sets,
+                &parser,
+            )?
+            .tags
+            {
+                old.entry(tag.label.clone()).or_default().push(tag);
             }
+            let tags = tags.lock().unwrap();
+            print!(
+                "{}",
+                changelog::render(
+                    &changelog::changes(&old, &tags),
+                    &since,
+                    &commit,
+                    &separator,
+                ),
+            );
         }
 
-        Subcommand::ListDirs => {
-            // Print all the directory references. The `unwrap` is safe assuming no poisoning.
-            for dir in dirs.lock().unwrap().iter() {
-                println!("{dir}");
+        Subcommand::History {
+            since,
+            interval,
+            format,
+        } => {
+            // Count the directives as of the last commit on or before each date. Dates with the
+            // same commit share the counts, and dates before the first commit have nothing to
+            // count.
+            let today = Date::today();
+            if since > today {
+                return Err(format!("The date {since} is after today ({today})."));
+            }
+            let mut snapshots = vec![];
+            let mut previous = None::<(String, Directives)>;
+            for date in history::dates(since, today, interval) {
+                let Some(commit) = git::commit_before(&format!("{date} 23:59:59"))? else {
+                    snapshots.push(history::Snapshot::new(date, "", &Directives::default()));
+                    continue;
+                };
+                if previous.as_ref().is_none_or(|(last, _)| *last != commit) {
+                    let directives = scan_revision(
+                        &commit,
+                        &settings.paths,
+                        &settings.excludes,
+                        &[&settings.tag_sigil, &settings.ref_sigil],
+                        &config.sigil_sets,
+                        &parser,
+                    )?;
+                    previous = Some((commit.clone(), directives));
+                }
+
+                // The `unwrap` is safe because the directives were just found, if necessary.
+                snapshots.push(history::Snapshot::new(
+                    date,
+                    &commit,
+                    &previous.as_ref().unwrap().1,
+                ));
             }
+
+            print!("{}", history::render(format, &snapshots));
         }
 
-        Subcommand::ListUnused(error_flag_set) => {
-            // Remove all the referenced tags. The `unwrap` is safe assuming no poisoning.
-            for r#ref in refs.lock().unwrap().iter() {
-                tags.lock()
-                    .unwrap() // Safe assuming no poisoning
-                    .remove(&r#ref.label);
+        Subcommand::ReportDiff { old, new, format } => {
+            // Read the problems from both results.
+            let read = |path: &Path| {
+                read_to_string(path)
+                    .map_err(|error| format!("Unable to read {}: {error}", path.to_string_lossy()))
+                    .and_then(|contents| {
+                        report_diff::parse(&contents).map_err(|error| {
+                            format!("Error in {}: {error}", path.to_string_lossy())
+                        })
+                    })
+            };
+            let report_diff = report_diff::diff(&read(&old)?, &read(&new)?);
+
+            // Print the comparison, and fail if anything got worse.
+            print!("{}", report_diff::render(format, &report_diff));
+            let new_errors = report_diff.new_errors();
+            if new_errors > 0 {
+                return Err(format!("Found {}.", count::count(new_errors, "new error")));
+            }
+        }
+
+        Subcommand::Docs {
+            format,
+            output,
+            base_url,
+            separator,
+        } => {
+            // Render the glossary. The `unwrap`s are safe assuming no poisoning.
+            let docs = docs::render(
+                format,
+                &tags.lock().unwrap(),
+                &refs.lock().unwrap(),
+                base_url.as_deref(),
+                &separator,
+            );
+
+            // Print or write the glossary.
+            if let Some(output) = output {
+                atomic::write(&output, docs)?;
+            } else {
+                print!("{docs}");
             }
+        }
+
+        Subcommand::Impact { region, format } => {
+            // Find the tags in the region and their references. The `unwrap`s are safe assuming no
+            // poisoning.
+            let tags = tags.lock().unwrap();
+            let refs = refs.lock().unwrap();
+            let impact = impact::impact(&region, tags.values().flatten(), &refs);
 
-            // Print the remaining tags. The `unwrap` is safe assuming no poisoning.
-            for dupes in tags.lock().unwrap().values() {
-                for dupe in dupes {
-                    println!("{dupe}");
+            if format == Format::Human {
+                // List the references under each tag, with those within the region last.
+                if impact.is_empty() {
+                    println!("No tags are defined in {region}.");
+                }
+                for (tag, refs) in &impact {
+                    println!("{tag}");
+                    if refs.is_empty() {
+                        println!("  (no references)");
+                    }
+                    let (within, elsewhere): (Vec<&Directive>, Vec<_>) = refs
+                        .iter()
+                        .copied()
+                        .partition(|r#ref| region.contains(r#ref));
+                    for r#ref in elsewhere {
+                        println!("  {ref}");
+                    }
+                    if !within.is_empty() {
+                        println!("  Within {region}:");
+                        for r#ref in within {
+                            println!("    {ref}");
+                        }
+                    }
                 }
+            } else {
+                // List each tag followed by its references.
+                let directives = impact
+                    .iter()
+                    .flat_map(|(tag, refs)| once(tag).chain(refs))
+                    .map(|directive| (*directive).clone())
+                    .collect::<Vec<_>>();
+                output::emit(&format, &directives)?;
             }
+        }
 
-            // Error out if the error flag has been passed and there are unused tags.
-            // The `unwrap` is safe assuming no poisoning.
-            if error_flag_set && !tags.lock().unwrap().is_empty() {
-                return Err(format!(
-                    "Found unused tags while using --{LIST_UNUSED_ERROR_OPTION}",
-                ));
+        Subcommand::FileReport { path } => {
+            // Only scanned files have anything to report. The `unwrap` is safe assuming no
+            // poisoning.
+            if !scanned
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|scanned_path| walk::normalize(scanned_path) == walk::normalize(&path))
+            {
+                return Err(format!("{} wasn't scanned.", path.to_string_lossy()));
             }
+
+            // Gather the report. The `unwrap`s are safe assuming no poisoning.
+            let provided =
+                providers::labels(&config.providers, &settings.paths, &settings.excludes)?;
+            let tags = tags.lock().unwrap();
+            let refs = refs.lock().unwrap();
+            let files = files.lock().unwrap();
+            let dirs = dirs.lock().unwrap();
+            let in_file =
+                |r#ref: &&Directive| walk::normalize(&r#ref.path) == walk::normalize(&path);
+            let targets = stat::stat_all(
+                files
+                    .iter()
+                    .chain(dirs.iter())
+                    .filter(in_file)
+                    .map(|r#ref| &*r#ref.label),
+            );
+            let report = file_report::report(
+                &path,
+                &tags.values().flatten().collect::<Vec<_>>(),
+                &refs,
+                files.iter().chain(dirs.iter()),
+                &Packages::new(&config.packages),
+                &provided,
+                &targets,
+            );
+
+            print!("{}", file_report::render(&path, &report));
+        }
+
+        Subcommand::Lsp => {
+            // Find the problems the same way as the check does for duplicate tags and dangling
+            // references, which are the problems that typing a directive can cause.
+            let provided =
+                providers::labels(&config.providers, &settings.paths, &settings.excludes)?;
+            let packages = Packages::new(&config.packages);
+            let check = |tags: &HashMap<String, Vec<Directive>>, refs: &[Directive]| {
+                let labels = tags
+                    .keys()
+                    .chain(&provided)
+                    .cloned()
+                    .collect::<HashSet<_>>();
+                let mut diagnostics =
+                    duplicates::check(tags, &packages, &config.duplicate_tolerance, |tag| {
+                        git::author_time(&tag.path, tag.line_number)
+                    });
+                diagnostics.extend(tag_references::check(&labels, refs));
+                severity::apply(
+                    &config.severity,
+                    suppressions::apply(&config.suppressions, diagnostics).0,
+                )
+            };
+
+            // Files opened in the editor are parsed as they're edited, unless they're excluded.
+            let parse = |path: &Path, contents: &[u8]| parser.parse_contents(path, contents);
+            let track = |path: &Path| {
+                !settings
+                    .excludes
+                    .iter()
+                    .any(|exclude| exclude.is_match(path))
+            };
+
+            // Serve the directives found by the scan. The `unwrap`s are safe assuming no
+            // poisoning.
+            let root = current_dir()
+                .map_err(|error| format!("Unable to determine the working directory: {error}"))?;
+            let directives = tags
+                .lock()
+                .unwrap()
+                .values()
+                .flatten()
+                .chain(refs.lock().unwrap().iter())
+                .cloned()
+                .collect::<Vec<_>>();
+            lsp::Server::new(root, directives, &parse, &check, &track)
+                .serve(&mut stdin().lock(), &mut stdout().lock())?;
+        }
+
+        Subcommand::Rename { old, new } => {
+            // Check the rename against the tags. The `unwrap`s are safe assuming no poisoning.
+            let tags = tags.lock().unwrap();
+            let refs = refs.lock().unwrap();
+            let renames = rename::validate_one(&old, &new, &tags.keys().cloned().collect())?;
+            rename_tags(&parser, &tags, &refs, &renames, settings.edit_options)?;
+        }
+
+        Subcommand::RenameBatch { mapping } => {
+            // Read the mapping, and check it against the tags. The `unwrap`s are safe assuming no
+            // poisoning.
+            let contents = read_to_string(&mapping).map_err(|error| {
+                format!("Unable to read {}: {error}", mapping.to_string_lossy())
+            })?;
+            let tags = tags.lock().unwrap();
+            let refs = refs.lock().unwrap();
+            let renames = rename::validate(
+                &rename::parse_mapping(&contents)?,
+                &tags.keys().cloned().collect(),
+            )?;
+            rename_tags(&parser, &tags, &refs, &renames, settings.edit_options)?;
+        }
+
+        Subcommand::Doctor { why } => {
+            println!("{}", doctor::why(&why, &settings.paths, &settings.excludes));
+        }
+
+        Subcommand::UpdateIndex => {
+            // Load the existing index, unless it was created with different settings.
+            let mut index = directive_index::load(directive_index_path)?
+                .filter(|index| index.settings == DirectiveIndex::new(&parser.settings()).settings)
+                .unwrap_or_else(|| DirectiveIndex::new(&parser.settings()));
+
+            // Bring it up to date.
+            let parser_clone = parser.clone();
+            let changed = index.update(
+                directive_index_path,
+                &settings.paths,
+                &settings.excludes,
+                settings.scan_copies,
+                &parser.settings(),
+                move |path, contents| parser_clone.parse_contents(path, contents),
+            );
+            directive_index::save(directive_index_path, &index)?;
+            println!(
+                "{} {} (generation {}, {}).",
+                if changed { "Updated" } else { "Checked" },
+                directive_index_path.to_string_lossy(),
+                index.generation,
+                count::count(index.files.len(), "file"),
+            );
+        }
+
+        Subcommand::Index { output, base_url } => {
+            // Render the index. The `unwrap`s are safe assuming no poisoning.
+            let tags = tags
+                .lock()
+                .unwrap()
+                .values()
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>();
+            let index = index::render(&tags, &refs.lock().unwrap(), base_url.as_deref());
+
+            // Write the index.
+            atomic::write(&output, index)?;
+            println!(
+                "Wrote {} to {}.",
+                count::count(tags.len(), "tag"),
+                output.to_string_lossy(),
+            );
         }
     }
 
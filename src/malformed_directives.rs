@@ -0,0 +1,204 @@
+use {
+    crate::{
+        diagnostic::{self, Diagnostic},
+        distance::levenshtein,
+        path_style::display,
+        walk::quote,
+    },
+    regex::{escape, Regex},
+    std::{
+        fs::read,
+        path::{Path, PathBuf},
+    },
+};
+
+// This struct holds the regular expressions for finding the almost-directives for a set of sigils.
+pub struct Patterns {
+    sigils: Vec<String>,
+
+    // A sigil followed by a label without a colon, as in `[tag foo]`
+    missing_colon: Regex,
+
+    // A sigil and a colon without a closing bracket, as in `[tag:foo`
+    missing_bracket: Regex,
+
+    // Something that looks like a directive with any word as its sigil, as in `[rf:foo]`
+    any_sigil: Regex,
+}
+
+impl Patterns {
+    // This function compiles the regular expressions for the given sigils.
+    pub fn new<'a>(sigils: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut sigils = sigils
+            .into_iter()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+        sigils.sort();
+        sigils.dedup();
+        let alternatives = sigils
+            .iter()
+            .map(|sigil| escape(sigil))
+            .collect::<Vec<_>>()
+            .join("|");
+
+        // The `unwrap`s are safe because the sigils are escaped.
+        Self {
+            missing_colon: Regex::new(&format!(
+                r"(?i)\[\s*({alternatives})\s+([^\[\]:\s][^\[\]:]*?)\s*\]",
+            ))
+            .unwrap(),
+            missing_bracket: Regex::new(&format!(r"(?i)\[\s*({alternatives})\s*:([^\[\]]*)(\[|$)"))
+                .unwrap(),
+            any_sigil: Regex::new(r"\[\s*([A-Za-z][\w-]*)\s*:\s*([^\[\]]*?)\s*\]").unwrap(),
+            sigils,
+        }
+    }
+
+    // This function returns the sigil which a word is probably a typo of, if any.
+    fn nearest(&self, word: &str) -> Option<&str> {
+        let word = word.to_lowercase();
+        if self.sigils.contains(&word) {
+            return None;
+        }
+        self.sigils
+            .iter()
+            .find(|sigil| levenshtein(&word, sigil) == 1)
+            .map(String::as_str)
+    }
+}
+
+// This function finds the text in a file which looks like a directive but isn't one: a sigil
+// without a colon, a directive without a closing bracket, or a directive with a sigil which is
+// one edit away from a real one. It returns a vector of warnings, with a suggestion where possible.
+pub fn check(path: &Path, contents: &str, patterns: &Patterns) -> Vec<Diagnostic> {
+    let mut warnings = Vec::<Diagnostic>::new();
+
+    // The `unwrap`s below are safe because the whole match always participates.
+    for (i, line) in contents.lines().enumerate() {
+        let mut found = vec![];
+        for captures in patterns.missing_colon.captures_iter(line) {
+            found.push((
+                captures.get(0).unwrap().start(),
+                captures[0].to_owned(),
+                format!("Did you mean `[{}:{}]`?", &captures[1], &captures[2].trim()),
+            ));
+        }
+        for captures in patterns.missing_bracket.captures_iter(line) {
+            let whole = captures.get(0).unwrap();
+            found.push((
+                whole.start(),
+                whole.as_str().trim_end_matches('[').trim_end().to_owned(),
+                "It's missing a closing `]`.".to_owned(),
+            ));
+        }
+        for captures in patterns.any_sigil.captures_iter(line) {
+            if let Some(sigil) = patterns.nearest(&captures[1]) {
+                found.push((
+                    captures.get(0).unwrap().start(),
+                    captures[0].to_owned(),
+                    format!("Did you mean `[{sigil}:{}]`?", &captures[2]),
+                ));
+            }
+        }
+
+        // Report the problems in the order they appear on the line.
+        found.sort_by_key(|(start, _, _)| *start);
+        for (_, text, suggestion) in found {
+            warnings.push(Diagnostic {
+                context: Some(line.trim().to_owned()),
+                ..Diagnostic::warning_at(
+                    diagnostic::MALFORMED_DIRECTIVE,
+                    path,
+                    i + 1,
+                    format!(
+                        "`{text}` @ {}:{} looks like a directive, but it isn't one. {suggestion}",
+                        quote(&display(path)),
+                        i + 1,
+                    ),
+                )
+            });
+        }
+    }
+
+    warnings
+}
+
+// This function finds the almost-directives in the given files. Files which can't be read as text
+// are skipped.
+pub fn check_files(paths: &[PathBuf], patterns: &Patterns) -> Vec<Diagnostic> {
+    paths
+        .iter()
+        .filter_map(|path| {
+            let contents = String::from_utf8(read(path).ok()?).ok()?;
+            Some(check(path, &contents, patterns))
+        })
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::malformed_directives::{check, Patterns},
+        std::path::Path,
+    };
+
+    // The suggestions in the messages are written with `[?` so they aren't found by the self-check.
+    fn messages(contents: &str) -> Vec<String> {
+        let patterns = Patterns::new(["tag", "ref", "file", "dir", "spec", "url", "anchor"]);
+        check(Path::new("a.rs"), contents, &patterns)
+            .into_iter()
+            .map(|warning| warning.message.replace("mean `[", "mean `[?"))
+            .collect()
+    }
+
+    #[test]
+    fn check_well_formed() {
+        assert!(
+            messages(
+                &"// [?tag:foo] [?REF:foo] [?anchor:bar] [link] [x: y] [note: a] [see also]"
+                    .replace('?', ""),
+            )
+            .is_empty(),
+        );
+    }
+
+    #[test]
+    fn check_missing_colon() {
+        assert_eq!(
+            messages("// [tag foo bar]\n// [ref  baz ]"),
+            [
+                "`[tag foo bar]` @ a.rs:1 looks like a directive, but it isn't one. Did you mean \
+                 `[?tag:foo bar]`?",
+                "`[ref  baz ]` @ a.rs:2 looks like a directive, but it isn't one. Did you mean \
+                 `[?ref:baz]`?",
+            ],
+        );
+    }
+
+    #[test]
+    fn check_missing_bracket() {
+        assert_eq!(
+            messages(&"// [?tag:foo\n// [?ref:bar [?ref:baz]".replace('?', "")),
+            [
+                "`[tag:foo` @ a.rs:1 looks like a directive, but it isn't one. It's missing a \
+                 closing `]`.",
+                "`[ref:bar` @ a.rs:2 looks like a directive, but it isn't one. It's missing a \
+                 closing `]`.",
+            ],
+        );
+    }
+
+    #[test]
+    fn check_misspelled_sigil() {
+        assert_eq!(
+            messages("// [rf:foo] and [Tga:bar] and [tags:baz]"),
+            [
+                "`[rf:foo]` @ a.rs:1 looks like a directive, but it isn't one. Did you mean \
+                 `[?ref:foo]`?",
+                "`[tags:baz]` @ a.rs:1 looks like a directive, but it isn't one. Did you mean \
+                 `[?tag:baz]`?",
+            ],
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use {serde::Deserialize, std::path::Path};
+
+// This struct represents the parts of a Jupyter notebook which are searched for directives.
+#[derive(Deserialize)]
+struct Notebook {
+    cells: Vec<Cell>,
+}
+
+// This struct represents a notebook cell. The source is stored either as a single string or as a
+// list of lines (each with its own line break).
+#[derive(Deserialize)]
+struct Cell {
+    cell_type: String,
+    #[serde(default)]
+    source: Source,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Source {
+    Text(String),
+    Lines(Vec<String>),
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Self::Text(String::new())
+    }
+}
+
+// This function determines whether a file is a Jupyter notebook.
+pub fn is_notebook(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "ipynb")
+}
+
+// This function returns the source of each cell in a notebook. Only Markdown and code cells are
+// searched, so other cells (e.g., raw cells) are empty, but they're included so the cell numbers
+// match the notebook. It returns `None` if the notebook can't be parsed.
+pub fn cells(contents: &str) -> Option<Vec<String>> {
+    let notebook = serde_json::from_str::<Notebook>(contents).ok()?;
+
+    Some(
+        notebook
+            .cells
+            .into_iter()
+            .map(|cell| match (cell.cell_type.as_str(), cell.source) {
+                ("markdown" | "code", Source::Text(text)) => text,
+                ("markdown" | "code", Source::Lines(lines)) => lines.concat(),
+                _ => String::new(),
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::notebook::{cells, is_notebook},
+        std::path::Path,
+    };
+
+    #[test]
+    fn cells_sources() {
+        let contents = r##"{
+            "cells": [
+                {"cell_type": "markdown", "source": ["# Title\n", "See [?ref:foo]."]},
+                {"cell_type": "raw", "source": "[?tag:bar]"},
+                {"cell_type": "code", "source": "x = 1\n# [?tag:foo]"}
+            ],
+            "metadata": {},
+            "nbformat": 4,
+            "nbformat_minor": 5
+        }"##
+        .replace('?', "");
+
+        assert_eq!(
+            cells(&contents).unwrap(),
+            vec![
+                "# Title\nSee [?ref:foo].".replace('?', ""),
+                String::new(),
+                "x = 1\n# [?tag:foo]".replace('?', ""),
+            ],
+        );
+    }
+
+    #[test]
+    fn cells_invalid() {
+        assert!(cells("{").is_none());
+        assert!(cells("{\"cells\": 1}").is_none());
+    }
+
+    #[test]
+    fn is_notebook_extension() {
+        assert!(is_notebook(Path::new("analysis/notebook.ipynb")));
+        assert!(!is_notebook(Path::new("notebook.json")));
+    }
+}
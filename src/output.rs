@@ -0,0 +1,467 @@
+use {
+    crate::{directive::Directive, path_style::display, schema::SCHEMA_VERSION, walk::quote},
+    serde::Serialize,
+    std::{
+        fmt::{self, Write},
+        process::Command,
+        str::FromStr,
+    },
+};
+
+// This struct represents a listing in the `json` format.
+#[derive(Serialize)]
+pub struct Listing<'a, T> {
+    pub schema_version: usize,
+    pub directives: &'a [T],
+}
+
+// This enum represents the formats in which directives can be listed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Format {
+    Human,
+    Json,
+    Template(Template),
+    Exec { template: ExecTemplate, run: bool },
+}
+
+// The names of the formats, for the command-line interface. The templates for the `template` and
+// `exec` formats are given separately, so they can't be parsed from their names
+// [tag:template_format_name].
+pub const FORMATS: &[&str] = &["human", "json", "template", "exec"];
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Unknown format `{s}`.")),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Human => "human",
+                Self::Json => "json",
+                Self::Template(_) => "template",
+                Self::Exec { .. } => "exec",
+            },
+        )
+    }
+}
+
+// This enum represents a field of a directive which can appear in a template.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Field {
+    Type,
+    Label,
+    Metadata(String),
+    Description,
+    Path,
+    Line,
+    Column,
+    Cell,
+    Context,
+    SigilSet,
+    Url,
+}
+
+// This enum represents a part of a template: either literal text or a placeholder for a field.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Piece {
+    Text(String),
+    Field(Field),
+}
+
+// This struct represents a template for rendering each directive on its own line, such as
+// `{path}:{line}: [{type}:{label}] {description}`. Placeholders are written in braces, and literal
+// braces are written twice (`{{` and `}}`).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+// The names of the fields, for error messages
+const TEMPLATE_FIELDS: &str = "`type`, `label`, `metadata.<key>`, `description`, `path`, `line`, \
+                               `column`, `cell`, `context`, `sigil_set`, or `url`";
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pieces = vec![];
+        let mut text = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest
+                        .find('}')
+                        .ok_or_else(|| format!("Unclosed placeholder in template `{s}`."))?;
+                    let name = &rest[..end];
+                    let field = match name {
+                        "type" => Field::Type,
+                        "label" => Field::Label,
+                        "description" => Field::Description,
+                        "path" => Field::Path,
+                        "line" => Field::Line,
+                        "column" => Field::Column,
+                        "cell" => Field::Cell,
+                        "context" => Field::Context,
+                        "sigil_set" => Field::SigilSet,
+                        "url" => Field::Url,
+                        _ => match name.strip_prefix("metadata.") {
+                            Some(key) if !key.is_empty() => Field::Metadata(key.to_owned()),
+                            _ => {
+                                return Err(format!(
+                                    "Unknown placeholder `{{{name}}}` in template. Expected \
+                                     {TEMPLATE_FIELDS}.",
+                                ));
+                            }
+                        },
+                    };
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(Piece::Field(field));
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => return Err(format!("Unmatched `}}` in template `{s}`.")),
+                _ => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+
+        Ok(Self { pieces })
+    }
+}
+
+impl Template {
+    // This function renders a directive with the template. Fields which the directive doesn't have
+    // (e.g., the cell of a directive which isn't in a notebook, or a URL outside of `links`) are
+    // rendered as empty.
+    pub fn render(&self, directive: &Directive, url: Option<&str>) -> String {
+        self.expand(directive, url, true)
+    }
+
+    // This function renders a directive with the template, quoting paths which contain unprintable
+    // characters only if `quote_paths` is set.
+    fn expand(&self, directive: &Directive, url: Option<&str>, quote_paths: bool) -> String {
+        let mut output = String::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Text(text) => output.push_str(text),
+                Piece::Field(field) => match field {
+                    Field::Type => output.push_str(directive.r#type.name()),
+                    Field::Label => output.push_str(&directive.label),
+                    Field::Metadata(key) => {
+                        output.push_str(directive.metadata.get(key).map_or("", String::as_str));
+                    }
+                    Field::Description => output.push_str(&directive.description),
+                    Field::Path if quote_paths => {
+                        output.push_str(&quote(&display(&directive.path)));
+                    }
+                    Field::Path => output.push_str(&display(&directive.path).to_string_lossy()),
+                    Field::Line => {
+                        let _ = write!(output, "{}", directive.line_number);
+                    }
+                    Field::Column => {
+                        // Directives whose column isn't known start at the beginning of the line.
+                        let _ = write!(output, "{}", directive.column.max(1));
+                    }
+                    Field::Cell => {
+                        if let Some(cell) = directive.cell {
+                            let _ = write!(output, "{cell}");
+                        }
+                    }
+                    Field::Context => output.push_str(&directive.context),
+                    Field::SigilSet => {
+                        output.push_str(directive.sigil_set.as_deref().unwrap_or_default());
+                    }
+                    Field::Url => output.push_str(url.unwrap_or_default()),
+                },
+            }
+        }
+        output
+    }
+}
+
+// This struct represents a template for a command to run for each directive, such as
+// `code -g {path}:{line}`. The template is split into words like a shell would split it (with
+// quotes and backslashes), and then each word is rendered like a `Template`. Since the fields are
+// substituted after splitting, they never need to be quoted.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExecTemplate {
+    words: Vec<Template>,
+}
+
+impl FromStr for ExecTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = vec![];
+        let mut word = None::<String>;
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\'' | '"' => {
+                    let word = word.get_or_insert_with(String::new);
+                    loop {
+                        match chars.next() {
+                            Some(next) if next == c => break,
+                            Some('\\') if c == '"' => {
+                                word.extend(chars.next());
+                            }
+                            Some(next) => word.push(next),
+                            None => return Err(format!("Unclosed quote in command `{s}`.")),
+                        }
+                    }
+                }
+                '\\' => word.get_or_insert_with(String::new).extend(chars.next()),
+                _ if c.is_whitespace() => words.extend(word.take()),
+                _ => word.get_or_insert_with(String::new).push(c),
+            }
+        }
+        words.extend(word);
+
+        if words.is_empty() {
+            return Err("The command is empty.".to_owned());
+        }
+        Ok(Self {
+            words: words
+                .iter()
+                .map(|word| word.parse())
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl ExecTemplate {
+    // This function renders the command for a directive as a list of words, the first of which is
+    // the program to run.
+    pub fn command(&self, directive: &Directive, url: Option<&str>) -> Vec<String> {
+        self.words
+            .iter()
+            .map(|word| word.expand(directive, url, false))
+            .collect()
+    }
+}
+
+// This function quotes a word for a POSIX shell, if necessary.
+fn shell_quote(word: &str) -> String {
+    if !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "%+,-./:=@_".contains(c))
+    {
+        word.to_owned()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+// This function renders a command so it can be pasted into a shell.
+pub fn shell_command(command: &[String]) -> String {
+    command
+        .iter()
+        .map(|word| shell_quote(word))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// This function runs commands one at a time, stopping at the first one which fails.
+pub fn run(commands: impl IntoIterator<Item = Vec<String>>) -> Result<(), String> {
+    for command in commands {
+        let rendered = shell_command(&command);
+        let status = Command::new(&command[0])
+            .args(&command[1..])
+            .status()
+            .map_err(|error| format!("Unable to run `{rendered}`: {error}"))?;
+        if !status.success() {
+            return Err(format!("`{rendered}` failed ({status})."));
+        }
+    }
+
+    Ok(())
+}
+
+// This function prints a list of directives in the given format, or runs the command for each one
+// if the format says to.
+pub fn emit(format: &Format, directives: &[Directive]) -> Result<(), String> {
+    if let Format::Exec {
+        template,
+        run: true,
+    } = format
+    {
+        return run(directives
+            .iter()
+            .map(|directive| template.command(directive, None)));
+    }
+
+    print!("{}", render(format, directives));
+    Ok(())
+}
+
+// This function renders a list of directives in the given format.
+pub fn render(format: &Format, directives: &[Directive]) -> String {
+    match format {
+        Format::Human => {
+            let mut output = String::new();
+            for directive in directives {
+                let _ = writeln!(output, "{directive}");
+            }
+            output
+        }
+        Format::Json => {
+            // The paths are printed in the chosen style, if any.
+            let directives = directives
+                .iter()
+                .map(|directive| Directive {
+                    path: display(&directive.path).into_owned(),
+                    ..directive.clone()
+                })
+                .collect::<Vec<_>>();
+
+            // The `unwrap` is safe because directives always serialize successfully.
+            let mut json = serde_json::to_string_pretty(&Listing {
+                schema_version: SCHEMA_VERSION,
+                directives: &directives,
+            })
+            .unwrap();
+            json.push('\n');
+            json
+        }
+        Format::Template(template) => {
+            let mut output = String::new();
+            for directive in directives {
+                let _ = writeln!(output, "{}", template.render(directive, None));
+            }
+            output
+        }
+        Format::Exec { template, .. } => {
+            let mut output = String::new();
+            for directive in directives {
+                let _ = writeln!(
+                    output,
+                    "{}",
+                    shell_command(&template.command(directive, None)),
+                );
+            }
+            output
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            directive::{Directive, Type},
+            output::{render, ExecTemplate, Format, Template},
+        },
+        std::path::Path,
+    };
+
+    fn tag() -> Directive {
+        Directive {
+            description: "Description".to_owned(),
+            ..Directive::new(Type::Tag, "tag1", "file1.rs", 1)
+        }
+    }
+
+    #[test]
+    fn render_human() {
+        assert_eq!(render(&Format::Human, &[]), "");
+        assert_eq!(
+            render(&Format::Human, &[tag()]),
+            "[?tag:tag1] @ file1.rs:1\n".replace('?', ""),
+        );
+    }
+
+    #[test]
+    fn render_json() {
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&Format::Json, &[tag()])).unwrap();
+
+        assert_eq!(json["schema_version"], 1_u64);
+        let directive = &json["directives"][0];
+        assert_eq!(directive["type"], "tag");
+        assert_eq!(directive["label"], "tag1");
+        assert!(directive["metadata"].as_object().unwrap().is_empty());
+        assert_eq!(directive["description"], "Description");
+        assert_eq!(directive["path"], "file1.rs");
+        assert_eq!(directive["line"], 1_u64);
+    }
+
+    #[test]
+    fn render_template() {
+        let mut tag = tag();
+        tag.metadata.insert("owner".to_owned(), "@team".to_owned());
+        let template = "{path}:{line}: [{type}:{label}] {description} {{{metadata.owner}}}"
+            .parse::<Template>()
+            .unwrap();
+
+        assert_eq!(
+            render(&Format::Template(template), &[tag]),
+            "file1.rs:1: [?tag:tag1] Description {@team}\n".replace('?', ""),
+        );
+    }
+
+    #[test]
+    fn render_exec() {
+        let mut tag = tag();
+        tag.path = Path::new("my dir/it's.rs").to_owned();
+        let template = r#"code -g "{path}:{line}:{column}" --label\ {label} ''"#
+            .parse::<ExecTemplate>()
+            .unwrap();
+
+        assert_eq!(
+            template.command(&tag, None),
+            ["code", "-g", "my dir/it's.rs:1:1", "--label tag1", ""],
+        );
+        assert_eq!(
+            render(
+                &Format::Exec {
+                    template,
+                    run: false,
+                },
+                &[tag],
+            ),
+            "code -g 'my dir/it'\\''s.rs:1:1' '--label tag1' ''\n",
+        );
+    }
+
+    #[test]
+    fn parse_exec_template_invalid() {
+        assert!("".parse::<ExecTemplate>().is_err());
+        assert!("  ".parse::<ExecTemplate>().is_err());
+        assert!("code 'unclosed".parse::<ExecTemplate>().is_err());
+        assert!("code {unknown}".parse::<ExecTemplate>().is_err());
+    }
+
+    #[test]
+    fn parse_template_invalid() {
+        assert!("{unknown}".parse::<Template>().is_err());
+        assert!("{metadata.}".parse::<Template>().is_err());
+        assert!("{label".parse::<Template>().is_err());
+        assert!("label}".parse::<Template>().is_err());
+        assert!("{{label}}".parse::<Template>().is_ok());
+    }
+}
@@ -0,0 +1,143 @@
+use {
+    crate::{config, directive::Directive},
+    std::{
+        cell::RefCell,
+        collections::HashMap,
+        path::{Path, PathBuf},
+    },
+};
+
+// This struct finds the package containing each file, remembering the answer for each directory.
+#[derive(Default)]
+pub struct Packages {
+    config: config::Packages,
+    roots: RefCell<HashMap<PathBuf, Option<PathBuf>>>,
+}
+
+impl Packages {
+    // This function prepares to find the packages described by the configuration.
+    pub fn new(config: &config::Packages) -> Self {
+        Self {
+            config: config.clone(),
+            roots: RefCell::new(HashMap::new()),
+        }
+    }
+
+    // This function returns the directory of the innermost package containing a file, if there is
+    // one.
+    pub fn package(&self, path: &Path) -> Option<PathBuf> {
+        if self.config.manifests.is_empty() && self.config.paths.is_empty() {
+            return None;
+        }
+        let directory = path.parent()?;
+        if let Some(root) = self.roots.borrow().get(directory) {
+            return root.clone();
+        }
+
+        let root = if self.is_package(directory) {
+            Some(directory.to_owned())
+        } else {
+            self.package(directory)
+        };
+        self.roots
+            .borrow_mut()
+            .insert(directory.to_owned(), root.clone());
+        root
+    }
+
+    // This function determines whether a directory is the root of a package. Since `*` matches
+    // across separators, only the outermost directory matching a glob counts (e.g., `services/*`
+    // matches `services/api` but not `services/api/src`).
+    fn is_package(&self, directory: &Path) -> bool {
+        self.config
+            .manifests
+            .iter()
+            .any(|manifest| directory.join(manifest).is_file())
+            || self.config.paths.iter().any(|pattern| {
+                pattern.is_match(directory)
+                    && !directory
+                        .ancestors()
+                        .skip(1)
+                        .any(|ancestor| pattern.is_match(ancestor))
+            })
+    }
+
+    // This function finds the tags which a reference refers to. Tags in the same package as the
+    // reference take precedence over those in other packages.
+    pub fn resolve<'a>(&self, tags: &[&'a Directive], r#ref: &Directive) -> Vec<&'a Directive> {
+        let package = self.package(&r#ref.path);
+        let local = tags
+            .iter()
+            .filter(|tag| self.package(&tag.path) == package)
+            .copied()
+            .collect::<Vec<_>>();
+
+        if local.is_empty() {
+            tags.to_vec()
+        } else {
+            local
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            config::parse,
+            directive::{Directive, Type},
+            packages::Packages,
+        },
+        std::{
+            env::temp_dir,
+            fs::{create_dir_all, remove_dir_all, write},
+            path::Path,
+            process,
+        },
+    };
+
+    fn directive(r#type: Type, path: &Path) -> Directive {
+        Directive::new(r#type, "label", path, 1)
+    }
+
+    #[test]
+    fn package_and_resolve() {
+        let directory = temp_dir().join(format!("tagref-packages-{}", process::id()));
+        let _ = remove_dir_all(&directory);
+        create_dir_all(directory.join("crates/a/src")).unwrap();
+        create_dir_all(directory.join("services/b")).unwrap();
+        write(directory.join("crates/a/Cargo.toml"), "").unwrap();
+        let config = parse(&format!(
+            "
+            [packages]
+            manifests = ['Cargo.toml']
+            paths = ['{}/services/*']
+            ",
+            directory.display(),
+        ))
+        .unwrap();
+        let packages = Packages::new(&config.packages);
+
+        let a = directory.join("crates/a/src/lib.rs");
+        let b = directory.join("services/b/main.rs");
+        let other = directory.join("other.rs");
+        let package_a = packages.package(&a);
+        let package_b = packages.package(&b);
+        let package_other = packages.package(&other);
+        let _ = remove_dir_all(&directory);
+
+        assert_eq!(package_a, Some(directory.join("crates/a")));
+        assert_eq!(package_b, Some(directory.join("services/b")));
+        assert_eq!(package_other, None);
+
+        let tag_a = directive(Type::Tag, &a);
+        let tag_b = directive(Type::Tag, &b);
+        let tags = [&tag_a, &tag_b];
+        let resolved = packages.resolve(&tags, &directive(Type::Ref, &b));
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].path, b);
+        let resolved = packages.resolve(&tags, &directive(Type::Ref, &other));
+        assert_eq!(resolved.len(), 2);
+        assert_eq!(Packages::default().package(&a), None);
+    }
+}
@@ -0,0 +1,163 @@
+use std::{
+    borrow::Cow,
+    fmt,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+    sync::OnceLock,
+};
+
+// This enum represents how the paths of scanned files are printed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PathStyle {
+    Relative,
+    Absolute,
+    GitRoot,
+}
+
+// The names of the styles, for the command-line interface
+pub const PATH_STYLES: &[&str] = &["relative", "absolute", "git-root"];
+
+impl FromStr for PathStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "relative" => Ok(Self::Relative),
+            "absolute" => Ok(Self::Absolute),
+            "git-root" => Ok(Self::GitRoot),
+            _ => Err(format!("Unknown path style `{s}`.")),
+        }
+    }
+}
+
+impl fmt::Display for PathStyle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Relative => "relative",
+                Self::Absolute => "absolute",
+                Self::GitRoot => "git-root",
+            },
+        )
+    }
+}
+
+// This struct represents the chosen style along with the directories it needs. The paths of
+// scanned files are relative to `working_directory` (unless they're absolute), and relative paths
+// are printed relative to `base`.
+struct Settings {
+    style: PathStyle,
+    working_directory: PathBuf,
+    base: PathBuf,
+}
+
+// The style chosen for this process, if any. It's set once, before anything is printed.
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+// This function chooses how paths are printed for the rest of the process. The `base` is the
+// directory which relative paths are relative to (it's ignored for absolute paths).
+pub fn set(style: PathStyle, working_directory: PathBuf, base: PathBuf) {
+    let _ = SETTINGS.set(Settings {
+        style,
+        working_directory,
+        base,
+    });
+}
+
+// This function removes `.` components from a path, and resolves `..` components lexically.
+fn clean(path: &Path) -> PathBuf {
+    let mut cleaned = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if cleaned.file_name().is_some() => {
+                cleaned.pop();
+            }
+            _ => cleaned.push(component),
+        }
+    }
+    cleaned
+}
+
+// This function renders an absolute path relative to the absolute directory `base`.
+fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let path = path.components().collect::<Vec<_>>();
+    let base = base.components().collect::<Vec<_>>();
+    let common = path.iter().zip(&base).take_while(|(x, y)| x == y).count();
+
+    let relative = base[common..]
+        .iter()
+        .map(|_| Component::ParentDir)
+        .chain(path[common..].iter().copied())
+        .collect::<PathBuf>();
+    if relative.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        relative
+    }
+}
+
+// This function renders a path in the given style.
+fn render(style: PathStyle, working_directory: &Path, base: &Path, path: &Path) -> PathBuf {
+    let absolute = clean(&working_directory.join(path));
+    match style {
+        PathStyle::Absolute => absolute,
+        PathStyle::Relative | PathStyle::GitRoot => relative_to(&absolute, &clean(base)),
+    }
+}
+
+// This function renders the path of a scanned file (or a path relative to the working directory)
+// in the chosen style. If no style was chosen, the path is printed as it is.
+pub fn display(path: &Path) -> Cow<'_, Path> {
+    match SETTINGS.get() {
+        Some(settings) => Cow::Owned(render(
+            settings.style,
+            &settings.working_directory,
+            &settings.base,
+            path,
+        )),
+        None => Cow::Borrowed(path),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use {
+        crate::path_style::{render, PathStyle, PATH_STYLES},
+        std::path::Path,
+    };
+
+    #[test]
+    fn render_styles() {
+        let render = |style, path| {
+            render(
+                style,
+                Path::new("/repo"),
+                Path::new("/repo/src"),
+                Path::new(path),
+            )
+            .to_string_lossy()
+            .into_owned()
+        };
+
+        assert_eq!(render(PathStyle::Absolute, "./src/a.rs"), "/repo/src/a.rs");
+        assert_eq!(render(PathStyle::Relative, "./src/a.rs"), "a.rs");
+        assert_eq!(render(PathStyle::Relative, "docs/b.md"), "../docs/b.md");
+        assert_eq!(render(PathStyle::Relative, "src"), ".");
+        assert_eq!(
+            render(PathStyle::GitRoot, "/elsewhere/c.rs"),
+            "../../elsewhere/c.rs",
+        );
+        assert_eq!(render(PathStyle::Absolute, "src/../d.rs"), "/repo/d.rs");
+    }
+
+    #[test]
+    fn style_names() {
+        for name in PATH_STYLES {
+            assert_eq!(name.parse::<PathStyle>().unwrap().to_string(), *name);
+        }
+        assert!("canonical".parse::<PathStyle>().is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use {
+    crate::{
+        config::RefPlacement,
+        diagnostic::{self, Diagnostic},
+        directive::Directive,
+        packages::Packages,
+    },
+    std::collections::HashMap,
+};
+
+// This function checks that each reference is placed in the same file as its tag or in a different
+// file, as required by the `ref-placement` setting. If tags in several packages have the label, the
+// one in the reference's package (if any) is its tag. References to tags that don't exist are left
+// for the tag reference check. It returns a vector of errors.
+pub fn check(
+    tags: &[Directive],
+    refs: &[Directive],
+    packages: &Packages,
+    placement: RefPlacement,
+) -> Vec<Diagnostic> {
+    let mut errors = Vec::<Diagnostic>::new();
+
+    if placement == RefPlacement::Any {
+        return errors;
+    }
+
+    // Index the tags by label.
+    let mut tags_by_label = HashMap::<&str, Vec<&Directive>>::new();
+    for tag in tags {
+        tags_by_label.entry(&tag.label).or_default().push(tag);
+    }
+
+    for r#ref in refs {
+        let candidates = tags_by_label
+            .get(r#ref.label.as_str())
+            .cloned()
+            .unwrap_or_default();
+        for tag in packages.resolve(&candidates, r#ref) {
+            let same_file = tag.path == r#ref.path;
+            let requirement = match placement {
+                RefPlacement::SameFile if !same_file => "the same file",
+                RefPlacement::OtherFile if same_file => "a different file",
+                RefPlacement::Any | RefPlacement::SameFile | RefPlacement::OtherFile => continue,
+            };
+
+            errors.push(Diagnostic::error(
+                diagnostic::REF_PLACEMENT,
+                r#ref,
+                format!(
+                    "{ref} refers to {tag}, but `ref-placement` requires references to be in \
+                        {requirement}.",
+                ),
+            ));
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            config::RefPlacement,
+            directive::{Directive, Type},
+            packages::Packages,
+            placement::check,
+        },
+        std::path::Path,
+    };
+
+    fn directive(r#type: Type, label: &str, path: &str) -> Directive {
+        Directive::new(r#type, label, path, 1)
+    }
+
+    fn example() -> (Vec<Directive>, Vec<Directive>) {
+        (
+            vec![directive(Type::Tag, "tag1", "file1.rs")],
+            vec![
+                directive(Type::Ref, "tag1", "file1.rs"),
+                directive(Type::Ref, "tag1", "file2.rs"),
+                directive(Type::Ref, "tag2", "file1.rs"),
+            ],
+        )
+    }
+
+    #[test]
+    fn check_any() {
+        let (tags, refs) = example();
+
+        assert!(check(&tags, &refs, &Packages::default(), RefPlacement::Any).is_empty());
+    }
+
+    #[test]
+    fn check_same_file() {
+        let (tags, refs) = example();
+
+        let errors = check(&tags, &refs, &Packages::default(), RefPlacement::SameFile);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("file2.rs"));
+        assert!(errors[0].message.ends_with("to be in the same file."));
+    }
+
+    #[test]
+    fn check_other_file() {
+        let (tags, refs) = example();
+
+        let errors = check(&tags, &refs, &Packages::default(), RefPlacement::OtherFile);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].location,
+            Some((Path::new("file1.rs").to_owned(), 1)),
+        );
+        assert!(errors[0].message.ends_with("to be in a different file."));
+    }
+}
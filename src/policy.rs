@@ -0,0 +1,264 @@
+use {
+    crate::{
+        config::{deserialize_optional_regex, deserialize_regex_map, PathPattern},
+        diagnostic::Diagnostic,
+        directive::{Directive, Type},
+    },
+    regex::Regex,
+    serde::Deserialize,
+    std::{
+        collections::{BTreeMap, HashMap},
+        fs::read_to_string,
+        io::ErrorKind,
+        path::Path,
+    },
+};
+
+// The policy file that is loaded when no other one is specified
+pub const DEFAULT_POLICY_PATH: &str = "tagref-policy.toml";
+
+// This enum represents what happens when a rule matches a directive.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Error,
+    Warn,
+    Ignore,
+}
+
+// This struct represents a policy rule. A rule matches a directive if all of its predicates are
+// satisfied. Predicates which are absent are vacuously satisfied.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Rule {
+    pub id: String,
+    pub action: Action,
+    pub message: Option<String>,
+    pub r#type: Option<Type>,
+    #[serde(default, deserialize_with = "deserialize_optional_regex")]
+    pub label: Option<Regex>,
+    pub path: Option<PathPattern>,
+    pub min_refs: Option<usize>,
+    pub max_refs: Option<usize>,
+    #[serde(default, deserialize_with = "deserialize_regex_map")]
+    pub metadata: BTreeMap<String, Regex>,
+    #[serde(default)]
+    pub without_metadata: Vec<String>,
+}
+
+impl Rule {
+    // This function determines whether the rule matches a directive with the given number of
+    // references.
+    pub fn matches(&self, directive: &Directive, refs: usize) -> bool {
+        self.r#type.is_none_or(|r#type| r#type == directive.r#type)
+            && self
+                .label
+                .as_ref()
+                .is_none_or(|label| label.is_match(&directive.label))
+            && self
+                .path
+                .as_ref()
+                .is_none_or(|path| path.is_match(&directive.path))
+            && self.min_refs.is_none_or(|min_refs| refs >= min_refs)
+            && self.max_refs.is_none_or(|max_refs| refs <= max_refs)
+            && self.metadata.iter().all(|(key, pattern)| {
+                directive
+                    .metadata
+                    .get(key)
+                    .is_some_and(|value| pattern.is_match(value))
+            })
+            && self
+                .without_metadata
+                .iter()
+                .all(|key| !directive.metadata.contains_key(key))
+    }
+}
+
+// This struct represents the contents of the policy file.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Policy {
+    pub rules: Vec<Rule>,
+}
+
+// This function parses the contents of a policy file.
+pub fn parse(contents: &str) -> Result<Policy, String> {
+    toml::from_str(contents).map_err(|error| error.to_string())
+}
+
+// This function loads the policy file. If a path is given, the file must exist. Otherwise, the
+// default policy file is loaded if it exists.
+pub fn load(path: Option<&Path>) -> Result<Policy, String> {
+    let (path, required) = match path {
+        Some(path) => (path, true),
+        None => (Path::new(DEFAULT_POLICY_PATH), false),
+    };
+
+    match read_to_string(path) {
+        Ok(contents) => parse(&contents)
+            .map_err(|error| format!("Error in {}: {error}", path.to_string_lossy())),
+        Err(error) if error.kind() == ErrorKind::NotFound && !required => Ok(Policy::default()),
+        Err(error) => Err(format!(
+            "Unable to read {}: {error}",
+            path.to_string_lossy(),
+        )),
+    }
+}
+
+// This function evaluates the policy against the given directives. For each directive, the first
+// matching rule (if any) determines the outcome, so an `ignore` rule exempts the directive from any
+// rules after it. The `ref_counts` map gives the number of references to each label. The resulting
+// diagnostics use the rule IDs as their codes.
+pub fn check<'a>(
+    policy: &Policy,
+    directives: impl IntoIterator<Item = &'a Directive>,
+    ref_counts: &HashMap<&str, usize>,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::<Diagnostic>::new();
+
+    for directive in directives {
+        let refs = match directive.r#type {
+            Type::Tag | Type::Ref => ref_counts
+                .get(directive.label.as_str())
+                .copied()
+                .unwrap_or(0),
+            Type::File | Type::Dir | Type::Spec | Type::Url => 0,
+        };
+
+        if let Some(rule) = policy
+            .rules
+            .iter()
+            .find(|rule| rule.matches(directive, refs))
+        {
+            let diagnostic = match rule.action {
+                Action::Error => Diagnostic::error,
+                Action::Warn => Diagnostic::warning,
+                Action::Ignore => continue,
+            };
+
+            diagnostics.push(diagnostic(
+                &rule.id,
+                directive,
+                format!(
+                    "{directive} violates rule `{}`{}",
+                    rule.id,
+                    rule.message
+                        .as_ref()
+                        .map_or_else(|| ".".to_owned(), |message| format!(": {message}")),
+                ),
+            ));
+        }
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            diagnostic::Severity,
+            directive::{Directive, Type},
+            policy::{check, parse},
+        },
+        std::collections::{BTreeMap, HashMap},
+    };
+
+    fn directive(r#type: Type, label: &str, path: &str, owner: Option<&str>) -> Directive {
+        let mut metadata = BTreeMap::new();
+        if let Some(owner) = owner {
+            metadata.insert("owner".to_owned(), owner.to_owned());
+        }
+
+        Directive {
+            metadata,
+            ..Directive::new(r#type, label, path, 1)
+        }
+    }
+
+    #[test]
+    fn check_empty() {
+        let policy = parse("").unwrap();
+
+        assert!(
+            check(
+                &policy,
+                &[directive(Type::Tag, "tag1", "file.rs", None)],
+                &HashMap::new(),
+            )
+            .is_empty(),
+        );
+    }
+
+    #[test]
+    fn check_rules() {
+        let policy = parse(
+            "
+            [[rules]]
+            id = 'allow-vendored'
+            action = 'ignore'
+            path = 'vendor/**'
+
+            [[rules]]
+            id = 'no-temp-tags'
+            action = 'error'
+            message = 'Temporary tags must not be committed.'
+            type = 'tag'
+            label = '^temp_'
+
+            [[rules]]
+            id = 'unused-tag'
+            action = 'warn'
+            type = 'tag'
+            max-refs = 0
+
+            [[rules]]
+            id = 'core-owned'
+            action = 'warn'
+            metadata = { owner = '^@core$' }
+
+            [[rules]]
+            id = 'owner-required'
+            action = 'error'
+            type = 'tag'
+            without-metadata = ['owner']
+            ",
+        )
+        .unwrap();
+
+        let directives = [
+            directive(Type::Tag, "temp_1", "./vendor/lib.rs", None),
+            directive(Type::Tag, "temp_2", "./src/main.rs", None),
+            directive(Type::Tag, "unused", "./src/main.rs", Some("@docs")),
+            directive(Type::Tag, "used", "./src/main.rs", Some("@core")),
+            directive(Type::Tag, "unowned", "./src/main.rs", None),
+            directive(Type::Ref, "used", "./src/main.rs", None),
+        ];
+        let mut ref_counts = HashMap::new();
+        ref_counts.insert("used", 1);
+        ref_counts.insert("unowned", 1);
+
+        let diagnostics = check(&policy, &directives, &ref_counts);
+        assert_eq!(diagnostics.len(), 4);
+        assert_eq!(diagnostics[0].code, "no-temp-tags");
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert!(
+            diagnostics[0]
+                .message
+                .ends_with("violates rule `no-temp-tags`: Temporary tags must not be committed."),
+        );
+        assert_eq!(diagnostics[1].code, "unused-tag");
+        assert_eq!(diagnostics[1].severity, Severity::Warning);
+        assert_eq!(diagnostics[2].code, "core-owned");
+        assert_eq!(diagnostics[3].code, "owner-required");
+        assert!(diagnostics[3].message.contains("unowned"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse("[[rules]]\naction = 'error'").is_err());
+        assert!(parse("[[rules]]\nid = 'x'\naction = 'explode'").is_err());
+        assert!(parse("[[rules]]\nid = 'x'\naction = 'warn'\ntype = 'thing'").is_err());
+        assert!(parse("[[rules]]\nid = 'x'\naction = 'warn'\nlabel = '('").is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use {
+    crate::schema::SCHEMA_VERSION,
+    serde::Serialize,
+    std::{
+        fmt,
+        str::FromStr,
+        sync::Mutex,
+        time::{Duration, Instant},
+    },
+};
+
+// How often to report progress while scanning
+const INTERVAL: Duration = Duration::from_millis(250);
+
+// This enum represents the ways to report progress.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ProgressFormat {
+    None,
+    Json,
+}
+
+// The names of the formats, for the command-line interface
+pub const PROGRESS_FORMATS: &[&str] = &["none", "json"];
+
+impl FromStr for ProgressFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Unknown progress format `{s}`.")),
+        }
+    }
+}
+
+impl fmt::Display for ProgressFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::None => "none",
+                Self::Json => "json",
+            },
+        )
+    }
+}
+
+// This struct represents a progress event, which is written to STDERR as a line of JSON.
+#[derive(Serialize)]
+struct ProgressEvent {
+    schema_version: usize,
+    event: &'static str,
+    files_scanned: usize,
+    directives_found: usize,
+    elapsed_seconds: f64,
+}
+
+// This struct represents the counts reported so far.
+struct State {
+    files_scanned: usize,
+    directives_found: usize,
+    last_report: Instant,
+}
+
+// This struct keeps track of the progress of a scan, and periodically reports it.
+pub struct Progress {
+    format: ProgressFormat,
+    start: Instant,
+    state: Mutex<State>,
+}
+
+impl Progress {
+    pub fn new(format: ProgressFormat) -> Self {
+        let start = Instant::now();
+        Self {
+            format,
+            start,
+            state: Mutex::new(State {
+                files_scanned: 0,
+                directives_found: 0,
+                last_report: start,
+            }),
+        }
+    }
+
+    // This function records that a file was scanned, and reports the progress if it hasn't been
+    // reported recently.
+    pub fn file_scanned(&self, directives: usize) {
+        if self.format == ProgressFormat::None {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap(); // Safe assuming no poisoning
+        state.files_scanned += 1;
+        state.directives_found += directives;
+        let now = Instant::now();
+        if now.duration_since(state.last_report) >= INTERVAL {
+            state.last_report = now;
+            eprintln!("{}", self.event("progress", &state));
+        }
+    }
+
+    // This function reports the final counts once the scan is done.
+    pub fn finish(&self) {
+        if self.format == ProgressFormat::None {
+            return;
+        }
+
+        let state = self.state.lock().unwrap(); // Safe assuming no poisoning
+        eprintln!("{}", self.event("done", &state));
+    }
+
+    // This function renders an event as a line of JSON.
+    fn event(&self, event: &'static str, state: &State) -> String {
+        // The `unwrap` is safe because events always serialize successfully.
+        serde_json::to_string(&ProgressEvent {
+            schema_version: SCHEMA_VERSION,
+            event,
+            files_scanned: state.files_scanned,
+            directives_found: state.directives_found,
+            elapsed_seconds: self.start.elapsed().as_secs_f64(),
+        })
+        .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::progress::{Progress, ProgressFormat},
+        serde_json::Value,
+    };
+
+    #[test]
+    fn event_json() {
+        let progress = Progress::new(ProgressFormat::Json);
+        progress.file_scanned(3);
+        progress.file_scanned(2);
+
+        let event =
+            serde_json::from_str::<Value>(&progress.event("done", &progress.state.lock().unwrap()))
+                .unwrap();
+
+        assert_eq!(event["schema_version"], 1_u64);
+        assert_eq!(event["event"], "done");
+        assert_eq!(event["files_scanned"], 2_u64);
+        assert_eq!(event["directives_found"], 5_u64);
+        assert!(event["elapsed_seconds"].as_f64().unwrap() >= 0.0_f64);
+    }
+
+    #[test]
+    fn format_round_trip() {
+        for format in [ProgressFormat::None, ProgressFormat::Json] {
+            assert_eq!(format.to_string().parse::<ProgressFormat>(), Ok(format));
+        }
+        assert!("xml".parse::<ProgressFormat>().is_err());
+    }
+}
@@ -0,0 +1,234 @@
+use {
+    crate::{config::Generated, diagnostic::Diagnostic, path_style::display, walk::normalize},
+    std::{
+        collections::HashMap,
+        fmt::Write,
+        fs::read_to_string,
+        path::{Component, Path, PathBuf},
+    },
+};
+
+// The marker which a generated file can use to name the file it was generated from, as in
+// `// tagref-source: ../templates/schema.rs.tmpl`. The path is relative to the directory of the
+// generated file, and the marker must be in one of the first few lines.
+pub const SOURCE_MARKER: &str = "tagref-source:";
+
+// The number of lines at the start of a file which are searched for the marker
+const HEADER_LINES: usize = 10;
+
+// This function resolves the `..` components of a path without consulting the filesystem, so
+// sources named relative to their generated files are printed like other paths.
+fn resolve(path: &Path) -> PathBuf {
+    let mut resolved = PathBuf::new();
+    for component in normalize(path).components() {
+        match component {
+            Component::ParentDir
+                if matches!(
+                    resolved.components().next_back(),
+                    Some(Component::Normal(_)),
+                ) =>
+            {
+                resolved.pop();
+            }
+            _ => resolved.push(component),
+        }
+    }
+    resolved
+}
+
+// This function finds the file which a file was generated from, if it was generated: first from
+// the marker in the file, and then from the rules in the configuration. The source is written like
+// the path of the generated file, i.e., starting with `./` if that does.
+fn source(path: &Path, contents: &str, rules: &[Generated]) -> Option<PathBuf> {
+    let marked = contents.lines().take(HEADER_LINES).find_map(|line| {
+        let (_, source) = line.split_once(SOURCE_MARKER)?;
+        let source = source.split_whitespace().next()?;
+        Some(resolve(
+            &path.parent().unwrap_or(Path::new("")).join(source),
+        ))
+    });
+
+    let source = marked.or_else(|| {
+        let normalized = normalize(path);
+        let normalized = normalized.to_string_lossy();
+        rules.iter().find_map(|rule| {
+            rule.pattern
+                .is_match(&normalized)
+                .then(|| resolve(Path::new(&*rule.pattern.replace(&normalized, &rule.source))))
+        })
+    })?;
+
+    Some(if path.starts_with(".") {
+        Path::new(".").join(source)
+    } else {
+        source
+    })
+}
+
+// This function finds the line in the source which corresponds to a problem in a generated file:
+// the first line which is the same as the one with the problem, or else the first line which
+// mentions its label. Problems which can't be matched up are reported at the first line.
+fn line(contents: &str, diagnostic: &Diagnostic) -> usize {
+    let context = diagnostic.context.as_deref().map(str::trim);
+    let label = diagnostic.label.as_deref();
+
+    contents
+        .lines()
+        .position(|line| context.is_some_and(|context| line.trim() == context))
+        .or_else(|| {
+            contents
+                .lines()
+                .position(|line| label.is_some_and(|label| line.contains(label)))
+        })
+        .map_or(1, |i| i + 1)
+}
+
+// This function moves the problems found in generated files to the files they were generated from,
+// so they're reported where they can be fixed. Problems whose sources don't exist stay where they
+// are.
+pub fn apply(rules: &[Generated], diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    apply_with(rules, diagnostics, |path| read_to_string(path).ok())
+}
+
+// This function moves the problems found in generated files, using the given function to read
+// files. Each file is only read once, no matter how many problems are found in it.
+fn apply_with<F: Fn(&Path) -> Option<String>>(
+    rules: &[Generated],
+    diagnostics: Vec<Diagnostic>,
+    read: F,
+) -> Vec<Diagnostic> {
+    let mut contents = HashMap::<PathBuf, Option<String>>::new();
+    let mut sources = HashMap::<PathBuf, Option<PathBuf>>::new();
+
+    diagnostics
+        .into_iter()
+        .map(|mut diagnostic| {
+            let Some((path, _)) = &diagnostic.location else {
+                return diagnostic;
+            };
+
+            let source = sources
+                .entry(path.clone())
+                .or_insert_with(|| {
+                    let generated = contents
+                        .entry(path.clone())
+                        .or_insert_with(|| read(path))
+                        .as_deref()?;
+                    source(path, generated, rules)
+                })
+                .clone();
+            let Some(source) = source else {
+                return diagnostic;
+            };
+            let Some(source_contents) = contents
+                .entry(source.clone())
+                .or_insert_with(|| read(&source))
+                .as_deref()
+            else {
+                return diagnostic;
+            };
+
+            let line_number = line(source_contents, &diagnostic);
+            let _ = write!(
+                diagnostic.message,
+                "\nReported at {}:{line_number}, which this file is generated from.",
+                display(&source).to_string_lossy(),
+            );
+            diagnostic.location = Some((source, line_number));
+            diagnostic
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            config,
+            diagnostic::{Diagnostic, Severity},
+            provenance::{apply_with, resolve},
+        },
+        std::{collections::HashMap, path::Path},
+    };
+
+    fn diagnostic(path: &str, line_number: usize, label: &str, context: &str) -> Diagnostic {
+        Diagnostic {
+            code: "E002".to_owned(),
+            severity: Severity::Error,
+            message: format!("No tag found for {label}."),
+            location: Some((Path::new(path).to_owned(), line_number)),
+            label: Some(label.to_owned()),
+            context: Some(context.to_owned()),
+        }
+    }
+
+    #[test]
+    fn resolve_parent_components() {
+        assert_eq!(
+            resolve(Path::new("./gen/../templates/a.tmpl")),
+            Path::new("templates/a.tmpl"),
+        );
+        assert_eq!(resolve(Path::new("../a.tmpl")), Path::new("../a.tmpl"));
+    }
+
+    #[test]
+    fn apply_marker_and_rules() {
+        let config = config::parse(
+            r"
+            [[generated]]
+            pattern = '^gen/(.*)\.rs$'
+            source = 'templates/$1.rs.tmpl'
+            ",
+        )
+        .unwrap();
+        let files = HashMap::from([
+            ("gen/a.rs", "// Generated.\nfn a() {}\n// See cache.\n"),
+            (
+                "gen/b.rs",
+                "// tagref-source: ../schemas/b.json\n\n\n// See b.\n",
+            ),
+            (
+                "templates/a.rs.tmpl",
+                "{{ header }}\n// See {{ name }}.\n// See cache.\n",
+            ),
+            ("schemas/b.json", "{\n  \"b\": \"See b.\"\n}\n"),
+            ("src/c.rs", "// See c.\n"),
+        ]);
+
+        let diagnostics = apply_with(
+            &config.generated,
+            vec![
+                diagnostic("./gen/a.rs", 3, "cache", "// See cache."),
+                diagnostic("gen/b.rs", 4, "b", "// See b."),
+                diagnostic("./src/c.rs", 1, "c", "// See c."),
+                diagnostic("gen/missing.rs", 1, "d", "// See d."),
+            ],
+            |path| {
+                files
+                    .get(path.to_string_lossy().trim_start_matches("./"))
+                    .map(|contents| (*contents).to_owned())
+            },
+        );
+
+        let locations = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let (path, line_number) = diagnostic.location.as_ref().unwrap();
+                (path.to_string_lossy().into_owned(), *line_number)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            locations,
+            [
+                ("./templates/a.rs.tmpl".to_owned(), 3),
+                ("schemas/b.json".to_owned(), 2),
+                ("./src/c.rs".to_owned(), 1),
+                ("gen/missing.rs".to_owned(), 1),
+            ],
+        );
+        assert!(diagnostics[0].message.ends_with(
+            "\nReported at ./templates/a.rs.tmpl:3, which this file is generated from.",
+        ));
+        assert_eq!(diagnostics[2].message, "No tag found for c.");
+    }
+}
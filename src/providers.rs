@@ -0,0 +1,148 @@
+use {
+    crate::{
+        config::{PathPattern, Provider},
+        directive::normalize_label,
+        walk,
+    },
+    std::{
+        collections::HashSet,
+        fs::read_to_string,
+        io::Read,
+        mem::take,
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+    },
+};
+
+// This function returns the labels of the tags from the given providers. The files within the
+// given paths (except the excluded ones) are searched for symbols.
+pub fn labels(
+    providers: &[Provider],
+    paths: &[PathBuf],
+    excludes: &[PathPattern],
+) -> Result<HashSet<String>, String> {
+    let mut labels = HashSet::new();
+
+    for provider in providers {
+        match provider {
+            Provider::Ctags { file } => {
+                let contents = read_to_string(file).map_err(|error| {
+                    format!("Unable to read {}: {error}", file.to_string_lossy())
+                })?;
+                labels.extend(ctags(&contents).map(ToOwned::to_owned));
+            }
+            Provider::Symbols { path } => {
+                if !cfg!(feature = "tree-sitter") {
+                    return Err(
+                        "This build of Tagref doesn't support `symbols` providers. Install it \
+                         with `cargo install tagref --features tree-sitter` to enable it."
+                            .to_owned(),
+                    );
+                }
+
+                let symbols = Arc::new(Mutex::new(HashSet::new()));
+                let symbols_clone = symbols.clone();
+                let pattern = path.clone();
+                walk::walk(paths, excludes, None, false, move |path, mut file| {
+                    let mut contents = String::new();
+                    if pattern.is_match(path) && file.read_to_string(&mut contents).is_ok() {
+                        symbols_clone
+                            .lock()
+                            .unwrap() // Safe assuming no poisoning
+                            .extend(self::symbols(path, &contents));
+                    }
+                });
+                labels.extend(take(&mut *symbols.lock().unwrap())); // Safe assuming no poisoning
+            }
+        }
+    }
+
+    Ok(labels.iter().map(|label| normalize_label(label)).collect())
+}
+
+// This function returns the names in a tags file in the format written by `ctags`. The lines
+// starting with `!_` are metadata rather than tags.
+fn ctags(contents: &str) -> impl Iterator<Item = &str> {
+    contents
+        .lines()
+        .filter(|line| !line.starts_with("!_"))
+        .filter_map(|line| line.split('\t').next())
+        .filter(|name| !name.is_empty())
+}
+
+// This function returns the names of the functions, types, etc. defined in a file, using a
+// tree-sitter grammar chosen by the file extension. These are the `name` fields of the definitions
+// and declarations in the syntax tree.
+#[cfg(feature = "tree-sitter")]
+fn symbols(path: &Path, contents: &str) -> Vec<String> {
+    let Some(language) = crate::comments::grammar(path) else {
+        return vec![];
+    };
+    let mut parser = tree_sitter::Parser::new();
+    if parser.set_language(&language).is_err() {
+        return vec![];
+    }
+    let Some(tree) = parser.parse(contents, None) else {
+        return vec![];
+    };
+
+    // Visit every node in order.
+    let mut symbols = vec![];
+    let mut cursor = tree.walk();
+    'walk: loop {
+        let node = cursor.node();
+        if ["_item", "_definition", "_declaration", "_spec"]
+            .iter()
+            .any(|suffix| node.kind().ends_with(suffix))
+        {
+            if let Some(name) = node.child_by_field_name("name") {
+                symbols.push(contents[name.byte_range()].to_owned());
+            }
+        }
+        if cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'walk;
+            }
+        }
+    }
+
+    symbols
+}
+
+// Without tree-sitter, there are no symbols.
+#[cfg(not(feature = "tree-sitter"))]
+fn symbols(_path: &Path, _contents: &str) -> Vec<String> {
+    vec![]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::providers::ctags;
+
+    #[test]
+    fn ctags_names() {
+        let contents = "\
+            !_TAG_FILE_FORMAT\t2\t/extended format/\n\
+            Foo\tsrc/foo.rs\t3;\"\ts\n\
+            bar\tsrc/bar.rs\t/^fn bar() {$/;\"\tf\n\
+        ";
+
+        assert_eq!(ctags(contents).collect::<Vec<_>>(), vec!["Foo", "bar"]);
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    #[test]
+    fn symbols_rust() {
+        use {crate::providers::symbols, std::path::Path};
+
+        let contents = "pub struct Foo;\nfn bar() {}\nimpl Foo { fn baz(&self) {} }\n";
+
+        assert_eq!(
+            symbols(Path::new("a.rs"), contents),
+            vec!["Foo", "bar", "baz"],
+        );
+    }
+}
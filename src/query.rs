@@ -0,0 +1,411 @@
+use {
+    crate::{
+        config::PathPattern,
+        directive::{Directive, Type},
+        walk::normalize,
+    },
+    regex::Regex,
+    std::collections::HashMap,
+};
+
+// This enum represents the properties of a directive which a query can inspect.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Field {
+    Type,
+    Label,
+    Path,
+    Description,
+    Metadata(String),
+    Line,
+    Refs,
+}
+
+impl Field {
+    // This function determines whether the field is numeric (as opposed to textual).
+    fn is_numeric(&self) -> bool {
+        matches!(self, Self::Line | Self::Refs)
+    }
+}
+
+// This enum represents the comparison operators.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    // This function applies the comparison to a pair of values.
+    fn apply<T: PartialOrd + ?Sized>(self, x: &T, y: &T) -> bool {
+        match self {
+            Self::Equal => x == y,
+            Self::NotEqual => x != y,
+            Self::Less => x < y,
+            Self::LessOrEqual => x <= y,
+            Self::Greater => x > y,
+            Self::GreaterOrEqual => x >= y,
+        }
+    }
+}
+
+// This enum represents a parsed query.
+pub enum Query {
+    And(Box<Self>, Box<Self>),
+    Or(Box<Self>, Box<Self>),
+    Not(Box<Self>),
+    Text(Field, Comparison, String),
+    Number(Field, Comparison, usize),
+    Regex(Field, Regex),
+    Glob(PathPattern),
+}
+
+impl Query {
+    // This function determines whether a directive satisfies the query. The `ref_counts` map gives
+    // the number of references to each label.
+    pub fn matches(&self, directive: &Directive, ref_counts: &HashMap<&str, usize>) -> bool {
+        match self {
+            Self::And(x, y) => x.matches(directive, ref_counts) && y.matches(directive, ref_counts),
+            Self::Or(x, y) => x.matches(directive, ref_counts) || y.matches(directive, ref_counts),
+            Self::Not(x) => !x.matches(directive, ref_counts),
+            Self::Text(field, comparison, value) => {
+                comparison.apply(text(field, directive).as_str(), value.as_str())
+            }
+            Self::Number(field, comparison, value) => comparison.apply(
+                &match field {
+                    Field::Line => directive.line_number,
+                    _ => match directive.r#type {
+                        Type::Tag | Type::Ref => ref_counts
+                            .get(directive.label.as_str())
+                            .copied()
+                            .unwrap_or(0),
+                        Type::File | Type::Dir | Type::Spec | Type::Url => 0,
+                    },
+                },
+                value,
+            ),
+            Self::Regex(field, regex) => regex.is_match(&text(field, directive)),
+            Self::Glob(pattern) => pattern.is_match(&directive.path),
+        }
+    }
+}
+
+// This function returns the value of a textual field of a directive.
+fn text(field: &Field, directive: &Directive) -> String {
+    match field {
+        Field::Type => directive.r#type.name().to_owned(),
+        Field::Label => directive.label.clone(),
+        Field::Path => normalize(&directive.path).to_string_lossy().into_owned(),
+        Field::Description => directive.description.clone(),
+        Field::Metadata(key) => directive.metadata.get(key).cloned().unwrap_or_default(),
+        Field::Line | Field::Refs => String::new(),
+    }
+}
+
+// This enum represents the tokens of the query language.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Identifier(String),
+    String(String),
+    Number(usize),
+    Symbol(&'static str),
+}
+
+// The symbols of the query language, with longer symbols first so they take precedence
+const SYMBOLS: &[&str] = &[
+    "&&", "||", "==", "!=", "<=", ">=", "!~", "<", ">", "~", "!", "(", ")",
+];
+
+// This function splits a query into tokens.
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = input.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else if c == '"' || c == '\'' {
+            let mut value = String::new();
+            let mut chars = rest[1..].char_indices();
+            let mut end = None;
+            while let Some((i, d)) = chars.next() {
+                if d == c {
+                    end = Some(i + 2);
+                    break;
+                } else if d == '\\' {
+                    match chars.next() {
+                        Some((_, escaped)) => value.push(escaped),
+                        None => break,
+                    }
+                } else {
+                    value.push(d);
+                }
+            }
+            let Some(end) = end else {
+                return Err("Unterminated string in query.".to_owned());
+            };
+            tokens.push(Token::String(value));
+            rest = &rest[end..];
+        } else if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            tokens.push(Token::Number(rest[..end].parse().map_err(|_| {
+                format!("Number `{}` in query is too large.", &rest[..end])
+            })?));
+            rest = &rest[end..];
+        } else if c.is_alphanumeric() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.' || c == '-'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Identifier(rest[..end].to_owned()));
+            rest = &rest[end..];
+        } else {
+            return Err(format!("Unexpected character `{c}` in query."));
+        }
+
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+// This struct holds the state of the recursive descent parser.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    // This function returns the next token without consuming it.
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    // This function consumes the next token.
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    // This function consumes the next token if it's the given symbol.
+    fn eat(&mut self, symbol: &'static str) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    // disjunction = conjunction ("||" conjunction)*
+    fn disjunction(&mut self) -> Result<Query, String> {
+        let mut query = self.conjunction()?;
+        while self.eat("||") {
+            query = Query::Or(Box::new(query), Box::new(self.conjunction()?));
+        }
+        Ok(query)
+    }
+
+    // conjunction = negation ("&&" negation)*
+    fn conjunction(&mut self) -> Result<Query, String> {
+        let mut query = self.negation()?;
+        while self.eat("&&") {
+            query = Query::And(Box::new(query), Box::new(self.negation()?));
+        }
+        Ok(query)
+    }
+
+    // negation = "!" negation | "(" disjunction ")" | comparison
+    fn negation(&mut self) -> Result<Query, String> {
+        if self.eat("!") {
+            Ok(Query::Not(Box::new(self.negation()?)))
+        } else if self.eat("(") {
+            let query = self.disjunction()?;
+            if self.eat(")") {
+                Ok(query)
+            } else {
+                Err("Expected `)` in query.".to_owned())
+            }
+        } else {
+            self.comparison()
+        }
+    }
+
+    // comparison = field operator value
+    fn comparison(&mut self) -> Result<Query, String> {
+        let field = match self.next() {
+            Some(Token::Identifier(name)) => match name.as_str() {
+                "type" => Field::Type,
+                "label" => Field::Label,
+                "path" => Field::Path,
+                "description" => Field::Description,
+                "line" => Field::Line,
+                "refs" => Field::Refs,
+                _ => match name.strip_prefix("metadata.") {
+                    Some(key) if !key.is_empty() => Field::Metadata(key.to_owned()),
+                    _ => return Err(format!("Unknown field `{name}` in query.")),
+                },
+            },
+            _ => return Err("Expected a field name in query.".to_owned()),
+        };
+
+        let operator = match self.next() {
+            Some(Token::Symbol(symbol)) if !matches!(symbol, "&&" | "||" | "!" | "(" | ")") => {
+                symbol
+            }
+            _ => return Err("Expected a comparison operator in query.".to_owned()),
+        };
+
+        let value = match self.next() {
+            Some(Token::Identifier(value) | Token::String(value)) => value,
+            Some(Token::Number(value)) => value.to_string(),
+            _ => return Err(format!("Expected a value after `{operator}` in query.")),
+        };
+
+        if operator == "~" || operator == "!~" {
+            if field.is_numeric() {
+                return Err(format!(
+                    "The `{operator}` operator only applies to text fields.",
+                ));
+            }
+
+            let query = if field == Field::Path {
+                Query::Glob(PathPattern::new(&value).map_err(|error| format!("In query: {error}"))?)
+            } else {
+                Query::Regex(
+                    field,
+                    Regex::new(&value)
+                        .map_err(|error| format!("In query: invalid pattern `{value}`: {error}"))?,
+                )
+            };
+
+            return Ok(if operator == "~" {
+                query
+            } else {
+                Query::Not(Box::new(query))
+            });
+        }
+
+        let comparison = match operator {
+            "==" => Comparison::Equal,
+            "!=" => Comparison::NotEqual,
+            "<" => Comparison::Less,
+            "<=" => Comparison::LessOrEqual,
+            ">" => Comparison::Greater,
+            _ => Comparison::GreaterOrEqual,
+        };
+
+        if field.is_numeric() {
+            Ok(Query::Number(
+                field,
+                comparison,
+                value
+                    .parse()
+                    .map_err(|_| format!("Expected a number after `{operator}` in query."))?,
+            ))
+        } else {
+            Ok(Query::Text(field, comparison, value))
+        }
+    }
+}
+
+// This function parses a query such as `type == tag && refs == 0 && path ~ "src/**"`.
+pub fn parse(input: &str) -> Result<Query, String> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        position: 0,
+    };
+
+    let query = parser.disjunction()?;
+
+    if parser.position < parser.tokens.len() {
+        return Err("Unexpected input at the end of the query.".to_owned());
+    }
+
+    Ok(query)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            directive::{Directive, Type},
+            query::parse,
+        },
+        std::collections::{BTreeMap, HashMap},
+    };
+
+    fn directive(r#type: Type, label: &str, path: &str, line_number: usize) -> Directive {
+        let mut metadata = BTreeMap::new();
+        metadata.insert("owner".to_owned(), "@core".to_owned());
+
+        Directive {
+            metadata,
+            description: "Keep this sorted.".to_owned(),
+            ..Directive::new(r#type, label, path, line_number)
+        }
+    }
+
+    fn matches(query: &str, directive: &Directive) -> bool {
+        let mut ref_counts = HashMap::new();
+        ref_counts.insert("used", 2);
+
+        parse(query).unwrap().matches(directive, &ref_counts)
+    }
+
+    #[test]
+    fn parse_fields() {
+        let tag = directive(Type::Tag, "temp_cache", "./src/cache.rs", 12);
+
+        assert!(matches("type == tag", &tag));
+        assert!(matches("type != 'ref'", &tag));
+        assert!(matches("label == \"temp_cache\"", &tag));
+        assert!(matches("label ~ '^temp_'", &tag));
+        assert!(matches("label !~ '^perm_'", &tag));
+        assert!(matches("path == 'src/cache.rs'", &tag));
+        assert!(matches("path ~ 'src/**'", &tag));
+        assert!(matches("description ~ sorted", &tag));
+        assert!(matches("metadata.owner == '@core'", &tag));
+        assert!(matches("metadata.missing == ''", &tag));
+        assert!(matches("line >= 12 && line < 13", &tag));
+        assert!(matches("refs == 0", &tag));
+        assert!(matches(
+            "refs == 2",
+            &directive(Type::Ref, "used", "file.rs", 1),
+        ));
+    }
+
+    #[test]
+    fn parse_precedence() {
+        let tag = directive(Type::Tag, "foo", "file.rs", 1);
+
+        assert!(matches("type == ref && label == bar || label == foo", &tag));
+        assert!(!matches(
+            "type == ref && (label == bar || label == foo)",
+            &tag,
+        ));
+        assert!(matches("!type == ref", &tag));
+        assert!(!matches("!(type == tag || type == ref)", &tag));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse("").is_err());
+        assert!(parse("type").is_err());
+        assert!(parse("type ==").is_err());
+        assert!(parse("color == red").is_err());
+        assert!(parse("line == foo").is_err());
+        assert!(parse("refs ~ 1").is_err());
+        assert!(parse("label ~ '('").is_err());
+        assert!(parse("label == 'foo").is_err());
+        assert!(parse("(label == foo").is_err());
+        assert!(parse("label == foo)").is_err());
+        assert!(parse("label == foo $").is_err());
+    }
+}
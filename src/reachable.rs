@@ -0,0 +1,254 @@
+use {
+    crate::directive::Directive,
+    std::{
+        collections::{BTreeSet, HashMap, HashSet, VecDeque},
+        fmt,
+        path::Path,
+        str::FromStr,
+    },
+};
+
+// This enum represents the rules for deciding which references belong to a tag. A tag has an edge
+// to every tag referenced by the references which belong to it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Edges {
+    // Every reference in the file containing the tag
+    File,
+
+    // The references between the tag and the next tag in the same file
+    Section,
+
+    // The references on the same line as the tag
+    Line,
+}
+
+// The names of the edge semantics, for the command-line interface
+pub const EDGES: &[&str] = &["file", "section", "line"];
+
+impl FromStr for Edges {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "file" => Ok(Self::File),
+            "section" => Ok(Self::Section),
+            "line" => Ok(Self::Line),
+            _ => Err(format!("Unknown edge semantics `{s}`.")),
+        }
+    }
+}
+
+impl fmt::Display for Edges {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::File => "file",
+                Self::Section => "section",
+                Self::Line => "line",
+            },
+        )
+    }
+}
+
+// This struct represents the graph of tags, where each tag points to the tags it references. Only
+// labels which belong to tags appear in the graph, so dangling references don't add edges.
+pub struct Graph<'a> {
+    edges: HashMap<&'a str, BTreeSet<&'a str>>,
+}
+
+impl<'a> Graph<'a> {
+    // This function builds the graph from the tags and the tag references.
+    pub fn new(
+        tags: &'a HashMap<String, Vec<Directive>>,
+        refs: &'a [Directive],
+        edges: Edges,
+    ) -> Self {
+        // Group the tags by file, sorted by line number so each tag's section can be found.
+        let mut tags_by_path = HashMap::<&Path, Vec<&Directive>>::new();
+        for tag in tags.values().flatten() {
+            tags_by_path.entry(&tag.path).or_default().push(tag);
+        }
+        for file_tags in tags_by_path.values_mut() {
+            file_tags.sort_by_key(|tag| tag.line_number);
+        }
+
+        // Add an edge for each reference which belongs to a tag.
+        let mut graph = HashMap::<&str, BTreeSet<&str>>::new();
+        for r#ref in refs {
+            if !tags.contains_key(&r#ref.label) {
+                continue;
+            }
+
+            let Some(file_tags) = tags_by_path.get(r#ref.path.as_path()) else {
+                continue;
+            };
+
+            let owners = match edges {
+                Edges::File => file_tags.iter().collect::<Vec<_>>(),
+                Edges::Section => {
+                    // The owner is the last tag at or before the reference.
+                    file_tags
+                        .iter()
+                        .rev()
+                        .find(|tag| tag.line_number <= r#ref.line_number)
+                        .map(|owner| {
+                            file_tags
+                                .iter()
+                                .filter(|tag| tag.line_number == owner.line_number)
+                                .collect()
+                        })
+                        .unwrap_or_default()
+                }
+                Edges::Line => file_tags
+                    .iter()
+                    .filter(|tag| tag.line_number == r#ref.line_number)
+                    .collect(),
+            };
+
+            for owner in owners {
+                if owner.label != r#ref.label {
+                    graph
+                        .entry(owner.label.as_str())
+                        .or_default()
+                        .insert(r#ref.label.as_str());
+                }
+            }
+        }
+
+        Self { edges: graph }
+    }
+
+    // This function returns the labels reachable from the given label (not including the label
+    // itself), in breadth-first order with ties broken alphabetically.
+    pub fn reachable(&self, from: &str) -> Vec<&'a str> {
+        let mut visited = HashSet::<&str>::new();
+        visited.insert(from);
+        let mut queue = VecDeque::from([from]);
+        let mut closure = vec![];
+
+        while let Some(label) = queue.pop_front() {
+            for next in self.edges.get(label).into_iter().flatten() {
+                if visited.insert(next) {
+                    closure.push(*next);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        closure
+    }
+
+    // This function returns a shortest path between two labels (including both ends), if there is
+    // one.
+    pub fn path(&self, from: &'a str, to: &str) -> Option<Vec<&'a str>> {
+        let mut parents = HashMap::<&str, &str>::new();
+        let mut visited = HashSet::<&str>::new();
+        visited.insert(from);
+        let mut queue = VecDeque::from([from]);
+
+        while let Some(label) = queue.pop_front() {
+            if label == to {
+                // Walk back to the start to recover the path.
+                let mut path = vec![label];
+                let mut current = label;
+                while let Some(parent) = parents.get(current) {
+                    path.push(parent);
+                    current = parent;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for next in self.edges.get(label).into_iter().flatten() {
+                if visited.insert(next) {
+                    parents.insert(next, label);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            directive::{Directive, Type},
+            reachable::{Edges, Graph},
+        },
+        std::collections::HashMap,
+    };
+
+    fn fixture() -> (HashMap<String, Vec<Directive>>, Vec<Directive>) {
+        let mut tags = HashMap::new();
+        for (label, path, line_number) in [
+            ("a", "file1.rs", 1),
+            ("b", "file1.rs", 10),
+            ("c", "file2.rs", 1),
+            ("d", "file3.rs", 1),
+        ] {
+            tags.insert(
+                label.to_owned(),
+                vec![Directive::new(Type::Tag, label, path, line_number)],
+            );
+        }
+
+        let refs = vec![
+            Directive::new(Type::Ref, "c", "file1.rs", 1),
+            Directive::new(Type::Ref, "d", "file1.rs", 11),
+            Directive::new(Type::Ref, "a", "file2.rs", 5),
+            Directive::new(Type::Ref, "missing", "file3.rs", 2),
+        ];
+
+        (tags, refs)
+    }
+
+    #[test]
+    fn reachable_file() {
+        let (tags, refs) = fixture();
+        let graph = Graph::new(&tags, &refs, Edges::File);
+
+        assert_eq!(graph.reachable("a"), ["c", "d"]);
+        assert_eq!(graph.reachable("b"), ["c", "d", "a"]);
+        assert_eq!(graph.reachable("c"), ["a", "d"]);
+        assert!(graph.reachable("d").is_empty());
+    }
+
+    #[test]
+    fn reachable_section() {
+        let (tags, refs) = fixture();
+        let graph = Graph::new(&tags, &refs, Edges::Section);
+
+        assert_eq!(graph.reachable("a"), ["c"]);
+        assert_eq!(graph.reachable("b"), ["d"]);
+        assert_eq!(graph.reachable("c"), ["a"]);
+    }
+
+    #[test]
+    fn reachable_line() {
+        let (tags, refs) = fixture();
+        let graph = Graph::new(&tags, &refs, Edges::Line);
+
+        assert_eq!(graph.reachable("a"), ["c"]);
+        assert!(graph.reachable("b").is_empty());
+        assert!(graph.reachable("c").is_empty());
+    }
+
+    #[test]
+    fn path_between() {
+        let (tags, refs) = fixture();
+        let graph = Graph::new(&tags, &refs, Edges::Section);
+
+        assert_eq!(graph.path("c", "a"), Some(vec!["c", "a"]));
+        assert_eq!(graph.path("a", "a"), Some(vec!["a"]));
+        assert_eq!(graph.path("c", "c"), Some(vec!["c"]));
+        assert_eq!(graph.path("a", "d"), None);
+
+        let graph = Graph::new(&tags, &refs, Edges::File);
+        assert_eq!(graph.path("c", "d"), Some(vec!["c", "a", "d"]));
+    }
+}
@@ -0,0 +1,21 @@
+use std::fs::File;
+
+// This function tells the operating system that a file is about to be read from start to finish,
+// so it can start loading the file into the page cache right away and read ahead aggressively.
+// This cuts the time spent waiting on the disk when the cache is cold. The advice is only a hint,
+// so any errors are ignored.
+#[cfg(all(feature = "readahead", target_os = "linux"))]
+pub fn advise(file: &File) {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: The file descriptor is valid for as long as `file` is, and advice doesn't change the
+    // results of reading the file.
+    unsafe {
+        libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_SEQUENTIAL);
+        libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_WILLNEED);
+    }
+}
+
+// Without the `readahead` feature (or on other platforms), files are read without any advice.
+#[cfg(not(all(feature = "readahead", target_os = "linux")))]
+pub fn advise(_file: &File) {}
@@ -0,0 +1,360 @@
+use {
+    crate::{
+        directive::{normalize_label, split_metadata, Type},
+        edit::{self, Edit},
+        fix::Change,
+    },
+    regex::{Captures, Regex},
+    std::{
+        collections::{BTreeSet, HashMap, HashSet},
+        fs::read_to_string,
+        path::{Path, PathBuf},
+    },
+};
+
+// This function splits a line of a CSV file into its fields. Fields may be quoted with double
+// quotes, in which case a doubled quote stands for a literal one.
+fn csv_fields(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = vec![];
+    let mut chars = line.chars().peekable();
+    loop {
+        let mut field = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                    None => return Err("a quoted field is missing its closing quote".to_owned()),
+                }
+            }
+            if chars.peek().is_some_and(|c| *c != ',') {
+                return Err("a quoted field is followed by something other than a comma".to_owned());
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                field.push(c);
+            }
+        }
+        fields.push(field.trim().to_owned());
+        if chars.next().is_none() {
+            return Ok(fields);
+        }
+    }
+}
+
+// This function parses a mapping file with an old label and a new label on each line, separated by
+// a comma. Blank lines are skipped, as is a header line of `old,new`. It returns the pairs in
+// order.
+pub fn parse_mapping(contents: &str) -> Result<Vec<(String, String)>, String> {
+    let mut mapping = vec![];
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = csv_fields(line).map_err(|error| format!("Line {}: {error}.", i + 1))?;
+        let [old, new] = &fields[..] else {
+            return Err(format!(
+                "Line {} has {} fields, but it should have two (the old label and the new one).",
+                i + 1,
+                fields.len(),
+            ));
+        };
+        if i == 0 && old == "old" && new == "new" {
+            continue;
+        }
+        mapping.push((normalize_label(old), normalize_label(new)));
+    }
+
+    Ok(mapping)
+}
+
+// This function checks that a new label would be read back the same way from a directive: it must
+// not be empty, contain `]`, have whitespace around it, or end with anything which would be read as
+// metadata.
+fn check_label(label: &str) -> Result<(), String> {
+    if label.is_empty() || label.contains(']') || label.trim() != label {
+        Err(format!("`{label}` isn't a valid label."))
+    } else if split_metadata(label).0 != label {
+        Err(format!(
+            "`{label}` isn't a valid label, since the end of it would be read as metadata.",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+// This function checks that a mapping can be applied to the given tags: every old label must be
+// the label of a tag, and no two tags may end up with the same label. Every problem is reported at
+// once. It returns the renames which actually change something.
+pub fn validate(
+    mapping: &[(String, String)],
+    labels: &HashSet<String>,
+) -> Result<HashMap<String, String>, String> {
+    let mut problems = vec![];
+    let mut renames = HashMap::<String, String>::new();
+    for (old, new) in mapping {
+        if let Err(problem) = check_label(new) {
+            problems.push(problem);
+        } else if !labels.contains(old) {
+            problems.push(format!("There is no tag `{old}` to rename."));
+        } else if let Some(other) = renames.get(old).filter(|other| *other != new) {
+            problems.push(format!("`{old}` is renamed to both `{other}` and `{new}`."));
+        } else if old != new {
+            renames.insert(old.clone(), new.clone());
+        }
+    }
+
+    // Check that every new label is unique, including the labels which aren't renamed.
+    let mut sources = HashMap::<&str, Vec<&str>>::new();
+    for label in labels {
+        let new = renames.get(label).unwrap_or(label);
+        sources.entry(new).or_default().push(label);
+    }
+    let mut collisions = sources
+        .into_iter()
+        .filter(|(_, olds)| olds.len() > 1)
+        .collect::<Vec<_>>();
+    collisions.sort_unstable();
+    for (new, mut olds) in collisions {
+        olds.sort_unstable();
+        problems.push(format!(
+            "`{new}` would be the label of several tags (currently {}).",
+            olds.iter()
+                .map(|old| format!("`{old}`"))
+                .collect::<Vec<_>>()
+                .join(", "),
+        ));
+    }
+
+    if problems.is_empty() {
+        Ok(renames)
+    } else {
+        Err(format!(
+            "The mapping can't be applied:\n{}",
+            problems
+                .iter()
+                .map(|problem| format!("  {problem}"))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ))
+    }
+}
+
+// This function checks that a single tag can be renamed: the old label must be the label of a tag,
+// and the new one must be valid and not already taken. It returns the rename as a mapping, which is
+// empty if the labels are the same.
+pub fn validate_one(
+    old: &str,
+    new: &str,
+    labels: &HashSet<String>,
+) -> Result<HashMap<String, String>, String> {
+    let (old, new) = (normalize_label(old.trim()), normalize_label(new));
+    check_label(&new)?;
+    if !labels.contains(&old) {
+        Err(format!("There is no tag `{old}` to rename."))
+    } else if old == new {
+        Ok(HashMap::new())
+    } else if labels.contains(&new) {
+        Err(format!("There is already a tag `{new}`."))
+    } else {
+        Ok(HashMap::from([(old, new)]))
+    }
+}
+
+// This function renames the labels of the tags and tag references in the contents of a file.
+// Everything else (including any metadata and the whitespace within each directive) is left alone.
+// It returns the new contents and a list of the changes.
+pub fn rewrite(
+    path: &Path,
+    contents: &str,
+    regexes: &[(Type, &Regex)],
+    renames: &HashMap<String, String>,
+) -> (String, Vec<Change>) {
+    let mut changes = Vec::new();
+    let mut new_contents = String::with_capacity(contents.len());
+
+    for (i, line) in contents.split_inclusive('\n').enumerate() {
+        let mut line = line.to_owned();
+
+        for (r#type, regex) in regexes {
+            line = regex
+                .replace_all(&line, |captures: &Captures| {
+                    // The `unwrap`s are safe because these groups always participate in a match.
+                    let whole = captures.get(0).unwrap();
+                    let inner = captures.get(1).unwrap();
+                    let label = if matches!(r#type, Type::Tag | Type::Ref) {
+                        split_metadata(inner.as_str()).0
+                    } else {
+                        inner.as_str().to_owned()
+                    };
+                    let Some(new) = renames.get(&normalize_label(&label)) else {
+                        return whole.as_str().to_owned();
+                    };
+
+                    // The label is at the start of the contents, since they're trimmed.
+                    let start = inner.start() - whole.start();
+                    let after = format!(
+                        "{}{new}{}",
+                        &whole.as_str()[..start],
+                        &whole.as_str()[start + label.len()..],
+                    );
+                    changes.push(Change {
+                        path: path.to_owned(),
+                        line_number: i + 1,
+                        before: whole.as_str().to_owned(),
+                        after: after.clone(),
+                    });
+
+                    after
+                })
+                .into_owned();
+        }
+
+        new_contents.push_str(&line);
+    }
+
+    (new_contents, changes)
+}
+
+// This function applies the renames to the given files, each of which is read and written once, as
+// the options allow. It returns a list of the changes.
+pub fn apply<'a>(
+    paths: impl IntoIterator<Item = &'a PathBuf>,
+    regexes: &[(Type, &Regex)],
+    renames: &HashMap<String, String>,
+    options: edit::Options,
+) -> Result<Vec<Change>, String> {
+    let mut changes = Vec::new();
+    let mut edits = Vec::new();
+
+    // Visit each file once, in a deterministic order.
+    for path in paths.into_iter().collect::<BTreeSet<_>>() {
+        let contents = read_to_string(path)
+            .map_err(|error| format!("Unable to read {}: {error}", path.to_string_lossy()))?;
+        let (new_contents, file_changes) = rewrite(path, &contents, regexes, renames);
+        if !file_changes.is_empty() {
+            edits.push(Edit {
+                path: path.clone(),
+                contents: new_contents,
+            });
+            changes.extend(file_changes);
+        }
+    }
+
+    edit::write_all(&edits, options)?;
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            directive::{compile_directive_regex, Type},
+            rename::{parse_mapping, rewrite, validate, validate_one},
+        },
+        std::{
+            collections::{HashMap, HashSet},
+            path::Path,
+        },
+    };
+
+    fn labels(labels: &[&str]) -> HashSet<String> {
+        labels.iter().map(|label| (*label).to_owned()).collect()
+    }
+
+    #[test]
+    fn parse_mapping_csv() {
+        let mapping =
+            parse_mapping("old,new\n\ncache, storage.cache\n\"a,b\",\"say \"\"hi\"\"\"\n").unwrap();
+
+        assert_eq!(
+            mapping,
+            [
+                ("cache".to_owned(), "storage.cache".to_owned()),
+                ("a,b".to_owned(), "say \"hi\"".to_owned()),
+            ],
+        );
+        assert!(parse_mapping("a,b,c").is_err());
+        assert!(parse_mapping("\"a,b").is_err());
+    }
+
+    #[test]
+    fn validate_collisions() {
+        let mapping = parse_mapping("a,b\nb,c\nd,d").unwrap();
+        let renames = validate(&mapping, &labels(&["a", "b", "d"])).unwrap();
+        assert_eq!(renames.len(), 2);
+        assert_eq!(renames["b"], "c");
+
+        let mapping = parse_mapping("a,c\nb,c\nx,y\nd,e]").unwrap();
+        let error = validate(&mapping, &labels(&["a", "b", "d"])).unwrap_err();
+        assert!(error.contains("There is no tag `x` to rename."));
+        assert!(error.contains("`e]` isn't a valid label."));
+        assert!(error.contains("`c` would be the label of several tags (currently `a`, `b`)."));
+
+        let mapping = parse_mapping("a,b").unwrap();
+        assert!(validate(&mapping, &labels(&["a", "b"])).is_err());
+
+        let mapping = parse_mapping("a,x owner=@y").unwrap();
+        assert!(
+            validate(&mapping, &labels(&["a"]))
+                .unwrap_err()
+                .contains("`x owner=@y` isn't a valid label, since the end of it would be read as"),
+        );
+    }
+
+    #[test]
+    fn validate_one_existing() {
+        let labels = labels(&["a", "b", "\u{e9}"]);
+        assert_eq!(validate_one(" a ", "c", &labels).unwrap()["a"], "c");
+        assert!(validate_one("a", "a", &labels).unwrap().is_empty());
+        assert_eq!(
+            validate_one("a", "b", &labels).unwrap_err(),
+            "There is already a tag `b`.",
+        );
+        assert_eq!(
+            validate_one("x", "y", &labels).unwrap_err(),
+            "There is no tag `x` to rename.",
+        );
+        assert_eq!(
+            validate_one("a", "e\u{301}", &labels).unwrap_err(),
+            "There is already a tag `\u{e9}`.",
+        );
+        assert!(validate_one("a", "c]", &labels).is_err());
+        assert!(validate_one("a", " c", &labels).is_err());
+        assert!(validate_one("a", "x owner=@y", &labels).is_err());
+    }
+
+    #[test]
+    fn rewrite_labels() {
+        let tag_regex = compile_directive_regex("tag");
+        let ref_regex = compile_directive_regex("ref");
+        let regexes = [(Type::Tag, &tag_regex), (Type::Ref, &ref_regex)];
+        let renames = [("cache", "storage.cache"), ("other", "unused")]
+            .iter()
+            .map(|(old, new)| ((*old).to_owned(), (*new).to_owned()))
+            .collect::<HashMap<_, _>>();
+
+        let contents = "
+            [?TAG : cache owner=@x ] See [?ref:cache].
+            [?tag:cache_two] [?ref: cache]
+        "
+        .replace('?', "");
+
+        let (new_contents, changes) = rewrite(Path::new("file.rs"), &contents, &regexes, &renames);
+        assert_eq!(
+            new_contents,
+            "
+            [?TAG : storage.cache owner=@x ] See [?ref:storage.cache].
+            [?tag:cache_two] [?ref: storage.cache]
+        "
+            .replace('?', ""),
+        );
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[2].line_number, 3);
+    }
+}
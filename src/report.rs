@@ -0,0 +1,1307 @@
+use {
+    crate::{
+        count::count,
+        diagnostic::{Diagnostic, Severity},
+        path_style::display,
+        schema::SCHEMA_VERSION,
+        walk::{normalize, quote},
+    },
+    serde::Serialize,
+    std::{
+        cmp::Reverse,
+        collections::{BTreeMap, BTreeSet},
+        fmt::{self, Write},
+        path::PathBuf,
+        str::FromStr,
+    },
+};
+
+// This enum represents the formats in which the results of a check can be reported.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReportFormat {
+    Human,
+    GithubReview,
+    Rdjson,
+    Bitbucket,
+    Azure,
+    Teamcity,
+    Buildkite,
+    Sonar,
+    Gerrit,
+    Json,
+    JsonSummary,
+    Html,
+}
+
+// The names of the formats, for the command-line interface
+pub const REPORT_FORMATS: &[&str] = &[
+    "human",
+    "github-review",
+    "rdjson",
+    "bitbucket",
+    "azure",
+    "teamcity",
+    "buildkite",
+    "sonar",
+    "gerrit",
+    "json",
+    "json-summary",
+    "html",
+];
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Self::Human),
+            "github-review" => Ok(Self::GithubReview),
+            "rdjson" => Ok(Self::Rdjson),
+            "bitbucket" => Ok(Self::Bitbucket),
+            "azure" => Ok(Self::Azure),
+            "teamcity" => Ok(Self::Teamcity),
+            "buildkite" => Ok(Self::Buildkite),
+            "sonar" => Ok(Self::Sonar),
+            "gerrit" => Ok(Self::Gerrit),
+            "json" => Ok(Self::Json),
+            "json-summary" => Ok(Self::JsonSummary),
+            "html" => Ok(Self::Html),
+            _ => Err(format!("Unknown format `{s}`.")),
+        }
+    }
+}
+
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Human => "human",
+                Self::GithubReview => "github-review",
+                Self::Rdjson => "rdjson",
+                Self::Bitbucket => "bitbucket",
+                Self::Azure => "azure",
+                Self::Teamcity => "teamcity",
+                Self::Buildkite => "buildkite",
+                Self::Sonar => "sonar",
+                Self::Gerrit => "gerrit",
+                Self::Json => "json",
+                Self::JsonSummary => "json-summary",
+                Self::Html => "html",
+            },
+        )
+    }
+}
+
+// This struct represents the request body for creating a pull request review with the GitHub API.
+#[derive(Serialize)]
+struct GithubReview {
+    body: String,
+    event: &'static str,
+    comments: Vec<GithubReviewComment>,
+}
+
+// This struct represents an inline comment in a pull request review.
+#[derive(Serialize)]
+struct GithubReviewComment {
+    path: String,
+    line: usize,
+    side: &'static str,
+    body: String,
+}
+
+// This struct represents a report in the Reviewdog Diagnostic Format.
+#[derive(Serialize)]
+struct Rdjson {
+    source: RdjsonSource,
+    diagnostics: Vec<RdjsonDiagnostic>,
+}
+
+// This struct identifies the tool which produced a Reviewdog report.
+#[derive(Serialize)]
+struct RdjsonSource {
+    name: &'static str,
+    url: &'static str,
+}
+
+// This struct represents a problem in a Reviewdog report.
+#[derive(Serialize)]
+struct RdjsonDiagnostic {
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location: Option<RdjsonLocation>,
+    severity: &'static str,
+    code: RdjsonCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original_output: Option<String>,
+}
+
+// This struct represents the location of a problem in a Reviewdog report.
+#[derive(Serialize)]
+struct RdjsonLocation {
+    path: String,
+    range: RdjsonRange,
+}
+
+// This struct represents a range of lines in a Reviewdog report. Only the start is known.
+#[derive(Serialize)]
+struct RdjsonRange {
+    start: RdjsonPosition,
+}
+
+// This struct represents a position in a Reviewdog report.
+#[derive(Serialize)]
+struct RdjsonPosition {
+    line: usize,
+}
+
+// This struct represents the code of a problem in a Reviewdog report.
+#[derive(Serialize)]
+struct RdjsonCode {
+    value: String,
+}
+
+// Bitbucket rejects annotation summaries longer than this many characters.
+const BITBUCKET_SUMMARY_LIMIT: usize = 450;
+
+// This struct represents a Bitbucket Code Insights report along with its annotations, which are
+// uploaded with separate requests.
+#[derive(Serialize)]
+struct Bitbucket {
+    report: BitbucketReport,
+    annotations: Vec<BitbucketAnnotation>,
+}
+
+// This struct represents the request body for creating a Bitbucket Code Insights report.
+#[derive(Serialize)]
+struct BitbucketReport {
+    title: &'static str,
+    details: String,
+    report_type: &'static str,
+    reporter: &'static str,
+    result: &'static str,
+}
+
+// This struct represents a Bitbucket Code Insights annotation.
+#[derive(Serialize)]
+struct BitbucketAnnotation {
+    external_id: String,
+    annotation_type: &'static str,
+    summary: String,
+    details: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<usize>,
+    severity: &'static str,
+}
+
+// This struct represents a report in SonarQube's generic issue import format.
+#[derive(Serialize)]
+struct Sonar {
+    issues: Vec<SonarIssue>,
+}
+
+// This struct represents an issue in a SonarQube report.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SonarIssue {
+    engine_id: &'static str,
+    rule_id: String,
+    severity: &'static str,
+    r#type: &'static str,
+    primary_location: SonarLocation,
+}
+
+// This struct represents the location of an issue in a SonarQube report.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SonarLocation {
+    message: String,
+    file_path: String,
+    text_range: SonarTextRange,
+}
+
+// This struct represents a range of lines in a SonarQube report. Only the start is known.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SonarTextRange {
+    start_line: usize,
+}
+
+// This struct represents the request body for setting a review on a Gerrit revision.
+#[derive(Serialize)]
+struct Gerrit {
+    message: String,
+    tag: &'static str,
+    robot_comments: BTreeMap<String, Vec<GerritRobotComment>>,
+}
+
+// This struct represents a comment from a robot on a line of a Gerrit change.
+#[derive(Serialize)]
+struct GerritRobotComment {
+    robot_id: &'static str,
+    robot_run_id: &'static str,
+    line: usize,
+    message: String,
+}
+
+// This function summarizes the number of errors and warnings.
+fn summary(diagnostics: &[Diagnostic]) -> String {
+    let errors = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .count();
+
+    format!(
+        "Tagref found {} and {}.",
+        count(errors, "error"),
+        count(diagnostics.len() - errors, "warning"),
+    )
+}
+
+// This function returns the first line of a message, truncated to the given number of characters.
+fn first_line(message: &str, limit: usize) -> String {
+    let line = message.lines().next().unwrap_or_default();
+    if line.chars().count() <= limit {
+        line.to_owned()
+    } else {
+        let mut truncated = line.chars().take(limit - 1).collect::<String>();
+        truncated.push('…');
+        truncated
+    }
+}
+
+// This function renders a diagnostic as Markdown for a comment, quoting its context (if any) so
+// readers don't need to open the file.
+fn markdown(diagnostic: &Diagnostic) -> String {
+    let mut markdown = format!(
+        "**Tagref {} `{}`:** {}",
+        diagnostic.severity,
+        diagnostic.code,
+        diagnostic.message.trim_end(),
+    );
+    if let Some(context) = &diagnostic.context {
+        let _ = write!(markdown, "\n\n```\n{context}\n```");
+    }
+    markdown
+}
+
+// This function builds a GitHub pull request review. Problems with a location become inline
+// comments. The rest are listed in the body of the review, since they can't be attached to a line.
+fn github_review(diagnostics: &[Diagnostic]) -> GithubReview {
+    let mut body = summary(diagnostics);
+    let mut comments = vec![];
+    for diagnostic in diagnostics {
+        if let Some((path, line)) = &diagnostic.location {
+            comments.push(GithubReviewComment {
+                path: display(&normalize(path)).to_string_lossy().into_owned(),
+                line: *line,
+                side: "RIGHT",
+                body: markdown(diagnostic),
+            });
+        } else {
+            body.push_str("\n\n");
+            body.push_str(&markdown(diagnostic));
+        }
+    }
+
+    GithubReview {
+        body,
+        event: "COMMENT",
+        comments,
+    }
+}
+
+// This function builds a Reviewdog report.
+fn rdjson(diagnostics: &[Diagnostic]) -> Rdjson {
+    Rdjson {
+        source: RdjsonSource {
+            name: "tagref",
+            url: "https://github.com/stepchowfun/tagref",
+        },
+        diagnostics: diagnostics
+            .iter()
+            .map(|diagnostic| RdjsonDiagnostic {
+                message: diagnostic.message.trim_end().to_owned(),
+                location: diagnostic
+                    .location
+                    .as_ref()
+                    .map(|(path, line)| RdjsonLocation {
+                        path: display(&normalize(path)).to_string_lossy().into_owned(),
+                        range: RdjsonRange {
+                            start: RdjsonPosition { line: *line },
+                        },
+                    }),
+                severity: match diagnostic.severity {
+                    Severity::Warning => "WARNING",
+                    Severity::Error => "ERROR",
+                },
+                code: RdjsonCode {
+                    value: diagnostic.code.clone(),
+                },
+                original_output: diagnostic.context.clone(),
+            })
+            .collect(),
+    }
+}
+
+// This function builds a Bitbucket Code Insights report.
+fn bitbucket(diagnostics: &[Diagnostic]) -> Bitbucket {
+    let failed = diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Error);
+
+    Bitbucket {
+        report: BitbucketReport {
+            title: "Tagref",
+            details: summary(diagnostics),
+            report_type: "BUG",
+            reporter: "tagref",
+            result: if failed { "FAILED" } else { "PASSED" },
+        },
+        annotations: diagnostics
+            .iter()
+            .enumerate()
+            .map(|(i, diagnostic)| BitbucketAnnotation {
+                external_id: format!("tagref-{}", i + 1),
+                annotation_type: "BUG",
+                summary: first_line(&diagnostic.message, BITBUCKET_SUMMARY_LIMIT),
+                details: format!(
+                    "{} ({}): {}{}",
+                    diagnostic.code,
+                    diagnostic.severity,
+                    diagnostic.message.trim_end(),
+                    diagnostic
+                        .context
+                        .as_ref()
+                        .map_or_else(String::new, |context| format!("\n\n{context}")),
+                ),
+                path: diagnostic
+                    .location
+                    .as_ref()
+                    .map(|(path, _)| display(&normalize(path)).to_string_lossy().into_owned()),
+                line: diagnostic.location.as_ref().map(|(_, line)| *line),
+                severity: match diagnostic.severity {
+                    Severity::Warning => "MEDIUM",
+                    Severity::Error => "HIGH",
+                },
+            })
+            .collect(),
+    }
+}
+
+// This function builds a SonarQube report. SonarQube requires every issue to be in a file, so
+// problems without a location are omitted.
+fn sonar(diagnostics: &[Diagnostic]) -> Sonar {
+    Sonar {
+        issues: diagnostics
+            .iter()
+            .filter_map(|diagnostic| {
+                diagnostic.location.as_ref().map(|(path, line)| SonarIssue {
+                    engine_id: "tagref",
+                    rule_id: diagnostic.code.clone(),
+                    severity: match diagnostic.severity {
+                        Severity::Warning => "MINOR",
+                        Severity::Error => "MAJOR",
+                    },
+                    r#type: "CODE_SMELL",
+                    primary_location: SonarLocation {
+                        message: diagnostic.message.trim_end().to_owned(),
+                        file_path: display(&normalize(path)).to_string_lossy().into_owned(),
+                        text_range: SonarTextRange { start_line: *line },
+                    },
+                })
+            })
+            .collect(),
+    }
+}
+
+// This function builds a Gerrit review. Problems with a location become robot comments. The rest
+// are listed in the review message, since they can't be attached to a line.
+fn gerrit(diagnostics: &[Diagnostic]) -> Gerrit {
+    let mut message = summary(diagnostics);
+    let mut robot_comments = BTreeMap::<String, Vec<GerritRobotComment>>::new();
+    for diagnostic in diagnostics {
+        let text = format!(
+            "{} `{}`: {}",
+            diagnostic.severity,
+            diagnostic.code,
+            diagnostic.message.trim_end(),
+        );
+        if let Some((path, line)) = &diagnostic.location {
+            robot_comments
+                .entry(display(&normalize(path)).to_string_lossy().into_owned())
+                .or_default()
+                .push(GerritRobotComment {
+                    robot_id: "tagref",
+                    robot_run_id: env!("CARGO_PKG_VERSION"),
+                    line: *line,
+                    message: text,
+                });
+        } else {
+            message.push_str("\n\n");
+            message.push_str(&text);
+        }
+    }
+
+    Gerrit {
+        message,
+        tag: "autogenerated:tagref",
+        robot_comments,
+    }
+}
+
+// This function escapes text for an Azure Pipelines logging command. Property values must also
+// escape the characters which delimit properties.
+fn azure_escape(text: &str, property: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '%' => escaped.push_str("%AZP25"),
+            '\r' => escaped.push_str("%0D"),
+            '\n' => escaped.push_str("%0A"),
+            ';' if property => escaped.push_str("%3B"),
+            ']' if property => escaped.push_str("%5D"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// This function renders each problem as an Azure Pipelines `task.logissue` logging command.
+fn azure(diagnostics: &[Diagnostic]) -> String {
+    let mut output = String::new();
+    for diagnostic in diagnostics {
+        let mut properties = format!(
+            "type={}",
+            match diagnostic.severity {
+                Severity::Warning => "warning",
+                Severity::Error => "error",
+            },
+        );
+        if let Some((path, line)) = &diagnostic.location {
+            let _ = write!(
+                properties,
+                ";sourcepath={};linenumber={line}",
+                azure_escape(&display(&normalize(path)).to_string_lossy(), true),
+            );
+        }
+        let _ = write!(properties, ";code={}", azure_escape(&diagnostic.code, true));
+
+        let _ = writeln!(
+            output,
+            "##vso[task.logissue {properties};]{}",
+            azure_escape(diagnostic.message.trim_end(), false),
+        );
+    }
+    output
+}
+
+// This function escapes a value for a TeamCity service message.
+fn teamcity_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '|' | '\'' | '[' | ']' => {
+                escaped.push('|');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("|n"),
+            '\r' => escaped.push_str("|r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// This function renders the problems as TeamCity service messages. Each problem is an inspection,
+// and any errors are also reported as a build problem.
+fn teamcity(diagnostics: &[Diagnostic]) -> String {
+    let mut output = String::new();
+
+    // Declare each kind of inspection before it's used.
+    let codes = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.code.as_str())
+        .collect::<BTreeSet<_>>();
+    for code in codes {
+        let code = teamcity_escape(code);
+        let _ = writeln!(
+            output,
+            "##teamcity[inspectionType id='{code}' name='{code}' \
+                description='Tagref check {code}' category='Tagref']",
+        );
+    }
+
+    for diagnostic in diagnostics {
+        let mut attributes = format!(
+            "typeId='{}' message='{}'",
+            teamcity_escape(&diagnostic.code),
+            teamcity_escape(diagnostic.message.trim_end()),
+        );
+        if let Some((path, line)) = &diagnostic.location {
+            let _ = write!(
+                attributes,
+                " file='{}' line='{line}'",
+                teamcity_escape(&display(&normalize(path)).to_string_lossy()),
+            );
+        }
+        let _ = writeln!(
+            output,
+            "##teamcity[inspection {attributes} SEVERITY='{}']",
+            match diagnostic.severity {
+                Severity::Warning => "WARNING",
+                Severity::Error => "ERROR",
+            },
+        );
+    }
+
+    if diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Error)
+    {
+        let _ = writeln!(
+            output,
+            "##teamcity[buildProblem description='{}' identity='tagref']",
+            teamcity_escape(&summary(diagnostics)),
+        );
+    }
+
+    output
+}
+
+// This function renders the problems as Markdown for a Buildkite annotation. The problems are
+// grouped by file, with errors before warnings.
+fn buildkite(diagnostics: &[Diagnostic]) -> String {
+    let mut output = format!("### {}\n", summary(diagnostics));
+
+    // Group the problems by file. Problems without a location go last.
+    let mut groups = BTreeMap::<Option<PathBuf>, Vec<&Diagnostic>>::new();
+    for diagnostic in diagnostics {
+        groups
+            .entry(
+                diagnostic
+                    .location
+                    .as_ref()
+                    .map(|(path, _)| display(&normalize(path)).into_owned()),
+            )
+            .or_default()
+            .push(diagnostic);
+    }
+    let (unlocated, located): (Vec<_>, Vec<_>) =
+        groups.into_iter().partition(|(path, _)| path.is_none());
+
+    for (path, mut group) in located.into_iter().chain(unlocated) {
+        let _ = writeln!(
+            output,
+            "\n#### {}\n",
+            path.map_or_else(
+                || "Other problems".to_owned(),
+                |path| format!("`{}`", quote(&path)),
+            ),
+        );
+
+        group.sort_by_key(|diagnostic| Reverse(diagnostic.severity));
+        for diagnostic in group {
+            let _ = writeln!(
+                output,
+                "- {} **{} `{}`**{}: {}",
+                match diagnostic.severity {
+                    Severity::Warning => ":warning:",
+                    Severity::Error => ":x:",
+                },
+                match diagnostic.severity {
+                    Severity::Warning => "Warning",
+                    Severity::Error => "Error",
+                },
+                diagnostic.code,
+                diagnostic
+                    .location
+                    .as_ref()
+                    .map_or_else(String::new, |(_, line)| format!(" (line {line})")),
+                diagnostic.message.trim_end().replace('\n', "\n  "),
+            );
+        }
+    }
+
+    output
+}
+
+// This struct represents the totals for a check, which are reported by the `json-summary` format.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Totals {
+    pub tags: usize,
+    pub tag_references: usize,
+    pub file_references: usize,
+    pub directory_references: usize,
+    pub spec_references: usize,
+    pub url_references: usize,
+    pub files_scanned: usize,
+    pub duration_seconds: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub configuration: Option<Configuration>,
+}
+
+// This struct represents the settings which were in effect for a check. With `--explain-pass`, it's
+// reported along with the totals, e.g., for audit logs which need to show what was validated.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Configuration {
+    pub paths: Vec<String>,
+    pub excludes: Vec<String>,
+    pub sigils: BTreeMap<String, String>,
+    pub config_file: Option<String>,
+    pub policy_file: Option<String>,
+    pub profile: Option<String>,
+    pub search: String,
+    pub structured_values: bool,
+}
+
+// This function summarizes the problems found by a check by severity, e.g., for the last line of
+// the human-readable output.
+pub fn tally(errors: usize, warnings: usize) -> String {
+    format!(
+        "Found {} and {}.",
+        count(errors, "error"),
+        count(warnings, "warning"),
+    )
+}
+
+// This function explains the result of a check which passed, listing the totals for each kind of
+// directive and the settings which were in effect.
+pub fn explain(totals: &Totals, configuration: &Configuration, warnings: usize) -> String {
+    let list = |items: &[String]| {
+        if items.is_empty() {
+            "(none)".to_owned()
+        } else {
+            items.join(", ")
+        }
+    };
+    let optional = |item: &Option<String>| item.clone().unwrap_or_else(|| "(none)".to_owned());
+
+    [
+        format!("Tags: {}", totals.tags),
+        format!("Tag references: {}", totals.tag_references),
+        format!("File references: {}", totals.file_references),
+        format!("Directory references: {}", totals.directory_references),
+        format!("Spec references: {}", totals.spec_references),
+        format!("URL references: {}", totals.url_references),
+        format!("Files scanned: {}", totals.files_scanned),
+        format!("Warnings: {warnings}"),
+        format!("Paths: {}", list(&configuration.paths)),
+        format!("Excluded paths: {}", list(&configuration.excludes)),
+        format!(
+            "Sigils: {}",
+            configuration
+                .sigils
+                .iter()
+                .map(|(kind, sigil)| format!("{kind}={sigil}"))
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        format!(
+            "Configuration file: {}",
+            optional(&configuration.config_file),
+        ),
+        format!("Policy file: {}", optional(&configuration.policy_file)),
+        format!("Profile: {}", optional(&configuration.profile)),
+        format!("Search: {}", configuration.search),
+        format!(
+            "Structured values: {}",
+            if configuration.structured_values {
+                "yes"
+            } else {
+                "no"
+            },
+        ),
+    ]
+    .join("\n")
+}
+
+// This struct represents the totals for a check along with the number of problems, but not the
+// problems themselves, for dashboards which only track the numbers.
+#[derive(Serialize)]
+struct JsonSummary<'a> {
+    schema_version: usize,
+    #[serde(flatten)]
+    totals: &'a Totals,
+    errors: usize,
+    warnings: usize,
+    problems_by_code: BTreeMap<&'a str, usize>,
+}
+
+// This function summarizes the results of a check.
+fn json_summary<'a>(totals: &'a Totals, diagnostics: &'a [Diagnostic]) -> JsonSummary<'a> {
+    let mut problems_by_code = BTreeMap::new();
+    for diagnostic in diagnostics {
+        *problems_by_code
+            .entry(diagnostic.code.as_str())
+            .or_insert(0) += 1;
+    }
+
+    JsonSummary {
+        schema_version: SCHEMA_VERSION,
+        totals,
+        errors: diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .count(),
+        warnings: diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Warning)
+            .count(),
+        problems_by_code,
+    }
+}
+
+// This struct represents the data shown in the HTML report, which is written alongside it so other
+// frontends can render the same data. It's also the `json` format.
+#[derive(Serialize)]
+struct HtmlData<'a> {
+    schema_version: usize,
+    #[serde(flatten)]
+    totals: &'a Totals,
+    diagnostics: &'a [Diagnostic],
+}
+
+// These are the styles of the HTML report. The colors are custom properties, so a page which embeds
+// the report can override them. The dark theme is used if the reader prefers it, unless the
+// `data-theme` attribute of the report chooses a theme.
+const HTML_STYLE: &str = "
+.tagref-report {
+  --tagref-background: #ffffff;
+  --tagref-foreground: #1f2328;
+  --tagref-muted: #59636e;
+  --tagref-border: #d1d9e0;
+  --tagref-error: #cf222e;
+  --tagref-warning: #9a6700;
+  background: var(--tagref-background);
+  color: var(--tagref-foreground);
+  font-family: system-ui, sans-serif;
+  padding: 1rem;
+}
+@media (prefers-color-scheme: dark) {
+  .tagref-report:not([data-theme=light]) {
+    --tagref-background: #0d1117;
+    --tagref-foreground: #e6edf3;
+    --tagref-muted: #9198a1;
+    --tagref-border: #3d444d;
+    --tagref-error: #ff7b72;
+    --tagref-warning: #d29922;
+  }
+}
+.tagref-report[data-theme=dark] {
+  --tagref-background: #0d1117;
+  --tagref-foreground: #e6edf3;
+  --tagref-muted: #9198a1;
+  --tagref-border: #3d444d;
+  --tagref-error: #ff7b72;
+  --tagref-warning: #d29922;
+}
+.tagref-report input {
+  background: inherit;
+  border: 1px solid var(--tagref-border);
+  color: inherit;
+  margin-bottom: 1rem;
+  padding: 0.25rem 0.5rem;
+  width: 100%;
+}
+.tagref-report table { border-collapse: collapse; width: 100%; }
+.tagref-report th, .tagref-report td {
+  border-bottom: 1px solid var(--tagref-border);
+  padding: 0.25rem 0.5rem;
+  text-align: left;
+  vertical-align: top;
+}
+.tagref-report .totals, .tagref-report .context { color: var(--tagref-muted); }
+.tagref-report .error { color: var(--tagref-error); }
+.tagref-report .warning { color: var(--tagref-warning); }
+.tagref-report pre { margin: 0; white-space: pre-wrap; }
+";
+
+// This is the script which filters the problems in the HTML report as the reader types.
+const HTML_SCRIPT: &str = "
+document.querySelectorAll('.tagref-report').forEach((report) => {
+  const search = report.querySelector('input');
+  search.addEventListener('input', () => {
+    const query = search.value.toLowerCase();
+    report.querySelectorAll('tbody tr').forEach((row) => {
+      row.hidden = !row.textContent.toLowerCase().includes(query);
+    });
+  });
+});
+";
+
+// This function escapes text for HTML.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// This function renders the problems as a self-contained HTML page (with no external resources),
+// with a search box for filtering them.
+fn html(diagnostics: &[Diagnostic], totals: &Totals) -> String {
+    let mut rows = String::new();
+    for diagnostic in diagnostics {
+        let severity = match diagnostic.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        let location = diagnostic
+            .location
+            .as_ref()
+            .map(|(path, line)| format!("{}:{line}", quote(&display(&normalize(path)))))
+            .unwrap_or_default();
+        let context = diagnostic
+            .context
+            .as_ref()
+            .map(|context| format!("<pre class=\"context\">{}</pre>", escape_html(context)))
+            .unwrap_or_default();
+        let _ = writeln!(
+            rows,
+            "<tr><td class=\"{severity}\">{severity}</td><td>{}</td><td>{}</td><td><pre>{}</pre>\
+             {context}</td></tr>",
+            escape_html(&diagnostic.code),
+            escape_html(&location),
+            escape_html(&diagnostic.message),
+        );
+    }
+
+    format!(
+        "<!DOCTYPE html>
+<html lang=\"en\">
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>Tagref report</title>
+<style>{HTML_STYLE}</style>
+</head>
+<body>
+<div class=\"tagref-report\">
+<h1>{}</h1>
+<p class=\"totals\">{} tags, {} tag references, {} file references, {} directory references, {} \
+         spec references, and {} URL references in {} files</p>
+<input type=\"search\" placeholder=\"Search the problems\" aria-label=\"Search the problems\">
+<table>
+<thead><tr><th>Severity</th><th>Code</th><th>Location</th><th>Problem</th></tr></thead>
+<tbody>
+{rows}</tbody>
+</table>
+</div>
+<script>{HTML_SCRIPT}</script>
+</body>
+</html>
+",
+        escape_html(&summary(diagnostics)),
+        totals.tags,
+        totals.tag_references,
+        totals.file_references,
+        totals.directory_references,
+        totals.spec_references,
+        totals.url_references,
+        totals.files_scanned,
+    )
+}
+
+// This function renders the data shown in the HTML report as JSON.
+pub fn html_data(diagnostics: &[Diagnostic], totals: &Totals) -> String {
+    json(&HtmlData {
+        schema_version: SCHEMA_VERSION,
+        totals,
+        diagnostics,
+    })
+}
+
+// This function serializes a report as pretty-printed JSON with a trailing newline.
+fn json<T: Serialize>(report: &T) -> String {
+    // The `unwrap` is safe because reports always serialize successfully.
+    let mut json = serde_json::to_string_pretty(report).unwrap();
+    json.push('\n');
+    json
+}
+
+// This function renders the results of a check in the given format. In the human-readable format,
+// only the messages are rendered, since the caller decides how to present each severity.
+pub fn render(format: ReportFormat, diagnostics: &[Diagnostic], totals: &Totals) -> String {
+    match format {
+        ReportFormat::Human => diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        ReportFormat::GithubReview => json(&github_review(diagnostics)),
+        ReportFormat::Rdjson => json(&rdjson(diagnostics)),
+        ReportFormat::Bitbucket => json(&bitbucket(diagnostics)),
+        ReportFormat::Azure => azure(diagnostics),
+        ReportFormat::Teamcity => teamcity(diagnostics),
+        ReportFormat::Buildkite => buildkite(diagnostics),
+        ReportFormat::Sonar => json(&sonar(diagnostics)),
+        ReportFormat::Gerrit => json(&gerrit(diagnostics)),
+        ReportFormat::Json => html_data(diagnostics, totals),
+        ReportFormat::JsonSummary => json(&json_summary(totals, diagnostics)),
+        ReportFormat::Html => html(diagnostics, totals),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            diagnostic::{Diagnostic, Severity},
+            report::{
+                explain, first_line, html_data, render, tally, Configuration, ReportFormat, Totals,
+                REPORT_FORMATS,
+            },
+        },
+        std::{collections::BTreeMap, path::Path},
+    };
+
+    fn diagnostics() -> Vec<Diagnostic> {
+        vec![
+            Diagnostic {
+                code: "E002".to_owned(),
+                severity: Severity::Error,
+                message: "No tag found.".to_owned(),
+                location: Some((Path::new("./src/main.rs").to_owned(), 3)),
+                label: None,
+                context: Some("// See [?ref:missing].".replace('?', "")),
+            },
+            Diagnostic {
+                code: "unused".to_owned(),
+                severity: Severity::Warning,
+                message: "Unused.".to_owned(),
+                location: None,
+                label: None,
+                context: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn render_human() {
+        assert_eq!(render(ReportFormat::Human, &[], &Totals::default()), "");
+        assert_eq!(
+            render(ReportFormat::Human, &diagnostics(), &Totals::default()),
+            "No tag found.\n\nUnused.",
+        );
+    }
+
+    #[test]
+    fn render_github_review() {
+        let json: serde_json::Value = serde_json::from_str(&render(
+            ReportFormat::GithubReview,
+            &diagnostics(),
+            &Totals::default(),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            json["body"],
+            "Tagref found 1 error and 1 warning.\n\n**Tagref warning `unused`:** Unused.",
+        );
+        assert_eq!(json["event"], "COMMENT");
+        assert_eq!(json["comments"].as_array().unwrap().len(), 1);
+        assert_eq!(json["comments"][0]["path"], "src/main.rs");
+        assert_eq!(json["comments"][0]["line"], 3_u64);
+        assert_eq!(json["comments"][0]["side"], "RIGHT");
+        assert_eq!(
+            json["comments"][0]["body"],
+            "**Tagref error `E002`:** No tag found.\n\n```\n// See [?ref:missing].\n```"
+                .replace('?', ""),
+        );
+    }
+
+    #[test]
+    fn render_rdjson() {
+        let json: serde_json::Value = serde_json::from_str(&render(
+            ReportFormat::Rdjson,
+            &diagnostics(),
+            &Totals::default(),
+        ))
+        .unwrap();
+
+        assert_eq!(json["source"]["name"], "tagref");
+        assert_eq!(json["diagnostics"].as_array().unwrap().len(), 2);
+        assert_eq!(json["diagnostics"][0]["message"], "No tag found.");
+        assert_eq!(json["diagnostics"][0]["location"]["path"], "src/main.rs");
+        assert_eq!(
+            json["diagnostics"][0]["location"]["range"]["start"]["line"],
+            3_u64,
+        );
+        assert_eq!(json["diagnostics"][0]["severity"], "ERROR");
+        assert_eq!(json["diagnostics"][0]["code"]["value"], "E002");
+        assert_eq!(
+            json["diagnostics"][0]["original_output"],
+            "// See [?ref:missing].".replace('?', ""),
+        );
+        assert!(json["diagnostics"][1].get("location").is_none());
+        assert!(json["diagnostics"][1].get("original_output").is_none());
+        assert_eq!(json["diagnostics"][1]["severity"], "WARNING");
+    }
+
+    #[test]
+    fn render_bitbucket() {
+        let json: serde_json::Value = serde_json::from_str(&render(
+            ReportFormat::Bitbucket,
+            &diagnostics(),
+            &Totals::default(),
+        ))
+        .unwrap();
+
+        assert_eq!(json["report"]["result"], "FAILED");
+        assert_eq!(
+            json["report"]["details"],
+            "Tagref found 1 error and 1 warning.",
+        );
+        assert_eq!(json["annotations"].as_array().unwrap().len(), 2);
+        assert_eq!(json["annotations"][0]["external_id"], "tagref-1");
+        assert_eq!(json["annotations"][0]["summary"], "No tag found.");
+        assert_eq!(
+            json["annotations"][0]["details"],
+            "E002 (error): No tag found.\n\n// See [?ref:missing].".replace('?', ""),
+        );
+        assert_eq!(json["annotations"][0]["path"], "src/main.rs");
+        assert_eq!(json["annotations"][0]["line"], 3_u64);
+        assert_eq!(json["annotations"][0]["severity"], "HIGH");
+        assert!(json["annotations"][1].get("path").is_none());
+        assert_eq!(json["annotations"][1]["severity"], "MEDIUM");
+    }
+
+    #[test]
+    fn render_azure() {
+        let mut diagnostics = diagnostics();
+        diagnostics[1].message = "50% unused;\nreally.".to_owned();
+
+        assert_eq!(
+            render(ReportFormat::Azure, &diagnostics, &Totals::default()),
+            "##vso[task.logissue type=error;sourcepath=src/main.rs;linenumber=3;code=E002;]\
+                No tag found.\n\
+             ##vso[task.logissue type=warning;code=unused;]50%AZP25 unused;%0Areally.\n",
+        );
+    }
+
+    #[test]
+    fn render_teamcity() {
+        let mut diagnostics = diagnostics();
+        diagnostics[1].message = "It's [unused].".to_owned();
+
+        assert_eq!(
+            render(ReportFormat::Teamcity, &diagnostics, &Totals::default()),
+            "##teamcity[inspectionType id='E002' name='E002' description='Tagref check E002' \
+                category='Tagref']\n\
+             ##teamcity[inspectionType id='unused' name='unused' \
+                description='Tagref check unused' category='Tagref']\n\
+             ##teamcity[inspection typeId='E002' message='No tag found.' file='src/main.rs' \
+                line='3' SEVERITY='ERROR']\n\
+             ##teamcity[inspection typeId='unused' message='It|'s |[unused|].' \
+                SEVERITY='WARNING']\n\
+             ##teamcity[buildProblem description='Tagref found 1 error and 1 warning.' \
+                identity='tagref']\n",
+        );
+    }
+
+    #[test]
+    fn render_buildkite() {
+        let mut diagnostics = diagnostics();
+        diagnostics.push(Diagnostic {
+            code: "unused".to_owned(),
+            severity: Severity::Warning,
+            message: "Unused.\n  Details.".to_owned(),
+            location: Some((Path::new("./src/main.rs").to_owned(), 1)),
+            label: None,
+            context: None,
+        });
+
+        assert_eq!(
+            render(ReportFormat::Buildkite, &diagnostics, &Totals::default()),
+            "### Tagref found 1 error and 2 warnings.\n\
+             \n\
+             #### `src/main.rs`\n\
+             \n\
+             - :x: **Error `E002`** (line 3): No tag found.\n\
+             - :warning: **Warning `unused`** (line 1): Unused.\n    Details.\n\
+             \n\
+             #### Other problems\n\
+             \n\
+             - :warning: **Warning `unused`**: Unused.\n",
+        );
+    }
+
+    #[test]
+    fn render_sonar() {
+        let json: serde_json::Value = serde_json::from_str(&render(
+            ReportFormat::Sonar,
+            &diagnostics(),
+            &Totals::default(),
+        ))
+        .unwrap();
+
+        assert_eq!(json["issues"].as_array().unwrap().len(), 1);
+        assert_eq!(json["issues"][0]["engineId"], "tagref");
+        assert_eq!(json["issues"][0]["ruleId"], "E002");
+        assert_eq!(json["issues"][0]["severity"], "MAJOR");
+        assert_eq!(json["issues"][0]["type"], "CODE_SMELL");
+        assert_eq!(
+            json["issues"][0]["primaryLocation"]["message"],
+            "No tag found.",
+        );
+        assert_eq!(
+            json["issues"][0]["primaryLocation"]["filePath"],
+            "src/main.rs",
+        );
+        assert_eq!(
+            json["issues"][0]["primaryLocation"]["textRange"]["startLine"],
+            3_u64,
+        );
+    }
+
+    #[test]
+    fn render_gerrit() {
+        let json: serde_json::Value = serde_json::from_str(&render(
+            ReportFormat::Gerrit,
+            &diagnostics(),
+            &Totals::default(),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            json["message"],
+            "Tagref found 1 error and 1 warning.\n\nwarning `unused`: Unused.",
+        );
+        assert_eq!(json["tag"], "autogenerated:tagref");
+        let comments = json["robot_comments"]["src/main.rs"].as_array().unwrap();
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0]["robot_id"], "tagref");
+        assert_eq!(comments[0]["line"], 3_u64);
+        assert_eq!(comments[0]["message"], "error `E002`: No tag found.");
+    }
+
+    #[test]
+    fn render_json_summary() {
+        let totals = Totals {
+            tags: 2,
+            tag_references: 3,
+            files_scanned: 4,
+            ..Totals::default()
+        };
+        let json: serde_json::Value =
+            serde_json::from_str(&render(ReportFormat::JsonSummary, &diagnostics(), &totals))
+                .unwrap();
+
+        assert_eq!(json["schema_version"], 1_u64);
+        assert_eq!(json["tags"], 2_u64);
+        assert_eq!(json["tag_references"], 3_u64);
+        assert_eq!(json["files_scanned"], 4_u64);
+        assert_eq!(json["errors"], 1_u64);
+        assert_eq!(json["warnings"], 1_u64);
+        assert_eq!(json["problems_by_code"]["E002"], 1_u64);
+        assert_eq!(json["problems_by_code"]["unused"], 1_u64);
+        assert!(json.get("diagnostics").is_none());
+        assert!(json.get("configuration").is_none());
+    }
+
+    #[test]
+    fn render_json_summary_configuration() {
+        let totals = Totals {
+            configuration: Some(Configuration {
+                paths: vec![".".to_owned()],
+                search: "everything".to_owned(),
+                ..Configuration::default()
+            }),
+            ..Totals::default()
+        };
+        let json: serde_json::Value =
+            serde_json::from_str(&render(ReportFormat::JsonSummary, &[], &totals)).unwrap();
+
+        assert_eq!(json["configuration"]["paths"][0], ".");
+        assert_eq!(json["configuration"]["search"], "everything");
+        assert!(json["configuration"]["config_file"].is_null());
+    }
+
+    #[test]
+    fn render_html() {
+        let mut diagnostics = diagnostics();
+        diagnostics[1].message = "<script>alert('Unused.')</script>".to_owned();
+        let html = render(ReportFormat::Html, &diagnostics, &Totals::default());
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<h1>Tagref found 1 error and 1 warning.</h1>"));
+        assert!(html.contains("<td>src/main.rs:3</td>"));
+        assert!(html.contains("&lt;script&gt;alert(&#39;Unused.&#39;)&lt;/script&gt;"));
+        assert!(html.contains("<input type=\"search\""));
+        assert!(!html.contains("src=") && !html.contains("<link"));
+    }
+
+    #[test]
+    fn render_html_data() {
+        let json: serde_json::Value =
+            serde_json::from_str(&html_data(&diagnostics(), &Totals::default())).unwrap();
+
+        assert_eq!(json["schema_version"], 1_u64);
+        assert_eq!(json["tags"], 0_u64);
+        assert_eq!(json["diagnostics"][0]["code"], "E002");
+        assert_eq!(json["diagnostics"][1]["severity"], "warning");
+    }
+
+    #[test]
+    fn explain_pass() {
+        let totals = Totals {
+            tags: 2,
+            files_scanned: 3,
+            ..Totals::default()
+        };
+        let mut sigils = BTreeMap::new();
+        sigils.insert("ref".to_owned(), "ref".to_owned());
+        sigils.insert("tag".to_owned(), "tag".to_owned());
+        let configuration = Configuration {
+            paths: vec!["src".to_owned(), "docs".to_owned()],
+            sigils,
+            config_file: Some(".tagref.toml".to_owned()),
+            search: "comments".to_owned(),
+            ..Configuration::default()
+        };
+        let explanation = explain(&totals, &configuration, 1);
+
+        assert!(explanation.contains("Tags: 2\n"));
+        assert!(explanation.contains("Files scanned: 3\n"));
+        assert!(explanation.contains("Warnings: 1\n"));
+        assert!(explanation.contains("Paths: src, docs\n"));
+        assert!(explanation.contains("Excluded paths: (none)\n"));
+        assert!(explanation.contains("Sigils: ref=ref, tag=tag\n"));
+        assert!(explanation.contains("Configuration file: .tagref.toml\n"));
+        assert!(explanation.contains("Search: comments\n"));
+        assert!(explanation.ends_with("Structured values: no"));
+    }
+
+    #[test]
+    fn tally_by_severity() {
+        assert_eq!(tally(2, 1), "Found 2 errors and 1 warning.");
+        assert_eq!(tally(0, 0), "Found 0 errors and 0 warnings.");
+    }
+
+    #[test]
+    fn format_names() {
+        for name in REPORT_FORMATS {
+            assert_eq!(name.parse::<ReportFormat>().unwrap().to_string(), *name);
+        }
+        assert_eq!(REPORT_FORMATS.len(), 12);
+    }
+
+    #[test]
+    fn first_line_truncated() {
+        assert_eq!(first_line("abc\ndef", 3), "abc");
+        assert_eq!(first_line("abcd", 3), "ab…");
+        assert_eq!(first_line("", 3), "");
+    }
+}
@@ -0,0 +1,252 @@
+use {
+    crate::{
+        diagnostic::{Diagnostic, Severity},
+        schema::SCHEMA_VERSION,
+        walk::normalize,
+    },
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::{HashMap, VecDeque},
+        fmt::Write,
+        path::PathBuf,
+        str::FromStr,
+    },
+};
+
+// This enum represents the output formats for a comparison of two check results.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DiffFormat {
+    Human,
+    Json,
+}
+
+// The names of the formats, for the command-line interface
+pub const DIFF_FORMATS: &[&str] = &["human", "json"];
+
+impl FromStr for DiffFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Unknown format `{s}`.")),
+        }
+    }
+}
+
+// This struct represents the part of a check result which is compared: any JSON document with a
+// `diagnostics` field, such as the `json` report or an export.
+#[derive(Deserialize)]
+struct Results {
+    diagnostics: Vec<Diagnostic>,
+}
+
+// This function parses the problems in a check result.
+pub fn parse(contents: &str) -> Result<Vec<Diagnostic>, String> {
+    serde_json::from_str::<Results>(contents)
+        .map(|results| results.diagnostics)
+        .map_err(|error| error.to_string())
+}
+
+// This struct represents how the problems changed between two check results.
+#[derive(Debug, Default, Serialize)]
+pub struct ReportDiff {
+    pub schema_version: usize,
+    pub new: Vec<Diagnostic>,
+    pub fixed: Vec<Diagnostic>,
+    pub unchanged: Vec<Diagnostic>,
+}
+
+// This function returns what identifies a problem across runs. Line numbers shift as code is edited
+// elsewhere in a file, so the problem is identified by its file, label, and the line containing it
+// instead. Problems with neither a label nor a line are identified by their message.
+fn key(diagnostic: &Diagnostic) -> (&str, Option<PathBuf>, Option<&str>, Option<&str>) {
+    (
+        &diagnostic.code,
+        diagnostic
+            .location
+            .as_ref()
+            .map(|(path, _)| normalize(path)),
+        diagnostic.label.as_deref(),
+        diagnostic.context.as_deref().or_else(|| {
+            diagnostic
+                .label
+                .is_none()
+                .then_some(diagnostic.message.as_str())
+        }),
+    )
+}
+
+// This function compares the problems from two runs. A problem which occurs more times in the new
+// run than in the old one is new that many times, and vice versa for fixed problems. The unchanged
+// problems are reported as they appear in the new run.
+pub fn diff(old: &[Diagnostic], new: &[Diagnostic]) -> ReportDiff {
+    // Index the old problems by what identifies them.
+    let mut remaining = HashMap::<_, VecDeque<usize>>::new();
+    for (i, diagnostic) in old.iter().enumerate() {
+        remaining.entry(key(diagnostic)).or_default().push_back(i);
+    }
+
+    let mut report_diff = ReportDiff {
+        schema_version: SCHEMA_VERSION,
+        ..ReportDiff::default()
+    };
+    for diagnostic in new {
+        if remaining
+            .get_mut(&key(diagnostic))
+            .and_then(VecDeque::pop_front)
+            .is_some()
+        {
+            report_diff.unchanged.push(diagnostic.clone());
+        } else {
+            report_diff.new.push(diagnostic.clone());
+        }
+    }
+
+    // Whatever is left from the old run was fixed. The problems are kept in the old run's order.
+    let mut fixed = remaining.into_values().flatten().collect::<Vec<_>>();
+    fixed.sort_unstable();
+    report_diff.fixed = fixed.into_iter().map(|i| old[i].clone()).collect();
+
+    report_diff
+}
+
+impl ReportDiff {
+    // This function returns the number of new errors, which are the only problems which fail the
+    // comparison.
+    pub fn new_errors(&self) -> usize {
+        self.new
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .count()
+    }
+}
+
+// This function renders the comparison in the given format.
+pub fn render(format: DiffFormat, report_diff: &ReportDiff) -> String {
+    match format {
+        DiffFormat::Human => {
+            let mut output = String::new();
+            for (heading, diagnostics) in [
+                ("New", &report_diff.new),
+                ("Fixed", &report_diff.fixed),
+                ("Unchanged", &report_diff.unchanged),
+            ] {
+                if !output.is_empty() {
+                    output.push('\n');
+                }
+                let _ = writeln!(output, "{heading} ({}):", diagnostics.len());
+                for diagnostic in diagnostics {
+                    for line in diagnostic.message.lines() {
+                        let _ = writeln!(output, "  {line}");
+                    }
+                }
+            }
+            output
+        }
+        DiffFormat::Json => {
+            // The `unwrap` is safe because comparisons always serialize successfully.
+            let mut output = serde_json::to_string_pretty(report_diff).unwrap();
+            output.push('\n');
+            output
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            diagnostic::{Diagnostic, Severity},
+            report_diff::{diff, parse, render, DiffFormat},
+        },
+        std::path::Path,
+    };
+
+    fn diagnostic(
+        severity: Severity,
+        label: &str,
+        path: &str,
+        line_number: usize,
+        context: &str,
+    ) -> Diagnostic {
+        Diagnostic {
+            code: "E002".to_owned(),
+            severity,
+            message: format!("No tag found for {label} @ {path}:{line_number}."),
+            location: Some((Path::new(path).to_owned(), line_number)),
+            label: Some(label.to_owned()),
+            context: Some(context.to_owned()),
+        }
+    }
+
+    #[test]
+    fn diff_ignores_shifted_lines() {
+        let old = vec![
+            diagnostic(Severity::Error, "a", "./src/a.rs", 3, "// a"),
+            diagnostic(Severity::Error, "b", "./src/a.rs", 5, "// b"),
+            diagnostic(Severity::Error, "c", "./src/a.rs", 9, "// c"),
+        ];
+        let new = vec![
+            diagnostic(Severity::Error, "a", "src/a.rs", 13, "// a"),
+            diagnostic(Severity::Warning, "d", "./src/a.rs", 1, "// d"),
+            diagnostic(Severity::Error, "c", "./src/a.rs", 19, "// c"),
+            diagnostic(Severity::Error, "c", "./src/a.rs", 20, "// c"),
+        ];
+
+        let report_diff = diff(&old, &new);
+
+        let labels = |diagnostics: &[Diagnostic]| {
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.label.clone().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(labels(&report_diff.new), ["d", "c"]);
+        assert_eq!(labels(&report_diff.fixed), ["b"]);
+        assert_eq!(labels(&report_diff.unchanged), ["a", "c"]);
+        assert_eq!(report_diff.unchanged[0].location.as_ref().unwrap().1, 13);
+        assert_eq!(report_diff.new_errors(), 1);
+    }
+
+    #[test]
+    fn diff_without_labels() {
+        let problem = |message: &str| Diagnostic {
+            code: "P001".to_owned(),
+            severity: Severity::Error,
+            message: message.to_owned(),
+            location: None,
+            label: None,
+            context: None,
+        };
+
+        let report_diff = diff(&[problem("Old.")], &[problem("New.")]);
+
+        assert_eq!(report_diff.new.len(), 1);
+        assert_eq!(report_diff.fixed.len(), 1);
+    }
+
+    #[test]
+    fn parse_and_render() {
+        let old = parse(r#"{"schema_version": 1, "diagnostics": []}"#).unwrap();
+        let new = parse(
+            r#"{
+                "files_scanned": 1,
+                "diagnostics": [{
+                    "code": "E003",
+                    "severity": "error",
+                    "message": "Missing.\nhttps://example.com/a.rs#L2",
+                    "location": ["a.rs", 2]
+                }]
+            }"#,
+        )
+        .unwrap();
+        assert!(parse("{}").is_err());
+
+        assert_eq!(
+            render(DiffFormat::Human, &diff(&old, &new)),
+            "New (1):\n  Missing.\n  https://example.com/a.rs#L2\n\nFixed (0):\n\nUnchanged (0):\n",
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use {
+    crate::{
+        config::RequiredTag,
+        diagnostic::{self, Diagnostic, Severity},
+        directive::Directive,
+    },
+    std::path::PathBuf,
+};
+
+// This function checks that every file matching a `required-tags` entry contains a tag with a
+// matching label and, for exclusive entries, that such tags don't appear anywhere else. The
+// `files` are the paths of all the scanned files. It returns a vector of errors.
+pub fn check(
+    files: &[PathBuf],
+    tags: &[Directive],
+    requirements: &[RequiredTag],
+) -> Vec<Diagnostic> {
+    let mut errors = Vec::<Diagnostic>::new();
+
+    for (i, requirement) in requirements.iter().enumerate() {
+        // Check that each matching file contains a matching tag.
+        for path in files.iter().filter(|path| requirement.path.is_match(path)) {
+            if !tags
+                .iter()
+                .any(|tag| tag.path == *path && requirement.label.is_match(&tag.label))
+            {
+                errors.push(Diagnostic {
+                    code: diagnostic::MISSING_REQUIRED_TAG.to_owned(),
+                    severity: Severity::Error,
+                    message: format!(
+                        "{} has no tag matching `{}`, but `required-tags` entry #{} (`{}`) \
+                            requires one.",
+                        path.to_string_lossy(),
+                        requirement.label,
+                        i + 1,
+                        requirement.path,
+                    ),
+                    location: Some((path.clone(), 1)),
+                    label: None,
+                    context: None,
+                });
+            }
+        }
+
+        // Check that matching tags don't appear in other files.
+        if requirement.exclusive {
+            for tag in tags.iter().filter(|tag| {
+                requirement.label.is_match(&tag.label) && !requirement.path.is_match(&tag.path)
+            }) {
+                errors.push(Diagnostic::error(
+                    diagnostic::MISPLACED_REQUIRED_TAG,
+                    tag,
+                    format!(
+                        "{tag} matches `{}`, but `required-tags` entry #{} only allows such tags \
+                            in `{}`.",
+                        requirement.label,
+                        i + 1,
+                        requirement.path,
+                    ),
+                ));
+            }
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            config::parse,
+            directive::{Directive, Type},
+            required_tags::check,
+        },
+        std::path::PathBuf,
+    };
+
+    fn tag(label: &str, path: &str) -> Directive {
+        Directive::new(Type::Tag, label, path, 1)
+    }
+
+    #[test]
+    fn check_empty() {
+        assert!(
+            check(
+                &[PathBuf::from("./file.rs")],
+                &[tag("tag1", "./file.rs")],
+                &[],
+            )
+            .is_empty(),
+        );
+    }
+
+    #[test]
+    fn check_required_tags() {
+        let config = parse(
+            "
+            [[required-tags]]
+            path = 'migrations/*.sql'
+            label = '^migration_'
+            exclusive = true
+            ",
+        )
+        .unwrap();
+
+        let files = [
+            PathBuf::from("./migrations/0001.sql"),
+            PathBuf::from("./migrations/0002.sql"),
+            PathBuf::from("./src/main.rs"),
+        ];
+        let tags = [
+            tag("migration_init", "./migrations/0001.sql"),
+            tag("other", "./migrations/0002.sql"),
+            tag("migration_stray", "./src/main.rs"),
+        ];
+
+        let errors = check(&files, &tags, &config.required_tags);
+        assert_eq!(errors.len(), 2);
+        assert!(
+            errors[0]
+                .message
+                .starts_with("./migrations/0002.sql has no tag matching `^migration_`"),
+        );
+        assert!(errors[1].message.contains("migration_stray"));
+        assert!(
+            errors[1]
+                .message
+                .contains("only allows such tags in `migrations/*.sql`"),
+        );
+    }
+
+    #[test]
+    fn check_not_exclusive() {
+        let config = parse(
+            "
+            [[required-tags]]
+            path = '*.sql'
+            label = '^migration_'
+            ",
+        )
+        .unwrap();
+
+        assert!(
+            check(
+                &[PathBuf::from("./0001.sql")],
+                &[
+                    tag("migration_init", "./0001.sql"),
+                    tag("migration_stray", "./main.rs"),
+                ],
+                &config.required_tags,
+            )
+            .is_empty(),
+        );
+    }
+}
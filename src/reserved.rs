@@ -0,0 +1,86 @@
+use crate::{
+    config::ReservedLabel,
+    diagnostic::{self, Diagnostic},
+    directive::Directive,
+};
+
+// This function checks that no tags use reserved labels. It returns a vector of errors, each of
+// which identifies the configuration entry which forbids the label.
+pub fn check(tags: &[Directive], reserved_labels: &[ReservedLabel]) -> Vec<Diagnostic> {
+    let mut errors = Vec::<Diagnostic>::new();
+
+    for tag in tags {
+        if let Some((i, reserved_label)) = reserved_labels
+            .iter()
+            .enumerate()
+            .find(|(_, reserved_label)| reserved_label.matches(&tag.label))
+        {
+            let entry = match (&reserved_label.label, &reserved_label.pattern) {
+                (Some(label), _) => format!("label `{label}`"),
+                (None, Some(pattern)) => format!("pattern `{pattern}`"),
+                (None, None) => String::new(),
+            };
+            let reason = reserved_label
+                .reason
+                .as_ref()
+                .map_or_else(String::new, |reason| format!(" {reason}"));
+            errors.push(Diagnostic::error(
+                diagnostic::RESERVED_LABEL,
+                tag,
+                format!(
+                    "{tag} uses a reserved label (forbidden by `reserved-labels` entry #{}, \
+                     {entry}).{reason}",
+                    i + 1,
+                ),
+            ));
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::parse,
+        directive::{Directive, Type},
+        reserved::check,
+    };
+
+    fn tag(label: &str) -> Directive {
+        Directive::new(Type::Tag, label, "file.rs", 1)
+    }
+
+    #[test]
+    fn check_empty() {
+        assert!(check(&[tag("todo")], &[]).is_empty());
+    }
+
+    #[test]
+    fn check_reserved() {
+        let config = parse(
+            "
+            [[reserved-labels]]
+            label = 'old_cache'
+            reason = 'Use cache_v2 instead.'
+
+            [[reserved-labels]]
+            pattern = '^(todo|fixme)$'
+            ",
+        )
+        .unwrap();
+
+        let errors = check(
+            &[tag("old_cache"), tag("cache_v2"), tag("fixme")],
+            &config.reserved_labels,
+        );
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].message.contains("entry #1, label `old_cache`"));
+        assert!(errors[0].message.ends_with("Use cache_v2 instead."));
+        assert!(
+            errors[1]
+                .message
+                .contains("entry #2, pattern `^(todo|fixme)$`"),
+        );
+    }
+}
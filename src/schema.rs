@@ -0,0 +1,45 @@
+// The version of the JSON documents which Tagref writes (the listings, the `json-summary` report,
+// exports, indices, progress events, label validation requests, the history, and comparisons of
+// check results), which is incremented whenever any of them changes incompatibly. Every document
+// includes it as `schema_version`.
+pub const SCHEMA_VERSION: usize = 1;
+
+// The JSON Schema describing those documents, which is printed by `--schema`
+pub const SCHEMA: &str = include_str!("schema.json");
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::schema::{SCHEMA, SCHEMA_VERSION},
+        serde_json::Value,
+    };
+
+    #[test]
+    fn schema_version_matches() {
+        let schema = serde_json::from_str::<Value>(SCHEMA).unwrap();
+
+        assert_eq!(
+            schema["$defs"]["schema_version"]["const"],
+            Value::from(SCHEMA_VERSION),
+        );
+        for document in [
+            "listing",
+            "summary",
+            "html_data",
+            "export",
+            "index",
+            "directive_index",
+            "progress",
+            "label_validation",
+            "history",
+            "report_diff",
+        ] {
+            assert!(
+                schema["$defs"][document]["required"]
+                    .as_array()
+                    .unwrap()
+                    .contains(&Value::from("schema_version")),
+            );
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use {
+    crate::{
+        config::Level,
+        diagnostic::{Diagnostic, Severity},
+    },
+    std::collections::BTreeMap,
+};
+
+// This function applies the severity overrides from the configuration file, which are keyed by
+// code. Problems found by checks which are turned off are dropped.
+pub fn apply(overrides: &BTreeMap<String, Level>, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    diagnostics
+        .into_iter()
+        .filter_map(|mut diagnostic| {
+            match overrides.get(&diagnostic.code) {
+                Some(Level::Error) => diagnostic.severity = Severity::Error,
+                Some(Level::Warn) => diagnostic.severity = Severity::Warning,
+                Some(Level::Off) => return None,
+                None => {}
+            }
+            Some(diagnostic)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            config::Level,
+            diagnostic::{Diagnostic, Severity},
+            severity::apply,
+        },
+        std::collections::BTreeMap,
+    };
+
+    fn diagnostic(code: &str, severity: Severity) -> Diagnostic {
+        Diagnostic {
+            code: code.to_owned(),
+            severity,
+            message: String::new(),
+            location: None,
+            label: None,
+            context: None,
+        }
+    }
+
+    #[test]
+    fn apply_overrides() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert("E003".to_owned(), Level::Warn);
+        overrides.insert("E101".to_owned(), Level::Off);
+        overrides.insert("no-todos".to_owned(), Level::Error);
+
+        let diagnostics = apply(
+            &overrides,
+            vec![
+                diagnostic("E003", Severity::Error),
+                diagnostic("E101", Severity::Error),
+                diagnostic("no-todos", Severity::Warning),
+                diagnostic("E002", Severity::Error),
+            ],
+        );
+
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics[0].code, "E003");
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[1].code, "no-todos");
+        assert_eq!(diagnostics[1].severity, Severity::Error);
+        assert_eq!(diagnostics[2].code, "E002");
+        assert_eq!(diagnostics[2].severity, Severity::Error);
+    }
+}
@@ -0,0 +1,106 @@
+use {
+    crate::walk::normalize,
+    std::{fmt, path::Path, str::FromStr},
+};
+
+// This struct represents one of several disjoint slices of the files to scan, so the work can be
+// split across multiple jobs. The index is zero-based, but shards are written one-based (e.g.,
+// `1/4` is the first of four shards).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Shard {
+    index: u64,
+    count: u64,
+}
+
+impl Shard {
+    // This function determines whether a file belongs to this shard. The assignment only depends on
+    // the path, so every job agrees on it regardless of the order in which files are visited.
+    pub fn contains(&self, path: &Path) -> bool {
+        fnv1a(normalize(path).to_string_lossy().as_bytes()) % self.count == self.index
+    }
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("Invalid shard `{s}`. Expected `K/N` where 1 <= K <= N.");
+        let (index, count) = s.split_once('/').ok_or_else(error)?;
+        let index = index.trim().parse::<u64>().map_err(|_| error())?;
+        let count = count.trim().parse::<u64>().map_err(|_| error())?;
+        if index == 0 || index > count {
+            return Err(error());
+        }
+
+        Ok(Self {
+            index: index - 1,
+            count,
+        })
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.index + 1, self.count)
+    }
+}
+
+// This function computes the 64-bit FNV-1a hash of some bytes. Unlike the hashers in the standard
+// library, it's guaranteed to be stable across platforms and compiler versions.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::shard::{fnv1a, Shard},
+        std::path::Path,
+    };
+
+    #[test]
+    fn fnv1a_known_values() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn parse_valid() {
+        let shard = "2/4".parse::<Shard>().unwrap();
+
+        assert_eq!(shard.to_string(), "2/4");
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!("0/4".parse::<Shard>().is_err());
+        assert!("5/4".parse::<Shard>().is_err());
+        assert!("1/0".parse::<Shard>().is_err());
+        assert!("1".parse::<Shard>().is_err());
+        assert!("a/b".parse::<Shard>().is_err());
+    }
+
+    #[test]
+    fn contains_partition() {
+        let shards = (1..=3)
+            .map(|k: usize| format!("{k}/3").parse::<Shard>().unwrap())
+            .collect::<Vec<_>>();
+
+        for i in 0_usize..100 {
+            let path = format!("./src/file{i}.rs");
+            assert_eq!(
+                shards
+                    .iter()
+                    .filter(|shard| shard.contains(Path::new(&path)))
+                    .count(),
+                1,
+            );
+            assert_eq!(
+                shards[0].contains(Path::new(&path)),
+                shards[0].contains(Path::new(&path[2..])),
+            );
+        }
+    }
+}
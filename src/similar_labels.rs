@@ -0,0 +1,108 @@
+use {
+    crate::{
+        diagnostic::{self, Diagnostic},
+        directive::Directive,
+        distance::{levenshtein, threshold},
+    },
+    std::{cmp::Reverse, collections::HashMap},
+};
+
+// This function returns the label with the digits removed. Labels which only differ in their digits
+// (e.g., `schema_v1` and `schema_v2`) are meant to be similar.
+fn without_digits(label: &str) -> String {
+    label.chars().filter(|c| !c.is_ascii_digit()).collect()
+}
+
+// This function finds pairs of tags with labels so similar that one is probably a typo of the
+// other. The label with more references is suggested as the canonical one, and the warning is
+// located at the other tag. It returns a vector of warnings.
+pub fn check(
+    tags_map: &HashMap<String, Vec<Directive>>,
+    ref_counts: &HashMap<&str, usize>,
+) -> Vec<Diagnostic> {
+    let mut warnings = Vec::<Diagnostic>::new();
+
+    // Sort the labels so the warnings are deterministic.
+    let mut labels = tags_map.keys().collect::<Vec<_>>();
+    labels.sort();
+
+    for (i, x) in labels.iter().enumerate() {
+        for y in &labels[i + 1..] {
+            let max_distance = threshold(x).min(threshold(y));
+            if x.chars().count().abs_diff(y.chars().count()) > max_distance
+                || without_digits(x) == without_digits(y)
+                || levenshtein(x, y) > max_distance
+            {
+                continue;
+            }
+
+            // Prefer the label with more references, then the first one alphabetically.
+            let count = |label: &str| ref_counts.get(label).copied().unwrap_or(0);
+            let (canonical, other) = if (Reverse(count(x)), x) <= (Reverse(count(y)), y) {
+                (x, y)
+            } else {
+                (y, x)
+            };
+
+            // The `[0]`s are safe because each label has at least one tag.
+            let tag = &tags_map[other.as_str()][0];
+            warnings.push(Diagnostic::warning(
+                diagnostic::SIMILAR_LABELS,
+                tag,
+                format!(
+                    "{tag} and {} have similar labels. Did you mean `{canonical}`?",
+                    tags_map[canonical.as_str()][0],
+                ),
+            ));
+        }
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            directive::{Directive, Type},
+            similar_labels::check,
+        },
+        std::collections::HashMap,
+    };
+
+    fn tags(labels: &[&str]) -> HashMap<String, Vec<Directive>> {
+        labels
+            .iter()
+            .map(|label| {
+                (
+                    (*label).to_owned(),
+                    vec![Directive::new(Type::Tag, label, "file.rs", 1)],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn check_dissimilar() {
+        let tags = tags(&[
+            "cache_invalidation",
+            "retry_policy",
+            "schema_v1",
+            "schema_v2",
+        ]);
+
+        assert!(check(&tags, &HashMap::new()).is_empty());
+    }
+
+    #[test]
+    fn check_similar() {
+        let tags = tags(&["retry_poicy", "retry_policy", "cache"]);
+        let mut ref_counts = HashMap::new();
+        ref_counts.insert("retry_policy", 2);
+
+        let warnings = check(&tags, &ref_counts);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].label.as_deref(), Some("retry_poicy"));
+        assert!(warnings[0].message.contains("Did you mean `retry_policy`?"));
+    }
+}
@@ -0,0 +1,141 @@
+use {
+    crate::{
+        diagnostic::{self, Diagnostic},
+        directive::Directive,
+    },
+    serde_yaml::Value,
+    std::{
+        collections::{hash_map::Entry, HashMap},
+        fs::read_to_string,
+        path::Path,
+    },
+};
+
+// This function checks that spec references point to values within YAML or JSON documents. The
+// label of each reference is the path of a document, optionally followed by `#` and a JSON Pointer
+// (e.g., `openapi.yaml#/components/schemas/User`). Each document is only read once. It returns a
+// vector of errors.
+pub fn check(refs: &[Directive]) -> Vec<Diagnostic> {
+    let mut errors = Vec::<Diagnostic>::new();
+    let mut documents = HashMap::<&str, Result<Value, String>>::new();
+
+    for spec in refs {
+        let (path, pointer) = spec
+            .label
+            .split_once('#')
+            .unwrap_or((spec.label.as_str(), ""));
+        let document = match documents.entry(path) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(load(Path::new(path))),
+        };
+        let message = match document {
+            Ok(document) => {
+                if resolve(document, pointer).is_some() {
+                    continue;
+                }
+                format!("{spec} does not point to anything in {path}.")
+            }
+            Err(error) => format!("Error when validating {spec}: {error}"),
+        };
+        errors.push(Diagnostic::error(diagnostic::BAD_SPEC_REF, spec, message));
+    }
+
+    errors
+}
+
+// This function reads a YAML or JSON document, depending on its extension.
+fn load(path: &Path) -> Result<Value, String> {
+    let contents = read_to_string(path).map_err(|error| error.to_string())?;
+
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        serde_json::from_str::<serde_json::Value>(&contents)
+            .map_err(|error| error.to_string())
+            .and_then(|value| serde_yaml::to_value(value).map_err(|error| error.to_string()))
+    } else {
+        serde_yaml::from_str(&contents).map_err(|error| error.to_string())
+    }
+}
+
+// This function finds the value a JSON Pointer (RFC 6901) refers to. YAML mappings can have keys
+// which aren't strings, such as the status codes in an OpenAPI document, so those are compared by
+// how they're written.
+fn resolve<'a>(value: &'a Value, pointer: &str) -> Option<&'a Value> {
+    if pointer.is_empty() {
+        return Some(value);
+    }
+
+    pointer
+        .strip_prefix('/')?
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .try_fold(value, |value, token| match value {
+            Value::Mapping(mapping) => mapping
+                .iter()
+                .find(|(key, _)| match key {
+                    Value::String(key) => *key == token,
+                    Value::Number(key) => key.to_string() == token,
+                    Value::Bool(key) => key.to_string() == token,
+                    _ => false,
+                })
+                .map(|(_, value)| value),
+            Value::Sequence(sequence) => token
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| sequence.get(index)),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        spec_references::{check, resolve},
+    };
+
+    #[test]
+    fn resolve_pointers() {
+        let document = serde_yaml::from_str(
+            "
+            components:
+              schemas:
+                User: {}
+                a/b~c: {}
+            responses:
+              200:
+                - description: OK
+            ",
+        )
+        .unwrap();
+
+        assert!(resolve(&document, "").is_some());
+        assert!(resolve(&document, "/components/schemas/User").is_some());
+        assert!(resolve(&document, "/components/schemas/a~1b~0c").is_some());
+        assert!(resolve(&document, "/responses/200/0/description").is_some());
+        assert!(resolve(&document, "/components/schemas/Account").is_none());
+        assert!(resolve(&document, "/responses/200/1").is_none());
+        assert!(resolve(&document, "components").is_none());
+    }
+
+    #[test]
+    fn check_specs() {
+        let spec = |label: &str| Directive::new(Type::Spec, label, "file1.rs", 1);
+        let refs = [
+            spec("toast.yml#/tasks/build"),
+            spec("toast.yml#/tasks/nonexistent"),
+            spec("nonexistent.yml#/a"),
+        ];
+
+        let errors = check(&refs);
+        assert_eq!(errors.len(), 2);
+        assert!(
+            errors[0]
+                .message
+                .ends_with("does not point to anything in toast.yml."),
+        );
+        assert!(errors[1].message.starts_with("Error when validating"));
+    }
+}
@@ -0,0 +1,178 @@
+use {
+    crate::{
+        diagnostic::{self, Diagnostic},
+        directive::Directive,
+        distance::transposition_distance,
+    },
+    std::{collections::HashSet, fs::read_to_string, path::Path},
+};
+
+// The bundled word list: common English words and technical terms, one per line in lowercase, with
+// the most common words first
+const WORDS: &str = include_str!("words.txt");
+
+// This struct represents the words which are considered correctly spelled.
+pub struct Dictionary {
+    words: HashSet<String>,
+
+    // The same words, from the most to the least common (followed by those from the user
+    // dictionary), for choosing suggestions
+    ranked: Vec<String>,
+}
+
+impl Dictionary {
+    // This function builds a dictionary from the bundled word list and the contents of a user
+    // dictionary, which has one word per line. Blank lines and lines starting with `#` are skipped.
+    pub fn new(user_dictionary: &str) -> Self {
+        let ranked = WORDS
+            .lines()
+            .chain(user_dictionary.lines())
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+
+        Self {
+            words: ranked.iter().cloned().collect(),
+            ranked,
+        }
+    }
+
+    // This function loads the user dictionary, if there is one, and builds a dictionary from it.
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        let user_dictionary = match path {
+            Some(path) => read_to_string(path)
+                .map_err(|error| format!("Unable to read {}: {error}", path.to_string_lossy()))?,
+            None => String::new(),
+        };
+
+        Ok(Self::new(&user_dictionary))
+    }
+
+    // This function finds the known word which is closest to an unknown one, if there's one within
+    // two edits. Ties go to the more common word.
+    fn suggestion(&self, word: &str) -> Option<&str> {
+        let length = word.chars().count();
+        let mut best = None;
+        let mut best_distance = 3;
+        for candidate in &self.ranked {
+            if candidate.chars().count().abs_diff(length) >= best_distance {
+                continue;
+            }
+            let distance = transposition_distance(word, candidate);
+            if distance < best_distance {
+                best = Some(candidate.as_str());
+                best_distance = distance;
+            }
+        }
+
+        best
+    }
+
+    // This function determines whether a word is spelled correctly. Plurals and possessives of
+    // known words are accepted, as are contractions (e.g., "doesn't") of them.
+    fn contains(&self, word: &str) -> bool {
+        let word = word.to_lowercase();
+        let word = word.split('\'').next().unwrap_or(&word); // Drop `'s`, `'t`, etc.
+        self.words.contains(word)
+            || word
+                .strip_suffix('s')
+                .is_some_and(|stem| self.words.contains(stem))
+    }
+}
+
+// This function finds the words in a description which should be spell-checked. Code quoted in
+// backticks is skipped, as are paths, URLs, identifiers, and acronyms.
+fn words(description: &str) -> impl Iterator<Item = &str> {
+    description
+        .split('`')
+        .step_by(2)
+        .flat_map(str::split_whitespace)
+        .map(|chunk| chunk.trim_matches(|c: char| !c.is_alphanumeric()))
+        .filter(|chunk| chunk.chars().all(|c| c.is_alphabetic() || "'-".contains(c)))
+        .flat_map(|chunk| chunk.split('-'))
+        .filter(|word| word.chars().count() > 1 && !word.chars().skip(1).any(char::is_uppercase))
+}
+
+// This function checks the descriptions of the directives for words which aren't in the dictionary.
+// Each unknown word is reported once per directive, along with the closest known word if there is
+// one. It returns a vector of warnings.
+pub fn check<'a>(
+    directives: impl IntoIterator<Item = &'a Directive>,
+    dictionary: &Dictionary,
+) -> Vec<Diagnostic> {
+    let mut warnings = Vec::<Diagnostic>::new();
+
+    for directive in directives {
+        let mut reported = HashSet::new();
+        for word in words(&directive.description) {
+            if dictionary.contains(word) || !reported.insert(word.to_lowercase()) {
+                continue;
+            }
+
+            let suggestion = dictionary
+                .suggestion(&word.to_lowercase())
+                .map(|suggestion| format!(" Did you mean `{suggestion}`?"))
+                .unwrap_or_default();
+            warnings.push(Diagnostic::warning(
+                diagnostic::MISSPELLING,
+                directive,
+                format!(
+                    "The description of {directive} contains `{word}`, which may be \
+                        misspelled.{suggestion}",
+                ),
+            ));
+        }
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        spelling::{check, words, Dictionary},
+    };
+
+    fn tag(description: &str) -> Directive {
+        Directive {
+            description: description.to_owned(),
+            ..Directive::new(Type::Tag, "label", "file.rs", 1)
+        }
+    }
+
+    #[test]
+    fn words_skip_code() {
+        assert_eq!(
+            words(
+                "Run `cargo fmt` on src/main.rs, see https://x.y, and don't use fooBar or HTTP \
+                    retry_count (or self-evident 3rd things).",
+            )
+            .collect::<Vec<_>>(),
+            [
+                "Run", "on", "see", "and", "don't", "use", "or", "or", "self", "evident", "things",
+            ],
+        );
+    }
+
+    #[test]
+    fn check_misspellings() {
+        let dictionary = Dictionary::new("# Project terms\nfrobnicate\n");
+        let tags = [
+            tag("Entries are evicted when the schema changes."),
+            tag("We recieve the tags' descriptions and frobnicate them. Recieve!"),
+            tag("It doesn't matter."),
+        ];
+
+        let warnings = check(&tags, &dictionary);
+
+        assert_eq!(warnings.len(), 1);
+        assert!(
+            warnings[0]
+                .message
+                .contains("contains `recieve`, which may be misspelled."),
+        );
+        assert!(warnings[0].message.ends_with("Did you mean `receive`?"));
+    }
+}
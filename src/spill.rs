@@ -0,0 +1,315 @@
+use {
+    crate::directive::Directive,
+    std::{
+        cmp::{Ordering, Reverse},
+        collections::BinaryHeap,
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all, File},
+        io::{BufRead, BufReader, BufWriter, Lines, Write},
+        mem::size_of,
+        path::PathBuf,
+        process,
+        sync::atomic::{self, AtomicUsize},
+    },
+};
+
+// This counter distinguishes the spill directories of a process.
+static SPILLS: AtomicUsize = AtomicUsize::new(0);
+
+// This function estimates how much memory a directive occupies, including its strings.
+fn footprint(directive: &Directive) -> usize {
+    size_of::<Directive>()
+        + directive.label.len()
+        + directive.description.len()
+        + directive.context.len()
+        + directive.path.as_os_str().len()
+        + directive
+            .metadata
+            .iter()
+            .map(|(key, value)| key.len() + value.len())
+            .sum::<usize>()
+        + directive.sigil_set.as_ref().map_or(0, String::len)
+}
+
+// This function orders directives by label, then by location. Directives with the same label end
+// up next to each other.
+fn compare(x: &Directive, y: &Directive) -> Ordering {
+    (&x.label, &x.path, x.cell, x.line_number).cmp(&(&y.label, &y.path, y.cell, y.line_number))
+}
+
+// This struct represents the next directive from one of the sources being merged, ordered by
+// `compare` and then by source (so the merge is stable).
+struct Head {
+    directive: Directive,
+    source: usize,
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare(&self.directive, &other.directive).then(self.source.cmp(&other.source))
+    }
+}
+
+// This struct limits the memory used by a collection of directives. When the directives in memory
+// exceed the budget, they're sorted and written to a temporary file (a "run"). The runs and the
+// directives still in memory can then be merged back in sorted order, a batch at a time.
+pub struct Spill {
+    budget: Option<usize>,
+    buffered: usize,
+    directory: PathBuf,
+    runs: Vec<PathBuf>,
+    error: Option<String>,
+}
+
+impl Spill {
+    // This function creates a spill with a budget in bytes. Without a budget, nothing is spilled.
+    pub fn new(budget: Option<usize>) -> Self {
+        Self {
+            budget,
+            buffered: 0,
+            directory: temp_dir().join(format!(
+                "tagref-spill-{}-{}",
+                process::id(),
+                SPILLS.fetch_add(1, atomic::Ordering::SeqCst),
+            )),
+            runs: Vec::new(),
+            error: None,
+        }
+    }
+
+    // This function determines whether any directives were written to disk.
+    pub fn is_spilled(&self) -> bool {
+        !self.runs.is_empty()
+    }
+
+    // This function accounts for directives which were just added to `buffer`. If the buffer is now
+    // over budget, it's sorted and written to a new run. Errors are remembered and reported when
+    // the directives are read back, since scanning can't be interrupted.
+    pub fn track(&mut self, added: &[Directive], buffer: &mut Vec<Directive>) {
+        let Some(budget) = self.budget else {
+            return;
+        };
+        self.buffered += added.iter().map(footprint).sum::<usize>();
+        if self.buffered <= budget || self.error.is_some() {
+            return;
+        }
+
+        buffer.sort_by(compare);
+        let path = self.directory.join(format!("{}.jsonl", self.runs.len()));
+        let result = create_dir_all(&self.directory)
+            .and_then(|()| File::create(&path))
+            .and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                for directive in buffer.iter() {
+                    // The `unwrap` is safe because directives always serialize successfully.
+                    serde_json::to_writer(&mut writer, directive).unwrap();
+                    writer.write_all(b"\n")?;
+                }
+                writer.flush()
+            });
+        match result {
+            Ok(()) => {
+                self.runs.push(path);
+                buffer.clear();
+                buffer.shrink_to_fit();
+                self.buffered = 0;
+            }
+            Err(error) => {
+                self.error = Some(format!(
+                    "Unable to write {}: {error}",
+                    path.to_string_lossy(),
+                ));
+            }
+        }
+    }
+
+    // This function merges the runs and the directives in `buffer` in sorted order, and calls
+    // `visit` with consecutive batches of them. Each batch fits within the budget (or contains a
+    // single directive), and the directives with the same label are always in the same batch.
+    pub fn batches(
+        &self,
+        buffer: &mut [Directive],
+        mut visit: impl FnMut(&[Directive]),
+    ) -> Result<(), String> {
+        if let Some(error) = &self.error {
+            return Err(error.clone());
+        }
+        buffer.sort_by(compare);
+        if !self.is_spilled() {
+            visit(buffer);
+            return Ok(());
+        }
+
+        // Read each run one line at a time.
+        let mut runs = self
+            .runs
+            .iter()
+            .map(|path| {
+                File::open(path)
+                    .map(|file| (path, BufReader::new(file).lines()))
+                    .map_err(|error| format!("Unable to read {}: {error}", path.to_string_lossy()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let next = |(path, lines): &mut (&PathBuf, Lines<BufReader<File>>)| {
+            lines
+                .next()
+                .map(|line| {
+                    line.map_err(|error| error.to_string())
+                        .and_then(|line| {
+                            serde_json::from_str::<Directive>(&line).map_err(|e| e.to_string())
+                        })
+                        .map_err(|error| {
+                            format!("Unable to read {}: {error}", path.to_string_lossy())
+                        })
+                })
+                .transpose()
+        };
+
+        // Start with the first directive of each source. The directives in memory are the last
+        // source.
+        let mut heap = BinaryHeap::new();
+        for (source, run) in runs.iter_mut().enumerate() {
+            if let Some(directive) = next(run)? {
+                heap.push(Reverse(Head { directive, source }));
+            }
+        }
+        let mut in_memory = buffer.iter();
+        if let Some(directive) = in_memory.next() {
+            heap.push(Reverse(Head {
+                directive: directive.clone(),
+                source: runs.len(),
+            }));
+        }
+
+        // Repeatedly take the smallest directive, and replace it with the next one from its
+        // source.
+        let budget = self.budget.unwrap_or(usize::MAX);
+        let mut batch = Vec::<Directive>::new();
+        let mut batch_size = 0;
+        while let Some(Reverse(Head { directive, source })) = heap.pop() {
+            let replacement = if source == runs.len() {
+                in_memory.next().cloned()
+            } else {
+                next(&mut runs[source])?
+            };
+            if let Some(directive) = replacement {
+                heap.push(Reverse(Head { directive, source }));
+            }
+
+            // Only start a new batch between labels.
+            if batch_size > budget
+                && batch
+                    .last()
+                    .is_some_and(|last| last.label != directive.label)
+            {
+                visit(&batch);
+                batch.clear();
+                batch_size = 0;
+            }
+            batch_size += footprint(&directive);
+            batch.push(directive);
+        }
+        if !batch.is_empty() {
+            visit(&batch);
+        }
+
+        Ok(())
+    }
+
+    // This function reads all the directives back into `buffer`, in sorted order.
+    pub fn reload(&self, buffer: &mut Vec<Directive>) -> Result<(), String> {
+        if !self.is_spilled() && self.error.is_none() {
+            return Ok(());
+        }
+        let mut all = Vec::new();
+        self.batches(buffer, |batch| all.extend_from_slice(batch))?;
+        *buffer = all;
+        Ok(())
+    }
+}
+
+impl Drop for Spill {
+    // The runs are deleted when they're no longer needed.
+    fn drop(&mut self) {
+        if self.is_spilled() {
+            let _ = remove_dir_all(&self.directory);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        directive::{Directive, Type},
+        spill::{footprint, Spill},
+    };
+
+    fn directive(label: &str, line_number: usize) -> Directive {
+        Directive::new(Type::Ref, label, "file.rs", line_number)
+    }
+
+    #[test]
+    fn spill_without_budget() {
+        let mut spill = Spill::new(None);
+        let mut buffer = vec![directive("b", 1), directive("a", 2)];
+
+        spill.track(&buffer.clone(), &mut buffer);
+        let mut batches = Vec::new();
+        spill
+            .batches(&mut buffer, |batch| batches.push(batch.len()))
+            .unwrap();
+
+        assert!(!spill.is_spilled());
+        assert_eq!(batches, [2]);
+        assert_eq!(buffer[0].label, "a");
+    }
+
+    #[test]
+    fn spill_and_merge() {
+        // Each run holds about two directives.
+        let mut spill = Spill::new(Some(footprint(&directive("a", 1)) * 2));
+        let mut buffer = Vec::new();
+        for (label, line_number) in [("c", 1), ("a", 2), ("b", 3), ("a", 4), ("c", 5), ("b", 6)] {
+            let added = [directive(label, line_number)];
+            buffer.extend_from_slice(&added);
+            spill.track(&added, &mut buffer);
+        }
+        buffer.push(directive("a", 7));
+
+        let mut batches = Vec::new();
+        spill
+            .batches(&mut buffer, |batch| {
+                batches.push(
+                    batch
+                        .iter()
+                        .map(|directive| format!("{}{}", directive.label, directive.line_number))
+                        .collect::<Vec<_>>(),
+                );
+            })
+            .unwrap();
+
+        assert!(spill.is_spilled());
+        assert_eq!(batches.concat(), ["a2", "a4", "a7", "b3", "b6", "c1", "c5"]);
+        assert!(batches.len() > 1);
+        for pair in batches.windows(2) {
+            assert_ne!(pair[0].last().unwrap()[..1], pair[1][0][..1]);
+        }
+
+        spill.reload(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), 7);
+    }
+}
@@ -0,0 +1,132 @@
+use {
+    crate::{
+        diagnostic::{self, Diagnostic},
+        directive::Directive,
+        distance::close_matches,
+    },
+    std::{collections::HashSet, path::Path},
+};
+
+// This function returns the spans of a description which are quoted in backticks, e.g., `foo` in
+// "See `foo`."
+fn quoted(description: &str) -> impl Iterator<Item = &str> {
+    description.split('`').skip(1).step_by(2)
+}
+
+// This function determines whether a quoted span looks like a path, as opposed to a label or some
+// code. URLs and globs don't count.
+fn is_path(span: &str) -> bool {
+    span.contains('/')
+        && !span.contains("://")
+        && !span.contains(|c: char| c.is_whitespace() || "*?[]{}()".contains(c))
+}
+
+// This function determines whether a quoted span looks like a label.
+fn is_label(span: &str) -> bool {
+    !span.is_empty()
+        && span
+            .chars()
+            .all(|c| c.is_alphanumeric() || "_-.:".contains(c))
+}
+
+// This function checks the descriptions of the references for quoted paths (relative to the
+// working directory, like file references) which don't exist. It returns a vector of warnings.
+pub fn check_paths(refs: &[Directive]) -> Vec<Diagnostic> {
+    let mut warnings = Vec::<Diagnostic>::new();
+
+    for r#ref in refs {
+        for span in quoted(&r#ref.description).filter(|span| is_path(span)) {
+            if !Path::new(span).exists() {
+                warnings.push(Diagnostic::warning(
+                    diagnostic::STALE_DESCRIPTION,
+                    r#ref,
+                    format!(
+                        "The description of {ref} mentions `{span}`, which doesn't exist. The \
+                            description may be stale.",
+                    ),
+                ));
+            }
+        }
+    }
+
+    warnings
+}
+
+// This function checks the descriptions of the references for quoted labels which aren't the
+// labels of any tags, but are close to exactly one (e.g., because the tag was renamed). It returns
+// a vector of warnings.
+pub fn check_labels(refs: &[Directive], labels: &HashSet<String>) -> Vec<Diagnostic> {
+    let mut warnings = Vec::<Diagnostic>::new();
+
+    for r#ref in refs {
+        for span in quoted(&r#ref.description).filter(|span| is_label(span)) {
+            if labels.contains(span) {
+                continue;
+            }
+            if let [label] = close_matches(span, labels.iter().map(String::as_str))[..] {
+                warnings.push(Diagnostic::warning(
+                    diagnostic::STALE_DESCRIPTION,
+                    r#ref,
+                    format!(
+                        "The description of {ref} mentions `{span}`, which isn't a tag. Did you \
+                            mean `{label}`? The description may be stale.",
+                    ),
+                ));
+            }
+        }
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            directive::{Directive, Type},
+            stale_descriptions::{check_labels, check_paths},
+        },
+        std::collections::HashSet,
+    };
+
+    fn r#ref(description: &str) -> Directive {
+        Directive {
+            description: description.to_owned(),
+            ..Directive::new(Type::Ref, "label", "file.rs", 1)
+        }
+    }
+
+    #[test]
+    fn check_paths_missing() {
+        let refs = [
+            r#ref("See `src/main.rs` and `src/missing.rs`."),
+            r#ref("See `https://example.com/a` and `src/*.rs`."),
+            r#ref("See src/gone.rs, which isn't quoted."),
+        ];
+
+        let warnings = check_paths(&refs);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].message.contains("mentions `src/missing.rs`,"));
+    }
+
+    #[test]
+    fn check_labels_renamed() {
+        let labels = ["retry_policy", "cache_keys"]
+            .iter()
+            .map(|label| (*label).to_owned())
+            .collect::<HashSet<_>>();
+        let refs = [
+            r#ref("Like `retry_policy`, this backs off."),
+            r#ref("Like `retry_polcy`, this backs off."),
+            r#ref("Uses `Vec::new()` and `unrelated`."),
+        ];
+
+        let warnings = check_labels(&refs, &labels);
+        assert_eq!(warnings.len(), 1);
+        assert!(
+            warnings[0].message.contains(
+                "mentions `retry_polcy`, which isn't a tag. Did you mean `retry_policy`?",
+            ),
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use {
+    crate::walk::normalize,
+    std::{
+        collections::{HashMap, HashSet},
+        fs::metadata,
+        num::NonZeroUsize,
+        path::{Path, PathBuf},
+        thread::{self, available_parallelism},
+    },
+};
+
+// Statting a path is cheap on a local disk, so it's only worth starting a thread for many of them.
+const MIN_PATHS_PER_THREAD: usize = 64;
+
+// This enum represents what a path points to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Target {
+    File,
+    Dir,
+    Other,
+    Error(String),
+}
+
+impl Target {
+    // This function determines what a path points to.
+    fn new(path: &str) -> Self {
+        match metadata(path) {
+            Ok(metadata) if metadata.is_file() => Self::File,
+            Ok(metadata) if metadata.is_dir() => Self::Dir,
+            Ok(_) => Self::Other,
+            Err(error) => Self::Error(error.to_string()),
+        }
+    }
+}
+
+// This function determines what each of the given paths points to. Each unique path is only
+// statted once, and the paths are statted in parallel, since that can take a while on a network
+// filesystem.
+pub fn stat_all<'a, I: IntoIterator<Item = &'a str>>(paths: I) -> HashMap<&'a str, Target> {
+    let unique = paths
+        .into_iter()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let threads = available_parallelism().map_or(1, NonZeroUsize::get);
+    let chunk_size = unique.len().div_ceil(threads).max(MIN_PATHS_PER_THREAD);
+
+    thread::scope(|scope| {
+        unique
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|path| (*path, Target::new(path)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap()) // Safe since statting doesn't panic
+            .collect()
+    })
+}
+
+// This function determines what each of the given paths points to according to the set of scanned
+// files, without touching the filesystem. A path is a file if it was scanned, and a directory if it
+// contains a scanned file. Anything else (e.g., an ignored file) is treated as missing.
+pub fn scanned_targets<'a, I: IntoIterator<Item = &'a str>>(
+    paths: I,
+    scanned: &[PathBuf],
+) -> HashMap<&'a str, Target> {
+    let files = scanned
+        .iter()
+        .map(|path| normalize(path))
+        .collect::<HashSet<_>>();
+    let dirs = files
+        .iter()
+        .flat_map(|path| path.ancestors().skip(1))
+        .map(Path::to_owned)
+        .collect::<HashSet<_>>();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let normalized = normalize(Path::new(path));
+            let target = if files.contains(&normalized) {
+                Target::File
+            } else if dirs.contains(&normalized) {
+                Target::Dir
+            } else {
+                Target::Error(
+                    "it isn't one of the scanned files (it may not exist, or it may be ignored or \
+                        excluded)"
+                        .to_owned(),
+                )
+            };
+            (path, target)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::stat::{scanned_targets, stat_all, Target},
+        std::path::PathBuf,
+    };
+
+    #[test]
+    fn stat_all_empty() {
+        assert!(stat_all([]).is_empty());
+    }
+
+    #[test]
+    fn stat_all_targets() {
+        let targets = stat_all(["Cargo.toml", "src", "Cargo.toml", "nonexistent"]);
+
+        assert_eq!(targets.len(), 3);
+        assert_eq!(targets["Cargo.toml"], Target::File);
+        assert_eq!(targets["src"], Target::Dir);
+        assert!(matches!(targets["nonexistent"], Target::Error(_)));
+    }
+
+    #[test]
+    fn stat_all_many() {
+        let paths = (0_usize..1_000)
+            .map(|i| format!("nonexistent-{}", i % 300))
+            .collect::<Vec<_>>();
+        let targets = stat_all(paths.iter().map(String::as_str));
+
+        assert_eq!(targets.len(), 300);
+    }
+
+    #[test]
+    fn scanned_targets_membership() {
+        let scanned = [PathBuf::from("./src/main.rs"), PathBuf::from("README.md")];
+        let targets = scanned_targets(
+            ["src/main.rs", "./README.md", "src", "target/generated.rs"],
+            &scanned,
+        );
+
+        assert_eq!(targets["src/main.rs"], Target::File);
+        assert_eq!(targets["./README.md"], Target::File);
+        assert_eq!(targets["src"], Target::Dir);
+        assert!(matches!(targets["target/generated.rs"], Target::Error(_)));
+    }
+}
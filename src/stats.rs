@@ -0,0 +1,284 @@
+use {
+    crate::directive::Directive,
+    std::{
+        cmp::Reverse,
+        collections::{HashMap, HashSet},
+        path::PathBuf,
+    },
+};
+
+// This struct represents the parts of the codebase which are most heavily cross-referenced.
+pub struct Hotspots {
+    // Tags with more references than the threshold, along with their reference counts
+    pub tags: Vec<(Directive, usize)>,
+
+    // Files with the most references to the tags they contain
+    pub inbound: Vec<(PathBuf, usize)>,
+
+    // Files with the most references to tags elsewhere
+    pub outbound: Vec<(PathBuf, usize)>,
+}
+
+// This function counts the references to each tag.
+pub fn ref_counts(refs: &[Directive]) -> HashMap<&str, usize> {
+    let mut counts = HashMap::new();
+    for r#ref in refs {
+        *counts.entry(r#ref.label.as_str()).or_insert(0) += 1;
+    }
+    counts
+}
+
+// This function sorts a list of counts in descending order, breaking ties by the key so the output
+// is deterministic, and keeps the first `top` entries.
+fn top_counts<K: Ord>(counts: HashMap<K, usize>, top: usize) -> Vec<(K, usize)> {
+    let mut counts = counts
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .collect::<Vec<_>>();
+    counts.sort_by(|(x_key, x_count), (y_key, y_count)| {
+        (Reverse(x_count), x_key).cmp(&(Reverse(y_count), y_key))
+    });
+    counts.truncate(top);
+    counts
+}
+
+// This function finds the tags with more than `threshold` references, as well as the `top` files
+// with the most inbound and outbound references.
+pub fn hotspots(
+    tags: &HashMap<String, Vec<Directive>>,
+    refs: &[Directive],
+    threshold: usize,
+    top: usize,
+) -> Hotspots {
+    let counts = ref_counts(refs);
+
+    // Find the tags with too many references.
+    let mut hot_tags = tags
+        .values()
+        .flatten()
+        .filter_map(|tag| {
+            counts
+                .get(tag.label.as_str())
+                .filter(|count| **count > threshold)
+                .map(|count| (tag.clone(), *count))
+        })
+        .collect::<Vec<_>>();
+    hot_tags.sort_by(|(x_tag, x_count), (y_tag, y_count)| {
+        (Reverse(x_count), &x_tag.label, &x_tag.path).cmp(&(
+            Reverse(y_count),
+            &y_tag.label,
+            &y_tag.path,
+        ))
+    });
+
+    // Count the references into each file. If a tag is defined in multiple files, each of them is
+    // counted.
+    let mut inbound = HashMap::<PathBuf, usize>::new();
+    for (label, dupes) in tags {
+        let count = counts.get(label.as_str()).copied().unwrap_or(0);
+        for path in dupes.iter().map(|tag| &tag.path).collect::<HashSet<_>>() {
+            *inbound.entry(path.clone()).or_insert(0) += count;
+        }
+    }
+
+    // Count the references out of each file.
+    let mut outbound = HashMap::<PathBuf, usize>::new();
+    for r#ref in refs {
+        *outbound.entry(r#ref.path.clone()).or_insert(0) += 1;
+    }
+
+    Hotspots {
+        tags: hot_tags,
+        inbound: top_counts(inbound, top),
+        outbound: top_counts(outbound, top),
+    }
+}
+
+// This struct represents the tags and references attributed to an owner.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Ownership {
+    // The number of tags the owner defines
+    pub tags: usize,
+
+    // The number of the owner's tags which have no references
+    pub unused: usize,
+
+    // The number of references in the owner's code to tags which don't exist
+    pub dangling: usize,
+}
+
+// This function tallies, for each owner, the tags they define, how many of those are unused, and
+// how many dangling references are in their code. The `owners_of` function determines who owns a
+// directive; a directive with several owners counts for each of them, and one with none counts
+// for the `None` entry. The result is sorted as a cleanup queue: the owners with the most unused
+// tags and dangling references come first, and the unowned directives come last among equals.
+pub fn owners<F: Fn(&Directive) -> Vec<String>>(
+    tags: &HashMap<String, Vec<Directive>>,
+    refs: &[Directive],
+    owners_of: F,
+) -> Vec<(Option<String>, Ownership)> {
+    let counts = ref_counts(refs);
+    let mut ownership = HashMap::<Option<String>, Ownership>::new();
+    let mut attribute = |directive: &Directive, update: &dyn Fn(&mut Ownership)| {
+        let owners = owners_of(directive);
+        if owners.is_empty() {
+            update(ownership.entry(None).or_default());
+        }
+        for owner in owners {
+            update(ownership.entry(Some(owner)).or_default());
+        }
+    };
+
+    for tag in tags.values().flatten() {
+        let unused = !counts.contains_key(tag.label.as_str());
+        attribute(tag, &|ownership| {
+            ownership.tags += 1;
+            if unused {
+                ownership.unused += 1;
+            }
+        });
+    }
+
+    for r#ref in refs.iter().filter(|r#ref| !tags.contains_key(&r#ref.label)) {
+        attribute(r#ref, &|ownership| ownership.dangling += 1);
+    }
+
+    let mut ownership = ownership.into_iter().collect::<Vec<_>>();
+    ownership.sort_by(|(x_owner, x), (y_owner, y)| {
+        (Reverse(x.unused + x.dangling), x_owner.is_none(), x_owner).cmp(&(
+            Reverse(y.unused + y.dangling),
+            y_owner.is_none(),
+            y_owner,
+        ))
+    });
+    ownership
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            directive::{Directive, Type},
+            stats::{hotspots, owners, Ownership},
+        },
+        std::{collections::HashMap, path::Path},
+    };
+
+    fn directive(r#type: Type, label: &str, path: &str) -> Directive {
+        Directive::new(r#type, label, path, 1)
+    }
+
+    #[test]
+    fn hotspots_empty() {
+        let hotspots = hotspots(&HashMap::new(), &[], 0, 10);
+
+        assert!(hotspots.tags.is_empty());
+        assert!(hotspots.inbound.is_empty());
+        assert!(hotspots.outbound.is_empty());
+    }
+
+    #[test]
+    fn hotspots_counts() {
+        let mut tags = HashMap::new();
+        tags.insert(
+            "tag1".to_owned(),
+            vec![directive(Type::Tag, "tag1", "file1.rs")],
+        );
+        tags.insert(
+            "tag2".to_owned(),
+            vec![directive(Type::Tag, "tag2", "file2.rs")],
+        );
+        tags.insert(
+            "tag3".to_owned(),
+            vec![directive(Type::Tag, "tag3", "file2.rs")],
+        );
+
+        let refs = [
+            directive(Type::Ref, "tag1", "file3.rs"),
+            directive(Type::Ref, "tag1", "file3.rs"),
+            directive(Type::Ref, "tag1", "file4.rs"),
+            directive(Type::Ref, "tag2", "file3.rs"),
+            directive(Type::Ref, "tag2", "file4.rs"),
+            directive(Type::Ref, "tag3", "file4.rs"),
+        ];
+
+        let hotspots = hotspots(&tags, &refs, 1, 1);
+
+        assert_eq!(hotspots.tags.len(), 2);
+        assert_eq!(hotspots.tags[0].0.label, "tag1");
+        assert_eq!(hotspots.tags[0].1, 3);
+        assert_eq!(hotspots.tags[1].0.label, "tag2");
+        assert_eq!(hotspots.tags[1].1, 2);
+        assert_eq!(hotspots.inbound, [(Path::new("file1.rs").to_owned(), 3)]);
+        assert_eq!(hotspots.outbound, [(Path::new("file3.rs").to_owned(), 3)]);
+    }
+
+    #[test]
+    fn owners_counts() {
+        let mut owned = directive(Type::Tag, "tag1", "storage/file1.rs");
+        owned
+            .metadata
+            .insert("owner".to_owned(), "@acme/search".to_owned());
+        let mut tags = HashMap::new();
+        tags.insert("tag1".to_owned(), vec![owned]);
+        tags.insert(
+            "tag2".to_owned(),
+            vec![directive(Type::Tag, "tag2", "storage/file2.rs")],
+        );
+        tags.insert(
+            "tag3".to_owned(),
+            vec![directive(Type::Tag, "tag3", "misc/file3.rs")],
+        );
+
+        let refs = [
+            directive(Type::Ref, "tag1", "misc/file3.rs"),
+            directive(Type::Ref, "tag4", "storage/file1.rs"),
+            directive(Type::Ref, "tag5", "storage/file2.rs"),
+        ];
+
+        let owners = owners(&tags, &refs, |directive| {
+            directive
+                .metadata
+                .get("owner")
+                .cloned()
+                .or_else(|| {
+                    directive
+                        .path
+                        .starts_with("storage")
+                        .then(|| "@acme/storage".to_owned())
+                })
+                .into_iter()
+                .collect()
+        });
+
+        assert_eq!(
+            owners,
+            [
+                (
+                    Some("@acme/storage".to_owned()),
+                    Ownership {
+                        tags: 1,
+                        unused: 1,
+                        dangling: 2,
+                    },
+                ),
+                (
+                    None,
+                    Ownership {
+                        tags: 1,
+                        unused: 1,
+                        dangling: 0,
+                    },
+                ),
+                (
+                    Some("@acme/search".to_owned()),
+                    Ownership {
+                        tags: 1,
+                        unused: 0,
+                        dangling: 0,
+                    },
+                ),
+            ],
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use {
+    std::{ops::Range, path::Path},
+    toml_edit::{ImDocument, Item, Table, Value},
+    yaml_rust2::{
+        parser::{Event, Parser},
+        scanner::TScalarStyle,
+    },
+};
+
+// This struct represents a string value whose source text can't be searched line by line, such as
+// a multi-line YAML scalar or a string with escape sequences. The range is the part of the file to
+// replace, and the value is what to replace it with.
+struct Replacement {
+    range: Range<usize>,
+    value: String,
+}
+
+// This function rewrites a YAML, JSON, or TOML file so the string values can be searched line by
+// line. Each string value which spans several lines or contains escape sequences is decoded and
+// moved onto the line where it starts, and the lines it occupied are left blank, so directives in
+// it are found on that line even if the source splits them. Everything else, including comments, is
+// left alone. It returns `None` for other files, or if the file can't be parsed.
+pub fn extract(path: &Path, contents: &str) -> Option<String> {
+    let replacements = match path.extension()?.to_str()? {
+        "yaml" | "yml" => yaml(contents)?,
+        "json" => json(contents)?,
+        "toml" => toml(contents)?,
+        _ => return None,
+    };
+
+    let mut extracted = String::with_capacity(contents.len());
+    let mut next = 0;
+    for replacement in replacements {
+        // Skip any values which overlap ones which were already replaced.
+        if replacement.range.start < next {
+            continue;
+        }
+
+        // Keep the line breaks so the line numbers after the value don't change.
+        extracted.push_str(&contents[next..replacement.range.start]);
+        extracted.extend(replacement.value.chars().map(|c| match c {
+            '\n' | '\r' => ' ',
+            _ => c,
+        }));
+        extracted.extend(
+            contents[replacement.range.clone()]
+                .chars()
+                .filter(|c| *c == '\n'),
+        );
+        next = replacement.range.end;
+    }
+    extracted.push_str(&contents[next..]);
+
+    Some(extracted)
+}
+
+// This function determines whether the source text of a string value can't be searched as is.
+fn needs_replacement(source: &str) -> bool {
+    source.contains(['\n', '\\'])
+}
+
+// This function returns the byte offset just after the closing quote of a string, given the text
+// after the opening quote. Double-quoted strings use backslash escapes, and single-quoted strings
+// escape a quote by doubling it.
+fn closing_quote(text: &str, quote: char) -> Option<usize> {
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' && quote == '"' {
+            chars.next();
+        } else if c == quote {
+            if quote == '\'' && chars.peek().is_some_and(|(_, next)| *next == '\'') {
+                chars.next();
+            } else {
+                return Some(i + 1);
+            }
+        }
+    }
+
+    None
+}
+
+// This function returns the byte offset of each line in a file.
+fn line_offsets(contents: &str) -> Vec<usize> {
+    let mut offsets = vec![0];
+    offsets.extend(contents.match_indices('\n').map(|(i, _)| i + 1));
+    offsets
+}
+
+// This function returns the byte offset at which the given line ends, not counting the line break.
+fn line_end(contents: &str, offsets: &[usize], line: usize) -> usize {
+    offsets
+        .get(line + 1)
+        .map_or(contents.len(), |offset| offset - 1)
+}
+
+// This function finds the multi-line scalars in a YAML file. The parser only reports where each
+// scalar starts, so the end is taken to be the last line of content before the next event.
+fn yaml(contents: &str) -> Option<Vec<Replacement>> {
+    let mut parser = Parser::new_from_str(contents);
+    let mut events = vec![];
+    loop {
+        let (event, marker) = parser.next_token().ok()?;
+        if event == Event::StreamEnd {
+            break;
+        }
+        // The parser counts lines from 1 and columns from 0, in characters.
+        events.push((event, marker.line().saturating_sub(1), marker.col()));
+    }
+
+    let offsets = line_offsets(contents);
+    let lines = contents.split('\n').collect::<Vec<_>>();
+    let mut replacements = vec![];
+    for (i, (event, line, column)) in events.iter().enumerate() {
+        let Event::Scalar(value, style, ..) = event else {
+            continue;
+        };
+
+        // Find the start of the scalar and the line of the next event.
+        let start = offsets[*line]
+            + lines[*line]
+                .char_indices()
+                .nth(*column)
+                .map_or(lines[*line].len(), |(j, _)| j);
+        let next_line = events
+            .get(i + 1)
+            .map_or(lines.len(), |(_, next_line, _)| *next_line);
+
+        let (range, value) = match style {
+            TScalarStyle::SingleQuoted | TScalarStyle::DoubleQuoted => {
+                // Quoted scalars end at the closing quote.
+                let quote = if *style == TScalarStyle::SingleQuoted {
+                    '\''
+                } else {
+                    '"'
+                };
+                let end = start + 1 + closing_quote(&contents[start + 1..], quote)?;
+                (start..end, value.clone())
+            }
+            TScalarStyle::Plain => {
+                // Plain scalars end at the last line of content, or a comment on that line.
+                let Some(last) = (*line + 1..next_line).rev().find(|j| {
+                    let trimmed = lines[*j].trim();
+                    !trimmed.is_empty() && !trimmed.starts_with('#')
+                }) else {
+                    continue;
+                };
+                let end = lines[last]
+                    .find(" #")
+                    .map_or_else(|| line_end(contents, &offsets, last), |j| offsets[last] + j);
+                (start..end, value.clone())
+            }
+            TScalarStyle::Literal | TScalarStyle::Folded => {
+                // Block scalars start at the first line of content and end at the last line
+                // indented at least as much. The header is kept, since it may contain a comment.
+                let Some(header) = (0..*line).rev().find(|j| !lines[*j].trim().is_empty()) else {
+                    continue;
+                };
+                let Some(last) = (*line..next_line).rev().find(|j| {
+                    let content = lines[*j].trim_start();
+                    !content.is_empty() && lines[*j].len() - content.len() >= *column
+                }) else {
+                    continue;
+                };
+                (
+                    line_end(contents, &offsets, header)..line_end(contents, &offsets, last),
+                    format!(" {value}"),
+                )
+            }
+        };
+
+        if contents[range.clone()].contains('\n') {
+            replacements.push(Replacement { range, value });
+        }
+    }
+
+    Some(replacements)
+}
+
+// This function finds the strings with escape sequences in a JSON file.
+fn json(contents: &str) -> Option<Vec<Replacement>> {
+    serde_json::from_str::<serde_json::Value>(contents).ok()?;
+
+    let mut replacements = vec![];
+    let mut next = 0;
+    while let Some(offset) = contents[next..].find('"') {
+        let start = next + offset;
+        let end = start + 1 + closing_quote(&contents[start + 1..], '"')?;
+        if needs_replacement(&contents[start..end]) {
+            replacements.push(Replacement {
+                range: start..end,
+                value: serde_json::from_str(&contents[start..end]).ok()?,
+            });
+        }
+        next = end;
+    }
+
+    Some(replacements)
+}
+
+// This function finds the multi-line strings and the strings with escape sequences in a TOML file.
+fn toml(contents: &str) -> Option<Vec<Replacement>> {
+    let document = ImDocument::parse(contents).ok()?;
+    let mut replacements = vec![];
+    toml_table(contents, document.as_table(), &mut replacements);
+    replacements.sort_by_key(|replacement| replacement.range.start);
+
+    Some(replacements)
+}
+
+// This function finds the strings which need to be replaced in a TOML table.
+fn toml_table(contents: &str, table: &Table, replacements: &mut Vec<Replacement>) {
+    for (_, item) in table {
+        match item {
+            Item::None => {}
+            Item::Value(value) => toml_value(contents, value, replacements),
+            Item::Table(table) => toml_table(contents, table, replacements),
+            Item::ArrayOfTables(tables) => {
+                for table in tables {
+                    toml_table(contents, table, replacements);
+                }
+            }
+        }
+    }
+}
+
+// This function finds the strings which need to be replaced in a TOML value.
+fn toml_value(contents: &str, value: &Value, replacements: &mut Vec<Replacement>) {
+    match value {
+        Value::String(string) => {
+            if let Some(range) = string.span() {
+                if needs_replacement(&contents[range.clone()]) {
+                    replacements.push(Replacement {
+                        range,
+                        value: string.value().clone(),
+                    });
+                }
+            }
+        }
+        Value::Array(array) => {
+            for value in array {
+                toml_value(contents, value, replacements);
+            }
+        }
+        Value::InlineTable(table) => {
+            for (_, value) in table {
+                toml_value(contents, value, replacements);
+            }
+        }
+        Value::Integer(_) | Value::Float(_) | Value::Boolean(_) | Value::Datetime(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {crate::structured::extract, std::path::Path};
+
+    #[test]
+    fn extract_yaml_folded() {
+        let contents =
+            "a: > # [?ref:bar]\n  see [?tag:foo\n  owner=x]\n# [?ref:baz]\nb: 1\n".replace('?', "");
+        let extracted = extract(Path::new("a.yaml"), &contents).unwrap();
+
+        assert_eq!(
+            extracted,
+            "a: > # [?ref:bar] see [?tag:foo owner=x] \n\n\n# [?ref:baz]\nb: 1\n".replace('?', ""),
+        );
+    }
+
+    #[test]
+    fn extract_yaml_plain() {
+        let contents = "a: see [?tag:foo\n  owner=x] # [?ref:bar]\nb: c\n".replace('?', "");
+        let extracted = extract(Path::new("a.yml"), &contents).unwrap();
+
+        assert_eq!(
+            extracted,
+            "a: see [?tag:foo owner=x]\n # [?ref:bar]\nb: c\n".replace('?', ""),
+        );
+    }
+
+    #[test]
+    fn extract_yaml_quoted() {
+        let contents = "a: 'it''s [?tag:foo\n  owner=x]'\nb: 'c'\n".replace('?', "");
+        let extracted = extract(Path::new("a.yaml"), &contents).unwrap();
+
+        assert_eq!(
+            extracted,
+            "a: it's [?tag:foo owner=x]\n\nb: 'c'\n".replace('?', ""),
+        );
+    }
+
+    #[test]
+    fn extract_json() {
+        let contents = "{\"a\": \"[?ref:foo\\u005d\",\n \"b\": \"[?ref:bar]\"}".replace('?', "");
+        let extracted = extract(Path::new("a.json"), &contents).unwrap();
+
+        assert_eq!(
+            extracted,
+            "{\"a\": [?ref:foo],\n \"b\": \"[?ref:bar]\"}".replace('?', ""),
+        );
+    }
+
+    #[test]
+    fn extract_toml() {
+        let contents = "a = \"\"\"\nsee [?tag:foo \\\n  owner=x]\"\"\"\nb = 'c' # [?ref:bar]\n"
+            .replace('?', "");
+        let extracted = extract(Path::new("a.toml"), &contents).unwrap();
+
+        assert_eq!(
+            extracted,
+            "a = see [?tag:foo owner=x]\n\n\nb = 'c' # [?ref:bar]\n".replace('?', ""),
+        );
+    }
+
+    #[test]
+    fn extract_other() {
+        assert!(extract(Path::new("a.rs"), "[?ref:foo]").is_none());
+        assert!(extract(Path::new("a.json"), "{").is_none());
+    }
+}
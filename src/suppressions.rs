@@ -0,0 +1,102 @@
+use crate::{config::Suppression, diagnostic::Diagnostic};
+
+// This function removes the problems which are suppressed by the given rules. Each problem is
+// attributed to the first rule which suppresses it. It returns the remaining problems, along with
+// the number of problems suppressed by each rule.
+pub fn apply(
+    suppressions: &[Suppression],
+    diagnostics: Vec<Diagnostic>,
+) -> (Vec<Diagnostic>, Vec<usize>) {
+    let mut counts = vec![0; suppressions.len()];
+
+    let remaining = diagnostics
+        .into_iter()
+        .filter(|diagnostic| {
+            let Some((path, _)) = &diagnostic.location else {
+                return true;
+            };
+            let position = suppressions.iter().position(|suppression| {
+                suppression.path.is_match(path)
+                    && (suppression.codes.is_empty()
+                        || suppression.codes.contains(&diagnostic.code))
+                    && suppression.label.as_ref().is_none_or(|pattern| {
+                        diagnostic
+                            .label
+                            .as_ref()
+                            .is_some_and(|label| pattern.is_match(label))
+                    })
+            });
+            if let Some(i) = position {
+                counts[i] += 1;
+            }
+            position.is_none()
+        })
+        .collect();
+
+    (remaining, counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            config,
+            diagnostic::{Diagnostic, Severity},
+            suppressions::apply,
+        },
+        std::path::Path,
+    };
+
+    fn diagnostic(code: &str, path: &str, label: Option<&str>) -> Diagnostic {
+        Diagnostic {
+            code: code.to_owned(),
+            severity: Severity::Error,
+            message: String::new(),
+            location: Some((Path::new(path).to_owned(), 1)),
+            label: label.map(ToOwned::to_owned),
+            context: None,
+        }
+    }
+
+    #[test]
+    fn apply_empty() {
+        let (remaining, counts) = apply(&[], vec![diagnostic("E002", "a.rs", None)]);
+
+        assert_eq!(remaining.len(), 1);
+        assert!(counts.is_empty());
+    }
+
+    #[test]
+    fn apply_rules() {
+        let config = config::parse(
+            "
+            [[suppressions]]
+            path = 'examples/**'
+            codes = ['E002']
+            label = '^example_'
+
+            [[suppressions]]
+            path = 'vendor/**'
+
+            [[suppressions]]
+            path = 'docs/**'
+            ",
+        )
+        .unwrap();
+
+        let (remaining, counts) = apply(
+            &config.suppressions,
+            vec![
+                diagnostic("E002", "./examples/a.rs", Some("example_foo")),
+                diagnostic("E002", "./examples/a.rs", Some("foo")),
+                diagnostic("E001", "./examples/a.rs", Some("example_foo")),
+                diagnostic("E001", "./vendor/a.rs", Some("foo")),
+                diagnostic("E105", "./vendor/b.rs", None),
+                diagnostic("E002", "./src/a.rs", Some("example_foo")),
+            ],
+        );
+
+        assert_eq!(remaining.len(), 3);
+        assert_eq!(counts, vec![1, 2, 0]);
+    }
+}
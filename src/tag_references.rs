@@ -1,13 +1,22 @@
-use {crate::directive::Directive, std::collections::HashSet};
+use {
+    crate::{
+        diagnostic::{self, Diagnostic},
+        directive::Directive,
+    },
+    std::collections::HashSet,
+};
 
-// This function checks that tag references actually point to tags. It returns a vector of error
-// strings.
-pub fn check(tags: &HashSet<String>, refs: &[Directive]) -> Vec<String> {
-    let mut errors = Vec::<String>::new();
+// This function checks that tag references actually point to tags. It returns a vector of errors.
+pub fn check(tags: &HashSet<String>, refs: &[Directive]) -> Vec<Diagnostic> {
+    let mut errors = Vec::<Diagnostic>::new();
 
     for r#ref in refs {
         if !tags.contains(&r#ref.label) {
-            errors.push(format!("No tag found for {ref}."));
+            errors.push(Diagnostic::error(
+                diagnostic::DANGLING_REF,
+                r#ref,
+                format!("No tag found for {ref}."),
+            ));
         }
     }
 
@@ -21,7 +30,7 @@ mod tests {
             directive::{Directive, Type},
             tag_references::check,
         },
-        std::{collections::HashSet, path::Path},
+        std::collections::HashSet,
     };
 
     #[test]
@@ -37,12 +46,7 @@ mod tests {
         let mut tags = HashSet::new();
         tags.insert("ref1".to_owned());
 
-        let refs = vec![Directive {
-            r#type: Type::Ref,
-            label: "ref1".to_owned(),
-            path: Path::new("file1.rs").to_owned(),
-            line_number: 1,
-        }];
+        let refs = vec![Directive::new(Type::Ref, "ref1", "file1.rs", 1)];
 
         assert!(check(&tags, &refs).is_empty());
     }
@@ -53,31 +57,18 @@ mod tests {
         tags.insert("ref1".to_owned());
 
         let refs = vec![
-            Directive {
-                r#type: Type::Ref,
-                label: "ref1".to_owned(),
-                path: Path::new("file1.rs").to_owned(),
-                line_number: 1,
-            },
-            Directive {
-                r#type: Type::Ref,
-                label: "ref2".to_owned(),
-                path: Path::new("file2.rs").to_owned(),
-                line_number: 2,
-            },
-            Directive {
-                r#type: Type::Ref,
-                label: "ref3".to_owned(),
-                path: Path::new("file3.rs").to_owned(),
-                line_number: 3,
-            },
+            Directive::new(Type::Ref, "ref1", "file1.rs", 1),
+            Directive::new(Type::Ref, "ref2", "file2.rs", 2),
+            Directive::new(Type::Ref, "ref3", "file3.rs", 3),
         ];
 
         let errors = check(&tags, &refs);
         assert_eq!(errors.len(), 2);
         assert!(
-            (errors[0].contains(&refs[1].label) && errors[1].contains(&refs[2].label))
-                || (errors[0].contains(&refs[2].label) && errors[1].contains(&refs[1].label)),
+            (errors[0].message.contains(&refs[1].label)
+                && errors[1].message.contains(&refs[2].label))
+                || (errors[0].message.contains(&refs[2].label)
+                    && errors[1].message.contains(&refs[1].label)),
         );
     }
 }
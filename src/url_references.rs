@@ -0,0 +1,370 @@
+use {
+    crate::{
+        atomic,
+        config::Urls,
+        diagnostic::{self, Diagnostic},
+        directive::Directive,
+    },
+    std::{
+        cmp::max,
+        collections::{BTreeMap, HashMap},
+        fs::read_to_string,
+        io::ErrorKind,
+        path::Path,
+        sync::Mutex,
+        thread,
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    },
+};
+
+// This enum represents the result of requesting a URL.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    // The URL works.
+    Ok,
+
+    // The URL is broken, e.g., the server responded with `404 Not Found`.
+    Broken(String),
+
+    // The URL couldn't be checked, but it might work if requested again, e.g., the request timed
+    // out or the server responded with `503 Service Unavailable`.
+    Transient(String),
+}
+
+// This struct spaces out the requests to each domain.
+struct RateLimiter {
+    interval: Duration,
+    next: Mutex<HashMap<String, Instant>>,
+}
+
+impl RateLimiter {
+    // This function waits until it's time for the next request to a domain.
+    fn wait(&self, domain: &str) {
+        // Reserve the next slot for the domain. The `unwrap` is safe assuming no poisoning.
+        let slot = {
+            let mut next = self.next.lock().unwrap();
+            let now = Instant::now();
+            let slot = next.get(domain).map_or(now, |slot| max(*slot, now));
+            next.insert(domain.to_owned(), slot + self.interval);
+            slot
+        };
+
+        thread::sleep(slot.saturating_duration_since(Instant::now()));
+    }
+}
+
+// This function returns the domain of an HTTP(S) URL in lowercase, without any user information or
+// port. It returns `None` for other URLs.
+fn domain(url: &str) -> Option<String> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    let host = if host.starts_with('[') {
+        // IPv6 addresses are enclosed in brackets, since they contain colons.
+        &host[..=host.find(']')?]
+    } else {
+        host.split(':').next()?
+    };
+
+    (!host.is_empty()).then(|| host.to_ascii_lowercase())
+}
+
+// This function determines whether a domain is one of the given domains or a subdomain of one.
+fn is_within(domain: &str, domains: &[String]) -> bool {
+    domains.iter().any(|entry| {
+        let entry = entry.trim_start_matches('.').to_ascii_lowercase();
+        domain == entry
+            || domain
+                .strip_suffix(&entry)
+                .is_some_and(|prefix| prefix.ends_with('.'))
+    })
+}
+
+// This function reads the cache of working URLs, which maps each URL to when it was last found to
+// work (as a Unix timestamp). A missing or corrupt cache is treated as empty.
+fn load_cache(path: &Path) -> Result<BTreeMap<String, u64>, String> {
+    match read_to_string(path) {
+        Ok(contents) => Ok(serde_json::from_str(&contents).unwrap_or_default()),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(error) => Err(format!(
+            "Unable to read {}: {error}",
+            path.to_string_lossy(),
+        )),
+    }
+}
+
+// This function requests a URL. Rate limiting (`429 Too Many Requests`), server errors, and
+// network errors are considered transient.
+fn fetch(agent: &ureq::Agent, url: &str) -> Outcome {
+    match agent.get(url).call() {
+        Ok(_) => Outcome::Ok,
+        Err(ureq::Error::Status(status, response)) => {
+            let reason = format!("{status} {}", response.status_text());
+            if status == 429 || status >= 500 {
+                Outcome::Transient(reason)
+            } else {
+                Outcome::Broken(reason)
+            }
+        }
+        Err(ureq::Error::Transport(error)) => Outcome::Transient(error.to_string()),
+    }
+}
+
+// This function checks that URL references work by requesting them, according to the settings. It
+// returns a vector of errors for broken URLs and warnings for URLs which couldn't be checked.
+pub fn check(refs: &[Directive], settings: &Urls) -> Result<Vec<Diagnostic>, String> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(settings.timeout_seconds))
+        .build();
+
+    check_with(refs, settings, |url| fetch(&agent, url))
+}
+
+// This function checks URL references, using the given function to request each URL. Each URL is
+// only requested once, no matter how many references it has.
+fn check_with<F: Fn(&str) -> Outcome + Sync>(
+    refs: &[Directive],
+    settings: &Urls,
+    fetch: F,
+) -> Result<Vec<Diagnostic>, String> {
+    let mut diagnostics = Vec::<Diagnostic>::new();
+
+    // Load the cache, forgetting the entries which have expired.
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let mut cache = match &settings.cache {
+        Some(path) => load_cache(path)?,
+        None => BTreeMap::new(),
+    };
+    cache.retain(|_, checked| {
+        now.saturating_sub(*checked) < settings.cache_ttl_hours.saturating_mul(60 * 60)
+    });
+
+    // Decide which URLs to request.
+    let mut urls = BTreeMap::<&str, Vec<&Directive>>::new();
+    for r#ref in refs {
+        urls.entry(r#ref.label.as_str()).or_default().push(r#ref);
+    }
+    let mut queue = Vec::new();
+    for (url, refs) in &urls {
+        let Some(domain) = domain(url) else {
+            for r#ref in refs {
+                diagnostics.push(Diagnostic::error(
+                    diagnostic::BAD_URL_REF,
+                    r#ref,
+                    format!("{ref} does not point to an HTTP or HTTPS URL."),
+                ));
+            }
+            continue;
+        };
+        if (!settings.allow_domains.is_empty() && !is_within(&domain, &settings.allow_domains))
+            || is_within(&domain, &settings.deny_domains)
+            || cache.contains_key(*url)
+        {
+            continue;
+        }
+        queue.push((*url, domain));
+    }
+
+    // Request the URLs, retrying after transient failures with exponential backoff.
+    let limiter = RateLimiter {
+        interval: Duration::from_millis(settings.interval_ms),
+        next: Mutex::new(HashMap::new()),
+    };
+    let queue = Mutex::new(queue);
+    let outcomes = Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        for _ in 0..settings.concurrency {
+            scope.spawn(|| {
+                // The lock on the queue is released before each request. The `unwrap`s are safe
+                // assuming no poisoning.
+                loop {
+                    let next = queue.lock().unwrap().pop();
+                    let Some((url, domain)) = next else {
+                        break;
+                    };
+                    let mut attempt = 0;
+                    let outcome = loop {
+                        limiter.wait(&domain);
+                        match fetch(url) {
+                            Outcome::Transient(_) if attempt < settings.retries => {
+                                thread::sleep(limiter.interval.saturating_mul(2_u32.pow(attempt)));
+                                attempt += 1;
+                            }
+                            outcome => break outcome,
+                        }
+                    };
+                    outcomes.lock().unwrap().push((url, outcome));
+                }
+            });
+        }
+    });
+
+    // Report the results. The `unwrap` is safe assuming no poisoning.
+    for (url, outcome) in outcomes.into_inner().unwrap() {
+        let (diagnostic, code, problem): (fn(&str, &Directive, String) -> Diagnostic, _, _) =
+            match outcome {
+                Outcome::Ok => {
+                    cache.insert(url.to_owned(), now);
+                    continue;
+                }
+                Outcome::Broken(reason) => (
+                    Diagnostic::error,
+                    diagnostic::BAD_URL_REF,
+                    format!("is broken ({reason})"),
+                ),
+                Outcome::Transient(reason) => (
+                    Diagnostic::warning,
+                    diagnostic::UNREACHABLE_URL,
+                    format!("could not be checked ({reason})"),
+                ),
+            };
+        for r#ref in &urls[url] {
+            diagnostics.push(diagnostic(code, r#ref, format!("{ref} {problem}.")));
+        }
+    }
+
+    // Remember the URLs which work.
+    if let Some(path) = &settings.cache {
+        // The `unwrap` is safe because maps with string keys always serialize successfully.
+        atomic::write(path, serde_json::to_string_pretty(&cache).unwrap() + "\n")?;
+    }
+
+    diagnostics.sort_by(|x, y| x.location.cmp(&y.location));
+    Ok(diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            config::Urls,
+            diagnostic::Severity,
+            directive::{Directive, Type},
+            url_references::{check_with, domain, is_within, Outcome},
+        },
+        std::{
+            env::temp_dir,
+            fs::{read_to_string, remove_file, write},
+            process,
+            sync::Mutex,
+        },
+    };
+
+    fn url(label: &str, line_number: usize) -> Directive {
+        Directive::new(Type::Url, label, "file.rs", line_number)
+    }
+
+    #[test]
+    fn domain_of_url() {
+        assert_eq!(
+            domain("https://Docs.Example.com/a?b#c").as_deref(),
+            Some("docs.example.com"),
+        );
+        assert_eq!(
+            domain("http://user@example.com:8080").as_deref(),
+            Some("example.com"),
+        );
+        assert_eq!(domain("http://[::1]:8080/").as_deref(), Some("[::1]"));
+        assert_eq!(domain("mailto:someone@example.com"), None);
+        assert_eq!(domain("https:///path"), None);
+    }
+
+    #[test]
+    fn domain_within() {
+        let domains = ["example.com".to_owned()];
+
+        assert!(is_within("example.com", &domains));
+        assert!(is_within("docs.example.com", &domains));
+        assert!(!is_within("badexample.com", &domains));
+    }
+
+    #[test]
+    fn check_urls() {
+        let cache = temp_dir().join(format!("tagref-urls-{}.json", process::id()));
+        write(
+            &cache,
+            "{\"https://example.com/cached\": 18446744073709551615}",
+        )
+        .unwrap();
+        let settings = Urls {
+            check: true,
+            interval_ms: 0,
+            cache: Some(cache.clone()),
+            deny_domains: vec!["denied.example.com".to_owned()],
+            ..Urls::default()
+        };
+        let refs = [
+            url("https://example.com/ok", 1),
+            url("https://example.com/missing", 2),
+            url("https://example.com/flaky", 3),
+            url("https://example.com/down", 4),
+            url("https://example.com/cached", 5),
+            url("https://denied.example.com/", 6),
+            url("ftp://example.com/", 7),
+            url("https://example.com/ok", 8),
+        ];
+
+        let requests = Mutex::new(Vec::new());
+        let diagnostics = check_with(&refs, &settings, |url| {
+            let mut requests = requests.lock().unwrap();
+            requests.push(url.to_owned());
+            match url {
+                "https://example.com/missing" => Outcome::Broken("404 Not Found".to_owned()),
+                "https://example.com/flaky"
+                    if requests.iter().filter(|request| *request == url).count() == 1 =>
+                {
+                    Outcome::Transient("503 Service Unavailable".to_owned())
+                }
+                "https://example.com/down" => Outcome::Transient("timed out".to_owned()),
+                _ => Outcome::Ok,
+            }
+        })
+        .unwrap();
+        let cached = read_to_string(&cache).unwrap();
+        let _ = remove_file(&cache);
+
+        let mut requests = requests.into_inner().unwrap();
+        requests.sort();
+        assert_eq!(
+            requests,
+            [
+                "https://example.com/down",
+                "https://example.com/down",
+                "https://example.com/down",
+                "https://example.com/flaky",
+                "https://example.com/flaky",
+                "https://example.com/missing",
+                "https://example.com/ok",
+            ],
+        );
+
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert!(
+            diagnostics[0]
+                .message
+                .ends_with("is broken (404 Not Found)."),
+        );
+        assert_eq!(diagnostics[1].severity, Severity::Warning);
+        assert!(
+            diagnostics[1]
+                .message
+                .ends_with("could not be checked (timed out)."),
+        );
+        assert_eq!(diagnostics[2].severity, Severity::Error);
+        assert!(
+            diagnostics[2]
+                .message
+                .ends_with("does not point to an HTTP or HTTPS URL."),
+        );
+
+        assert!(cached.contains("\"https://example.com/ok\""));
+        assert!(cached.contains("\"https://example.com/flaky\""));
+        assert!(cached.contains("\"https://example.com/cached\""));
+        assert!(!cached.contains("missing"));
+    }
+}
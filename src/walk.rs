@@ -1,70 +1,362 @@
 use {
-    ignore::{overrides::OverrideBuilder, WalkBuilder, WalkState},
+    crate::{
+        config::PathPattern, directive_index::DEFAULT_DIRECTIVE_INDEX_PATH, edit::BACKUP_DIR,
+        readahead, shard::Shard,
+    },
+    ignore::{
+        overrides::{Override, OverrideBuilder},
+        WalkBuilder, WalkState,
+    },
     std::{
+        collections::HashSet,
+        fmt::Write,
         fs::File,
-        path::{Path, PathBuf},
+        path::{absolute, Component, Path, PathBuf},
         sync::{
             atomic::{AtomicUsize, Ordering},
-            Arc,
+            Arc, Mutex,
         },
     },
 };
 
+// This enum identifies a physical file, regardless of which path was used to reach it.
+#[derive(Eq, Hash, PartialEq)]
+enum FileId {
+    #[cfg(unix)]
+    Inode(u64, u64),
+    #[cfg(not(unix))]
+    Path(PathBuf),
+}
+
+impl FileId {
+    // This function identifies a file by its device and inode where possible, or by its canonical
+    // path otherwise.
+    #[cfg(unix)]
+    fn new(_path: &Path, file: &File) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        file.metadata()
+            .ok()
+            .map(|metadata| Self::Inode(metadata.dev(), metadata.ino()))
+    }
+
+    #[cfg(not(unix))]
+    fn new(path: &Path, _file: &File) -> Option<Self> {
+        path.canonicalize().ok().map(Self::Path)
+    }
+}
+
+// This function returns the patterns for the paths which are never traversed: the directories of
+// version control systems, the one with the backups from editing files, and the directive index
+// (which quotes the directives it records).
+fn overrides() -> Override {
+    OverrideBuilder::new("")
+        .add("!.git/")
+        .unwrap() // Safe by manual inspection
+        .add("!.hg/")
+        .unwrap() // Safe by manual inspection
+        .add(&format!("!{BACKUP_DIR}/"))
+        .unwrap() // Safe by manual inspection
+        .add(&format!("!{DEFAULT_DIRECTIVE_INDEX_PATH}"))
+        .unwrap() // Safe by manual inspection
+        .build()
+        .unwrap() // Safe by manual inspection
+}
+
 // This function visits each file in the given directory and calls the given callback with the path
 // and the file. It skips files which cannot be read (e.g., due to lack of permissions). It also
-// skips over symlinks. The number of files traversed is returned.
+// skips over symlinks, and files outside the given shard (if any). Unless `visit_copies` is set,
+// files which were already visited via another path (e.g., due to overlapping paths or hard links)
+// are skipped too. Paths matching any of the excluded patterns are skipped, along with their
+// contents in the case of directories. All the paths are traversed together in parallel, so many
+// small paths use every core. The number of files traversed is returned.
 pub fn walk<T: 'static + Clone + Send + FnMut(&Path, File)>(
     paths: &[PathBuf],
+    excludes: &[PathPattern],
+    shard: Option<Shard>,
+    visit_copies: bool,
     callback: T,
 ) -> usize {
     // Keep track of the number of files traversed, and allow multiple threads to update it.
     let files_scanned = Arc::new(AtomicUsize::new(0));
 
-    // Scan each of the given paths.
-    for path in paths {
-        // Traverse the filesystem in parallel.
-        WalkBuilder::new(path)
-            .hidden(false)
-            .require_git(false)
-            .overrides(
-                OverrideBuilder::new("")
-                    .add("!.git/")
-                    .unwrap() // Safe by manual inspection
-                    .add("!.hg/")
-                    .unwrap() // Safe by manual inspection
-                    .build()
-                    .unwrap(), // Safe by manual inspection
-            )
-            .build_parallel()
-            .run(|| {
-                // These clones will be moved into the closure below, and that closure will be sent
-                // to a new thread.
-                let mut callback = callback.clone();
-                let files_scanned = files_scanned.clone();
-
-                // This closure will be sent to a new thread.
-                Box::new(move |result| {
-                    // Proceed if we have access to the path.
-                    if let Ok(dir_entry) = result {
-                        // Here, `file_type()` should always return a `Some`. It could only return
-                        // `None` if the file represents STDIN, and that isn't the case here.
-                        if dir_entry.file_type().unwrap().is_file() {
-                            // Try to open the file.
-                            let possible_file = File::open(dir_entry.path());
-                            if let Ok(file) = possible_file {
+    // Keep track of which files have been visited, so each one is only visited once.
+    let visited = Arc::new(Mutex::new(HashSet::new()));
+
+    // Share the excluded patterns with every thread.
+    let excludes = Arc::new(excludes.to_owned());
+
+    // Scan all the given paths in a single traversal.
+    let Some((first, rest)) = paths.split_first() else {
+        return 0;
+    };
+    let mut builder = WalkBuilder::new(first);
+    for path in rest {
+        builder.add(path);
+    }
+
+    // Traverse the filesystem in parallel.
+    builder
+        .hidden(false)
+        .require_git(false)
+        .overrides(overrides())
+        .build_parallel()
+        .run(|| {
+            // These clones will be moved into the closure below, and that closure will be sent
+            // to a new thread.
+            let mut callback = callback.clone();
+            let files_scanned = files_scanned.clone();
+            let visited = visited.clone();
+            let excludes = excludes.clone();
+
+            // This closure will be sent to a new thread.
+            Box::new(move |result| {
+                // Proceed if we have access to the path.
+                if let Ok(dir_entry) = result {
+                    // Skip the path if it's excluded.
+                    if excludes
+                        .iter()
+                        .any(|pattern| pattern.is_match(dir_entry.path()))
+                    {
+                        return WalkState::Skip;
+                    }
+
+                    // Here, `file_type()` should always return a `Some`. It could only return
+                    // `None` if the file represents STDIN, and that isn't the case here.
+                    if dir_entry.file_type().unwrap().is_file()
+                        && shard.is_none_or(|shard| shard.contains(dir_entry.path()))
+                    {
+                        // Try to open the file.
+                        let possible_file = File::open(dir_entry.path());
+                        if let Ok(file) = possible_file {
+                            // Skip the file if it was already visited. The `unwrap` is safe
+                            // assuming no poisoning.
+                            if visit_copies
+                                || FileId::new(dir_entry.path(), &file)
+                                    .is_none_or(|id| visited.lock().unwrap().insert(id))
+                            {
                                 // Process the file and increment the counter.
+                                readahead::advise(&file);
                                 callback(dir_entry.path(), file);
                                 files_scanned.fetch_add(1, Ordering::SeqCst);
                             }
                         }
                     }
+                }
 
-                    // Don't stop...believing!
-                    WalkState::Continue
-                })
-            });
-    }
+                // Don't stop...believing!
+                WalkState::Continue
+            })
+        });
 
     // Return the number of files traversed.
     files_scanned.load(Ordering::SeqCst)
 }
+
+// This function returns the files and directories directly inside the given directory which `walk`
+// would traverse, i.e., those which aren't ignored or excluded. The paths are normalized.
+pub fn children(dir: &Path, excludes: &[PathPattern]) -> HashSet<PathBuf> {
+    WalkBuilder::new(dir)
+        .max_depth(Some(1))
+        .hidden(false)
+        .require_git(false)
+        .overrides(overrides())
+        .build()
+        .filter_map(Result::ok)
+        .filter(|dir_entry| {
+            dir_entry.depth() == 1
+                && dir_entry
+                    .file_type()
+                    .is_some_and(|file_type| file_type.is_file() || file_type.is_dir())
+                && !excludes
+                    .iter()
+                    .any(|pattern| pattern.is_match(dir_entry.path()))
+        })
+        .map(|dir_entry| normalize(dir_entry.path()))
+        .collect()
+}
+
+// This function removes any `.` components from a path produced by `walk`, so it can be matched
+// against patterns which are relative to the working directory.
+pub fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+// This function removes any of the given paths which are the same as, or inside, another one, so
+// the files under them aren't traversed twice. Paths are compared after resolving symlinks where
+// possible. It returns the remaining paths in their original order, along with a description of
+// each path which was removed.
+pub fn roots(paths: &[PathBuf]) -> (Vec<PathBuf>, Vec<String>) {
+    let keys = paths
+        .iter()
+        .map(|path| {
+            path.canonicalize()
+                .or_else(|_| absolute(normalize(path)))
+                .unwrap_or_else(|_| normalize(path))
+        })
+        .collect::<Vec<_>>();
+
+    let mut roots = vec![];
+    let mut removals = vec![];
+    for (i, (path, key)) in paths.iter().zip(&keys).enumerate() {
+        // Find the outermost path which covers this one. Among identical paths, the first is kept.
+        let outermost = keys
+            .iter()
+            .enumerate()
+            .filter(|(j, other)| {
+                if other == &key {
+                    *j < i
+                } else {
+                    key.starts_with(other)
+                }
+            })
+            .min_by_key(|(j, other)| (other.components().count(), *j));
+
+        match outermost {
+            None => roots.push(path.clone()),
+            Some((j, other)) if other == key => removals.push(format!(
+                "The path `{}` is the same as `{}`, so it will only be scanned once.",
+                path.display(),
+                paths[j].display(),
+            )),
+            Some((j, _)) => removals.push(format!(
+                "The path `{}` is inside `{}`, so it will only be scanned as part of that path.",
+                path.display(),
+                paths[j].display(),
+            )),
+        }
+    }
+
+    (roots, removals)
+}
+
+// This function renders a path for line-oriented output. Like Git, it quotes paths which contain
+// control characters (e.g., line breaks) or bytes which aren't valid UTF-8, using C-style escapes
+// for them, so each path stays on one line and can be recovered exactly. Other paths, including
+// those with spaces or non-ASCII characters, are rendered as they are.
+pub fn quote(path: &Path) -> String {
+    let bytes = path.as_os_str().as_encoded_bytes();
+    let chunks = bytes.utf8_chunks().collect::<Vec<_>>();
+    if chunks
+        .iter()
+        .all(|chunk| chunk.invalid().is_empty() && !chunk.valid().contains(char::is_control))
+    {
+        return path.to_string_lossy().into_owned();
+    }
+
+    let mut quoted = "\"".to_owned();
+    for chunk in chunks {
+        for c in chunk.valid().chars() {
+            match c {
+                '"' | '\\' => {
+                    quoted.push('\\');
+                    quoted.push(c);
+                }
+                '\n' => quoted.push_str("\\n"),
+                '\r' => quoted.push_str("\\r"),
+                '\t' => quoted.push_str("\\t"),
+                _ if c.is_control() => {
+                    let mut buffer = [0; 4];
+                    for byte in c.encode_utf8(&mut buffer).bytes() {
+                        let _ = write!(quoted, "\\{byte:03o}");
+                    }
+                }
+                _ => quoted.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            let _ = write!(quoted, "\\{byte:03o}");
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::walk::{quote, roots, walk},
+        std::{
+            env::temp_dir,
+            fs::{create_dir_all, remove_dir_all, write},
+            path::{Path, PathBuf},
+            process,
+            sync::{Arc, Mutex},
+        },
+    };
+
+    #[test]
+    fn walk_several_paths() {
+        let directory = temp_dir().join(format!("tagref-walk-{}", process::id()));
+        let _ = remove_dir_all(&directory);
+        let paths = (0_i32..3_i32)
+            .map(|i| {
+                let path = directory.join(format!("package{i}"));
+                create_dir_all(&path).unwrap();
+                write(path.join("lib.rs"), "").unwrap();
+                path
+            })
+            .collect::<Vec<_>>();
+
+        let visited = Arc::new(Mutex::new(Vec::new()));
+        let visited_clone = visited.clone();
+        let files_scanned = walk(&paths, &[], None, false, move |path, _| {
+            visited_clone.lock().unwrap().push(path.to_owned());
+        });
+        let _ = remove_dir_all(&directory);
+
+        let mut visited = visited.lock().unwrap().clone();
+        visited.sort();
+        assert_eq!(files_scanned, 3);
+        assert_eq!(
+            visited,
+            paths
+                .iter()
+                .map(|path| path.join("lib.rs"))
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(walk(&[], &[], None, false, |_, _| {}), 0);
+    }
+
+    #[test]
+    fn roots_overlapping() {
+        let paths = ["src/walk", "src", "./src", "docs", "src"]
+            .iter()
+            .map(PathBuf::from)
+            .collect::<Vec<_>>();
+        let (roots, removals) = roots(&paths);
+
+        assert_eq!(roots, [PathBuf::from("src"), PathBuf::from("docs")]);
+        assert_eq!(
+            removals,
+            [
+                "The path `src/walk` is inside `src`, so it will only be scanned as part of that \
+                 path.",
+                "The path `./src` is the same as `src`, so it will only be scanned once.",
+                "The path `src` is the same as `src`, so it will only be scanned once.",
+            ],
+        );
+    }
+
+    #[test]
+    fn quote_paths() {
+        assert_eq!(quote(Path::new("docs/ünï cödé/a.md")), "docs/ünï cödé/a.md");
+        assert_eq!(
+            quote(Path::new("a\nb\\\"c\u{7}.rs")),
+            "\"a\\nb\\\\\\\"c\\007.rs\"",
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn quote_invalid_utf8() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        assert_eq!(
+            quote(Path::new(OsStr::from_bytes(b"bad\xff.rs"))),
+            "\"bad\\377.rs\"",
+        );
+    }
+}
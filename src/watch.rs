@@ -0,0 +1,343 @@
+use {
+    crate::{
+        cache,
+        config::PathPattern,
+        directive::Directives,
+        directive_index::{DirectiveIndex, FileEntry},
+        walk::{self, normalize},
+    },
+    notify::{recommended_watcher, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher},
+    std::{
+        collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+        fs::read,
+        io::Read,
+        path::{absolute, Path, PathBuf},
+        sync::{
+            mpsc::{channel, Receiver},
+            Arc, Mutex,
+        },
+        time::Duration,
+    },
+};
+
+// How long to wait for more changes after one is noticed, so a burst of them (e.g., from switching
+// branches) is handled all at once
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+// This struct represents a watch on the scanned paths, along with the directories known to be
+// scanned. Changes in other directories (e.g., ignored ones) are ignored cheaply.
+pub struct Watch {
+    // The watcher stops when it's dropped, so it's kept here.
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+
+    // The scanned paths, as given and as absolute paths
+    roots: Vec<(PathBuf, PathBuf)>,
+
+    // The normalized paths of the directories known to be scanned
+    dirs: BTreeSet<PathBuf>,
+}
+
+impl Watch {
+    // This function starts watching the given paths for changes to the files in the index.
+    pub fn new(paths: &[PathBuf], index: &DirectiveIndex) -> Result<Self, String> {
+        let (sender, events) = channel();
+        let mut watcher = recommended_watcher(sender)
+            .map_err(|error| format!("Unable to watch for changes: {error}"))?;
+        let mut roots = vec![];
+        for path in paths {
+            let absolute_path = absolute(path)
+                .map_err(|error| format!("Unable to watch {}: {error}", path.to_string_lossy()))?;
+            watcher
+                .watch(&absolute_path, RecursiveMode::Recursive)
+                .map_err(|error| format!("Unable to watch {}: {error}", path.to_string_lossy()))?;
+            roots.push((path.clone(), absolute_path));
+        }
+
+        let mut dirs = paths
+            .iter()
+            .filter(|path| path.is_dir())
+            .map(|path| normalize(path))
+            .collect::<BTreeSet<_>>();
+        for file in index.files.keys() {
+            dirs.extend(Path::new(file).ancestors().skip(1).map(Path::to_owned));
+        }
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            roots,
+            dirs,
+        })
+    }
+
+    // This function waits for something to change, and returns the paths which changed in the
+    // same form that `walk` would produce them.
+    pub fn wait(&self) -> Result<BTreeSet<PathBuf>, String> {
+        let mut changed = BTreeSet::new();
+        let record = |changed: &mut BTreeSet<PathBuf>, event: notify::Result<Event>| {
+            let event = event.map_err(|error| format!("Unable to watch for changes: {error}"))?;
+            if !matches!(event.kind, EventKind::Access(_)) {
+                changed.extend(event.paths.iter().filter_map(|path| {
+                    self.roots.iter().find_map(|(root, absolute_root)| {
+                        path.strip_prefix(absolute_root)
+                            .ok()
+                            .map(|relative| root.join(relative))
+                    })
+                }));
+            }
+            Ok::<_, String>(())
+        };
+
+        loop {
+            record(
+                &mut changed,
+                self.events
+                    .recv()
+                    .map_err(|_| "The watch stopped unexpectedly.".to_owned())?,
+            )?;
+            while let Ok(event) = self.events.recv_timeout(SETTLE_TIME) {
+                record(&mut changed, event)?;
+            }
+            if !changed.is_empty() {
+                return Ok(changed);
+            }
+        }
+    }
+
+    // This function brings the index up to date with the changed paths, without traversing the
+    // rest of the scanned paths. Each changed path is checked against the directory it's in, so
+    // ignored and excluded files are skipped like they are by `walk`. New directories are
+    // traversed in full. It returns whether anything changed, in which case the generation is
+    // incremented.
+    pub fn refresh<F: Fn(&Path, &[u8]) -> Directives>(
+        &mut self,
+        index: &mut DirectiveIndex,
+        changed: &BTreeSet<PathBuf>,
+        excludes: &[PathPattern],
+        settings: &[impl AsRef<str>],
+        parse: F,
+    ) -> bool {
+        let settings = settings
+            .iter()
+            .map(|setting| setting.as_ref().to_owned())
+            .collect::<Vec<_>>();
+        let previous = index.files.clone();
+        let mut listings = HashMap::<PathBuf, HashSet<PathBuf>>::new();
+
+        for path in changed {
+            // Changes to the known directories themselves are reported for their contents too, so
+            // they can be skipped as long as the directories are still there.
+            let normalized = normalize(path);
+            if self.dirs.contains(&normalized) && path.is_dir() {
+                continue;
+            }
+
+            // Find the outermost new path, i.e., the path itself or the new directory containing
+            // it. Paths outside the known directories (e.g., in ignored ones) are skipped, unless
+            // they were given explicitly.
+            let (outermost, visible) = if self
+                .roots
+                .iter()
+                .any(|(root, _)| normalize(root) == normalized)
+            {
+                (path.clone(), true)
+            } else {
+                let Some(known) = normalized
+                    .ancestors()
+                    .skip(1)
+                    .find(|ancestor| self.dirs.contains(*ancestor))
+                else {
+                    continue;
+                };
+                let depth = path.components().count() - normalized.components().count()
+                    + known.components().count()
+                    + 1;
+                let outermost = path.components().take(depth).collect::<PathBuf>();
+                let listing = listings.entry(known.to_owned()).or_insert_with(|| {
+                    walk::children(
+                        outermost
+                            .parent()
+                            .filter(|parent| !parent.as_os_str().is_empty())
+                            .unwrap_or(Path::new(".")),
+                        excludes,
+                    )
+                });
+                let visible = listing.contains(&normalize(&outermost));
+                (outermost, visible)
+            };
+
+            // Forget what was there before, and then scan whatever is there now.
+            let key = normalize(&outermost);
+            index
+                .files
+                .retain(|file, _| !Path::new(file).starts_with(&key));
+            self.dirs.retain(|dir| !dir.starts_with(&key));
+            if visible {
+                self.scan(index, &outermost, &previous, &settings, excludes, &parse);
+            }
+        }
+
+        let changed = index.files.len() != previous.len()
+            || index.files.iter().any(|(key, entry)| {
+                previous
+                    .get(key)
+                    .is_none_or(|previous_entry| previous_entry.hash != entry.hash)
+            });
+        if changed {
+            index.generation += 1;
+        }
+        changed
+    }
+
+    // This function adds the files in the given path to the index. Files which haven't changed
+    // aren't parsed again.
+    fn scan<F: Fn(&Path, &[u8]) -> Directives>(
+        &mut self,
+        index: &mut DirectiveIndex,
+        path: &Path,
+        previous: &BTreeMap<String, FileEntry>,
+        settings: &[String],
+        excludes: &[PathPattern],
+        parse: &F,
+    ) {
+        let entry = |file_path: &Path, contents: &[u8]| {
+            let key = normalize(file_path).to_string_lossy().into_owned();
+            let hash = cache::key(settings, contents);
+            let directives = match previous.get(&key) {
+                Some(entry) if entry.hash == hash => entry.directives.clone(),
+                _ => parse(file_path, contents),
+            };
+            (key, FileEntry { hash, directives })
+        };
+
+        if path.is_file() {
+            if let Ok(contents) = read(path) {
+                let (key, file_entry) = entry(path, &contents);
+                index.files.insert(key, file_entry);
+            }
+            return;
+        }
+        if !path.is_dir() {
+            return;
+        }
+
+        // The files in a new directory are read as they're traversed and parsed afterward, since
+        // the traversal needs its callback to be `'static`.
+        let found = Arc::new(Mutex::new(Vec::new()));
+        let found_clone = found.clone();
+        walk::walk(
+            &[path.to_owned()],
+            excludes,
+            None,
+            false,
+            move |file_path, mut file| {
+                let mut contents = Vec::new();
+                if file.read_to_end(&mut contents).is_ok() {
+                    found_clone
+                        .lock()
+                        .unwrap() // Safe assuming no poisoning
+                        .push((file_path.to_owned(), contents));
+                }
+            },
+        );
+
+        self.dirs.insert(normalize(path));
+        // The `unwrap` is safe assuming no poisoning.
+        for (file_path, contents) in found.lock().unwrap().drain(..) {
+            self.dirs.extend(
+                normalize(&file_path)
+                    .ancestors()
+                    .skip(1)
+                    .take_while(|ancestor| ancestor.starts_with(normalize(path)))
+                    .map(Path::to_owned),
+            );
+            let (key, file_entry) = entry(&file_path, &contents);
+            index.files.insert(key, file_entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            directive::{compile_directive_regex, parse, Directives},
+            directive_index::DirectiveIndex,
+            watch::Watch,
+        },
+        std::{
+            collections::BTreeSet,
+            env::temp_dir,
+            fs::{create_dir_all, remove_dir_all, remove_file, write},
+            path::Path,
+            process,
+        },
+    };
+
+    fn parse_tags(path: &Path, contents: &[u8]) -> Directives {
+        let regex = compile_directive_regex("tag");
+        parse(
+            &regex,
+            &regex,
+            &regex,
+            &regex,
+            &regex,
+            &regex,
+            &[],
+            path,
+            contents,
+        )
+    }
+
+    fn labels(index: &DirectiveIndex) -> Vec<String> {
+        index
+            .files
+            .values()
+            .flat_map(|entry| entry.directives.tags.iter().map(|tag| tag.label.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn refresh_changed_paths() {
+        let directory = temp_dir().join(format!("tagref-watch-test-{}", process::id()));
+        let _ = remove_dir_all(&directory);
+        create_dir_all(directory.join("ignored")).unwrap();
+        write(directory.join(".gitignore"), "ignored\n").unwrap();
+        write(directory.join("a.rs"), "[?tag:a]".replace('?', "")).unwrap();
+        write(directory.join("b.rs"), "[?tag:b]".replace('?', "")).unwrap();
+        let paths = [directory.clone()];
+        let mut index = DirectiveIndex::new(&["tag"]);
+        index.update(Path::new(""), &paths, &[], false, &["tag"], parse_tags);
+        let mut watch = Watch::new(&paths, &index).unwrap();
+
+        // Changed, new, and deleted files are picked up, along with the files in new directories.
+        write(directory.join("a.rs"), "[?tag:c]".replace('?', "")).unwrap();
+        remove_file(directory.join("b.rs")).unwrap();
+        create_dir_all(directory.join("new/deeper")).unwrap();
+        write(
+            directory.join("new/deeper/d.rs"),
+            "[?tag:d]".replace('?', ""),
+        )
+        .unwrap();
+        write(directory.join("ignored/e.rs"), "[?tag:e]".replace('?', "")).unwrap();
+        let changed = ["a.rs", "b.rs", "new/deeper/d.rs", "ignored/e.rs"]
+            .iter()
+            .map(|path| directory.join(path))
+            .collect::<BTreeSet<_>>();
+        assert!(watch.refresh(&mut index, &changed, &[], &["tag"], parse_tags));
+        assert_eq!(labels(&index), ["c", "d"]);
+        assert_eq!(index.generation, 2);
+
+        // Nothing changes if the files are the same, or if only ignored files changed.
+        assert!(!watch.refresh(&mut index, &changed, &[], &["tag"], parse_tags));
+        assert_eq!(index.generation, 2);
+
+        // Deleting a directory forgets everything in it.
+        remove_dir_all(directory.join("new")).unwrap();
+        let changed = BTreeSet::from([directory.join("new")]);
+        assert!(watch.refresh(&mut index, &changed, &[], &["tag"], parse_tags));
+        let _ = remove_dir_all(&directory);
+        assert_eq!(labels(&index), ["c"]);
+    }
+}